@@ -0,0 +1,78 @@
+//! Categorization throughput on a large synthetic scan: `cargo bench --bench categorize`.
+//!
+//! The application is a binary, so the categorizer and the modules it uses
+//! are compiled in here from their sources.
+
+#![allow(dead_code)]
+
+#[path = "../src/file_category.rs"]
+mod file_category;
+#[path = "../src/formatting.rs"]
+mod formatting;
+#[path = "../src/orphan_temps.rs"]
+mod orphan_temps;
+#[path = "../src/package_caches.rs"]
+mod package_caches;
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use file_category::FileCategory;
+
+const FILES: usize = 500_000;
+const ROUNDS: usize = 5;
+
+/// Paths shaped like a home directory's, spread over every rule.
+fn synthetic_paths() -> Vec<PathBuf> {
+    let shapes = [
+        "/home/user/.cache/thumbnails/large/{}.png",
+        "/home/user/.mozilla/firefox/abc.default/storage/{}.sqlite",
+        "/home/user/Downloads/installer-{}.deb",
+        "/home/user/projects/app/target/debug/deps/lib{}.rlib",
+        "/home/user/projects/web/node_modules/pkg-{}/index.js",
+        "/home/user/.local/share/Trash/files/old-{}.txt",
+        "/var/log/journal/app-{}.log",
+        "/tmp/session-{}.tmp",
+        "/home/user/Documents/report-{}.odt",
+        "/home/user/Pictures/IMG_{}.JPG",
+        "/home/user/.cargo/registry/cache/crate-{}.crate",
+        "/home/user/backup/settings-{}.bak",
+    ];
+    (0..FILES)
+        .map(|index| PathBuf::from(shapes[index % shapes.len()].replace("{}", &index.to_string())))
+        .collect()
+}
+
+fn main() {
+    let paths = synthetic_paths();
+    // Metadata as the scanner passes it, so the figures measure the rules and not the disk
+    let metadata = std::fs::metadata(std::env::current_exe().unwrap()).unwrap();
+
+    let mut best = f64::MAX;
+    let mut counts: HashMap<FileCategory, usize> = HashMap::new();
+    for round in 0..ROUNDS {
+        let start = Instant::now();
+        for path in &paths {
+            let category = FileCategory::categorize(black_box(path), Some(&metadata));
+            if round == 0 {
+                *counts.entry(category).or_default() += 1;
+            }
+        }
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+
+    println!(
+        "categorize: {} files in {:.1} ms, {:.0} files/s (best of {} rounds)",
+        FILES,
+        best * 1000.0,
+        FILES as f64 / best,
+        ROUNDS
+    );
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    for (category, count) in counts {
+        println!("  {:?}: {}", category, count);
+    }
+}
@@ -0,0 +1,125 @@
+//! Windows permission checks made before a cleanup.
+//!
+//! Deleting a file takes the `DELETE` right on it or `FILE_DELETE_CHILD` on its
+//! folder. Both are checked by opening the file and the folder for those rights
+//! only, which tells files locked down by their ACL or owned by another account
+//! apart before the cleanup starts, instead of failing one by one during it.
+
+use std::ffi::c_void;
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use log::{info, warn};
+
+type Handle = *mut c_void;
+
+const INVALID_HANDLE_VALUE: Handle = -1isize as Handle;
+const DELETE: u32 = 0x0001_0000;
+const FILE_DELETE_CHILD: u32 = 0x0000_0040;
+const FILE_SHARE_READ: u32 = 0x0000_0001;
+const FILE_SHARE_WRITE: u32 = 0x0000_0002;
+const FILE_SHARE_DELETE: u32 = 0x0000_0004;
+const OPEN_EXISTING: u32 = 3;
+/// Needed to open folders
+const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+/// Opens a link itself rather than its target
+const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+const ERROR_ACCESS_DENIED: i32 = 5;
+
+/// The built-in Administrators group, whatever the language of the system.
+const ADMINISTRATORS_SID: &str = "*S-1-5-32-544";
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn CreateFileW(
+        file_name: *const u16,
+        desired_access: u32,
+        share_mode: u32,
+        security_attributes: *mut c_void,
+        creation_disposition: u32,
+        flags_and_attributes: u32,
+        template_file: Handle,
+    ) -> Handle;
+    fn CloseHandle(handle: Handle) -> i32;
+}
+
+#[link(name = "shell32")]
+extern "system" {
+    fn IsUserAnAdmin() -> i32;
+}
+
+/// Whether `path` can be opened for `access` alone. Sharing every way keeps
+/// files other programs have open from being reported: they may be closed by
+/// the time the cleanup reaches them.
+fn can_open(path: &Path, access: u32) -> bool {
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let handle = unsafe {
+        CreateFileW(
+            wide.as_ptr(),
+            access,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            std::ptr::null_mut(),
+            OPEN_EXISTING,
+            FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT,
+            std::ptr::null_mut(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return std::io::Error::last_os_error().raw_os_error() != Some(ERROR_ACCESS_DENIED);
+    }
+    unsafe { CloseHandle(handle) };
+    true
+}
+
+/// Whether the permissions of `path` and of its folder let the current user delete it.
+pub fn check_delete_access(path: &Path) -> Result<(), String> {
+    if can_open(path, DELETE) {
+        return Ok(());
+    }
+    if path.parent().is_some_and(|parent| can_open(parent, FILE_DELETE_CHILD)) {
+        return Ok(());
+    }
+    Err("access denied by the file's permissions or owner".to_string())
+}
+
+/// Whether the application runs elevated, with the rights of the Administrators group.
+pub fn is_elevated() -> bool {
+    unsafe { IsUserAnAdmin() != 0 }
+}
+
+/// Makes the Administrators group the owner of `path`, grants it full control
+/// and clears the read-only attribute, so an elevated cleanup can delete it.
+pub fn take_ownership(path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let output = Command::new("takeown").arg("/F").arg(path).arg("/A").output()?;
+    if !output.status.success() {
+        return Err(format!("takeown failed: {}", String::from_utf8_lossy(&output.stdout).trim()).into());
+    }
+
+    let output = Command::new("icacls")
+        .arg(path)
+        .arg("/grant")
+        .arg(format!("{}:F", ADMINISTRATORS_SID))
+        .output()?;
+    if !output.status.success() {
+        return Err(format!("icacls failed: {}", String::from_utf8_lossy(&output.stdout).trim()).into());
+    }
+
+    let mut permissions = std::fs::symlink_metadata(path)?.permissions();
+    if permissions.readonly() {
+        #[allow(clippy::permissions_set_readonly_false)] // Windows only clears the attribute
+        permissions.set_readonly(false);
+        std::fs::set_permissions(path, permissions)?;
+    }
+
+    info!("Took ownership of {}", path.display());
+    Ok(())
+}
+
+/// Takes ownership of each of `paths`, logging the ones that fail.
+pub fn take_ownership_of(paths: &[PathBuf]) {
+    for path in paths {
+        if let Err(e) = take_ownership(path) {
+            warn!("Could not take ownership of {}: {}", path.display(), e);
+        }
+    }
+}
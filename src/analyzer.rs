@@ -0,0 +1,75 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use chrono::{DateTime, Utc};
+use log::info;
+
+use crate::browser_history::DownloadRecord;
+use crate::config::Config;
+use crate::duplicate_finder::DuplicateFinder;
+use crate::file_category::FileCategory;
+
+#[derive(Debug, Clone)]
+pub struct FileAnalysis {
+    pub path: PathBuf,
+    pub size: u64,
+    pub category: FileCategory,
+    pub reason: &'static str,
+    pub age_days: Option<u64>,
+    pub modified: Option<DateTime<Utc>>,
+    pub created: Option<DateTime<Utc>>,
+    pub excluded: bool,
+    pub duplicates: Option<Vec<PathBuf>>,
+    pub download: Option<DownloadRecord>,
+}
+
+impl FileAnalysis {
+    pub fn is_safe_to_delete(&self) -> bool {
+        !self.excluded && self.category.is_safe_to_delete()
+    }
+}
+
+/// Builds an analysis card for a single file. `candidates` are the files from the
+/// last scan; when there is none, duplicates are left unknown. `download` is the
+/// browser history entry of the file, if known.
+pub fn analyze_file(
+    path: &Path,
+    config: &Config,
+    candidates: Option<&[PathBuf]>,
+    download: Option<DownloadRecord>,
+) -> Result<FileAnalysis, Box<dyn std::error::Error + Send + Sync>> {
+    info!("Analyzing file: {}", path.display());
+
+    let metadata = std::fs::metadata(path)?;
+    if !metadata.is_file() {
+        return Err(format!("{} is not a regular file", path.display()).into());
+    }
+
+    let (category, reason) = if let Some(category) = config.category_rules.category(path) {
+        (category, "Matches a categorization rule from the settings")
+    } else if let Some(category) = config.learned_category(path) {
+        (category, "Matches a rule learned from a manual reassignment")
+    } else {
+        FileCategory::categorize_with_reason(path, Some(&metadata))
+    };
+
+    let age_days = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|duration| duration.as_secs() / (24 * 60 * 60));
+
+    let duplicates = candidates.map(|files| DuplicateFinder::new().find_duplicates_of(path, files));
+
+    Ok(FileAnalysis {
+        path: path.to_path_buf(),
+        size: metadata.len(),
+        category,
+        reason,
+        age_days,
+        modified: metadata.modified().ok().map(DateTime::from),
+        created: metadata.created().ok().map(DateTime::from),
+        excluded: config.is_path_excluded(path),
+        duplicates,
+        download,
+    })
+}
@@ -1,224 +1,1548 @@
 use egui::{Context, Ui, Vec2, Color32};
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
+use std::time::Duration;
 use log::{info, error};
-use dirs;
 
-use crate::scanner::{Scanner, ScanResult};
-use crate::duplicate_finder::DuplicateFinder;
-use crate::cleaner::Cleaner;
-use crate::config::Config;
-use crate::file_category::FileCategory;
-use crate::progress::{ProgressTracker, ProgressState};
+use crate::analyzer::{self, FileAnalysis};
+use crate::audit;
+use crate::bookmarks;
+use crate::category_rules::{CategoryRule, PatternKind};
+use crate::chunk_overlap::{self, ChunkOverlap};
+use crate::scanner::{self, Scanner, ScanResult};
+use crate::secure_wipe;
+use crate::snapshot::{self, Baseline};
+use crate::storage::{self, StorageArea};
+use crate::sync_folders::{self, SyncFolder};
+use crate::document_finder::{self, DocumentFinder, SimilarDocuments};
+use crate::duplicate_finder::{DuplicateFinder, DuplicateKeepPolicy};
+use crate::error::Error;
+use crate::cleaner::{Cleaner, CommandOutput, DryRunReport, TrashFallback};
+use crate::config::{self, Config};
+use crate::dir_sizes::{DirSize, DirSizeCache};
+use crate::disk_activity::{self, DiskActivityMonitor, DiskActivitySample};
+use crate::disk_health::{self, DiskHealth};
+use crate::drive_profile::{self, DriveProfile, DriveType};
+use crate::formatting::{self, DateStyle};
+use crate::headless;
+use crate::history::{self, Session};
+use crate::file_category::{self, DownloadAge, FileCategory, RebuildCost};
+use crate::file_identity::FileId;
+use crate::learned_rules::{LearnedRule, RuleAction};
+use crate::logging;
+use crate::low_memory;
+use crate::power;
+use crate::progress::{ProgressHub, ProgressState, RunningTask};
+use crate::quarantine::{self, QuarantineRun};
+use crate::report;
+use crate::retention::RetentionRule;
+use crate::music_finder::MusicFinder;
+use crate::notifier::{self, NotificationEvent};
+use crate::photo_finder::{PhotoFinder, PhotoKeepPolicy};
+use crate::system_storage::{self, ManagedArea};
+use crate::unicode_names;
+use crate::version_finder::VersionFinder;
+use crate::volumes::{self, ForeignVolume};
+use crate::wine_prefixes::{self, WinePrefix};
+
+/// Rough scan speed of a full drive in bytes of data per second, only used to
+/// warn before scanning one.
+const ROOT_SCAN_BYTES_PER_SEC: u64 = 100_000_000;
+
+/// Files listed per category before "Show More" must be clicked.
+const FILE_LIST_PAGE: usize = 1000;
+
+/// Files listed per page, fewer in low-memory mode.
+fn file_list_page() -> usize {
+    if low_memory::is_enabled() { low_memory::FILE_LIST_PAGE } else { FILE_LIST_PAGE }
+}
+
+/// Height of a category's file list, longer lists scroll.
+const FILE_LIST_HEIGHT: f32 = 300.0;
+
+/// Files of a pending cleanup shown for spot-checking in the confirmation dialog.
+const CLEANUP_SAMPLE_SIZE: usize = 20;
+
+pub const WINDOW_SIZE: [f32; 2] = [1200.0, 800.0];
+pub const MIN_WINDOW_SIZE: [f32; 2] = [800.0, 600.0];
+
+/// Size of the window in mini widget mode.
+const MINI_WIDGET_SIZE: [f32; 2] = [300.0, 90.0];
+
+/// How often the mini widget reads the free space again.
+const MINI_WIDGET_REFRESH: Duration = Duration::from_secs(5);
 
 #[derive(Default)]
 pub struct DiskCleanerApp {
     config: Config,
     scan_path: String,
+    /// Further folders scanned together with `scan_path`
+    extra_scan_paths: Vec<String>,
     scan_results: Option<ScanResult>,
+    /// Directories the current results were scanned from, whatever the path fields say now
+    scan_roots: Vec<PathBuf>,
+    /// When the results shown were restored from a previous session, the time they were saved
+    restored_at: Option<chrono::DateTime<chrono::Utc>>,
     duplicates: Vec<Vec<PathBuf>>,
+    /// The duplicate search was cancelled, so the groups are partial
+    duplicates_cancelled: bool,
+    /// Files the user chose to keep in their duplicate group
+    kept_duplicates: HashSet<FileId>,
+    cleanup_preview: CleanupPreview,
+    /// Groups of the pending cleanup in the order they will be removed
+    cleanup_order: Vec<CleanupStep>,
+    /// The user changed the order of the steps, so files go one at a time
+    cleanup_reordered: bool,
+    /// Note attached to the next cleanup run
+    cleanup_note: String,
+    /// Only report what the confirmed cleanup would remove
+    dry_run: bool,
+    /// Overwrite the files of the confirmed cleanup before deleting them permanently
+    secure_wipe: bool,
+    /// Stop the confirmed cleanup once `cleanup_target_gb` have been freed
+    stop_at_target: bool,
+    cleanup_target_gb: f64,
+    /// Duplicate groups sorted again after the keep policy changed
+    keep_policy_receiver: Option<mpsc::Receiver<Vec<Vec<PathBuf>>>>,
+    dry_run_receiver: Option<mpsc::Receiver<DryRunReport>>,
+    dry_run_report: Option<DryRunReport>,
+    /// Checks of every file made before a confirmed cleanup starts
+    preflight_receiver: Option<mpsc::Receiver<DryRunReport>>,
+    /// Files the preflight found the cleanup would fail on, waiting for the user's decision
+    preflight_report: Option<DryRunReport>,
+    /// Files to take ownership of before the next cleanup, with the user's consent
+    #[cfg(windows)]
+    ownership_to_take: Vec<PathBuf>,
+    show_quarantine: bool,
+    show_history: bool,
+    /// Past cleanups, newest first, read when the history window opens
+    history: Option<Result<Vec<Session>, String>>,
+    /// Session whose files are listed, with those files
+    history_entries: Option<(i64, Result<Vec<history::Entry>, String>)>,
+    /// Cleanups whose files are in the quarantine, oldest first
+    quarantine_runs: Vec<QuarantineRun>,
+    /// Files the last restore could not put back, with the reason
+    restore_failures: Vec<(PathBuf, String)>,
     selected_categories: HashMap<FileCategory, bool>,
-    progress: Arc<Mutex<ProgressTracker>>,
+    /// Files ticked or unticked one by one, overriding their category's checkbox
+    file_choices: HashMap<FileCategory, HashMap<PathBuf, bool>>,
+    /// Build folders of projects ticked or unticked as a whole, overriding the checkbox of their category
+    artifact_choices: HashMap<PathBuf, bool>,
+    progress: ProgressHub,
+    /// What failed last and why, shown until the next task starts
+    progress_error: Option<(&'static str, Error)>,
     is_scanning: bool,
     is_cleaning: bool,
     cleaned_space: u64,
+    /// Files the last cleanup removed and, when it was cancelled, those it left in place
+    cleaned_files: (usize, Option<usize>),
+    /// Files the last cleanup could not remove, with the reason
+    failed_files: Vec<(PathBuf, Error)>,
     show_settings: bool,
     show_duplicates: bool,
     confirmation_dialog: bool,
     files_to_delete: Vec<PathBuf>,
+    /// Size of `files_to_delete`, measured once when the confirmation opens
+    cleanup_estimate: u64,
+    commands_to_run: Vec<String>,
+    command_outputs: Vec<CommandOutput>,
+    analysis: Option<FileAnalysis>,
+    analysis_error: Option<String>,
+    show_close_dialog: bool,
+    quit_when_idle: bool,
+    foreign_volumes: Vec<ForeignVolume>,
+    sync_folders: Vec<SyncFolder>,
+    version_families: Vec<Vec<PathBuf>>,
+    show_versions: bool,
+    photo_groups: Vec<Vec<PathBuf>>,
+    photo_receiver: Option<mpsc::Receiver<(PhotoKeepPolicy, Vec<Vec<PathBuf>>)>>,
+    show_photos: bool,
+    song_groups: Vec<Vec<PathBuf>>,
+    songs_receiver: Option<mpsc::Receiver<Vec<Vec<PathBuf>>>>,
+    show_songs: bool,
+    similar_documents: Vec<SimilarDocuments>,
+    documents_receiver: Option<mpsc::Receiver<Vec<SimilarDocuments>>>,
+    show_documents: bool,
+    selection: FileSelection,
+    remember_as_rule: bool,
+    /// Outcome of the last "Save Report", shown under the results
+    report_message: Option<String>,
+    drive_health: Option<DiskHealth>,
+    health_receiver: Option<mpsc::Receiver<Option<DiskHealth>>>,
+    show_failing_drive_warning: bool,
+    /// Bytes used on the volume the user is about to scan in full, while asking them to confirm
+    root_scan_warning: Option<(PathBuf, u64)>,
+    /// Cost of a scan of a removable drive or on battery, waiting for the user's go
+    scan_activity_warning: Option<ScanActivityEstimate>,
+    /// The current scan only reads metadata: no drive health query and no duplicate search after it
+    quick_scan: bool,
+    dir_sizes: DirSizeCache,
+    /// Number of files listed per category, grown by "Show More"
+    file_list_limits: HashMap<FileCategory, usize>,
+    dir_sizes_receiver: Option<mpsc::Receiver<DirSizeCache>>,
+    /// Folder whose subfolders the top directories window lists, `None` while closed
+    top_dirs_path: Option<PathBuf>,
+    /// Its subfolders, sorted, until the folder, the order or the sizes change
+    top_dirs: Option<Vec<(PathBuf, DirSize)>>,
+    top_dirs_sort: DirSort,
+    wine_prefixes: Vec<WinePrefix>,
+    wine_receiver: Option<mpsc::Receiver<Vec<WinePrefix>>>,
+    show_wine_prefixes: bool,
+    /// Space left to the system's own tools, listed while the window is open
+    system_storage: Option<Vec<ManagedArea>>,
+    /// Why the last storage tool could not be opened
+    system_storage_error: Option<String>,
+    show_chunk_comparison: bool,
+    /// Only a small always-on-top summary is shown instead of the full window
+    mini_mode: bool,
+    /// Free space of the scanned volume, with when it was read
+    mini_free_space: Option<(std::time::Instant, u64)>,
+    /// Watches the writes of every process while the disk activity window is open
+    disk_activity: Option<DiskActivityMonitor>,
+    disk_activity_sample: DiskActivitySample,
+    /// Process whose open files are listed, with those files
+    disk_activity_process: Option<(u32, Vec<(PathBuf, u64)>)>,
+    /// Large files picked for comparing their content chunk by chunk
+    chunk_pair: [Option<PathBuf>; 2],
+    chunk_overlap: Option<Result<ChunkOverlap, String>>,
+    chunk_receiver: Option<mpsc::Receiver<Result<ChunkOverlap, String>>>,
+    /// Bytes of the compared files read so far
+    chunk_bytes_read: Arc<AtomicU64>,
+    /// Retention rule being written in the settings, and its folder
+    new_retention_rule: RetentionRule,
+    new_retention_root: String,
+    /// Path the categorization rules are tried on in the settings
+    rule_test_path: String,
+    /// Module being given its own log level in the settings
+    new_log_module: String,
+    /// Space taken by the application's own files, read when the settings open
+    storage_usage: Option<Vec<StorageArea>>,
+    /// Bytes freed by the last pruning or clearing from the settings
+    storage_freed: Option<u64>,
+    /// What a scheduled cleanup would remove from the current results, while shown
+    scheduled_cleanup_plan: Option<String>,
+    /// Drive holding the scan path
+    drive_profile: Option<DriveProfile>,
+    baseline: Option<Baseline>,
+    /// Files of the current results hidden because the baseline already had them
+    baseline_hidden: usize,
+    /// Scan path whose drive suggestions the user dismissed
+    dismissed_drive_suggestions: Option<String>,
+}
+
+/// Files selected in the results list, for batch operations.
+#[derive(Default)]
+struct FileSelection {
+    files: HashSet<FileId>,
+    /// File the last plain or ctrl click landed on, where shift-click ranges start
+    anchor: Option<(FileCategory, FileId)>,
+}
+
+impl FileSelection {
+    /// Updates the selection for a click on row `index` of `category`, following
+    /// the usual conventions: ctrl toggles a row, shift extends from the anchor.
+    fn click(&mut self, category: FileCategory, files: &[PathBuf], index: usize, modifiers: egui::Modifiers) {
+        let id = FileId::of(&files[index]);
+        let anchor_index = self.anchor
+            .filter(|(anchor_category, _)| modifiers.shift && *anchor_category == category)
+            .and_then(|(_, anchor)| files.iter().position(|file| FileId::of(file) == anchor));
+        
+        match anchor_index {
+            Some(anchor) => {
+                let range = anchor.min(index)..=anchor.max(index);
+                if !modifiers.command {
+                    self.files.clear();
+                }
+                self.files.extend(files[range].iter().map(|file| FileId::of(file)));
+            }
+            None if modifiers.command => {
+                if !self.files.remove(&id) {
+                    self.files.insert(id);
+                }
+                self.anchor = Some((category, id));
+            }
+            None => {
+                self.files.clear();
+                self.files.insert(id);
+                self.anchor = Some((category, id));
+            }
+        }
+    }
+
+    fn contains(&self, file: &Path) -> bool {
+        self.files.contains(&FileId::of(file))
+    }
+
+    fn take(&mut self) -> HashSet<FileId> {
+        self.anchor = None;
+        std::mem::take(&mut self.files)
+    }
+}
+
+/// Files of a pending cleanup grouped for review in the confirmation dialog.
+#[derive(Default)]
+struct CleanupPreview {
+    /// Files to delete by category, `None` for files outside the scan results
+    by_category: Vec<(Option<FileCategory>, Vec<PathBuf>)>,
+    /// The kept file of each duplicate group with the copies to delete
+    duplicate_groups: Vec<(PathBuf, Vec<PathBuf>)>,
+    /// Random files of the cleanup with their sizes, biggest first
+    sample: Vec<(PathBuf, u64)>,
+}
+
+/// What a scan is expected to cost, shown before scanning a removable drive
+/// or on battery.
+struct ScanActivityEstimate {
+    /// Why the scan is announced
+    reasons: Vec<String>,
+    /// Last known size and number of files of the scanned folders
+    known_size: Option<(u64, usize)>,
+    /// How long the last scan of the same folders took
+    last_duration: Option<Duration>,
+}
+
+/// A group of files removed together, one after the other, by a cleanup.
+struct CleanupStep {
+    label: String,
+    /// Biggest first, so the most space is freed if the cleanup is cancelled
+    files: Vec<PathBuf>,
+    size: u64,
+}
+
+impl CleanupStep {
+    fn new(label: String, files: Vec<PathBuf>) -> Self {
+        let mut sized: Vec<(PathBuf, u64)> = files.into_iter()
+            .map(|file| {
+                let size = std::fs::symlink_metadata(&file).map(|metadata| metadata.len()).unwrap_or(0);
+                (file, size)
+            })
+            .collect();
+        sized.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        Self {
+            label,
+            size: sized.iter().map(|(_, size)| size).sum(),
+            files: sized.into_iter().map(|(file, _)| file).collect(),
+        }
+    }
+}
+
+/// Order of the folders in the top directories window.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum DirSort {
+    #[default]
+    Size,
+    Files,
+    Name,
+}
+
+/// Where the top folders of a category are handed to the file manager.
+#[derive(Clone, Copy)]
+enum LocationExport {
+    Bookmarks,
+    Shortcuts,
+}
+
+#[derive(Clone, Copy)]
+enum QuarantineAction {
+    RestoreRun(usize),
+    RestoreFile(usize, usize),
+    Purge(usize),
+}
+
+#[derive(Clone, Copy)]
+enum PreflightAction {
+    SkipRefused,
+    #[cfg(windows)]
+    TakeOwnership,
+    Cancel,
+}
+
+#[derive(Clone, Copy)]
+enum BaselineAction {
+    Set,
+    Clear,
+}
+
+#[derive(Clone, Copy)]
+enum BatchAction {
+    Exclude,
+    Protect,
+    MoveTo(FileCategory),
+    Delete,
 }
 
 impl DiskCleanerApp {
-    pub fn new() -> Self {
-        let mut app = Self::default();
-        app.scan_path = dirs::home_dir()
-            .unwrap_or_else(|| PathBuf::from("/"))
-            .to_string_lossy()
-            .to_string();
-        
-        // Initialize all categories as selected by default
-        for category in FileCategory::all() {
-            app.selected_categories.insert(category, true);
+    pub fn new(initial_file: Option<PathBuf>) -> Self {
+        let config = Config::load();
+        let mut app = Self {
+            // Start with the categories the user chose to pre-select
+            selected_categories: FileCategory::all()
+                .into_iter()
+                .map(|category| (category, config.is_category_preselected(category)))
+                .collect(),
+            config,
+            scan_path: dirs::home_dir()
+                .unwrap_or_else(|| PathBuf::from("/"))
+                .to_string_lossy()
+                .to_string(),
+            foreign_volumes: volumes::detect_foreign_volumes(),
+            sync_folders: sync_folders::detect_sync_folders(),
+            dir_sizes: DirSizeCache::load(),
+            baseline: Baseline::load(),
+            ..Default::default()
+        };
+        
+        if let Some(snapshot) = snapshot::load() {
+            let mut paths = snapshot.scan_paths.iter().map(|path| path.to_string_lossy().to_string());
+            app.scan_path = paths.next().unwrap_or_default();
+            app.extra_scan_paths = paths.collect();
+            app.scan_roots = snapshot.scan_paths;
+            untick_locked_files(&mut app.file_choices, &snapshot.result, None);
+            app.scan_results = Some(snapshot.result);
+            app.restored_at = Some(snapshot.saved_at);
+            app.hide_baseline_files();
+            app.refresh_dir_sizes();
+        }
+        
+        let storage_settings = app.config.storage.clone();
+        thread::spawn(move || storage::prune(&storage_settings));
+        
+        if let Some(path) = initial_file {
+            app.analyze_file(&path);
         }
         
         app
     }
 
+    /// Leaves only the files that appeared since the baseline in the results.
+    fn hide_baseline_files(&mut self) {
+        if let (Some(baseline), Some(results)) = (&self.baseline, &mut self.scan_results) {
+            self.baseline_hidden = baseline.hide_known_files(results);
+        }
+    }
+
+    fn apply_baseline_action(&mut self, action: BaselineAction) {
+        match action {
+            BaselineAction::Set => {
+                let Some(results) = &self.scan_results else {
+                    return;
+                };
+                // Files hidden by the current baseline are not in the results but still belong in it
+                let baseline = Baseline::of(&self.scan_roots, results, self.baseline.as_ref());
+                if let Err(e) = baseline.save() {
+                    error!("Failed to save baseline: {}", e);
+                    return;
+                }
+                self.baseline = Some(baseline);
+                self.hide_baseline_files();
+            }
+            BaselineAction::Clear => {
+                if let Err(e) = Baseline::clear() {
+                    error!("Failed to remove baseline: {}", e);
+                }
+                self.baseline = None;
+                self.baseline_hidden = 0;
+                info!("Baseline cleared, scan again to see every file");
+            }
+        }
+    }
+
+    /// Shows the cached sizes right away and brings them up to date in the background.
+    fn refresh_dir_sizes(&mut self) {
+        if self.scan_roots.is_empty() {
+            return;
+        }
+        let roots = self.scan_roots.clone();
+        
+        let (sender, receiver) = mpsc::channel();
+        self.dir_sizes_receiver = Some(receiver);
+        thread::spawn(move || {
+            let mut cache = DirSizeCache::load();
+            let changed: usize = roots.iter().map(|root| cache.refresh(root)).sum();
+            if changed > 0 {
+                if let Err(e) = cache.save() {
+                    error!("Failed to save directory size cache: {}", e);
+                }
+            }
+            let _ = sender.send(cache);
+        });
+    }
+
+    fn analyze_file(&mut self, path: &Path) {
+        let candidates = self.scan_results.as_ref().map(|results| {
+            results.live_files()
+        });
+        
+        let download = self.scan_results.as_ref()
+            .and_then(|results| results.download_sources.get(path).cloned());
+        
+        match analyzer::analyze_file(path, &self.config, candidates.as_deref(), download) {
+            Ok(analysis) => {
+                self.analysis = Some(analysis);
+                self.analysis_error = None;
+            }
+            Err(e) => {
+                error!("Analysis failed: {}", e);
+                self.analysis = None;
+                self.analysis_error = Some(format!("Could not analyze {}: {}", path.display(), e));
+            }
+        }
+    }
+
     fn start_scan(&mut self) {
         if self.is_scanning {
             return;
         }
 
-        let path = PathBuf::from(&self.scan_path);
-        if !path.exists() {
-            error!("Scan path does not exist: {}", self.scan_path);
+        let roots = self.scan_paths();
+        if let Some(missing) = roots.iter().find(|root| !root.exists()) {
+            error!("Scan path does not exist: {}", missing.display());
             return;
         }
+        let Some(path) = roots.first().cloned() else {
+            return;
+        };
 
         self.is_scanning = true;
         self.scan_results = None;
+        self.scan_roots = roots.clone();
+        self.restored_at = None;
         self.duplicates.clear();
+        self.keep_policy_receiver = None;
+        self.photo_receiver = None;
+        self.songs_receiver = None;
+        self.documents_receiver = None;
+        self.file_list_limits.clear();
+        // The scan records fresh sizes, a refresh still running would bring back older ones
+        self.dir_sizes_receiver = None;
         
-        let progress = Arc::clone(&self.progress);
-        progress.lock().unwrap().reset();
+        let progress = self.progress.start_task();
+        self.progress_error = None;
+        
+        // SMART queries can take a few seconds, read them beside the scan
+        if !self.quick_scan {
+            let (sender, receiver) = mpsc::channel();
+            self.health_receiver = Some(receiver);
+            let health_path = path.clone();
+            thread::spawn(move || {
+                let _ = sender.send(disk_health::read_health(&health_path));
+            });
+        }
         
-        let _scan_path = self.scan_path.clone();
         let config = self.config.clone();
+        let webhooks = config.webhooks.clone();
         
         thread::spawn(move || {
             let mut scanner = Scanner::new(config);
-            match scanner.scan(&path, progress.clone()) {
+            match scanner.scan(&roots, progress.clone()) {
                 Ok(results) => {
-                    progress.lock().unwrap().set_scan_complete(results);
+                    if let Err(e) = snapshot::save(&roots, &results) {
+                        error!("Failed to save scan snapshot: {}", e);
+                    }
+                    let outcome = if results.cancelled { "cancelled" } else { "complete" };
+                    let scanned = roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>().join(", ");
+                    notifier::notify(
+                        &webhooks,
+                        NotificationEvent::ScanComplete,
+                        &format!("Scan of {} {}", scanned, outcome),
+                        &format!("{} files, {}", results.total_files, formatting::format_size(results.total_size)),
+                    );
+                    progress.set_scan_complete(results);
                 }
                 Err(e) => {
                     error!("Scan failed: {}", e);
-                    progress.lock().unwrap().set_error(format!("Scan failed: {}", e));
+                    notifier::notify(&webhooks, NotificationEvent::Failure, "Scan failed", &e.to_string());
+                    progress.set_error("Scan", e);
                 }
             }
         });
     }
 
+    /// The folders entered in the path fields, in order.
+    fn scan_paths(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.scan_path)
+            .chain(&self.extra_scan_paths)
+            .map(|path| path.trim())
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
+
+    /// A whole drive takes far longer to walk than most people expect, so
+    /// confirm before scanning one.
+    fn request_scan(&mut self) {
+        let drive_root = self.scan_paths().into_iter().find(|path| volumes::is_volume_root(path));
+        if let Some(path) = drive_root {
+            let used = volumes::volume_usage(&path).map(|(_, used)| used).unwrap_or(0);
+            self.root_scan_warning = Some((path, used));
+        } else {
+            self.request_scan_activity();
+        }
+    }
+
+    /// Reading a removable drive keeps it spun up and a long scan drains a
+    /// battery, so say what the scan will cost first and offer a lighter one.
+    fn request_scan_activity(&mut self) {
+        match self.scan_activity_estimate() {
+            Some(estimate) => self.scan_activity_warning = Some(estimate),
+            None => {
+                self.quick_scan = false;
+                self.start_scan();
+            }
+        }
+    }
+
+    fn scan_activity_estimate(&self) -> Option<ScanActivityEstimate> {
+        let roots = self.scan_paths();
+        let mut reasons = Vec::new();
+        let removable: Vec<String> = roots.iter()
+            .filter(|root| DriveProfile::detect(&root.to_string_lossy()).drive_type == DriveType::Removable)
+            .map(|root| root.display().to_string())
+            .collect();
+        if !removable.is_empty() {
+            reasons.push(format!("{} is on a removable drive, which stays spun up and powered while it is read.", removable.join(", ")));
+        }
+        if power::on_battery() {
+            reasons.push("The computer is running on battery.".to_string());
+        }
+        if reasons.is_empty() {
+            return None;
+        }
+        
+        let known: Vec<_> = roots.iter().filter_map(|root| self.dir_sizes.get(root)).collect();
+        let known_size = (!known.is_empty() && known.len() == roots.len()).then(|| {
+            (known.iter().map(|size| size.size).sum(), known.iter().map(|size| size.files).sum())
+        });
+        let last_duration = self.scan_results.as_ref()
+            .filter(|results| self.scan_roots == roots && !results.cancelled)
+            .map(|results| results.scan_duration);
+        Some(ScanActivityEstimate { reasons, known_size, last_duration })
+    }
+
+    /// Hashing reads every file in full, which a failing drive may not survive,
+    /// so ask first when SMART reports trouble.
+    fn request_duplicate_scan(&mut self) {
+        if self.drive_health.as_ref().is_some_and(DiskHealth::is_failing) {
+            self.show_failing_drive_warning = true;
+        } else {
+            self.start_duplicate_scan();
+        }
+    }
+
     fn start_duplicate_scan(&mut self) {
+        if self.progress.is_busy() {
+            return;
+        }
+
         if let Some(ref results) = self.scan_results {
-            let progress = Arc::clone(&self.progress);
-            let files = results.files_by_category.values()
-                .flatten()
-                .cloned()
-                .collect::<Vec<_>>();
+            let progress = self.progress.start_task();
+            self.progress_error = None;
+            let files = results.live_files();
+            let min_age_hours = self.config.duplicate_min_age_hours;
+            let hash_network_files = self.config.hash_network_files;
+            let keep_policy = self.config.duplicate_keep_policy;
+            let preferred_folders = self.config.preferred_folders.clone();
+            self.keep_policy_receiver = None;
             
             thread::spawn(move || {
-                let mut finder = DuplicateFinder::new();
-                match finder.find_duplicates(&files, progress.clone()) {
-                    Ok(duplicates) => {
-                        progress.lock().unwrap().set_duplicates_complete(duplicates);
-                    }
-                    Err(e) => {
-                        error!("Duplicate scan failed: {}", e);
-                        progress.lock().unwrap().set_error(format!("Duplicate scan failed: {}", e));
-                    }
-                }
+                let mut finder = DuplicateFinder::new()
+                    .with_min_age(Duration::from_secs(u64::from(min_age_hours) * 60 * 60))
+                    .with_network_hashing(hash_network_files)
+                    .with_keep_policy(keep_policy, &preferred_folders);
+                let duplicates = finder.find_duplicates(&files, progress.clone());
+                progress.set_duplicates_complete(duplicates);
+            });
+        }
+    }
+
+    /// Sorts the duplicate groups found again for the current keep policy,
+    /// away from the UI thread as every file is read again.
+    fn apply_keep_policy(&mut self) {
+        let mut groups = self.duplicates.clone();
+        let policy = self.config.duplicate_keep_policy;
+        let preferred_folders = self.config.preferred_folders.clone();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            policy.apply(&mut groups, &preferred_folders);
+            let _ = sender.send(groups);
+        });
+        self.keep_policy_receiver = Some(receiver);
+    }
+
+    fn find_version_families(&mut self) {
+        if let Some(ref results) = self.scan_results {
+            let files = results.live_files();
+            self.version_families = VersionFinder::find_version_families(&files, self.config.unify_unicode_names);
+        }
+    }
+
+    fn find_photo_copies(&mut self) {
+        if self.photo_receiver.is_some() {
+            return;
+        }
+        if let Some(ref results) = self.scan_results {
+            // EXIF is read from every photo, keep it off the UI thread
+            let files = results.live_files();
+            let (policy, unify_names) = (self.config.photo_keep_policy, self.config.unify_unicode_names);
+            let (sender, receiver) = mpsc::channel();
+            self.photo_receiver = Some(receiver);
+            thread::spawn(move || {
+                let _ = sender.send((policy, PhotoFinder::find_format_copies(&files, policy, unify_names)));
+            });
+        }
+    }
+
+    fn find_duplicate_songs(&mut self) {
+        if self.songs_receiver.is_some() {
+            return;
+        }
+        if let Some(ref results) = self.scan_results {
+            // Tags and duration are probed from every track, keep it off the UI thread
+            let files = results.live_files();
+            let (sender, receiver) = mpsc::channel();
+            self.songs_receiver = Some(receiver);
+            thread::spawn(move || {
+                let _ = sender.send(MusicFinder::find_duplicate_tracks(&files));
+            });
+        }
+    }
+
+    fn find_similar_documents(&mut self) {
+        if self.documents_receiver.is_some() {
+            return;
+        }
+        if let Some(ref results) = self.scan_results {
+            // Every document is unpacked and read, keep it off the UI thread
+            let files = results.live_files();
+            let (sender, receiver) = mpsc::channel();
+            self.documents_receiver = Some(receiver);
+            thread::spawn(move || {
+                let _ = sender.send(DocumentFinder::find_similar_documents(
+                    &files,
+                    document_finder::DEFAULT_SIMILARITY_THRESHOLD,
+                ));
             });
         }
     }
 
+    fn find_wine_prefixes(&mut self) {
+        if self.wine_receiver.is_some() {
+            return;
+        }
+        
+        // Measuring prefixes walks gigabytes of game files, keep it off the UI thread
+        let (sender, receiver) = mpsc::channel();
+        self.wine_receiver = Some(receiver);
+        thread::spawn(move || {
+            let _ = sender.send(wine_prefixes::find_prefixes());
+        });
+    }
+
+    fn compare_chunks(&mut self) {
+        let [Some(first), Some(second)] = self.chunk_pair.clone() else {
+            return;
+        };
+        if self.chunk_receiver.is_some() {
+            return;
+        }
+        
+        // Both files are read in full, which takes minutes for VM images
+        let (sender, receiver) = mpsc::channel();
+        self.chunk_receiver = Some(receiver);
+        self.chunk_overlap = None;
+        self.chunk_bytes_read = Arc::new(AtomicU64::new(0));
+        let bytes_read = Arc::clone(&self.chunk_bytes_read);
+        thread::spawn(move || {
+            let overlap = chunk_overlap::compare(&first, &second, &bytes_read).map_err(|e| e.to_string());
+            let _ = sender.send(overlap);
+        });
+    }
+
+    /// Opens the confirmation dialog for deleting exactly `files`.
+    fn confirm_deletion(&mut self, files: Vec<PathBuf>) {
+        self.files_to_delete = files;
+        self.drop_protected_files();
+        self.estimate_cleanup();
+        self.preview_cleanup();
+        self.commands_to_run.clear();
+        self.confirmation_dialog = !self.files_to_delete.is_empty();
+    }
+
     fn prepare_cleanup(&mut self) {
         self.files_to_delete.clear();
+        self.commands_to_run.clear();
         
         if let Some(ref results) = self.scan_results {
-            for (category, selected) in &self.selected_categories {
-                if *selected {
-                    if let Some(files) = results.files_by_category.get(category) {
-                        self.files_to_delete.extend(files.iter().cloned());
-                    }
+            for category in FileCategory::all() {
+                if !self.config.is_category_enabled(category) {
+                    continue;
+                }
+                if let Some(files) = results.files_by_category.get(&category) {
+                    self.files_to_delete.extend(
+                        files.iter()
+                            .filter(|file| is_file_ticked(&self.file_choices, &self.artifact_choices, &self.selected_categories, category, file))
+                            .cloned()
+                    );
+                }
+            }
+        }
+        
+        // Custom commands attached to the selected categories
+        for category in FileCategory::all() {
+            if !self.selected_categories.get(&category).copied().unwrap_or(false)
+                || !self.config.is_category_enabled(category)
+            {
+                continue;
+            }
+            if let Some(command) = self.config.category_commands.get(&category) {
+                if !command.trim().is_empty() {
+                    self.commands_to_run.push(command.clone());
                 }
             }
         }
         
-        // Add selected duplicates (keep first file in each group)
+        // Add duplicates, keeping the file the user picked in each group or else the first
         for duplicate_group in &self.duplicates {
             if duplicate_group.len() > 1 {
-                self.files_to_delete.extend(duplicate_group.iter().skip(1).cloned());
+                let kept = self.kept_file(duplicate_group);
+                self.files_to_delete.extend(
+                    duplicate_group.iter().enumerate()
+                        .filter(|(index, _)| *index != kept)
+                        .map(|(_, file)| file.clone())
+                );
             }
         }
         
-        if !self.files_to_delete.is_empty() {
+        // Build folders are cleaned as a unit: the folders their files leave empty go with them
+        if let Some(ref results) = self.scan_results {
+            let in_artifacts: Vec<PathBuf> = self.files_to_delete.iter()
+                .filter(|file| results.dev_artifacts.iter().any(|artifact| file.starts_with(&artifact.path)))
+                .cloned()
+                .collect();
+            if !in_artifacts.is_empty() {
+                let projects: Vec<PathBuf> = results.dev_artifacts.iter()
+                    .filter_map(|artifact| artifact.path.parent().map(Path::to_path_buf))
+                    .collect();
+                let emptied = scanner::folders_emptied_by(&in_artifacts, &projects);
+                self.files_to_delete.extend(emptied);
+            }
+        }
+        
+        // Folders the other deletions leave empty go with them
+        if self.selected_categories.get(&FileCategory::EmptyFolders).copied().unwrap_or(false)
+            && self.config.is_category_enabled(FileCategory::EmptyFolders)
+        {
+            let emptied = scanner::folders_emptied_by(&self.files_to_delete, &self.scan_roots);
+            self.files_to_delete.extend(emptied);
+        }
+        
+        self.drop_protected_files();
+        self.estimate_cleanup();
+        self.preview_cleanup();
+        
+        if !self.files_to_delete.is_empty() || !self.commands_to_run.is_empty() {
             self.confirmation_dialog = true;
         }
     }
 
-    fn execute_cleanup(&mut self) {
-        if self.files_to_delete.is_empty() {
-            return;
+    /// Index of the file kept when cleaning a duplicate group.
+    fn kept_file(&self, group: &[PathBuf]) -> usize {
+        group.iter()
+            .position(|file| self.kept_duplicates.contains(&FileId::of(file)))
+            .unwrap_or(0)
+    }
+
+    /// Last check before asking for confirmation: protected and excluded files
+    /// are never deleted, whatever put them in the list.
+    fn drop_protected_files(&mut self) {
+        let config = &self.config;
+        let before = self.files_to_delete.len();
+        self.files_to_delete.retain(|file| !config.is_path_protected(file) && !config.is_path_excluded(file));
+        if self.files_to_delete.len() < before {
+            info!("Left {} protected or excluded files out of the cleanup", before - self.files_to_delete.len());
         }
+    }
 
-        self.is_cleaning = true;
-        self.confirmation_dialog = false;
+    fn estimate_cleanup(&mut self) {
+        self.cleanup_estimate = Cleaner::new(self.config.use_trash)
+            .estimate_cleanup_size(&self.files_to_delete);
+    }
+
+    fn preview_cleanup(&mut self) {
+        let mut remaining: HashSet<FileId> = self.files_to_delete.iter().map(|file| FileId::of(file)).collect();
+        let mut preview = CleanupPreview::default();
         
-        let files = self.files_to_delete.clone();
-        let progress = Arc::clone(&self.progress);
-        let use_trash = self.config.use_trash;
+        for group in &self.duplicates {
+            let kept = self.kept_file(group);
+            let removed: Vec<PathBuf> = group.iter().enumerate()
+                .filter(|(index, file)| *index != kept && remaining.contains(&FileId::of(file)))
+                .map(|(_, file)| file.clone())
+                .collect();
+            if !removed.is_empty() {
+                preview.duplicate_groups.push((group[kept].clone(), removed));
+            }
+        }
         
-        thread::spawn(move || {
-            let cleaner = Cleaner::new(use_trash);
-            match cleaner.clean_files(&files, progress.clone()) {
-                Ok(cleaned_bytes) => {
-                    progress.lock().unwrap().set_cleanup_complete(cleaned_bytes);
+        if let Some(results) = &self.scan_results {
+            for category in FileCategory::all() {
+                let Some(files) = results.files_by_category.get(&category) else {
+                    continue;
+                };
+                let selected: Vec<PathBuf> = files.iter()
+                    .filter(|file| remaining.remove(&FileId::of(file)))
+                    .cloned()
+                    .collect();
+                if !selected.is_empty() {
+                    preview.by_category.push((Some(category), selected));
                 }
-                Err(e) => {
-                    error!("Cleanup failed: {}", e);
-                    progress.lock().unwrap().set_error(format!("Cleanup failed: {}", e));
+            }
+        }
+        
+        if !remaining.is_empty() {
+            // Folders emptied by the other deletions are not in the results
+            let (folders, others): (Vec<PathBuf>, Vec<PathBuf>) = self.files_to_delete.iter()
+                .filter(|file| remaining.contains(&FileId::of(file)))
+                .cloned()
+                .partition(|file| file.is_dir());
+            let (artifact_folders, folders): (Vec<PathBuf>, Vec<PathBuf>) = folders.into_iter()
+                .partition(|folder| file_category::dev_artifact_root(folder).is_some());
+            if !artifact_folders.is_empty() {
+                match preview.by_category.iter_mut().find(|(category, _)| *category == Some(FileCategory::DevArtifacts)) {
+                    Some((_, listed)) => listed.extend(artifact_folders),
+                    None => preview.by_category.push((Some(FileCategory::DevArtifacts), artifact_folders)),
                 }
             }
-        });
+            if !folders.is_empty() {
+                match preview.by_category.iter_mut().find(|(category, _)| *category == Some(FileCategory::EmptyFolders)) {
+                    Some((_, listed)) => listed.extend(folders),
+                    None => preview.by_category.push((Some(FileCategory::EmptyFolders), folders)),
+                }
+            }
+            if !others.is_empty() {
+                preview.by_category.push((None, others));
+            }
+        }
+        
+        preview.sample = sample_files(&self.files_to_delete, CLEANUP_SAMPLE_SIZE);
+        self.cleanup_preview = preview;
+        self.order_cleanup();
     }
 
-    fn draw_scan_section(&mut self, ui: &mut Ui) {
-        ui.heading("Disk Scanner");
+    /// Splits the pending cleanup into steps, duplicates first and then each
+    /// category as listed, for the user to reorder before confirming.
+    fn order_cleanup(&mut self) {
+        let mut queued = HashSet::new();
+        let mut order = Vec::new();
         
-        ui.horizontal(|ui| {
-            ui.label("Scan Path:");
-            ui.text_edit_singleline(&mut self.scan_path);
-            
-            if ui.button("Browse").clicked() {
-                // In a real implementation, you'd use a file dialog here
-                info!("File dialog would open here");
+        let duplicates: Vec<PathBuf> = self.cleanup_preview.duplicate_groups.iter()
+            .flat_map(|(_, removed)| removed.iter())
+            .filter(|file| queued.insert(FileId::of(file)))
+            .cloned()
+            .collect();
+        if !duplicates.is_empty() {
+            order.push(CleanupStep::new("Duplicates".to_string(), duplicates));
+        }
+        
+        for (category, files) in &self.cleanup_preview.by_category {
+            let files: Vec<PathBuf> = files.iter()
+                .filter(|file| queued.insert(FileId::of(file)))
+                .cloned()
+                .collect();
+            if !files.is_empty() {
+                let label = category.map_or_else(|| "Other files".to_string(), |category| self.config.category_name(category));
+                order.push(CleanupStep::new(label, files));
             }
-        });
+        }
         
-        ui.horizontal(|ui| {
-            if ui.button("Start Scan").clicked() && !self.is_scanning {
-                self.start_scan();
+        self.cleanup_order = order;
+        self.cleanup_reordered = false;
+    }
+
+    /// Space the confirmed cleanup should stop at, if the user set one, in
+    /// decimal gigabytes like the sizes shown.
+    fn cleanup_target(&self) -> Option<u64> {
+        self.stop_at_target.then_some((self.cleanup_target_gb.max(0.1) * 1e9) as u64)
+    }
+
+    /// Puts the files to delete in the order of the cleanup steps.
+    fn apply_cleanup_order(&mut self) {
+        let order = std::mem::take(&mut self.cleanup_order);
+        self.files_to_delete = order.into_iter().flat_map(|step| step.files).collect();
+    }
+
+    fn apply_batch_action(&mut self, action: BatchAction) {
+        let ids = self.selection.take();
+        let files = self.scan_results.as_ref()
+            .map(|results| results.paths_of(&ids))
+            .unwrap_or_default();
+        info!("Applying batch action to {} files", files.len());
+        
+        match action {
+            BatchAction::Exclude => {
+                for file in &files {
+                    if !self.config.excluded_paths.contains(file) {
+                        self.config.excluded_paths.push(file.clone());
+                    }
+                }
+                if let Some(results) = &mut self.scan_results {
+                    results.remove_files(&ids);
+                }
+                self.save_config();
             }
-            
-            if ui.button("Find Duplicates").clicked() && self.scan_results.is_some() {
-                self.start_duplicate_scan();
+            BatchAction::Protect => {
+                self.learn_rule(&files, RuleAction::Protect);
+                for file in files {
+                    if !self.config.protected_paths.contains(&file) {
+                        self.config.protected_paths.push(file);
+                    }
+                }
+                self.save_config();
             }
-            
-            if ui.button("Settings").clicked() {
-                self.show_settings = !self.show_settings;
+            BatchAction::MoveTo(category) => {
+                if self.learn_rule(&files, RuleAction::Category(category)) {
+                    self.save_config();
+                }
+                if let Some(results) = &mut self.scan_results {
+                    results.remove_files(&ids);
+                    results.add_files(category, &files);
+                }
             }
-        });
+            BatchAction::Delete => self.confirm_deletion(files),
+        }
     }
 
-    fn draw_progress(&self, ui: &mut Ui) {
-        let progress = self.progress.lock().unwrap();
+    /// Records a rule generalizing a manual decision, when the user asked for it.
+    fn learn_rule(&mut self, files: &[PathBuf], action: RuleAction) -> bool {
+        if !self.remember_as_rule {
+            return false;
+        }
         
-        match &progress.state {
-            ProgressState::Idle => {},
-            ProgressState::Scanning { current_path, files_processed } => {
-                ui.label(format!("Scanning: {} files processed", files_processed));
-                ui.label(format!("Current: {}", current_path));
-                ui.add(egui::ProgressBar::new(0.5).show_percentage());
-            },
-            ProgressState::FindingDuplicates { files_processed, total_files } => {
-                let progress_value = *files_processed as f32 / *total_files as f32;
-                ui.label(format!("Finding duplicates: {}/{}", files_processed, total_files));
-                ui.add(egui::ProgressBar::new(progress_value).show_percentage());
-            },
-            ProgressState::Cleaning { files_processed, total_files } => {
-                let progress_value = *files_processed as f32 / *total_files as f32;
-                ui.label(format!("Cleaning: {}/{}", files_processed, total_files));
-                ui.add(egui::ProgressBar::new(progress_value).show_percentage());
-            },
-            ProgressState::Complete { .. } => {
-                ui.label("Operation completed successfully");
-            },
-            ProgressState::Error(msg) => {
-                ui.colored_label(Color32::RED, format!("Error: {}", msg));
-            },
+        match LearnedRule::generalize(files, action) {
+            Some(rule) => {
+                info!("Learned rule: {}", rule.description(&self.config));
+                self.config.learned_rules.retain(|existing| {
+                    existing.extension != rule.extension || existing.under != rule.under
+                });
+                self.config.learned_rules.push(rule);
+                true
+            }
+            None => {
+                info!("Selected files share no extension, no rule learned");
+                false
+            }
+        }
+    }
+
+    fn save_report(&mut self) {
+        let Some(results) = &self.scan_results else {
+            return;
+        };
+        
+        self.report_message = Some(match report::default_report_path() {
+            Some(path) => match report::write_csv_report(results, &path, &self.config) {
+                Ok(()) => format!("Report saved to {}", path.display()),
+                Err(e) => format!("Could not save report: {}", e),
+            },
+            None => "No documents folder to save the report to".to_string(),
+        });
+    }
+
+    /// Asks where to export the results, as JSON or CSV, and writes them there.
+    fn export_results(&mut self) {
+        let Some(results) = &self.scan_results else {
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("Export scan results")
+            .add_filter("CSV", &["csv"])
+            .add_filter("JSON", &["json"])
+            .set_file_name(format!("disk-cleaner-results-{}.csv", chrono::Local::now().format("%Y%m%d-%H%M%S")))
+            .save_file()
+        else {
+            return;
+        };
+        
+        self.report_message = Some(match report::export(results, &path, &self.config) {
+            Ok(()) => format!("Results exported to {}", path.display()),
+            Err(e) => format!("Could not export the results: {}", e),
+        });
+    }
+
+    fn save_config(&self) {
+        logging::apply(&self.config.logging);
+        if let Err(e) = self.config.save() {
+            error!("Failed to save config: {}", e);
+        }
+    }
+
+    fn start_dry_run(&mut self) {
+        if self.progress.is_busy() || self.dry_run_receiver.is_some() {
+            return;
+        }
+        self.confirmation_dialog = false;
+        
+        let files = std::mem::take(&mut self.files_to_delete);
+        self.commands_to_run.clear();
+        self.dry_run_receiver = Some(self.spawn_dry_run(files, self.cleanup_target()));
+    }
+
+    /// Checks every file before the confirmed cleanup starts, so the files it
+    /// would fail on, for lack of permissions for instance, are reported
+    /// together up front. The cleanup starts right away when there are none.
+    fn start_preflight(&mut self) {
+        if self.progress.is_busy() || self.preflight_receiver.is_some() {
+            return;
+        }
+        self.confirmation_dialog = false;
+        self.preflight_receiver = Some(self.spawn_dry_run(self.files_to_delete.clone(), None));
+    }
+
+    fn finish_preflight(&mut self, report: DryRunReport) {
+        if report.removable.len() + report.refused.len() < self.files_to_delete.len() {
+            info!("Cleanup cancelled during its preflight");
+            self.files_to_delete.clear();
+            self.commands_to_run.clear();
+        } else if report.refused.is_empty() {
+            self.execute_cleanup();
+        } else {
+            info!("Preflight found {} files the cleanup would fail on", report.refused.len());
+            self.preflight_report = Some(report);
+        }
+    }
+
+    fn spawn_dry_run(&mut self, files: Vec<PathBuf>, target: Option<u64>) -> mpsc::Receiver<DryRunReport> {
+        let identities = self.scan_results.as_ref()
+            .map(|results| results.identities_of(&files))
+            .unwrap_or_default();
+        let mut allowed_roots: Vec<PathBuf> = self.scan_roots.clone();
+        if let Some(analysis) = &self.analysis {
+            allowed_roots.push(analysis.path.clone());
+        }
+        let use_trash = self.config.use_trash;
+        let use_quarantine = self.config.use_quarantine;
+        let wipe_passes = if self.secure_wipe { self.config.secure_wipe_passes } else { 0 };
+        let progress = self.progress.start_task();
+        self.progress_error = None;
+        
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let report = Cleaner::new(use_trash)
+                .with_quarantine(use_quarantine)
+                .with_secure_wipe(wipe_passes)
+                .with_target(target)
+                .with_allowed_roots(&allowed_roots)
+                .with_expected_identities(identities)
+                .dry_run(&files, progress);
+            let _ = sender.send(report);
+        });
+        receiver
+    }
+
+    fn execute_cleanup(&mut self) {
+        if self.files_to_delete.is_empty() && self.commands_to_run.is_empty() {
+            return;
+        }
+
+        self.is_cleaning = true;
+        self.confirmation_dialog = false;
+        
+        let files = self.files_to_delete.clone();
+        let commands = std::mem::take(&mut self.commands_to_run);
+        let progress = self.progress.start_task();
+        self.progress_error = None;
+        let use_trash = self.config.use_trash;
+        let use_quarantine = self.config.use_quarantine;
+        let checksum_manifest = self.config.checksum_manifest;
+        let sign_record = self.config.sign_cleanup_records;
+        let webhooks = self.config.webhooks.clone();
+        let note = std::mem::take(&mut self.cleanup_note);
+        let wipe_passes = if std::mem::take(&mut self.secure_wipe) { self.config.secure_wipe_passes } else { 0 };
+        let threads = self.config.cleanup_threads;
+        let trash_fallback = self.config.trash_fallback;
+        
+        let identities = self.scan_results.as_ref()
+            .map(|results| results.identities_of(&files))
+            .unwrap_or_default();
+        
+        // Files may only be deleted inside the scanned directory, or the file
+        // being analyzed when deleting from its analysis card
+        let mut allowed_roots: Vec<PathBuf> = self.scan_roots.clone();
+        if let Some(analysis) = &self.analysis {
+            allowed_roots.push(analysis.path.clone());
+        }
+        
+        let target = self.cleanup_target();
+        let in_order = self.cleanup_reordered;
+        #[cfg(windows)]
+        let ownership_to_take = std::mem::take(&mut self.ownership_to_take);
+        
+        thread::spawn(move || {
+            #[cfg(windows)]
+            crate::acl::take_ownership_of(&ownership_to_take);
+            let cleaner = Cleaner::new(use_trash)
+                .with_quarantine(use_quarantine)
+                .with_checksum_manifest(checksum_manifest)
+                .with_secure_wipe(wipe_passes)
+                .with_threads(threads)
+                .with_trash_fallback(trash_fallback)
+                .with_target(target)
+                .with_order(in_order)
+                .with_allowed_roots(&allowed_roots)
+                .with_expected_identities(identities)
+                .with_note(&note);
+            match cleaner.clean_files(&files, progress.clone()) {
+                Ok(report) => {
+                    if sign_record && !report.files.is_empty() {
+                        if let Err(e) = audit::write_signed_report(&report) {
+                            error!("Failed to write signed cleanup record: {}", e);
+                        }
+                    }
+                    
+                    let command_outputs = if progress.is_cancelled() {
+                        Vec::new()
+                    } else {
+                        cleaner.run_commands(&commands)
+                    };
+                    notifier::notify(
+                        &webhooks,
+                        NotificationEvent::CleanupComplete,
+                        if report.cancelled { "Cleanup cancelled" } else { "Cleanup complete" },
+                        &format!(
+                            "Cleaned {} files, {}{}",
+                            report.files.len(),
+                            formatting::format_size(report.cleaned_bytes()),
+                            report.note.as_ref().map(|note| format!("\nNote: {}", note)).unwrap_or_default()
+                        ),
+                    );
+                    progress.set_cleanup_complete(report, command_outputs);
+                }
+                Err(e) => {
+                    error!("Cleanup failed: {}", e);
+                    notifier::notify(&webhooks, NotificationEvent::Failure, "Cleanup failed", &e.to_string());
+                    progress.set_error("Cleanup", e);
+                }
+            }
+        });
+    }
+
+    /// Opens the system folder picker. The first folder picked becomes the scan
+    /// path and the others are scanned along with it.
+    fn browse_scan_folders(&mut self) {
+        let Some(folders) = rfd::FileDialog::new()
+            .set_title("Choose folders to scan")
+            .set_directory(&self.scan_path)
+            .pick_folders()
+        else {
+            return;
+        };
+        
+        let mut folders = folders.into_iter().map(|folder| folder.to_string_lossy().to_string());
+        let Some(first) = folders.next() else {
+            return;
+        };
+        info!("Picked {} to scan", first);
+        self.scan_path = first;
+        for folder in folders {
+            if folder != self.scan_path && !self.extra_scan_paths.contains(&folder) {
+                self.extra_scan_paths.push(folder);
+            }
+        }
+    }
+
+    fn draw_scan_section(&mut self, ui: &mut Ui) {
+        ui.heading("Disk Scanner");
+        
+        ui.horizontal(|ui| {
+            ui.label("Scan Path:");
+            ui.text_edit_singleline(&mut self.scan_path);
+            
+            if ui.button("Browse").on_hover_text("Pick one or more folders to scan").clicked() {
+                self.browse_scan_folders();
+            }
+            
+            if ui.button("Add Folder").on_hover_text("Scan another folder in the same session").clicked() {
+                self.extra_scan_paths.push(String::new());
+            }
+        });
+        
+        let mut removed = None;
+        for (index, path) in self.extra_scan_paths.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label("Also Scan:");
+                ui.text_edit_singleline(path);
+                if ui.button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.extra_scan_paths.remove(index);
+        }
+        
+        if let Some(size) = self.dir_sizes.get(Path::new(&self.scan_path)) {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Last known size: {} in {} files{}",
+                    formatting::format_size(size.size),
+                    size.files,
+                    if self.dir_sizes_receiver.is_some() { " (refreshing...)" } else { "" }
+                ));
+                if ui.button("Top Directories").on_hover_text("Where the space went, folder by folder").clicked() {
+                    self.top_dirs_path = Some(PathBuf::from(&self.scan_path));
+                    self.top_dirs = None;
+                }
+            });
+        }
+        
+        ui.horizontal(|ui| {
+            if ui.button("Start Scan").clicked() && !self.is_scanning {
+                self.request_scan();
+            }
+            
+            if ui.button("Find Duplicates").clicked() && self.scan_results.is_some() {
+                self.request_duplicate_scan();
+            }
+            
+            if ui.add_enabled(self.scan_results.is_some(), egui::Button::new("Compare Large Files"))
+                .on_hover_text("How much content two large files such as VM images or backups have in common")
+                .clicked()
+            {
+                self.show_chunk_comparison = true;
+            }
+            
+            if ui.button("Quarantine").on_hover_text("Restore files cleaned into the quarantine").clicked() {
+                self.quarantine_runs = quarantine::load_runs();
+                self.restore_failures.clear();
+                self.show_quarantine = true;
+            }
+            
+            if ui.button("Mini Widget").on_hover_text("Shrink to a small window that stays on top").clicked() {
+                self.set_mini_mode(ui.ctx(), true);
+            }
+            
+            if ui.button("Disk Activity").on_hover_text("Which programs are writing to disk right now").clicked() {
+                let path = (!self.scan_path.is_empty()).then(|| PathBuf::from(&self.scan_path));
+                self.disk_activity = Some(DiskActivityMonitor::new(path.as_deref()));
+                self.disk_activity_sample = DiskActivitySample::default();
+                self.disk_activity_process = None;
+            }
+            
+            if ui.button("History").on_hover_text("Past cleanups and the space they reclaimed").clicked() {
+                self.load_history();
+                self.show_history = true;
+            }
+            
+            if ui.button("Wine Prefixes").clicked() {
+                self.find_wine_prefixes();
+                self.show_wine_prefixes = true;
+            }
+            
+            if ui.button("System Storage").on_hover_text("Space the system manages itself, such as restore points and phone backups").clicked() {
+                self.system_storage = Some(system_storage::managed_areas());
+                self.system_storage_error = None;
+            }
+            
+            if ui.button("Settings").clicked() {
+                self.show_settings = !self.show_settings;
+                // Settings are saved as their window closes
+                if !self.show_settings {
+                    self.save_config();
+                }
+                self.storage_usage = None;
+                self.storage_freed = None;
+            }
+        });
+        
+        self.draw_drive_suggestions(ui);
+        
+        if let Some(health) = &self.drive_health {
+            let used = health.total_space.saturating_sub(health.available_space);
+            let text = format!(
+                "Drive {}: {} of {} used. {}",
+                health.device,
+                formatting::format_size(used),
+                formatting::format_size(health.total_space),
+                health.summary()
+            );
+            if health.is_failing() {
+                ui.colored_label(Color32::RED, format!("{}. Back up this drive soon.", text));
+            } else {
+                ui.label(text);
+            }
+        }
+        
+        if !self.foreign_volumes.is_empty() {
+            let mut scan = None;
+            ui.collapsing("Other environments", |ui| {
+                for volume in &self.foreign_volumes {
+                    ui.horizontal(|ui| {
+                        ui.label(volume.path.to_string_lossy());
+                        
+                        if volume.is_scannable() {
+                            if ui.add_enabled(!self.is_scanning, egui::Button::new("Scan")).clicked() {
+                                scan = Some(volume.path.to_string_lossy().to_string());
+                            }
+                        } else if let Ok(metadata) = std::fs::metadata(&volume.path) {
+                            ui.label(formatting::format_size(metadata.len()));
+                        }
+                    });
+                    ui.colored_label(Color32::YELLOW, volume.warning());
+                }
+            });
+            if let Some(path) = scan {
+                self.scan_path = path;
+                self.start_scan();
+            }
+        }
+    }
+
+    /// Suggests settings that suit the type of drive about to be scanned.
+    fn draw_drive_suggestions(&mut self, ui: &mut Ui) {
+        if self.drive_profile.as_ref().is_none_or(|profile| profile.path != self.scan_path) {
+            self.drive_profile = Some(DriveProfile::detect(&self.scan_path));
+        }
+        let Some(profile) = &self.drive_profile else {
+            return;
+        };
+        
+        if profile.is_fat {
+            ui.colored_label(
+                Color32::YELLOW,
+                "FAT/exFAT drive: it has no trash, so files cleaned with the trash enabled are moved to a \
+                 quarantine folder at its root instead. Its file times are imprecise, so ages are compared \
+                 with a day of margin.",
+            );
+        }
+        
+        if self.dismissed_drive_suggestions.as_ref() == Some(&self.scan_path) {
+            return;
+        }
+        let drive_type = profile.drive_type;
+        
+        let suggestions = drive_profile::suggestions(drive_type, &self.config);
+        if suggestions.is_empty() {
+            return;
+        }
+        
+        ui.group(|ui| {
+            ui.label(format!("This path is on {}. Suggested settings:", drive_type.description()));
+            for suggestion in &suggestions {
+                ui.label(format!("• {}", suggestion.description()));
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Apply").clicked() {
+                    for suggestion in &suggestions {
+                        suggestion.apply(&mut self.config);
+                    }
+                    self.save_config();
+                }
+                if ui.button("Dismiss").clicked() {
+                    self.dismissed_drive_suggestions = Some(self.scan_path.clone());
+                }
+            });
+        });
+    }
+
+    fn draw_progress(&mut self, ui: &mut Ui) {
+        for task in self.progress.tasks() {
+            ui.push_id(Arc::as_ptr(&task.tracker), |ui| draw_task_progress(ui, task));
+        }
+        
+        if self.progress.is_busy() {
+            return;
+        }
+        if let Some((action, error)) = &self.progress_error {
+            ui.colored_label(Color32::RED, format!("{} failed: {}", action, error));
+            if let Some(hint) = error.hint() {
+                ui.label(hint);
+            }
+            if error.is_retryable() && ui.button("Retry").clicked() {
+                match *action {
+                    "Scan" => self.start_scan(),
+                    "Duplicate scan" => self.request_duplicate_scan(),
+                    _ => self.start_preflight(),
+                }
+            }
         }
     }
 
     fn draw_results(&mut self, ui: &mut Ui) {
+        let mut batch_action = None;
+        let mut baseline_action = None;
+        
         if let Some(ref results) = self.scan_results {
             ui.heading("Scan Results");
             
+            if let Some(saved_at) = self.restored_at {
+                ui.label(format!(
+                    "Results of the previous session, scanned {}. Scan again to refresh them.",
+                    formatting::format_date(saved_at)
+                ));
+            }
+            
+            if results.cancelled {
+                ui.colored_label(Color32::YELLOW, "The scan was cancelled. These results only cover the files reached before it stopped.");
+            }
+            
+            ui.horizontal(|ui| {
+                match &self.baseline {
+                    Some(baseline) => {
+                        ui.label(format!(
+                            "Showing files new since the baseline of {} taken {} ({} older files hidden)",
+                            baseline.scan_paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", "),
+                            formatting::format_date(baseline.saved_at),
+                            self.baseline_hidden
+                        ));
+                        if !results.cancelled && ui.button("Update Baseline").clicked() {
+                            baseline_action = Some(BaselineAction::Set);
+                        }
+                        if ui.button("Clear Baseline").clicked() {
+                            baseline_action = Some(BaselineAction::Clear);
+                        }
+                    }
+                    None => {
+                        if !results.cancelled && ui.button("Set as Baseline")
+                            .on_hover_text("Treat these files as the clean state of this machine, later scans only show what is new")
+                            .clicked()
+                        {
+                            baseline_action = Some(BaselineAction::Set);
+                        }
+                    }
+                }
+            });
+            
             // Summary
             ui.label(format!("Total files scanned: {}", results.total_files));
-            ui.label(format!("Total size: {}", humansize::format_size(results.total_size, humansize::DECIMAL)));
+            ui.label(format!("Total size: {}", formatting::format_size(results.total_size)));
+            if results.roots.len() > 1 {
+                for root in &results.roots {
+                    ui.label(format!(
+                        "      {}: {} files, {}",
+                        root.path.display(),
+                        root.files,
+                        formatting::format_size(root.size)
+                    ));
+                }
+            }
+            
+            if let Some(coverage) = &results.coverage {
+                ui.label(format!(
+                    "Covered {:.0}% of the used space on {} ({} of {})",
+                    coverage.percent(),
+                    coverage.mount_point.display(),
+                    formatting::format_size(coverage.bytes_seen),
+                    formatting::format_size(coverage.volume_used)
+                ));
+                if coverage.inaccessible_entries > 0 {
+                    ui.colored_label(Color32::YELLOW, format!(
+                        "{} entries could not be read (permission denied or removed during the scan)",
+                        coverage.inaccessible_entries
+                    ));
+                }
+            }
             
             ui.separator();
             
@@ -226,82 +1550,1458 @@ impl DiskCleanerApp {
             ui.heading("File Categories");
             
             let mut total_selected_size = 0u64;
+            let mut total_selected_disk_size = 0u64;
+            let mut clicked_row = None;
+            let mut location_export = None;
+            
+            let mut preselection_changed = false;
             
             for category in FileCategory::all() {
+                if !self.config.is_category_enabled(category) {
+                    continue;
+                }
+                
                 if let Some(files) = results.files_by_category.get(&category) {
-                    let category_size: u64 = files.iter()
-                        .filter_map(|path| std::fs::metadata(path).ok())
-                        .map(|metadata| metadata.len())
-                        .sum();
+                    let stats = results.category_stats.get(&category).copied().unwrap_or_default();
+                    let (category_size, category_disk_size) = (stats.size, stats.disk_size);
                     
                     ui.horizontal(|ui| {
                         let mut selected = self.selected_categories.get(&category).copied().unwrap_or(false);
                         if ui.checkbox(&mut selected, "").changed() {
                             self.selected_categories.insert(category, selected);
+                            // The category checkbox ticks or unticks every file in it
+                            self.file_choices.remove(&category);
+                            if category == FileCategory::DevArtifacts {
+                                self.artifact_choices.clear();
+                            }
+                            untick_locked_files(&mut self.file_choices, results, Some(category));
+                            // Remember the choice as the default for the next start
+                            self.config.preselected_categories.retain(|preselected| *preselected != category);
+                            if selected {
+                                self.config.preselected_categories.push(category);
+                            }
+                            preselection_changed = true;
                         }
                         
-                        ui.label(format!("{:?}", category));
+                        ui.label(self.config.category_name(category));
                         ui.label(format!("{} files", files.len()));
-                        ui.label(format!("{}", humansize::format_size(category_size, humansize::DECIMAL)));
+                        let changed = self.file_choices.get(&category).map_or(0, |choices| {
+                            // Files left unticked because cleaning them would fail were not picked by hand
+                            choices.iter()
+                                .filter(|(file, ticked)| **ticked != selected
+                                    && (**ticked || !results.locked_files.contains_key(&FileId::of(file))))
+                                .count()
+                        });
+                        if changed > 0 {
+                            ui.label(format!("({} {} by hand)", changed, if selected { "unticked" } else { "ticked" }));
+                        }
+                        ui.label(formatting::format_size(category_size));
+                        if category_disk_size != category_size {
+                            ui.label(format!("({} on disk)", formatting::format_size(category_disk_size)));
+                        }
+                        
+                        ui.menu_button("Open in File Manager", |ui| {
+                            if cfg!(target_os = "linux") && ui.button("Add Top Folders to Bookmarks")
+                                .on_hover_text("Bookmark the folders holding most of this category in the file manager's sidebar")
+                                .clicked()
+                            {
+                                location_export = Some((category, LocationExport::Bookmarks));
+                                ui.close_menu();
+                            }
+                            if ui.button("Save Shortcuts to Top Folders...")
+                                .on_hover_text("Write a shortcut to each folder holding most of this category into a folder you choose")
+                                .clicked()
+                            {
+                                location_export = Some((category, LocationExport::Shortcuts));
+                                ui.close_menu();
+                            }
+                        });
                     });
                     
-                    if self.selected_categories.get(&category).copied().unwrap_or(false) {
+                    // Only the rows scrolled into view are built, and only the first
+                    // pages of a category are listed until the user asks for more
+                    egui::CollapsingHeader::new("Files")
+                        .id_source(category)
+                        .show(ui, |ui| {
+                            let shown = self.file_list_limits.get(&category)
+                                .copied()
+                                .unwrap_or(file_list_page())
+                                .min(files.len());
+                            let row_height = ui.text_style_height(&egui::TextStyle::Button)
+                                + 2.0 * ui.spacing().button_padding.y
+                                + ui.spacing().item_spacing.y;
+                            
+                            egui::ScrollArea::vertical()
+                                .id_source(("file_list", category))
+                                .max_height(FILE_LIST_HEIGHT)
+                                .auto_shrink([false, true])
+                                .show_rows(ui, row_height, shown, |ui, rows| {
+                                    for index in rows {
+                                        let file = &files[index];
+                                        let mut text = file.display().to_string();
+                                        if self.config.is_path_protected(file) {
+                                            text.push_str("  (protected)");
+                                        }
+                                        if let Some(lock) = results.locked_files.get(&FileId::of(file)) {
+                                            text.push_str(&format!("  ({})", lock.label()));
+                                        }
+                                        
+                                        ui.horizontal(|ui| {
+                                            let mut ticked = is_file_ticked(&self.file_choices, &self.artifact_choices, &self.selected_categories, category, file);
+                                            if ui.checkbox(&mut ticked, "").on_hover_text("Clean this file").changed() {
+                                                self.file_choices.entry(category).or_default().insert(file.clone(), ticked);
+                                            }
+                                            
+                                            let selected = self.selection.contains(file);
+                                            if ui.selectable_label(selected, text).clicked() {
+                                                clicked_row = Some((category, index));
+                                            }
+                                        });
+                                    }
+                                });
+                            
+                            if shown < files.len() {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("Showing {} of {} files", shown, files.len()));
+                                    if ui.button(format!("Show {} More", file_list_page().min(files.len() - shown))).clicked() {
+                                        self.file_list_limits.insert(category, shown + file_list_page());
+                                    }
+                                });
+                            }
+                        });
+                    
+                    if category == FileCategory::DevArtifacts && !results.dev_artifacts.is_empty() {
+                        egui::CollapsingHeader::new(format!("{} project folders", results.dev_artifacts.len()))
+                            .id_source("dev_artifacts")
+                            .show(ui, |ui| {
+                                egui::ScrollArea::vertical()
+                                    .id_source("dev_artifact_list")
+                                    .max_height(FILE_LIST_HEIGHT)
+                                    .show(ui, |ui| {
+                                        for artifact in &results.dev_artifacts {
+                                            ui.horizontal(|ui| {
+                                                let mut ticked = default_tick(&self.artifact_choices, &self.selected_categories, category, &artifact.path);
+                                                if ui.checkbox(&mut ticked, "").on_hover_text("Clean this whole folder").changed() {
+                                                    self.artifact_choices.insert(artifact.path.clone(), ticked);
+                                                    // Ticking the folder ticks every file in it
+                                                    if let Some(choices) = self.file_choices.get_mut(&category) {
+                                                        choices.retain(|file, _| !file.starts_with(&artifact.path));
+                                                    }
+                                                    untick_locked_files(&mut self.file_choices, results, Some(category));
+                                                }
+                                                ui.label(formatting::format_size(artifact.size));
+                                                ui.label(artifact.path.display().to_string());
+                                                ui.label(format!("({} files)", artifact.files));
+                                            });
+                                        }
+                                    });
+                            });
+                    }
+                    
+                    if category == FileCategory::PackageCaches {
+                        let mut managers: Vec<(&String, &u64)> = results.package_cache_sizes.iter().collect();
+                        managers.sort_by_key(|(_, size)| std::cmp::Reverse(**size));
+                        for (manager, size) in managers {
+                            ui.label(format!("      {} {}", formatting::format_size(*size), manager));
+                        }
+                    }
+                    
+                    if category == FileCategory::Downloads {
+                        for age in DownloadAge::all() {
+                            let Some(bucket) = results.download_buckets.get(&age) else {
+                                continue;
+                            };
+                            ui.horizontal(|ui| {
+                                ui.label(format!(
+                                    "      {} {} ({} files)",
+                                    formatting::format_size(bucket.size),
+                                    age.label(),
+                                    bucket.files.len()
+                                ));
+                                for (label, ticked) in [("Tick All", true), ("Untick All", false)] {
+                                    if ui.small_button(label).clicked() {
+                                        let choices = self.file_choices.entry(category).or_default();
+                                        choices.extend(bucket.files.iter().map(|file| (file.clone(), ticked)));
+                                        untick_locked_files(&mut self.file_choices, results, Some(category));
+                                    }
+                                }
+                                if ui.small_button("Select").on_hover_text("Select these downloads to exclude, protect or delete them").clicked() {
+                                    self.selection.take();
+                                    self.selection.files.extend(bucket.files.iter().map(|file| FileId::of(file)));
+                                }
+                            });
+                        }
+                    }
+                    
+                    if category == FileCategory::CacheFiles {
+                        for cost in RebuildCost::all() {
+                            if let Some(size) = results.cache_rebuild_sizes.get(&cost) {
+                                ui.label(format!(
+                                    "      {} {}",
+                                    formatting::format_size(*size),
+                                    cost.description()
+                                ));
+                            }
+                        }
+                    }
+                    
+                    let selected = self.selected_categories.get(&category).copied().unwrap_or(false);
+                    if selected {
                         total_selected_size += category_size;
+                        total_selected_disk_size += category_disk_size;
+                    }
+                    // Build folders ticked as a whole already know their size
+                    if category == FileCategory::DevArtifacts {
+                        for artifact in &results.dev_artifacts {
+                            if self.artifact_choices.get(&artifact.path).is_some_and(|ticked| *ticked != selected) {
+                                if selected {
+                                    total_selected_size = total_selected_size.saturating_sub(artifact.size);
+                                    total_selected_disk_size = total_selected_disk_size.saturating_sub(artifact.size);
+                                } else {
+                                    total_selected_size += artifact.size;
+                                    total_selected_disk_size += artifact.size;
+                                }
+                            }
+                        }
+                    }
+                    // Files picked by hand are few, their sizes are looked up as needed
+                    for (file, ticked) in self.file_choices.get(&category).into_iter().flatten() {
+                        if *ticked == default_tick(&self.artifact_choices, &self.selected_categories, category, file) {
+                            continue;
+                        }
+                        let size = std::fs::symlink_metadata(file).map_or(0, |metadata| metadata.len());
+                        if !*ticked {
+                            total_selected_size = total_selected_size.saturating_sub(size);
+                            total_selected_disk_size = total_selected_disk_size.saturating_sub(size);
+                        } else {
+                            total_selected_size += size;
+                            total_selected_disk_size += size;
+                        }
                     }
                 }
             }
             
-            ui.separator();
-            ui.label(format!("Selected for cleaning: {}", humansize::format_size(total_selected_size, humansize::DECIMAL)));
+            if preselection_changed {
+                self.save_config();
+            }
             
-            ui.horizontal(|ui| {
-                if ui.button("Clean Selected").clicked() {
-                    self.prepare_cleanup();
+            if let Some((category, index)) = clicked_row {
+                let modifiers = ui.input(|input| input.modifiers);
+                if let Some(files) = results.files_by_category.get(&category) {
+                    self.selection.click(category, files, index, modifiers);
                 }
-                
-                if ui.button("View Duplicates").clicked() {
-                    self.show_duplicates = !self.show_duplicates;
+            }
+            
+            if let Some((category, export)) = location_export {
+                let files = results.files_by_category.get(&category).map(Vec::as_slice).unwrap_or_default();
+                let directories = bookmarks::top_directories(files, bookmarks::TOP_DIRECTORIES);
+                let name = self.config.category_name(category);
+                let exported = match export {
+                    LocationExport::Bookmarks => Some(bookmarks::add_to_file_manager(&directories, &name)),
+                    LocationExport::Shortcuts => rfd::FileDialog::new()
+                        .set_title(format!("Save shortcuts to the top {} folders", name))
+                        .pick_folder()
+                        .map(|folder| bookmarks::write_shortcuts(&directories, &folder, &name)),
+                };
+                match exported {
+                    Some(Ok(count)) => self.report_message = Some(format!("Exported {} folders of {}", count, name)),
+                    Some(Err(e)) => self.report_message = Some(format!("Could not export the folders of {}: {}", name, e)),
+                    None => {}
                 }
-            });
-        }
-    }
-
-    fn draw_duplicates_window(&mut self, ctx: &Context) {
-        if self.show_duplicates {
-            egui::Window::new("Duplicate Files")
-                .default_size(Vec2::new(600.0, 400.0))
-                .show(ctx, |ui| {
-                    if self.duplicates.is_empty() {
-                        ui.label("No duplicate files found. Run duplicate scan first.");
-                    } else {
-                        ui.label(format!("Found {} groups of duplicate files", self.duplicates.len()));
-                        
-                        egui::ScrollArea::vertical().show(ui, |ui| {
-                            for (group_idx, group) in self.duplicates.iter().enumerate() {
-                                ui.group(|ui| {
-                                    ui.label(format!("Group {}: {} files", group_idx + 1, group.len()));
+            }
+            
+            if !self.selection.files.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} files selected:", self.selection.files.len()));
+                    
+                    if ui.button("Exclude").clicked() {
+                        batch_action = Some(BatchAction::Exclude);
+                    }
+                    
+                    if ui.button("Protect").clicked() {
+                        batch_action = Some(BatchAction::Protect);
+                    }
+                    
+                    ui.menu_button("Move to Category", |ui| {
+                        for category in FileCategory::all() {
+                            if ui.button(self.config.category_name(category)).clicked() {
+                                batch_action = Some(BatchAction::MoveTo(category));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    
+                    if ui.button("Delete Selected").clicked() {
+                        batch_action = Some(BatchAction::Delete);
+                    }
+                    
+                    if ui.button("Copy Paths").clicked() {
+                        copy_paths(ui, &results.paths_of(&self.selection.files));
+                    }
+                    
+                    if ui.button("Clear Selection").clicked() {
+                        self.selection.take();
+                    }
+                    
+                    ui.checkbox(&mut self.remember_as_rule, "Remember for future scans")
+                        .on_hover_text("Protect or move all files with the same extension in the same folder from now on");
+                });
+            }
+            
+            if results.recently_used_files > 0 {
+                ui.label(format!(
+                    "{} old files and downloads were left out because you opened them recently",
+                    results.recently_used_files
+                ));
+            }
+            
+            if results.temps_in_use > 0 {
+                ui.label(format!(
+                    "{} temporary files were left out because the program using them is still running",
+                    results.temps_in_use
+                ));
+            }
+            
+            if !results.locked_files.is_empty() {
+                ui.label(format!(
+                    "{} read-only, immutable or system files were left unticked, cleaning them would fail",
+                    results.locked_files.len()
+                ));
+            }
+            
+            if results.placeholder_files > 0 {
+                ui.label(format!(
+                    "{} online-only files ({}) in cloud sync folders were skipped, they take no space on this computer",
+                    results.placeholder_files,
+                    formatting::format_size(results.placeholder_size)
+                ));
+            }
+            
+            if !results.download_sources.is_empty() {
+                ui.label(format!(
+                    "{} downloads have a known source URL and can be downloaded again",
+                    results.download_sources.len()
+                ));
+            }
+            
+            ui.separator();
+            ui.label(format!("Selected for cleaning: {}", formatting::format_size(total_selected_size)));
+            if total_selected_disk_size < total_selected_size {
+                ui.label(format!(
+                    "Compressed or sparse files: cleaning frees about {} on disk",
+                    formatting::format_size(total_selected_disk_size)
+                ));
+            }
+            
+            ui.horizontal(|ui| {
+                if ui.button("Clean Selected").clicked() {
+                    self.prepare_cleanup();
+                }
+                
+                if ui.button("View Duplicates").clicked() {
+                    self.show_duplicates = !self.show_duplicates;
+                }
+                
+                if ui.button("Find Old Versions").clicked() {
+                    self.find_version_families();
+                    self.show_versions = true;
+                }
+                
+                if ui.button("Find Photo Copies").clicked() {
+                    self.find_photo_copies();
+                    self.show_photos = true;
+                }
+                
+                if ui.button("Find Duplicate Songs").clicked() {
+                    self.find_duplicate_songs();
+                    self.show_songs = true;
+                }
+                
+                if ui.button("Find Similar Documents").clicked() {
+                    self.find_similar_documents();
+                    self.show_documents = true;
+                }
+                
+                if ui.button("Save Report").clicked() {
+                    self.save_report();
+                }
+                
+                if ui.button("Export...").on_hover_text("Save every file of the results as JSON or CSV").clicked() {
+                    self.export_results();
+                }
+            });
+            
+            if let Some(message) = &self.report_message {
+                ui.label(message);
+            }
+        }
+        
+        if let Some(action) = batch_action {
+            self.apply_batch_action(action);
+        }
+        
+        if let Some(action) = baseline_action {
+            self.apply_baseline_action(action);
+        }
+    }
+
+    fn draw_failed_files(&mut self, ui: &mut Ui) {
+        if self.failed_files.is_empty() {
+            return;
+        }
+        
+        ui.separator();
+        let retryable: Vec<PathBuf> = self.failed_files.iter()
+            .filter(|(_, error)| error.is_retryable())
+            .map(|(file, _)| file.clone())
+            .collect();
+        ui.horizontal(|ui| {
+            ui.colored_label(Color32::RED, format!("{} files could not be cleaned", self.failed_files.len()));
+            if ui.small_button("Copy Paths").clicked() {
+                let files: Vec<PathBuf> = self.failed_files.iter().map(|(file, _)| file.clone()).collect();
+                copy_paths(ui, &files);
+            }
+            if !retryable.is_empty() && ui.small_button(format!("Retry {} Files", retryable.len()))
+                .on_hover_text("Try the files that were in use or on a busy drive again")
+                .clicked()
+            {
+                self.confirm_deletion(retryable);
+            }
+        });
+        
+        // One line of advice per kind of failure, not per file
+        let mut hints: Vec<&str> = self.failed_files.iter().filter_map(|(_, error)| error.hint()).collect();
+        hints.sort_unstable();
+        hints.dedup();
+        for hint in hints {
+            ui.label(hint);
+        }
+        
+        egui::CollapsingHeader::new("Details")
+            .id_source("failed_files")
+            .show(ui, |ui| {
+                for (file, reason) in self.failed_files.iter().take(file_list_page()) {
+                    ui.label(format!("{}: {}", file.display(), reason));
+                }
+                if self.failed_files.len() > file_list_page() {
+                    ui.label(format!("and {} more", self.failed_files.len() - file_list_page()));
+                }
+            });
+    }
+
+    fn draw_command_report(&self, ui: &mut Ui) {
+        if self.command_outputs.is_empty() {
+            return;
+        }
+        
+        ui.separator();
+        ui.heading("Custom Commands");
+        
+        for result in &self.command_outputs {
+            let color = if result.success { Color32::GREEN } else { Color32::RED };
+            egui::CollapsingHeader::new(egui::RichText::new(&result.command).color(color))
+                .id_source(&result.command)
+                .show(ui, |ui| {
+                    ui.monospace(&result.output);
+                });
+        }
+    }
+
+    fn draw_duplicates_window(&mut self, ctx: &Context) {
+        if self.show_duplicates {
+            egui::Window::new("Duplicate Files")
+                .default_size(Vec2::new(600.0, 400.0))
+                .show(ctx, |ui| {
+                    if self.duplicates.is_empty() {
+                        ui.label("No duplicate files found. Run duplicate scan first.");
+                    } else {
+                        ui.label(format!("Found {} groups of duplicate files", self.duplicates.len()));
+                        if self.duplicates_cancelled {
+                            ui.colored_label(Color32::YELLOW, "The search was cancelled. Only files hashed before it stopped are grouped, some copies may be missing.");
+                        }
+                        
+                        let mut keep = None;
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            for (group_idx, group) in self.duplicates.iter().enumerate() {
+                                let kept = self.kept_file(group);
+                                ui.group(|ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.label(format!("Group {}: {} files", group_idx + 1, group.len()));
+                                        if ui.small_button("Copy Paths").clicked() {
+                                            copy_paths(ui, group);
+                                        }
+                                    });
                                     
                                     for (file_idx, file) in group.iter().enumerate() {
-                                        let color = if file_idx == 0 { 
+                                        let color = if file_idx == kept { 
                                             Color32::GREEN 
                                         } else { 
                                             Color32::RED 
                                         };
                                         
-                                        ui.colored_label(color, file.to_string_lossy());
+                                        ui.colored_label(color, unicode_names::display(file));
                                         
-                                        if file_idx == 0 {
+                                        if file_idx == kept {
                                             ui.label("  (will be kept)");
                                         } else {
-                                            ui.label("  (will be deleted)");
+                                            ui.horizontal(|ui| {
+                                                ui.label("  (will be deleted)");
+                                                if ui.small_button("Keep This").clicked() {
+                                                    keep = Some((group_idx, file_idx));
+                                                }
+                                            });
                                         }
                                     }
                                 });
                                 ui.separator();
                             }
                         });
+                        
+                        if let Some((group_idx, file_idx)) = keep {
+                            let group = &self.duplicates[group_idx];
+                            for file in group {
+                                self.kept_duplicates.remove(&FileId::of(file));
+                            }
+                            self.kept_duplicates.insert(FileId::of(&group[file_idx]));
+                        }
+                    }
+                });
+        }
+    }
+
+    fn draw_versions_window(&mut self, ctx: &Context) {
+        if !self.show_versions {
+            return;
+        }
+        
+        let mut open = true;
+        egui::Window::new("Old Versions")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 400.0))
+            .show(ctx, |ui| {
+                if self.version_families.is_empty() {
+                    ui.label("No files with several versions found.");
+                    return;
+                }
+                
+                ui.label(format!("Found {} files with several versions", self.version_families.len()));
+                
+                if ui.button("Keep Newest Version Only").clicked() {
+                    let files = all_but_first(&self.version_families);
+                    self.confirm_deletion(files);
+                }
+                
+                draw_keep_first_groups(ui, &self.version_families, "newest, will be kept", "older version");
+            });
+        
+        if !open {
+            self.show_versions = false;
+        }
+    }
+
+    fn draw_photos_window(&mut self, ctx: &Context) {
+        if !self.show_photos {
+            return;
+        }
+        
+        let mut open = true;
+        egui::Window::new("Photo Copies")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 400.0))
+            .show(ctx, |ui| {
+                let policy = self.config.photo_keep_policy;
+                egui::ComboBox::from_label("Keep")
+                    .selected_text(format!("{:?}", self.config.photo_keep_policy))
+                    .show_ui(ui, |ui| {
+                        for option in PhotoKeepPolicy::all() {
+                            ui.selectable_value(&mut self.config.photo_keep_policy, option, format!("{:?}", option));
+                        }
+                    });
+                if policy != self.config.photo_keep_policy {
+                    PhotoFinder::apply_keep_policy(&mut self.photo_groups, self.config.photo_keep_policy);
+                }
+                
+                if self.photo_receiver.is_some() {
+                    ui.spinner();
+                    ui.label("Reading photo metadata...");
+                    return;
+                }
+                
+                if self.photo_groups.is_empty() {
+                    ui.label("No photos saved in several formats found.");
+                    return;
+                }
+                
+                ui.label(format!("Found {} photos saved in several formats", self.photo_groups.len()));
+                
+                if ui.button("Delete Other Formats").clicked() {
+                    let files = all_but_first(&self.photo_groups);
+                    self.confirm_deletion(files);
+                }
+                
+                draw_keep_first_groups(ui, &self.photo_groups, "will be kept", "other format");
+            });
+        
+        if !open {
+            self.show_photos = false;
+        }
+    }
+
+    fn draw_songs_window(&mut self, ctx: &Context) {
+        if !self.show_songs {
+            return;
+        }
+        
+        let mut open = true;
+        egui::Window::new("Duplicate Songs")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 400.0))
+            .show(ctx, |ui| {
+                if self.songs_receiver.is_some() {
+                    ui.spinner();
+                    ui.label("Reading song tags...");
+                    return;
+                }
+                
+                if self.song_groups.is_empty() {
+                    ui.label("No songs with several copies found.");
+                    return;
+                }
+                
+                ui.label(format!("Found {} songs with several copies", self.song_groups.len()));
+                
+                if ui.button("Keep Highest Bitrate Only").clicked() {
+                    let files = all_but_first(&self.song_groups);
+                    self.confirm_deletion(files);
+                }
+                
+                draw_keep_first_groups(ui, &self.song_groups, "highest bitrate, will be kept", "lower bitrate copy");
+            });
+        
+        if !open {
+            self.show_songs = false;
+        }
+    }
+
+    fn draw_documents_window(&mut self, ctx: &Context) {
+        if !self.show_documents {
+            return;
+        }
+        
+        let mut open = true;
+        egui::Window::new("Similar Documents")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 400.0))
+            .show(ctx, |ui| {
+                if self.documents_receiver.is_some() {
+                    ui.spinner();
+                    ui.label("Reading and comparing documents...");
+                    return;
+                }
+                
+                if self.similar_documents.is_empty() {
+                    ui.label("No near-identical documents found.");
+                    return;
+                }
+                
+                ui.label(format!("Found {} groups of near-identical documents", self.similar_documents.len()));
+                
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for group in &self.similar_documents {
+                        ui.group(|ui| {
+                            for (index, (file, similarity)) in group.files.iter().enumerate() {
+                                if index == 0 {
+                                    ui.label(format!("{}  (newest)", unicode_names::display(file)));
+                                } else {
+                                    ui.label(format!("{}  ({:.0}% similar)", unicode_names::display(file), similarity * 100.0));
+                                }
+                            }
+                        });
+                    }
+                });
+            });
+        
+        if !open {
+            self.show_documents = false;
+        }
+    }
+
+    fn draw_wine_prefixes_window(&mut self, ctx: &Context) {
+        if !self.show_wine_prefixes {
+            return;
+        }
+        
+        let mut open = true;
+        egui::Window::new("Wine and Proton Prefixes")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 400.0))
+            .show(ctx, |ui| {
+                if self.wine_receiver.is_some() {
+                    ui.spinner();
+                    ui.label("Measuring prefixes...");
+                    return;
+                }
+                
+                if self.wine_prefixes.is_empty() {
+                    ui.label("No Wine or Proton prefixes found.");
+                    return;
+                }
+                
+                let orphaned: u64 = self.wine_prefixes.iter()
+                    .filter(|prefix| prefix.orphaned)
+                    .map(|prefix| prefix.size)
+                    .sum();
+                if orphaned > 0 {
+                    ui.colored_label(Color32::YELLOW, format!(
+                        "{} in prefixes of games that are no longer installed",
+                        formatting::format_size(orphaned)
+                    ));
+                }
+                
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for prefix in &self.wine_prefixes {
+                        ui.horizontal(|ui| {
+                            let text = format!("{}  {}", formatting::format_size(prefix.size), prefix.name);
+                            if prefix.orphaned {
+                                ui.colored_label(Color32::RED, text);
+                            } else {
+                                ui.label(text);
+                            }
+                            
+                            if ui.small_button("Scan").on_hover_text(prefix.path.display().to_string()).clicked() {
+                                self.scan_path = prefix.path.to_string_lossy().to_string();
+                            }
+                        });
+                    }
+                });
+            });
+        
+        if !open {
+            self.show_wine_prefixes = false;
+        }
+    }
+
+    fn draw_system_storage_window(&mut self, ctx: &Context) {
+        let Some(areas) = &self.system_storage else {
+            return;
+        };
+        
+        let mut open = true;
+        let mut opened = None;
+        egui::Window::new("System Storage")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 300.0))
+            .show(ctx, |ui| {
+                ui.label("These are left alone by the cleaner, as only the system can remove them safely.");
+                
+                for area in areas {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.strong(area.name);
+                        if ui.button(format!("Open {}", area.tool.name)).clicked() {
+                            opened = Some(area.tool);
+                        }
+                    });
+                    ui.label(area.reason);
+                    if let Some(path) = &area.path {
+                        ui.label(path.display().to_string());
+                    }
+                }
+                
+                ui.separator();
+                if let Some(tool) = system_storage::storage_tool() {
+                    if ui.button(format!("Open {}", tool.name)).on_hover_text("See everything taking space on this computer").clicked() {
+                        opened = Some(tool);
+                    }
+                } else if areas.is_empty() {
+                    ui.label("No storage tool of the system was found.");
+                }
+                
+                if let Some(error) = &self.system_storage_error {
+                    ui.colored_label(Color32::RED, error);
+                }
+            });
+        
+        if let Some(tool) = opened {
+            self.system_storage_error = match tool.open() {
+                Ok(()) => None,
+                Err(e) => {
+                    error!("Failed to open {}: {}", tool.name, e);
+                    Some(format!("Could not open {}: {}", tool.name, e))
+                }
+            };
+        }
+        if !open {
+            self.system_storage = None;
+        }
+    }
+
+    fn draw_chunk_comparison_window(&mut self, ctx: &Context) {
+        if !self.show_chunk_comparison {
+            return;
+        }
+        
+        let candidates: Vec<PathBuf> = self.scan_results.iter()
+            .flat_map(|results| [FileCategory::LargeFiles, FileCategory::BackupSnapshots]
+                .into_iter()
+                .filter_map(|category| results.files_by_category.get(&category))
+                .flatten())
+            .cloned()
+            .collect();
+        
+        let mut open = true;
+        egui::Window::new("Compare Large Files")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 300.0))
+            .show(ctx, |ui| {
+                if candidates.is_empty() {
+                    ui.label("The scan found no large files or backups to compare.");
+                    return;
+                }
+                
+                for (index, label) in ["Keep:", "Compare:"].into_iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        let selected = self.chunk_pair[index].as_ref()
+                            .map(|path| path.display().to_string())
+                            .unwrap_or_else(|| "Choose a file".to_string());
+                        egui::ComboBox::from_id_source(("chunk_pair", index))
+                            .selected_text(selected)
+                            .width(480.0)
+                            .show_ui(ui, |ui| {
+                                for candidate in &candidates {
+                                    ui.selectable_value(&mut self.chunk_pair[index], Some(candidate.clone()), candidate.display().to_string());
+                                }
+                            });
+                    });
+                }
+                
+                let ready = matches!(&self.chunk_pair, [Some(first), Some(second)] if first != second);
+                if self.chunk_receiver.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(format!("Reading... {} so far", formatting::format_size(self.chunk_bytes_read.load(Ordering::Relaxed))));
+                    });
+                } else if ui.add_enabled(ready, egui::Button::new("Compare")).clicked() {
+                    self.compare_chunks();
+                }
+                
+                match &self.chunk_overlap {
+                    Some(Ok(overlap)) => {
+                        ui.separator();
+                        let percent = |shared: u64, size: u64| if size == 0 { 0.0 } else { shared as f64 * 100.0 / size as f64 };
+                        ui.label(format!(
+                            "{:.1}% of {} is also in {}",
+                            percent(overlap.second_shared, overlap.second_size),
+                            overlap.second.display(),
+                            overlap.first.display()
+                        ));
+                        ui.label(format!(
+                            "{:.1}% of {} is also in {}",
+                            percent(overlap.first_shared, overlap.first_size),
+                            overlap.first.display(),
+                            overlap.second.display()
+                        ));
+                        ui.label(format!(
+                            "Deleting the second file frees {} and loses {} of content found only in it.",
+                            formatting::format_size(overlap.second_size),
+                            formatting::format_size(overlap.second_unique())
+                        ));
+                        ui.label(format!(
+                            "The first file holds {} not found in the second.",
+                            formatting::format_size(overlap.first_unique())
+                        ));
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(Color32::RED, format!("Comparison failed: {}", e));
+                    }
+                    None => {}
+                }
+            });
+        
+        if !open {
+            self.show_chunk_comparison = false;
+        }
+    }
+
+    fn load_history(&mut self) {
+        self.history = Some(history::sessions().map_err(|e| e.to_string()));
+        self.history_entries = None;
+    }
+    
+    fn draw_history_window(&mut self, ctx: &Context) {
+        if !self.show_history {
+            return;
+        }
+        
+        let mut details = None;
+        let mut open = true;
+        egui::Window::new("Cleanup History")
+            .open(&mut open)
+            .default_size(Vec2::new(700.0, 450.0))
+            .show(ctx, |ui| {
+                let sessions = match &self.history {
+                    Some(Ok(sessions)) => sessions,
+                    Some(Err(e)) => {
+                        ui.colored_label(Color32::YELLOW, format!("Could not read the history: {}", e));
+                        return;
+                    }
+                    None => return,
+                };
+                if sessions.is_empty() {
+                    ui.label("No cleanup has been recorded yet.");
+                    return;
+                }
+                
+                let reclaimed: u64 = sessions.iter().map(|session| session.bytes).sum();
+                let files: usize = sessions.iter().map(|session| session.files).sum();
+                ui.label(format!(
+                    "{} reclaimed in {} files over {} cleanups",
+                    formatting::format_size(reclaimed),
+                    files,
+                    sessions.len()
+                ));
+                ui.separator();
+                
+                egui::ScrollArea::vertical()
+                    .id_source("history_sessions")
+                    .max_height(FILE_LIST_HEIGHT)
+                    .show(ui, |ui| {
+                        egui::Grid::new("history_grid").striped(true).show(ui, |ui| {
+                            for session in sessions {
+                                let duration = (session.finished_at - session.started_at).num_seconds().max(0);
+                                ui.label(formatting::format_date(session.finished_at))
+                                    .on_hover_text(format!("Took {} s", duration));
+                                ui.label(format!("{} files", session.files));
+                                ui.label(formatting::format_size(session.bytes));
+                                ui.label(if session.moved_to_trash { "to the trash" } else { "deleted" });
+                                if session.failures > 0 {
+                                    ui.colored_label(Color32::YELLOW, format!("{} failed", session.failures));
+                                } else {
+                                    ui.label("");
+                                }
+                                ui.label(session.note.as_deref().unwrap_or(""));
+                                if ui.small_button("Files").clicked() {
+                                    details = Some(session.id);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                
+                if let Some((_, entries)) = &self.history_entries {
+                    ui.separator();
+                    match entries {
+                        Ok(entries) => {
+                            egui::ScrollArea::vertical()
+                                .id_source("history_entries")
+                                .max_height(FILE_LIST_HEIGHT)
+                                .show(ui, |ui| {
+                                    for entry in entries {
+                                        match &entry.error {
+                                            None => ui.label(format!("{}  {}", formatting::format_size(entry.size), entry.path.display())),
+                                            Some(error) => ui.colored_label(Color32::YELLOW, format!("{}: {}", entry.path.display(), error)),
+                                        };
+                                    }
+                                });
+                        }
+                        Err(e) => {
+                            ui.colored_label(Color32::YELLOW, format!("Could not read the files: {}", e));
+                        }
+                    }
+                }
+            });
+        
+        if let Some(id) = details {
+            self.history_entries = Some((id, history::entries(id).map_err(|e| e.to_string())));
+        }
+        if !open {
+            self.show_history = false;
+        }
+    }
+    
+    fn draw_quarantine_window(&mut self, ctx: &Context) {
+        if !self.show_quarantine {
+            return;
+        }
+        
+        let mut action = None;
+        let mut open = true;
+        egui::Window::new("Quarantine")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 400.0))
+            .show(ctx, |ui| {
+                for (file, reason) in &self.restore_failures {
+                    ui.colored_label(Color32::YELLOW, format!("Not restored: {}: {}", file.display(), reason));
+                }
+                if !self.restore_failures.is_empty() && ui.button("Copy Paths").clicked() {
+                    let files: Vec<PathBuf> = self.restore_failures.iter().map(|(file, _)| file.clone()).collect();
+                    copy_paths(ui, &files);
+                }
+                
+                if self.quarantine_runs.is_empty() {
+                    ui.label("The quarantine is empty.");
+                    return;
+                }
+                
+                let total: u64 = self.quarantine_runs.iter().map(QuarantineRun::size).sum();
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} held in the quarantine", formatting::format_size(total)));
+                    if ui.button("Undo Last Cleanup").clicked() {
+                        action = Some(QuarantineAction::RestoreRun(self.quarantine_runs.len() - 1));
+                    }
+                });
+                ui.separator();
+                
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (run_index, run) in self.quarantine_runs.iter().enumerate().rev() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "{}: {} files, {}{}",
+                                formatting::format_date(run.quarantined_at),
+                                run.files.len(),
+                                formatting::format_size(run.size()),
+                                run.note.as_ref().map(|note| format!(" ({})", note)).unwrap_or_default()
+                            ));
+                            if ui.small_button("Restore All").clicked() {
+                                action = Some(QuarantineAction::RestoreRun(run_index));
+                            }
+                            if ui.small_button("Delete Permanently").clicked() {
+                                action = Some(QuarantineAction::Purge(run_index));
+                            }
+                        });
+                        egui::CollapsingHeader::new("Files")
+                            .id_source(("quarantine_run", run_index))
+                            .show(ui, |ui| {
+                                for (file_index, file) in run.files.iter().enumerate() {
+                                    ui.horizontal(|ui| {
+                                        ui.label(format!("{}  {}", formatting::format_size(file.size), file.original.display()));
+                                        if ui.small_button("Restore").clicked() {
+                                            action = Some(QuarantineAction::RestoreFile(run_index, file_index));
+                                        }
+                                    });
+                                }
+                            });
+                    }
+                });
+            });
+        
+        if let Some(action) = action {
+            self.apply_quarantine_action(action);
+        }
+        if !open {
+            self.show_quarantine = false;
+        }
+    }
+
+    fn apply_quarantine_action(&mut self, action: QuarantineAction) {
+        self.restore_failures.clear();
+        match action {
+            QuarantineAction::RestoreRun(index) => {
+                if let Some(run) = self.quarantine_runs.get(index) {
+                    self.restore_failures = quarantine::restore_run(run);
+                }
+            }
+            QuarantineAction::RestoreFile(run_index, file_index) => {
+                if let Some(file) = self.quarantine_runs.get(run_index).and_then(|run| run.files.get(file_index)) {
+                    if let Err(e) = quarantine::restore(file) {
+                        self.restore_failures.push((file.original.clone(), e.to_string()));
+                    }
+                }
+            }
+            QuarantineAction::Purge(index) => {
+                if let Some(run) = self.quarantine_runs.get(index) {
+                    if let Err(e) = quarantine::purge_run(run) {
+                        error!("Failed to empty quarantined cleanup: {}", e);
+                    }
+                }
+            }
+        }
+        self.quarantine_runs = quarantine::load_runs();
+    }
+
+    /// The subfolders of a folder with their sizes, from the sizes recorded by
+    /// the scans, opening a subfolder to see inside it.
+    fn draw_top_dirs_window(&mut self, ctx: &Context) {
+        let Some(folder) = self.top_dirs_path.clone() else {
+            return;
+        };
+        let Some(folder_size) = self.dir_sizes.get(&folder).copied() else {
+            self.top_dirs_path = None;
+            return;
+        };
+        // Folders above the scanned ones are only known when an earlier scan covered them
+        let parent = folder.parent().filter(|parent| self.dir_sizes.get(parent).is_some()).map(Path::to_path_buf);
+        let sort = self.top_dirs_sort;
+        let subfolders = self.top_dirs.get_or_insert_with(|| {
+            let mut subfolders = self.dir_sizes.subdirectories(&folder);
+            match sort {
+                DirSort::Size => subfolders.sort_by_key(|(_, size)| std::cmp::Reverse(size.size)),
+                DirSort::Files => subfolders.sort_by_key(|(_, size)| std::cmp::Reverse(size.files)),
+                DirSort::Name => subfolders.sort_by(|(a, _), (b, _)| a.cmp(b)),
+            }
+            subfolders
+        });
+        
+        let mut open_folder = None;
+        let mut open = true;
+        egui::Window::new("Top Directories")
+            .open(&mut open)
+            .default_size(Vec2::new(700.0, 450.0))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(parent.is_some(), egui::Button::new("Up")).clicked() {
+                        open_folder = parent.clone();
+                    }
+                    ui.label(format!(
+                        "{}: {} in {} files",
+                        folder.display(),
+                        formatting::format_size(folder_size.size),
+                        folder_size.files
+                    ));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Sort by:");
+                    for (option, label) in [(DirSort::Size, "Size"), (DirSort::Files, "Files"), (DirSort::Name, "Name")] {
+                        ui.selectable_value(&mut self.top_dirs_sort, option, label);
+                    }
+                });
+                ui.separator();
+                
+                egui::ScrollArea::vertical()
+                    .id_source("top_dirs_list")
+                    .max_height(FILE_LIST_HEIGHT)
+                    .show(ui, |ui| {
+                        egui::Grid::new("top_dirs_grid").striped(true).show(ui, |ui| {
+                            for (path, size) in subfolders.iter() {
+                                let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string());
+                                if ui.link(name).on_hover_text("Open this folder").clicked() {
+                                    open_folder = Some(path.clone());
+                                }
+                                ui.label(formatting::format_size(size.size));
+                                ui.label(format!("{:.0}%", size.size as f64 * 100.0 / folder_size.size.max(1) as f64));
+                                ui.label(format!("{} files", size.files));
+                                ui.end_row();
+                            }
+                            if folder_size.own_files() > 0 {
+                                ui.label("(files directly here)");
+                                ui.label(formatting::format_size(folder_size.own_size()));
+                                ui.label(format!("{:.0}%", folder_size.own_size() as f64 * 100.0 / folder_size.size.max(1) as f64));
+                                ui.label(format!("{} files", folder_size.own_files()));
+                                ui.end_row();
+                            }
+                        });
+                    });
+            });
+        
+        if self.top_dirs_sort != sort {
+            self.top_dirs = None;
+        }
+        if let Some(folder) = open_folder {
+            self.top_dirs_path = Some(folder);
+            self.top_dirs = None;
+        }
+        if !open {
+            self.top_dirs_path = None;
+        }
+    }
+    
+    fn set_mini_mode(&mut self, ctx: &Context, mini: bool) {
+        self.mini_mode = mini;
+        self.mini_free_space = None;
+        let (size, min_size, level) = if mini {
+            (MINI_WIDGET_SIZE, MINI_WIDGET_SIZE, egui::WindowLevel::AlwaysOnTop)
+        } else {
+            (WINDOW_SIZE, MIN_WINDOW_SIZE, egui::WindowLevel::Normal)
+        };
+        ctx.send_viewport_cmd(egui::ViewportCommand::MinInnerSize(min_size.into()));
+        ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size.into()));
+        ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(level));
+    }
+    
+    /// Free space and what the last scan found to clean, from the saved
+    /// results rather than a new scan, and a button to clean it.
+    fn draw_mini_widget(&mut self, ctx: &Context) {
+        if self.mini_free_space.is_none_or(|(read, _)| read.elapsed() >= MINI_WIDGET_REFRESH) {
+            let path = self.scan_roots.first().cloned().unwrap_or_else(|| PathBuf::from(&self.scan_path));
+            self.mini_free_space = volumes::with_disk_for(&path, |disk| disk.available_space())
+                .map(|space| (std::time::Instant::now(), space));
+        }
+        ctx.request_repaint_after(MINI_WIDGET_REFRESH);
+        
+        let reclaimable: Option<u64> = self.scan_results.as_ref().map(|results| {
+            FileCategory::all()
+                .into_iter()
+                .filter(|category| self.selected_categories.get(category).copied().unwrap_or(false)
+                    && self.config.is_category_enabled(*category))
+                .filter_map(|category| results.category_stats.get(&category))
+                .map(|stats| stats.size)
+                .sum()
+        });
+        
+        egui::CentralPanel::default().show(ctx, |ui| {
+            match self.mini_free_space {
+                Some((_, space)) => ui.label(format!("Free: {}", formatting::format_size(space))),
+                None => ui.label("Free: unknown"),
+            };
+            match reclaimable {
+                Some(size) => ui.label(format!("Reclaimable: {}", formatting::format_size(size))),
+                None => ui.label("Reclaimable: scan first"),
+            };
+            ui.horizontal(|ui| {
+                // The confirmation needs the full window
+                if ui.add_enabled(reclaimable.unwrap_or(0) > 0 && !self.is_scanning && !self.is_cleaning, egui::Button::new("Clean")).clicked() {
+                    self.set_mini_mode(ctx, false);
+                    self.prepare_cleanup();
+                }
+                if ui.button("Expand").clicked() {
+                    self.set_mini_mode(ctx, false);
+                }
+            });
+        });
+    }
+    
+    fn draw_disk_activity_window(&mut self, ctx: &Context) {
+        let Some(monitor) = self.disk_activity.as_mut() else {
+            return;
+        };
+        if monitor.is_due() {
+            self.disk_activity_sample = monitor.sample();
+            if let Some((pid, files)) = &mut self.disk_activity_process {
+                *files = disk_activity::open_files(*pid);
+            }
+        }
+        ctx.request_repaint_after(disk_activity::SAMPLE_INTERVAL);
+        
+        let volume = monitor.volume().map(Path::to_path_buf);
+        let sample = &self.disk_activity_sample;
+        let mut open = true;
+        egui::Window::new("Disk Activity")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 400.0))
+            .show(ctx, |ui| {
+                if let (Some(volume), Some(growth)) = (&volume, sample.volume_growth) {
+                    let rate = formatting::format_size(growth.unsigned_abs());
+                    if growth >= 0 {
+                        ui.label(format!("{} is filling at {}/s", volume.display(), rate));
+                    } else {
+                        ui.label(format!("{} is freeing {}/s", volume.display(), rate));
+                    }
+                }
+                
+                if sample.processes.is_empty() {
+                    ui.label("No program is writing to disk right now.");
+                    return;
+                }
+                
+                egui::ScrollArea::vertical()
+                    .id_source("disk_activity_list")
+                    .max_height(FILE_LIST_HEIGHT)
+                    .show(ui, |ui| {
+                        egui::Grid::new("disk_activity_grid").striped(true).show(ui, |ui| {
+                            ui.strong("Program");
+                            ui.strong("PID");
+                            ui.strong("Writing");
+                            ui.strong("Written in total");
+                            ui.label("");
+                            ui.end_row();
+                            for process in &sample.processes {
+                                ui.label(&process.name);
+                                ui.label(process.pid.to_string());
+                                ui.label(format!("{}/s", formatting::format_size(process.bytes_per_second)));
+                                ui.label(formatting::format_size(process.total_written));
+                                if cfg!(target_os = "linux") && ui.button("Open Files").clicked() {
+                                    self.disk_activity_process = Some((process.pid, disk_activity::open_files(process.pid)));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                
+                if let Some((pid, files)) = &self.disk_activity_process {
+                    ui.separator();
+                    let name = sample.processes.iter()
+                        .find(|process| process.pid == *pid)
+                        .map_or("the program", |process| process.name.as_str());
+                    if files.is_empty() {
+                        ui.label(format!("No open files of {} ({}) can be read", name, pid));
+                    } else {
+                        ui.label(format!("Files {} ({}) has open, biggest first:", name, pid));
+                        for (file, size) in files.iter().take(20) {
+                            ui.label(format!("{}  {}", formatting::format_size(*size), file.display()));
+                        }
+                    }
+                }
+            });
+        
+        if !open {
+            self.disk_activity = None;
+        }
+    }
+    
+    fn draw_dry_run_report(&mut self, ctx: &Context) {
+        let Some(report) = &self.dry_run_report else {
+            return;
+        };
+        
+        let mut open = true;
+        egui::Window::new("Dry Run")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 400.0))
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} files would be {}, reclaiming {}. Nothing was changed.",
+                    report.removable.len(),
+                    if report.moved_to_trash { "moved to the trash" } else { "permanently deleted" },
+                    formatting::format_size(report.reclaimed_bytes())
+                ));
+                let quarantined = report.removable.iter().filter(|file| file.quarantined_to.is_some()).count();
+                if quarantined > 0 {
+                    ui.label(format!("{} of them are on FAT drives and would go to the {} folder", quarantined, config::QUARANTINE_DIR_NAME));
+                }
+                
+                if !report.refused.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(Color32::YELLOW, format!("{} files would be skipped:", report.refused.len()));
+                        if ui.small_button("Copy Paths").clicked() {
+                            let files: Vec<PathBuf> = report.refused.iter().map(|(file, _)| file.clone()).collect();
+                            copy_paths(ui, &files);
+                        }
+                    });
+                    egui::ScrollArea::vertical()
+                        .id_source("dry_run_refused")
+                        .max_height(FILE_LIST_HEIGHT)
+                        .show(ui, |ui| {
+                            for (file, reason) in &report.refused {
+                                ui.label(format!("{}: {}", file.display(), reason));
+                            }
+                        });
+                }
+                
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_source("dry_run_removable")
+                    .max_height(FILE_LIST_HEIGHT)
+                    .show(ui, |ui| {
+                        for file in &report.removable {
+                            ui.label(format!("{}  {}", formatting::format_size(file.size), file.path.display()));
+                        }
+                    });
+            });
+        
+        if !open {
+            self.dry_run_report = None;
+        }
+    }
+
+    fn draw_preflight_window(&mut self, ctx: &Context) {
+        let Some(report) = &self.preflight_report else {
+            return;
+        };
+        
+        let mut action = None;
+        egui::Window::new("Cleanup Preflight")
+            .collapsible(false)
+            .default_size(Vec2::new(600.0, 400.0))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(Color32::YELLOW, format!(
+                        "{} of {} files cannot be cleaned:",
+                        report.refused.len(),
+                        self.files_to_delete.len()
+                    ));
+                    if ui.small_button("Copy Paths").clicked() {
+                        let files: Vec<PathBuf> = report.refused.iter().map(|(file, _)| file.clone()).collect();
+                        copy_paths(ui, &files);
+                    }
+                });
+                egui::ScrollArea::vertical()
+                    .id_source("preflight_refused")
+                    .max_height(FILE_LIST_HEIGHT)
+                    .show(ui, |ui| {
+                        for (file, reason) in report.refused.iter().take(file_list_page()) {
+                            ui.label(format!("{}: {}", file.display(), reason));
+                        }
+                        if report.refused.len() > file_list_page() {
+                            ui.label(format!("and {} more", report.refused.len() - file_list_page()));
+                        }
+                    });
+                
+                #[cfg(windows)]
+                if !report.permission_denied.is_empty() && crate::acl::is_elevated() {
+                    ui.separator();
+                    ui.label(format!(
+                        "{} of them are denied by their permissions or owner. Taking ownership makes the \
+                         Administrators group their owner with full control, so they can be deleted. \
+                         Only do this for files you are sure you want gone.",
+                        report.permission_denied.len()
+                    ));
+                    if ui.button("Take Ownership and Clean All").clicked() {
+                        action = Some(PreflightAction::TakeOwnership);
+                    }
+                }
+                
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Skip Them and Clean the Rest").clicked() {
+                        action = Some(PreflightAction::SkipRefused);
                     }
+                    if ui.button("Cancel").clicked() {
+                        action = Some(PreflightAction::Cancel);
+                    }
+                });
+            });
+        
+        if let Some(action) = action {
+            self.apply_preflight_action(action);
+        }
+    }
+
+    fn apply_preflight_action(&mut self, action: PreflightAction) {
+        let Some(report) = self.preflight_report.take() else {
+            return;
+        };
+        
+        match action {
+            PreflightAction::SkipRefused => {
+                let refused: HashSet<&PathBuf> = report.refused.iter().map(|(file, _)| file).collect();
+                self.files_to_delete.retain(|file| !refused.contains(file));
+                self.execute_cleanup();
+            }
+            #[cfg(windows)]
+            PreflightAction::TakeOwnership => {
+                let refused: HashSet<&PathBuf> = report.refused.iter()
+                    .map(|(file, _)| file)
+                    .filter(|file| !report.permission_denied.contains(file))
+                    .collect();
+                self.files_to_delete.retain(|file| !refused.contains(file));
+                self.ownership_to_take = report.permission_denied.clone();
+                self.execute_cleanup();
+            }
+            PreflightAction::Cancel => {
+                self.files_to_delete.clear();
+                self.commands_to_run.clear();
+            }
+        }
+    }
+
+    fn draw_scheduled_cleanup_plan(&mut self, ctx: &Context) {
+        let Some(plan) = &self.scheduled_cleanup_plan else {
+            return;
+        };
+        
+        let mut open = true;
+        egui::Window::new("Scheduled Cleanup")
+            .open(&mut open)
+            .default_size(Vec2::new(600.0, 400.0))
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.monospace(plan);
                 });
+            });
+        
+        if !open {
+            self.scheduled_cleanup_plan = None;
         }
     }
 
@@ -311,8 +3011,39 @@ impl DiskCleanerApp {
                 .default_size(Vec2::new(400.0, 300.0))
                 .show(ctx, |ui| {
                     ui.checkbox(&mut self.config.use_trash, "Use Trash/Recycle Bin (safer)");
+                    ui.checkbox(&mut self.config.use_quarantine, "Move cleaned files to the quarantine instead (can be restored from the app)");
+                    ui.checkbox(&mut self.config.checksum_manifest, "Write a checksum manifest next to quarantined files")
+                        .on_hover_text("A BLAKE3 checksum per file, which b3sum --check can verify. Files copied to another drive are always verified before the original is removed.");
+                    ui.horizontal(|ui| {
+                        ui.label("When the trash is full or unavailable:");
+                        egui::ComboBox::from_id_source("trash_fallback")
+                            .selected_text(self.config.trash_fallback.label())
+                            .show_ui(ui, |ui| {
+                                for fallback in TrashFallback::all() {
+                                    ui.selectable_value(&mut self.config.trash_fallback, fallback, fallback.label());
+                                }
+                            });
+                    }).response.on_hover_text("Network shares and some removable drives have no trash. Skipped files are listed as failed after the cleanup.");
                     ui.checkbox(&mut self.config.include_hidden_files, "Include hidden files");
                     ui.checkbox(&mut self.config.follow_symlinks, "Follow symbolic links");
+                    ui.checkbox(&mut self.config.read_browser_history, "Look up downloads in the browsers' history");
+                    ui.checkbox(&mut self.config.protect_recent_files, "Never suggest recently opened files as old files or downloads");
+                    ui.checkbox(&mut self.config.unify_unicode_names, "Treat names that differ only in their Unicode form as the same")
+                        .on_hover_text("macOS writes accented names decomposed, Linux and Windows composed, so copies between them differ byte for byte");
+                    ui.checkbox(&mut self.config.sign_cleanup_records, "Keep a signed record of deleted files");
+                    if cfg!(windows) {
+                        ui.checkbox(&mut self.config.use_mft_enumeration, "Fast NTFS scan using the master file table (requires admin)");
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Files removed at once:");
+                        ui.add(egui::DragValue::new(&mut self.config.cleanup_threads).range(0..=64));
+                        ui.label(if self.config.cleanup_threads == 0 { "(one per core)" } else { "" });
+                    }).response.on_hover_text("Removing many small files goes faster in parallel. Use 1 for slow USB drives or network shares.");
+                    ui.checkbox(&mut self.config.low_memory, "Low-memory mode (takes effect after a restart)")
+                        .on_hover_text("Works on fewer files at once, keeps no hashes in memory and shows shorter file lists");
+                    if !self.config.low_memory && low_memory::is_recommended() {
+                        ui.label("This computer has 4 GB of memory or less, low-memory mode is recommended.");
+                    }
                     
                     ui.separator();
                     
@@ -321,8 +3052,327 @@ impl DiskCleanerApp {
                         .text("Minimum file size (bytes)")
                         .logarithmic(true));
                     
-                    ui.add(egui::Slider::new(&mut self.config.max_file_age_days, 1..=365)
-                        .text("Maximum file age (days)"));
+                    ui.add(egui::Slider::new(&mut self.config.max_file_age_days, 1..=365)
+                        .text("Maximum file age (days)"));
+                    
+                    ui.add(egui::Slider::new(&mut self.config.duplicate_min_age_hours, 0..=72)
+                        .text("Ignore files changed in the last hours when finding duplicates"));
+                    ui.checkbox(&mut self.config.find_duplicates_after_scan, "Find duplicates after each scan");
+                    ui.checkbox(&mut self.config.hash_network_files, "Compare the content of files on network shares")
+                        .on_hover_text("Slow over Wi-Fi. When off, files on network shares with the same name and size count as duplicates");
+                    
+                    let keep_policy = self.config.duplicate_keep_policy;
+                    let preferred_folders = self.config.preferred_folders.len();
+                    ui.horizontal(|ui| {
+                        ui.label("Keep in each duplicate group:");
+                        egui::ComboBox::from_id_source("duplicate_keep_policy")
+                            .selected_text(self.config.duplicate_keep_policy.label())
+                            .show_ui(ui, |ui| {
+                                for policy in DuplicateKeepPolicy::all() {
+                                    ui.selectable_value(&mut self.config.duplicate_keep_policy, policy, policy.label());
+                                }
+                            });
+                    });
+                    if self.config.duplicate_keep_policy == DuplicateKeepPolicy::PreferredFolders {
+                        let mut removed = None;
+                        for (index, folder) in self.config.preferred_folders.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{}. {}", index + 1, folder.display()));
+                                if ui.small_button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+                        }
+                        if let Some(index) = removed {
+                            self.config.preferred_folders.remove(index);
+                        }
+                        if ui.button("Add Preferred Folder").on_hover_text("Copies in folders higher in the list are kept first").clicked() {
+                            if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                                self.config.preferred_folders.push(folder);
+                            }
+                        }
+                    }
+                    if keep_policy != self.config.duplicate_keep_policy || preferred_folders != self.config.preferred_folders.len() {
+                        self.apply_keep_policy();
+                    }
+                    
+                    ui.horizontal(|ui| {
+                        ui.label("Dates in reports:");
+                        for style in DateStyle::all() {
+                            let label = match style {
+                                DateStyle::Localized => "Local format",
+                                DateStyle::Iso8601 => "ISO 8601",
+                            };
+                            ui.radio_value(&mut self.config.report_date_style, style, label);
+                        }
+                    });
+                    
+                    ui.separator();
+                    
+                    ui.label("Categories:");
+                    egui::Grid::new("category_settings").show(ui, |ui| {
+                        for category in FileCategory::all() {
+                            // Left empty, the category keeps its default name
+                            let name = self.config.category_names.entry(category).or_default();
+                            ui.add(egui::TextEdit::singleline(name).hint_text(category.default_name()));
+                            
+                            let mut shown = self.config.is_category_enabled(category);
+                            if ui.checkbox(&mut shown, "Show").changed() {
+                                self.config.disabled_categories.retain(|disabled| *disabled != category);
+                                if !shown {
+                                    self.config.disabled_categories.push(category);
+                                    self.selected_categories.insert(category, false);
+                                }
+                            }
+                            
+                            let mut preselected = self.config.preselected_categories.contains(&category);
+                            if ui.add_enabled(shown, egui::Checkbox::new(&mut preselected, "Selected by default")).changed() {
+                                self.config.preselected_categories.retain(|other| *other != category);
+                                if preselected {
+                                    self.config.preselected_categories.push(category);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    self.config.category_names.retain(|_, name| !name.trim().is_empty());
+                    
+                    ui.separator();
+                    
+                    ui.label("Commands to run after cleaning a category:");
+                    for category in FileCategory::all() {
+                        ui.horizontal(|ui| {
+                            ui.label(self.config.category_name(category));
+                            let command = self.config.category_commands.entry(category).or_default();
+                            ui.text_edit_singleline(command);
+                        });
+                    }
+                    self.config.category_commands.retain(|_, command| !command.trim().is_empty());
+                    
+                    ui.separator();
+                    
+                    ui.label("Categorization rules, tried in order before the built-in ones:");
+                    let mut rules = self.config.category_rules.rules().to_vec();
+                    let mut removed = None;
+                    for (index, rule) in rules.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_id_source(("category_rule_kind", index))
+                                .selected_text(rule.kind.name())
+                                .show_ui(ui, |ui| {
+                                    for kind in PatternKind::all() {
+                                        ui.selectable_value(&mut rule.kind, kind, kind.name());
+                                    }
+                                });
+                            let hint = match rule.kind {
+                                PatternKind::Glob => "**/renders/*.exr",
+                                PatternKind::Extension => "iso",
+                                PatternKind::PathContains => "steamapps",
+                            };
+                            ui.add(egui::TextEdit::singleline(&mut rule.pattern).hint_text(hint));
+                            ui.label("as");
+                            egui::ComboBox::from_id_source(("category_rule_category", index))
+                                .selected_text(self.config.category_name(rule.category))
+                                .show_ui(ui, |ui| {
+                                    // Folders are never categorized by rules, only files
+                                    for category in FileCategory::all().into_iter().filter(|category| *category != FileCategory::EmptyFolders) {
+                                        ui.selectable_value(&mut rule.category, category, self.config.category_name(category));
+                                    }
+                                });
+                            if ui.small_button("Remove").clicked() {
+                                removed = Some(index);
+                            }
+                            if let Some(error) = rule.error() {
+                                ui.colored_label(Color32::RED, error);
+                            }
+                        });
+                    }
+                    if let Some(index) = removed {
+                        rules.remove(index);
+                    }
+                    if ui.button("Add Rule").clicked() {
+                        rules.push(CategoryRule {
+                            kind: PatternKind::Extension,
+                            pattern: String::new(),
+                            category: FileCategory::LargeFiles,
+                        });
+                    }
+                    if rules != self.config.category_rules.rules() {
+                        self.config.category_rules.set(rules);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Test a path:");
+                        ui.add(egui::TextEdit::singleline(&mut self.rule_test_path).hint_text("/home/user/Downloads/disk.iso"));
+                    });
+                    let test_path = Path::new(self.rule_test_path.trim());
+                    if !test_path.as_os_str().is_empty() {
+                        let result = if let Some(rule) = self.config.category_rules.matching(test_path) {
+                            format!("{}, by the rule {}", self.config.category_name(rule.category), rule.description(&self.config))
+                        } else if let Some(category) = self.config.learned_category(test_path) {
+                            format!("{}, by a learned rule", self.config.category_name(category))
+                        } else {
+                            let metadata = std::fs::symlink_metadata(test_path).ok();
+                            let (category, reason) = FileCategory::categorize_with_reason(test_path, metadata.as_ref());
+                            format!("{}, by the built-in rules: {}", self.config.category_name(category), reason)
+                        };
+                        ui.label(result);
+                    }
+                    
+                    if !self.config.learned_rules.is_empty() {
+                        ui.separator();
+                        ui.label("Learned rules:");
+                        let mut removed = None;
+                        for (index, rule) in self.config.learned_rules.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.label(rule.description(&self.config));
+                                if ui.small_button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+                        }
+                        if let Some(index) = removed {
+                            self.config.learned_rules.remove(index);
+                        }
+                    }
+                    
+                    ui.separator();
+                    
+                    ui.label("Retention rules, enforced by scheduled --retention runs:");
+                    let mut removed = None;
+                    for (index, rule) in self.config.retention_rules.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(rule.description(&self.config));
+                            if ui.small_button("Remove").clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                    }
+                    if let Some(index) = removed {
+                        self.config.retention_rules.remove(index);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Keep");
+                        egui::ComboBox::from_id_source("retention_category")
+                            .selected_text(self.config.category_name(self.new_retention_rule.category))
+                            .show_ui(ui, |ui| {
+                                for category in FileCategory::all() {
+                                    ui.selectable_value(&mut self.new_retention_rule.category, category, self.config.category_name(category));
+                                }
+                            });
+                        ui.label("for");
+                        ui.add(egui::DragValue::new(&mut self.new_retention_rule.keep_days).range(1..=3650).suffix(" days"));
+                        ui.label("in");
+                        ui.add(egui::TextEdit::singleline(&mut self.new_retention_root).hint_text("folder"));
+                        let root = self.new_retention_root.trim();
+                        if ui.add_enabled(!root.is_empty(), egui::Button::new("Add Rule")).clicked() {
+                            let mut rule = std::mem::take(&mut self.new_retention_rule);
+                            rule.roots = vec![PathBuf::from(root)];
+                            self.config.retention_rules.push(rule);
+                            self.new_retention_root.clear();
+                        }
+                    });
+                    
+                    ui.separator();
+                    
+                    ui.horizontal(|ui| {
+                        ui.label("Log level:");
+                        log_level_combo(ui, "log_level", &mut self.config.logging.level);
+                    });
+                    let mut removed = None;
+                    for (index, (module, level)) in self.config.logging.module_levels.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(module.as_str());
+                            log_level_combo(ui, ("module_log_level", index), level);
+                            if ui.small_button("Remove").clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                    }
+                    if let Some(index) = removed {
+                        self.config.logging.module_levels.remove(index);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.add(egui::TextEdit::singleline(&mut self.new_log_module)
+                            .hint_text("intelligent_disk_cleaner::scanner"));
+                        let module = self.new_log_module.trim();
+                        if ui.add_enabled(!module.is_empty(), egui::Button::new("Add Module Level")).clicked() {
+                            self.config.logging.module_levels.push((module.to_string(), log::LevelFilter::Debug));
+                            self.new_log_module.clear();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.config.logging.log_to_file, "Write the log to a file");
+                        ui.add_enabled(self.config.logging.log_to_file, egui::DragValue::new(&mut self.config.logging.max_file_size_mb)
+                            .range(1..=100)
+                            .prefix("rotate at ")
+                            .suffix(" MB"));
+                        ui.add_enabled(self.config.logging.log_to_file, egui::DragValue::new(&mut self.config.logging.kept_files)
+                            .range(0..=20)
+                            .prefix("keep ")
+                            .suffix(" old files"));
+                    });
+                    if self.config.logging.log_to_file {
+                        if let Some(dir) = logging::log_dir() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Log files are written to {}", dir.display()));
+                                if ui.small_button("Copy Path").clicked() {
+                                    copy_paths(ui, &[dir]);
+                                }
+                            });
+                        }
+                    }
+                    
+                    ui.separator();
+                    
+                    let usage = self.storage_usage.get_or_insert_with(storage::usage);
+                    let total: u64 = usage.iter().map(|area| area.size).sum();
+                    ui.label(format!("Storage used by the application: {}", formatting::format_size(total)));
+                    for area in usage.iter() {
+                        ui.label(format!("      {} {}", formatting::format_size(area.size), area.name))
+                            .on_hover_text(area.path.display().to_string());
+                    }
+                    ui.horizontal(|ui| {
+                        let mut limited = self.config.storage.keep_reports.is_some();
+                        if ui.checkbox(&mut limited, "Keep only the last").changed() {
+                            self.config.storage.keep_reports = limited.then_some(100);
+                        }
+                        if let Some(keep) = &mut self.config.storage.keep_reports {
+                            ui.add(egui::DragValue::new(keep).range(1..=10000));
+                        }
+                        ui.label("cleanup records");
+                    });
+                    ui.add(egui::DragValue::new(&mut self.config.storage.max_cache_mb)
+                        .range(10..=100_000)
+                        .prefix("Trim the caches to ")
+                        .suffix(" MB, least recently used first"));
+                    ui.horizontal(|ui| {
+                        if ui.button("Prune Now").clicked() {
+                            self.storage_freed = Some(storage::prune(&self.config.storage));
+                            self.storage_usage = None;
+                        }
+                        if ui.add_enabled(!self.is_scanning, egui::Button::new("Clear Caches and History"))
+                            .on_hover_text("The quarantine, the baseline and the settings are kept")
+                            .clicked()
+                        {
+                            self.storage_freed = Some(storage::clear_caches_and_history());
+                            self.storage_usage = None;
+                        }
+                        if let Some(freed) = self.storage_freed {
+                            ui.label(format!("Freed {}", formatting::format_size(freed)));
+                        }
+                    });
+                    
+                    ui.separator();
+                    
+                    ui.horizontal(|ui| {
+                        let explain = ui.add_enabled(self.scan_results.is_some(), egui::Button::new("Explain Scheduled Cleanup"))
+                            .on_hover_text("What a scheduled --clean run would remove from the last scan with these settings")
+                            .on_disabled_hover_text("Scan first to see what a scheduled cleanup would remove");
+                        if explain.clicked() {
+                            if let Some(results) = &self.scan_results {
+                                self.scheduled_cleanup_plan = Some(headless::explain_plan(&self.config, results));
+                            }
+                        }
+                    });
                 });
         }
     }
@@ -335,73 +3385,833 @@ impl DiskCleanerApp {
                 .show(ctx, |ui| {
                     ui.label(format!("Are you sure you want to delete {} files?", self.files_to_delete.len()));
                     
-                    let total_size: u64 = self.files_to_delete.iter()
-                        .filter_map(|path| std::fs::metadata(path).ok())
-                        .map(|metadata| metadata.len())
-                        .sum();
+                    ui.label(format!("Total size: {}", formatting::format_size(self.cleanup_estimate)));
+                    
+                    if !self.cleanup_preview.sample.is_empty() {
+                        ui.horizontal(|ui| {
+                            if self.cleanup_preview.sample.len() < self.files_to_delete.len() {
+                                ui.label(format!("A random sample of {} files, favoring the largest:", self.cleanup_preview.sample.len()));
+                                if ui.small_button("New Sample").clicked() {
+                                    self.cleanup_preview.sample = sample_files(&self.files_to_delete, CLEANUP_SAMPLE_SIZE);
+                                }
+                            } else {
+                                ui.label("Files to delete:");
+                            }
+                        });
+                        egui::ScrollArea::vertical()
+                            .id_source("cleanup_sample")
+                            .max_height(FILE_LIST_HEIGHT / 2.0)
+                            .show(ui, |ui| {
+                                for (file, size) in &self.cleanup_preview.sample {
+                                    ui.label(format!("{}  {}", formatting::format_size(*size), file.display()));
+                                }
+                            });
+                    }
+                    
+                    egui::CollapsingHeader::new("Review files")
+                        .show(ui, |ui| {
+                            egui::ScrollArea::vertical()
+                                .max_height(FILE_LIST_HEIGHT)
+                                .show(ui, |ui| draw_cleanup_preview(ui, &self.cleanup_preview, &self.config));
+                        });
+                    
+                    if self.cleanup_order.len() > 1 {
+                        egui::CollapsingHeader::new("Cleanup order")
+                            .show(ui, |ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label("Drag the groups to choose which are removed first.");
+                                    if ui.small_button("Biggest First").clicked() {
+                                        self.cleanup_order.sort_by_key(|step| std::cmp::Reverse(step.size));
+                                        self.cleanup_reordered = true;
+                                    }
+                                });
+                                
+                                let mut moved = None;
+                                for (index, step) in self.cleanup_order.iter().enumerate() {
+                                    let response = ui.dnd_drag_source(egui::Id::new(("cleanup_step", index)), index, |ui| {
+                                        ui.label(format!(
+                                            "{}. {}: {} files, {}",
+                                            index + 1,
+                                            step.label,
+                                            step.files.len(),
+                                            formatting::format_size(step.size)
+                                        ));
+                                    }).response;
+                                    if let Some(from) = response.dnd_release_payload::<usize>() {
+                                        moved = Some((*from, index));
+                                    }
+                                }
+                                if let Some((from, to)) = moved {
+                                    let step = self.cleanup_order.remove(from);
+                                    self.cleanup_order.insert(to, step);
+                                    self.cleanup_reordered = true;
+                                }
+                            });
+                    }
+                    
+                    if !self.commands_to_run.is_empty() {
+                        ui.label("The following commands will be run:");
+                        for command in &self.commands_to_run {
+                            ui.monospace(command);
+                        }
+                    }
                     
-                    ui.label(format!("Total size: {}", humansize::format_size(total_size, humansize::DECIMAL)));
+                    let mut synced: Vec<&SyncFolder> = Vec::new();
+                    for file in &self.files_to_delete {
+                        if let Some(folder) = sync_folders::sync_folder_for(&self.sync_folders, file) {
+                            if !synced.iter().any(|seen| seen.path == folder.path) {
+                                synced.push(folder);
+                            }
+                        }
+                    }
+                    for folder in synced {
+                        ui.colored_label(Color32::YELLOW, format!("{}: {}", folder.path.display(), folder.warning()));
+                    }
                     
-                    if self.config.use_trash {
+                    if self.secure_wipe {
+                        ui.colored_label(Color32::RED, format!(
+                            "Files will be overwritten {} time(s) and permanently deleted, with no way to recover them!",
+                            self.config.secure_wipe_passes
+                        ));
+                        ui.colored_label(Color32::YELLOW, "On SSDs and on copy-on-write file systems (Btrfs, ZFS, APFS), \
+                            older copies of the content may survive the overwrite, as may snapshots and backups.");
+                    } else if self.config.use_quarantine {
+                        ui.label("Files will be moved to the quarantine (can be restored from the Quarantine window)");
+                    } else if self.config.use_trash {
+                        let mut warned: Vec<&ForeignVolume> = Vec::new();
+                        for file in &self.files_to_delete {
+                            if let Some(volume) = volumes::foreign_volume_for(&self.foreign_volumes, file) {
+                                if !warned.iter().any(|seen| seen.path == volume.path) {
+                                    warned.push(volume);
+                                }
+                            }
+                        }
+                        if self.drive_profile.as_ref().is_some_and(|profile| profile.is_fat) {
+                            ui.colored_label(Color32::YELLOW, format!(
+                                "FAT/exFAT drives have no trash: files on them go to the {} folder at the drive's root",
+                                config::QUARANTINE_DIR_NAME
+                            ));
+                        }
+                        for volume in warned {
+                            ui.colored_label(Color32::YELLOW, format!("{}: {}", volume.path.display(), volume.warning()));
+                        }
+                        
                         ui.label("Files will be moved to trash (can be recovered)");
                     } else {
                         ui.colored_label(Color32::RED, "Files will be permanently deleted!");
                     }
                     
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.stop_at_target, "Stop once")
+                            .on_hover_text("Stop the cleanup as soon as this much space is freed, following the cleanup order");
+                        ui.add_enabled(
+                            self.stop_at_target,
+                            egui::DragValue::new(&mut self.cleanup_target_gb).range(0.1..=100_000.0).speed(0.1).suffix(" GB"),
+                        );
+                        ui.label("have been freed");
+                    });
+                    
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.secure_wipe, "Securely wipe, overwriting")
+                            .on_hover_text("Overwrite each file with random data before deleting it, for browser data or documents \
+                                that must not be recoverable. Skips the trash and the quarantine.");
+                        let passes = ui.add_enabled(
+                            self.secure_wipe,
+                            egui::DragValue::new(&mut self.config.secure_wipe_passes)
+                                .range(1..=secure_wipe::MAX_PASSES)
+                                .suffix(" time(s)"),
+                        );
+                        if passes.changed() {
+                            self.save_config();
+                        }
+                    });
+                    
+                    ui.horizontal(|ui| {
+                        ui.label("Note:");
+                        ui.add(egui::TextEdit::singleline(&mut self.cleanup_note)
+                            .hint_text("e.g. purge before OS upgrade"));
+                    });
+                    
                     ui.horizontal(|ui| {
                         if ui.button("Cancel").clicked() {
                             self.confirmation_dialog = false;
+                            self.secure_wipe = false;
                             self.files_to_delete.clear();
+                            self.commands_to_run.clear();
                         }
                         
-                        if ui.button("Confirm Delete").clicked() {
-                            self.execute_cleanup();
+                        let confirm = if self.dry_run { "Run Dry Run" } else { "Confirm Delete" };
+                        if ui.button(confirm).clicked() {
+                            self.apply_cleanup_order();
+                            if self.dry_run {
+                                self.start_dry_run();
+                            } else {
+                                self.start_preflight();
+                            }
                         }
+                        
+                        ui.checkbox(&mut self.dry_run, "Dry run")
+                            .on_hover_text("Check every file and report what would be removed, without touching anything");
                     });
                 });
         }
     }
 
+    fn draw_analysis_window(&mut self, ctx: &Context) {
+        if let Some(error) = &self.analysis_error {
+            let mut open = true;
+            egui::Window::new("File Analysis")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.colored_label(Color32::RED, error);
+                });
+            if !open {
+                self.analysis_error = None;
+            }
+        }
+
+        let Some(analysis) = self.analysis.clone() else {
+            return;
+        };
+
+        let mut open = true;
+        egui::Window::new("File Analysis")
+            .open(&mut open)
+            .default_size(Vec2::new(500.0, 300.0))
+            .show(ctx, |ui| {
+                ui.label(format!("File: {}", analysis.path.display()));
+                ui.label(format!("Size: {}", formatting::format_size(analysis.size)));
+                
+                match (analysis.modified, analysis.age_days) {
+                    (Some(modified), Some(days)) => {
+                        ui.label(format!("Last modified: {} ({} days ago)", formatting::format_date(modified), days))
+                    }
+                    _ => ui.label("Last modified: unknown"),
+                };
+                if let Some(created) = analysis.created {
+                    ui.label(format!("Created: {}", formatting::format_date(created)));
+                }
+                
+                ui.separator();
+                
+                ui.label(format!("Category: {}", self.config.category_name(analysis.category)));
+                ui.label(analysis.category.description());
+                ui.label(format!("Reason: {}", analysis.reason));
+                
+                if analysis.excluded {
+                    ui.colored_label(Color32::YELLOW, "This path is protected by the exclusion settings");
+                }
+                
+                if let Some(download) = &analysis.download {
+                    ui.label(format!("Downloaded with {} from: {}", download.browser, download.source_url));
+                    if let Some(date) = download.downloaded_at {
+                        ui.label(format!("Downloaded on: {}", formatting::format_date(date)));
+                    }
+                }
+                
+                ui.separator();
+                
+                match &analysis.duplicates {
+                    None => {
+                        ui.label("Run a scan to look for duplicates of this file.");
+                    }
+                    Some(duplicates) if duplicates.is_empty() => {
+                        ui.label("No duplicates found in the scanned files.");
+                    }
+                    Some(duplicates) => {
+                        ui.label(format!("{} duplicate(s) found:", duplicates.len()));
+                        for duplicate in duplicates {
+                            ui.label(format!("  {}", duplicate.display()));
+                        }
+                    }
+                }
+                
+                ui.separator();
+                
+                if analysis.is_safe_to_delete() {
+                    ui.colored_label(Color32::GREEN, "This file is generally safe to delete");
+                } else {
+                    ui.colored_label(Color32::YELLOW, "Review this file carefully before deleting it");
+                }
+                
+                ui.horizontal(|ui| {
+                    if !analysis.excluded && ui.button("Delete This File").clicked() {
+                        self.confirm_deletion(vec![analysis.path.clone()]);
+                    }
+                    
+                    if ui.button("Re-analyze").clicked() {
+                        self.analyze_file(&analysis.path);
+                    }
+                });
+            });
+
+        if !open {
+            self.analysis = None;
+        }
+    }
+
+    fn handle_dropped_files(&mut self, ctx: &Context) {
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw.dropped_files.iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        
+        if let [path] = dropped.as_slice() {
+            self.analyze_file(path);
+        }
+    }
+
+    fn operation_running(&self) -> bool {
+        self.is_scanning || self.is_cleaning || self.progress.is_busy()
+    }
+
+    fn handle_close_request(&mut self, ctx: &Context) {
+        if ctx.input(|i| i.viewport().close_requested()) && self.show_settings {
+            self.save_config();
+        }
+        
+        if ctx.input(|i| i.viewport().close_requested()) && self.operation_running() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            if !self.quit_when_idle {
+                self.show_close_dialog = true;
+            }
+        }
+        
+        if self.quit_when_idle && !self.operation_running() {
+            info!("Background operation finished, closing");
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+    }
+
+    fn draw_close_dialog(&mut self, ctx: &Context) {
+        if self.quit_when_idle {
+            egui::TopBottomPanel::top("closing_banner").show(ctx, |ui| {
+                ui.colored_label(Color32::YELLOW, "The application will close once the current operation stops.");
+            });
+        }
+        
+        if !self.show_close_dialog {
+            return;
+        }
+        
+        egui::Window::new("Operation In Progress")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("An operation is still running. Closing now could leave it half done.");
+                
+                ui.horizontal(|ui| {
+                    if ui.button("Stop After Current File").clicked() {
+                        self.progress.request_cancel();
+                        self.quit_when_idle = true;
+                        self.show_close_dialog = false;
+                    }
+                    
+                    if ui.button("Finish, Then Close").clicked() {
+                        self.quit_when_idle = true;
+                        self.show_close_dialog = false;
+                    }
+                    
+                    if ui.button("Keep Working").clicked() {
+                        self.show_close_dialog = false;
+                    }
+                });
+            });
+    }
+
+    fn draw_scan_activity_warning(&mut self, ctx: &Context) {
+        let Some(estimate) = &self.scan_activity_warning else {
+            return;
+        };
+        
+        let mut choice = None;
+        egui::Window::new("Scan This Drive Now?")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                for reason in &estimate.reasons {
+                    ui.colored_label(Color32::YELLOW, reason);
+                }
+                match estimate.known_size {
+                    Some((size, files)) => {
+                        ui.label(format!(
+                            "The last scans found {} files holding {}. Scanning reads the details of every file, not its content.",
+                            files,
+                            formatting::format_size(size)
+                        ));
+                        if self.config.find_duplicates_after_scan {
+                            ui.label(format!(
+                                "Finding duplicates after the scan then reads up to {} of file content.",
+                                formatting::format_size(size)
+                            ));
+                        }
+                    }
+                    None => {
+                        ui.label("These folders were not scanned before, their size is unknown.");
+                    }
+                }
+                match estimate.last_duration {
+                    Some(duration) => ui.label(format!("The last scan of these folders took {:.0} s.", duration.as_secs_f64().ceil())),
+                    None => ui.label("No earlier scan of these folders tells how long it takes."),
+                };
+                ui.label("A quick pass only reads file details: it skips the drive health query and the duplicate search.");
+                
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        choice = Some(None);
+                    }
+                    if ui.button("Quick Pass").clicked() {
+                        choice = Some(Some(true));
+                    }
+                    if ui.button("Full Scan").clicked() {
+                        choice = Some(Some(false));
+                    }
+                });
+            });
+        
+        if let Some(choice) = choice {
+            self.scan_activity_warning = None;
+            if let Some(quick) = choice {
+                self.quick_scan = quick;
+                self.start_scan();
+            }
+        }
+    }
+    
+    fn draw_root_scan_warning(&mut self, ctx: &Context) {
+        let Some((root, used)) = self.root_scan_warning.clone() else {
+            return;
+        };
+        
+        egui::Window::new("Scan Entire Drive?")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.colored_label(Color32::YELLOW, format!("{} is the root of a drive.", root.display()));
+                if used > 0 {
+                    let minutes = used / ROOT_SCAN_BYTES_PER_SEC / 60;
+                    ui.label(format!(
+                        "It holds {} of data, which can take {} to scan.",
+                        formatting::format_size(used),
+                        if minutes < 1 { "about a minute".to_string() } else { format!("around {} minutes", minutes) }
+                    ));
+                }
+                ui.label("Scanning a single folder such as your home or Downloads folder finds most junk in a fraction of the time.");
+                
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        self.root_scan_warning = None;
+                    }
+                    
+                    if let Some(home) = dirs::home_dir() {
+                        if ui.button("Scan Home Folder").clicked() {
+                            self.root_scan_warning = None;
+                            let home = home.to_string_lossy().to_string();
+                            for path in std::iter::once(&mut self.scan_path).chain(&mut self.extra_scan_paths) {
+                                if Path::new(path.trim()) == root {
+                                    *path = home.clone();
+                                }
+                            }
+                            self.request_scan_activity();
+                        }
+                    }
+                    
+                    if ui.button("Scan Entire Drive").clicked() {
+                        self.root_scan_warning = None;
+                        self.request_scan_activity();
+                    }
+                });
+            });
+    }
+
+    fn draw_failing_drive_warning(&mut self, ctx: &Context) {
+        if !self.show_failing_drive_warning {
+            return;
+        }
+        
+        egui::Window::new("Failing Drive")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.colored_label(Color32::RED, "SMART reports problems with this drive.");
+                ui.label("Finding duplicates reads every file in full, which can finish off a dying disk. Back up your data first.");
+                
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        self.show_failing_drive_warning = false;
+                    }
+                    
+                    if ui.button("Find Duplicates Anyway").clicked() {
+                        self.show_failing_drive_warning = false;
+                        self.start_duplicate_scan();
+                    }
+                });
+            });
+    }
+
     fn check_background_tasks(&mut self) {
-        let mut progress = self.progress.lock().unwrap();
+        if let Some(receiver) = &self.health_receiver {
+            if let Ok(health) = receiver.try_recv() {
+                self.drive_health = health;
+                self.health_receiver = None;
+            }
+        }
+        
+        if let Some(receiver) = &self.dir_sizes_receiver {
+            if let Ok(cache) = receiver.try_recv() {
+                self.dir_sizes = cache;
+                self.dir_sizes_receiver = None;
+                self.top_dirs = None;
+            }
+        }
+        
+        if let Some(receiver) = &self.wine_receiver {
+            if let Ok(prefixes) = receiver.try_recv() {
+                self.wine_prefixes = prefixes;
+                self.wine_receiver = None;
+            }
+        }
+        
+        if let Some(receiver) = &self.photo_receiver {
+            if let Ok((policy, mut groups)) = receiver.try_recv() {
+                if policy != self.config.photo_keep_policy {
+                    PhotoFinder::apply_keep_policy(&mut groups, self.config.photo_keep_policy);
+                }
+                self.photo_groups = groups;
+                self.photo_receiver = None;
+            }
+        }
+        
+        if let Some(receiver) = &self.songs_receiver {
+            if let Ok(groups) = receiver.try_recv() {
+                self.song_groups = groups;
+                self.songs_receiver = None;
+            }
+        }
+        
+        if let Some(receiver) = &self.documents_receiver {
+            if let Ok(documents) = receiver.try_recv() {
+                self.similar_documents = documents;
+                self.documents_receiver = None;
+            }
+        }
+        
+        if let Some(receiver) = &self.keep_policy_receiver {
+            if let Ok(groups) = receiver.try_recv() {
+                self.duplicates = groups;
+                self.keep_policy_receiver = None;
+            }
+        }
+        
+        if let Some(receiver) = &self.dry_run_receiver {
+            if let Ok(report) = receiver.try_recv() {
+                self.dry_run_report = Some(report);
+                self.dry_run_receiver = None;
+            }
+        }
         
-        match &progress.state {
+        if let Some(receiver) = &self.preflight_receiver {
+            if let Ok(report) = receiver.try_recv() {
+                self.preflight_receiver = None;
+                self.finish_preflight(report);
+            }
+        }
+        
+        if let Some(receiver) = &self.chunk_receiver {
+            if let Ok(overlap) = receiver.try_recv() {
+                self.chunk_overlap = Some(overlap);
+                self.chunk_receiver = None;
+            }
+        }
+        
+        // Results are moved out of the events, not cloned
+        for state in self.progress.poll() {
+            self.finish_task(state);
+        }
+    }
+
+    fn finish_task(&mut self, state: ProgressState) {
+        if let ProgressState::Complete { .. } = state {
+            self.progress_error = None;
+        }
+        match state {
             ProgressState::Complete { scan_result: Some(results), .. } => {
-                let results = results.clone();
-                self.scan_results = Some(results);
+                self.file_choices.clear();
+                self.artifact_choices.clear();
+                untick_locked_files(&mut self.file_choices, &results, None);
+                self.scan_results = Some(*results);
                 self.is_scanning = false;
-                progress.state = ProgressState::Idle;
+                self.dir_sizes = DirSizeCache::load();
+                self.top_dirs = None;
+                self.baseline_hidden = 0;
+                self.hide_baseline_files();
+                
+                let cancelled = self.scan_results.as_ref().is_some_and(|results| results.cancelled);
+                if self.config.find_duplicates_after_scan && !cancelled && !self.quick_scan {
+                    self.request_duplicate_scan();
+                }
             },
             ProgressState::Complete { duplicates: Some(duplicates), .. } => {
-                let duplicates = duplicates.clone();
-                self.duplicates = duplicates;
-                progress.state = ProgressState::Idle;
+                self.duplicates = duplicates.groups;
+                self.duplicates_cancelled = duplicates.cancelled;
             },
-            ProgressState::Complete { cleaned_bytes: Some(bytes), .. } => {
-                self.cleaned_space = *bytes;
+            ProgressState::Complete { cleaned_bytes: Some(bytes), failed_files, command_outputs, cleaned_files, files_left, .. } => {
+                self.cleaned_space = bytes;
+                self.cleaned_files = (cleaned_files, files_left);
+                self.failed_files = failed_files;
+                self.command_outputs = command_outputs;
                 self.is_cleaning = false;
                 self.files_to_delete.clear();
-                progress.state = ProgressState::Idle;
                 
                 // Refresh scan results after cleanup
-                if self.scan_results.is_some() {
-                    drop(progress); // Release the lock before calling start_scan
+                if self.scan_results.is_some() && !self.quit_when_idle {
                     self.start_scan();
-                    return;
                 }
             },
-            ProgressState::Error(_) => {
-                self.is_scanning = false;
-                self.is_cleaning = false;
+            ProgressState::Error { action, error } => {
+                match action {
+                    "Scan" => self.is_scanning = false,
+                    "Cleanup" => self.is_cleaning = false,
+                    _ => {}
+                }
+                self.progress_error = Some((action, error));
             },
             _ => {}
         }
     }
 }
 
+/// Whether `file` of `category` is ticked for cleanup: as [`default_tick`]
+/// says, unless the user ticked or unticked the file itself.
+fn is_file_ticked(
+    file_choices: &HashMap<FileCategory, HashMap<PathBuf, bool>>,
+    artifact_choices: &HashMap<PathBuf, bool>,
+    selected_categories: &HashMap<FileCategory, bool>,
+    category: FileCategory,
+    file: &Path,
+) -> bool {
+    file_choices.get(&category)
+        .and_then(|choices| choices.get(file))
+        .copied()
+        .unwrap_or_else(|| default_tick(artifact_choices, selected_categories, category, file))
+}
+
+/// Unticks the files of `category`, or of every category, that cleaning
+/// would fail on, leaving those the user picked by hand as they are.
+fn untick_locked_files(
+    file_choices: &mut HashMap<FileCategory, HashMap<PathBuf, bool>>,
+    results: &ScanResult,
+    only: Option<FileCategory>,
+) {
+    if results.locked_files.is_empty() {
+        return;
+    }
+    for (category, files) in &results.files_by_category {
+        if only.is_some_and(|only| only != *category) {
+            continue;
+        }
+        for file in files {
+            if results.locked_files.contains_key(&FileId::of(file)) {
+                file_choices.entry(*category).or_default().entry(file.clone()).or_insert(false);
+            }
+        }
+    }
+}
+
+/// Whether `file` of `category` is ticked unless picked by hand: as the build
+/// folder holding it when the user ticked or unticked that whole folder, and
+/// as its category otherwise.
+fn default_tick(
+    artifact_choices: &HashMap<PathBuf, bool>,
+    selected_categories: &HashMap<FileCategory, bool>,
+    category: FileCategory,
+    file: &Path,
+) -> bool {
+    let artifact_choice = (category == FileCategory::DevArtifacts)
+        .then(|| artifact_choices.iter().find(|(artifact, _)| file.starts_with(artifact)))
+        .flatten();
+    match artifact_choice {
+        Some((_, ticked)) => *ticked,
+        None => selected_categories.get(&category).copied().unwrap_or(false),
+    }
+}
+
+fn log_level_combo(ui: &mut Ui, id: impl std::hash::Hash, level: &mut log::LevelFilter) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(level.as_str())
+        .show_ui(ui, |ui| {
+            for option in log::LevelFilter::iter() {
+                ui.selectable_value(level, option, option.as_str());
+            }
+        });
+}
+
+/// Puts `files` on the system clipboard, one path per line, for pasting into other tools.
+fn copy_paths(ui: &Ui, files: &[PathBuf]) {
+    let text: Vec<String> = files.iter().map(|file| file.display().to_string()).collect();
+    ui.ctx().copy_text(text.join("\n"));
+    info!("Copied {} paths to the clipboard", files.len());
+}
+
+/// Up to `count` of `files` picked at random, each with a chance growing with
+/// its size, so a few big mistakes are more likely to show than many small
+/// ones. Every file has some chance, empty ones included.
+fn sample_files(files: &[PathBuf], count: usize) -> Vec<(PathBuf, u64)> {
+    let mut random = vec![0u8; files.len() * 8];
+    if let Err(e) = getrandom::getrandom(&mut random) {
+        error!("No random numbers to sample the cleanup: {}", e);
+    }
+    
+    // Weighted sampling without replacement: each file draws u^(1/weight)
+    // for a uniform u and the highest keys are kept. Logarithms keep the
+    // keys apart for large weights.
+    let mut keyed: Vec<(f64, &PathBuf, u64)> = files.iter()
+        .zip(random.chunks_exact(8))
+        .map(|(file, bytes)| {
+            let size = std::fs::symlink_metadata(file).map_or(0, |metadata| metadata.len());
+            let uniform = (u64::from_le_bytes(bytes.try_into().unwrap()) >> 11) as f64 / (1u64 << 53) as f64;
+            let key = uniform.max(f64::MIN_POSITIVE).ln() / (size as f64 + 1.0);
+            (key, file, size)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keyed.truncate(count);
+    
+    let mut sample: Vec<(PathBuf, u64)> = keyed.into_iter().map(|(_, file, size)| (file.clone(), size)).collect();
+    sample.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    sample
+}
+
+/// The state of one running task, with a button to cancel it alone.
+fn draw_task_progress(ui: &mut Ui, task: &RunningTask) {
+    let state = &task.state;
+    match state {
+        ProgressState::Idle => {
+            ui.label("Starting...");
+        },
+        ProgressState::Scanning { current_path, files_processed, total_files: None, .. } => {
+            ui.label(format!("Listing files: {} found", files_processed));
+            ui.label(format!("Current: {}", current_path));
+            ui.add(egui::ProgressBar::new(0.0).animate(true));
+        },
+        ProgressState::Scanning { current_path, files_processed, total_files: Some(total_files), .. } => {
+            ui.label(format!("Scanning: {}/{} files", files_processed, total_files));
+            ui.label(format!("Current: {}", current_path));
+            ui.add(egui::ProgressBar::new(state.fraction().unwrap_or(0.0)).show_percentage());
+        },
+        ProgressState::FindingDuplicates { files_processed, total_files, bytes_processed, total_bytes, shares, .. } => {
+            ui.label(format!(
+                "Finding duplicates: {}/{} files, {} of {} read",
+                files_processed,
+                total_files,
+                formatting::format_size(*bytes_processed),
+                formatting::format_size(*total_bytes)
+            ));
+            ui.add(egui::ProgressBar::new(state.fraction().unwrap_or(0.0)).show_percentage());
+            for share in shares {
+                ui.label(format!(
+                    "Reading {} at {}/s",
+                    share.mount_point.display(),
+                    formatting::format_size(share.bytes_per_second)
+                ));
+            }
+        },
+        ProgressState::Cleaning { files_processed, total_files, bytes_processed, total_bytes, freed_bytes, .. } => {
+            ui.label(format!(
+                "Cleaning: {}/{} files, {} of {}, {} freed so far",
+                files_processed,
+                total_files,
+                formatting::format_size(*bytes_processed),
+                formatting::format_size(*total_bytes),
+                formatting::format_size(*freed_bytes)
+            ));
+            ui.add(egui::ProgressBar::new(state.fraction().unwrap_or(0.0)).show_percentage());
+        },
+        // Final states end the task, they never reach a running one
+        ProgressState::Complete { .. } | ProgressState::Error { .. } => {},
+    }
+    if let Some(rate) = state.rate_summary() {
+        ui.label(rate);
+    }
+    
+    let cancelling = task.tracker.is_cancelled();
+    if ui.add_enabled(!cancelling, egui::Button::new(if cancelling { "Cancelling..." } else { "Cancel" })).clicked() {
+        task.tracker.request_cancel();
+    }
+}
+
+fn draw_cleanup_preview(ui: &mut Ui, preview: &CleanupPreview, config: &Config) {
+    for (category, files) in &preview.by_category {
+        let name = category.map_or_else(|| "Other files".to_string(), |category| config.category_name(category));
+        egui::CollapsingHeader::new(format!("{}: {} files", name, files.len()))
+            .id_source(("preview_category", name))
+            .show(ui, |ui| {
+                for file in files.iter().take(file_list_page()) {
+                    ui.label(file.display().to_string());
+                }
+                if files.len() > file_list_page() {
+                    ui.label(format!("and {} more", files.len() - file_list_page()));
+                }
+            });
+    }
+    
+    if !preview.duplicate_groups.is_empty() {
+        egui::CollapsingHeader::new(format!("Duplicates: {} groups", preview.duplicate_groups.len()))
+            .id_source("preview_duplicates")
+            .show(ui, |ui| {
+                for (kept, removed) in preview.duplicate_groups.iter().take(file_list_page()) {
+                    ui.group(|ui| {
+                        ui.colored_label(Color32::GREEN, format!("Keep {}", kept.display()));
+                        for file in removed {
+                            ui.colored_label(Color32::RED, format!("Delete {}", file.display()));
+                        }
+                    });
+                }
+                if preview.duplicate_groups.len() > file_list_page() {
+                    ui.label(format!("and {} more groups", preview.duplicate_groups.len() - file_list_page()));
+                }
+            });
+    }
+}
+
+/// Every file of each group except the first one, which is the copy to keep.
+fn all_but_first(groups: &[Vec<PathBuf>]) -> Vec<PathBuf> {
+    groups.iter()
+        .flat_map(|group| group.iter().skip(1).cloned())
+        .collect()
+}
+
+fn draw_keep_first_groups(ui: &mut Ui, groups: &[Vec<PathBuf>], keep_note: &str, other_note: &str) {
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for group in groups {
+            ui.group(|ui| {
+                for (index, file) in group.iter().enumerate() {
+                    if index == 0 {
+                        ui.colored_label(Color32::GREEN, format!("{}  ({})", unicode_names::display(file), keep_note));
+                    } else {
+                        ui.colored_label(Color32::RED, format!("{}  ({})", unicode_names::display(file), other_note));
+                    }
+                }
+            });
+        }
+    });
+}
+
 impl eframe::App for DiskCleanerApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
         self.check_background_tasks();
+        self.handle_dropped_files(ctx);
+        self.handle_close_request(ctx);
+        self.draw_close_dialog(ctx);
+        
+        if self.mini_mode {
+            self.draw_mini_widget(ctx);
+            if self.is_scanning || self.is_cleaning {
+                ctx.request_repaint();
+            }
+            return;
+        }
         
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Intelligent Disk Cleaner");
@@ -415,21 +4225,53 @@ impl eframe::App for DiskCleanerApp {
             
             self.draw_results(ui);
             
-            if self.cleaned_space > 0 {
+            if let (cleaned_files, Some(files_left)) = self.cleaned_files {
+                ui.separator();
+                ui.colored_label(
+                    Color32::YELLOW,
+                    format!(
+                        "Cleanup cancelled: {} files cleaned ({}), {} files left in place",
+                        cleaned_files,
+                        formatting::format_size(self.cleaned_space),
+                        files_left
+                    )
+                );
+            } else if self.cleaned_space > 0 {
                 ui.separator();
                 ui.colored_label(
                     Color32::GREEN,
-                    format!("Successfully cleaned: {}", humansize::format_size(self.cleaned_space, humansize::DECIMAL))
+                    format!("Successfully cleaned: {}", formatting::format_size(self.cleaned_space))
                 );
             }
+            
+            self.draw_failed_files(ui);
+            self.draw_command_report(ui);
         });
         
         self.draw_duplicates_window(ctx);
+        self.draw_versions_window(ctx);
+        self.draw_photos_window(ctx);
+        self.draw_songs_window(ctx);
+        self.draw_documents_window(ctx);
+        self.draw_wine_prefixes_window(ctx);
+        self.draw_system_storage_window(ctx);
+        self.draw_chunk_comparison_window(ctx);
         self.draw_settings_window(ctx);
+        self.draw_scheduled_cleanup_plan(ctx);
+        self.draw_disk_activity_window(ctx);
+        self.draw_top_dirs_window(ctx);
+        self.draw_dry_run_report(ctx);
+        self.draw_preflight_window(ctx);
+        self.draw_quarantine_window(ctx);
+        self.draw_history_window(ctx);
         self.draw_confirmation_dialog(ctx);
+        self.draw_failing_drive_warning(ctx);
+        self.draw_root_scan_warning(ctx);
+        self.draw_scan_activity_warning(ctx);
+        self.draw_analysis_window(ctx);
         
         // Request repaint for animations and progress updates
-        if self.is_scanning || self.is_cleaning {
+        if self.is_scanning || self.is_cleaning || self.quit_when_idle || self.wine_receiver.is_some() || self.chunk_receiver.is_some() || self.dry_run_receiver.is_some() || self.preflight_receiver.is_some() || self.keep_policy_receiver.is_some() || self.photo_receiver.is_some() || self.songs_receiver.is_some() || self.documents_receiver.is_some() {
             ctx.request_repaint();
         }
     }
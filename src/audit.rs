@@ -0,0 +1,148 @@
+use std::path::{Path, PathBuf};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signer, SigningKey};
+use log::info;
+use serde::{Serialize, Deserialize};
+
+use crate::config;
+use crate::error::Error;
+
+/// DER prefix of an Ed25519 SubjectPublicKeyInfo, followed by the 32 key bytes.
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Where the file was moved instead of the trash: the quarantine, or the
+    /// quarantine folder of a FAT drive, which has no trash
+    #[serde(default)]
+    pub quarantined_to: Option<PathBuf>,
+    /// Deleted for good rather than moved to the trash or the quarantine, as
+    /// when the trash could not take it
+    #[serde(default)]
+    pub deleted_permanently: bool,
+}
+
+/// What a cleanup run removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// The files can be restored, from the trash or the quarantine
+    pub moved_to_trash: bool,
+    pub files: Vec<RemovedFile>,
+    /// Files that were refused or could not be removed, with the reason
+    #[serde(default)]
+    pub failed: Vec<(PathBuf, Error)>,
+    /// Free-text label the user gave the run, e.g. "pre-upgrade purge"
+    #[serde(default)]
+    pub note: Option<String>,
+    /// The user stopped the cleanup before it went through every file
+    #[serde(default)]
+    pub cancelled: bool,
+    /// Files left in place, by a cancellation or once the target was reached
+    #[serde(default)]
+    pub files_left: usize,
+}
+
+impl CleanupReport {
+    pub fn cleaned_bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+}
+
+/// Writes `report` to the audit directory with a detached Ed25519 signature, so
+/// any later change to the record can be detected. The signature can be checked
+/// with `openssl pkeyutl -verify -pubin -inkey public_key.pem -rawin -in <report>.json -sigfile <report>.sig`.
+pub fn write_signed_report(report: &CleanupReport) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let dir = config::app_data_dir().ok_or("no data directory")?.join("audit");
+    std::fs::create_dir_all(&dir)?;
+
+    let (key, created) = load_or_create_key()?;
+    let pem_path = dir.join("public_key.pem");
+    if created || !pem_path.exists() {
+        let public_key = [ED25519_SPKI_PREFIX.as_slice(), key.verifying_key().as_bytes()].concat();
+        let pem = format!(
+            "-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n",
+            base64::engine::general_purpose::STANDARD.encode(public_key)
+        );
+        std::fs::write(pem_path, pem)?;
+    }
+
+    let content = serde_json::to_vec_pretty(report)?;
+    let signature = key.sign(&content);
+
+    // Records are never overwritten, runs finishing within the same
+    // microsecond get a counter
+    let stamp = report.finished_at.format("%Y%m%dT%H%M%S%.6fZ");
+    let mut attempt = 0;
+    let (name, path) = loop {
+        let name = match attempt {
+            0 => format!("cleanup-{}", stamp),
+            _ => format!("cleanup-{}_{}", stamp, attempt),
+        };
+        let path = dir.join(format!("{}.json", name));
+        match write_new(&path, &content) {
+            Ok(()) => break (name, path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e.into()),
+        }
+    };
+    write_new(&dir.join(format!("{}.sig", name)), &signature.to_bytes())?;
+
+    info!("Wrote signed cleanup record to {}", path.display());
+    Ok(path)
+}
+
+fn write_new(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?
+        .write_all(bytes)
+}
+
+/// The signing key lives in the config dir, readable by its owner only.
+/// Also returns whether the key was just created.
+fn load_or_create_key() -> Result<(SigningKey, bool), Box<dyn std::error::Error + Send + Sync>> {
+    let path = config::app_config_dir().ok_or("no config directory")?.join("audit_signing_key");
+
+    if let Ok(bytes) = std::fs::read(&path) {
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| "malformed audit signing key")?;
+        return Ok((SigningKey::from_bytes(&bytes), false));
+    }
+
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes)?;
+    write_private(&path, &bytes)?;
+    info!("Created audit signing key at {}", path.display());
+    Ok((SigningKey::from_bytes(&bytes), true))
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(bytes)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, bytes)
+}
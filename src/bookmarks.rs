@@ -0,0 +1,179 @@
+//! The folders holding most of a category, handed to the user's own file
+//! manager: as GTK bookmarks, which Nautilus, Thunar and the file dialogs
+//! list in their sidebar, or as a set of shortcuts in a folder of their
+//! choice, so the locations can be reviewed by hand before cleaning them.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use log::info;
+
+/// Folders exported for a category, biggest first.
+pub const TOP_DIRECTORIES: usize = 20;
+
+/// The folders directly holding `files`, with the size of those files,
+/// biggest first and at most `limit` of them.
+pub fn top_directories(files: &[PathBuf], limit: usize) -> Vec<(PathBuf, u64)> {
+    let mut sizes: HashMap<&Path, u64> = HashMap::new();
+    for file in files {
+        let Some(parent) = file.parent() else {
+            continue;
+        };
+        let size = std::fs::symlink_metadata(file).map_or(0, |metadata| metadata.len());
+        *sizes.entry(parent).or_default() += size;
+    }
+    let mut directories: Vec<(PathBuf, u64)> = sizes.into_iter()
+        .map(|(directory, size)| (directory.to_path_buf(), size))
+        .collect();
+    directories.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    directories.truncate(limit);
+    directories
+}
+
+fn gtk_bookmarks_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("gtk-3.0").join("bookmarks"))
+}
+
+/// Appends `directories` to the GTK bookmarks, labelled with `label` and the
+/// folder name. Folders already bookmarked are left as they are. Returns how
+/// many were added.
+pub fn add_to_file_manager(directories: &[(PathBuf, u64)], label: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let path = gtk_bookmarks_path().ok_or("no configuration directory")?;
+    let mut bookmarks = match std::fs::read_to_string(&path) {
+        Ok(bookmarks) => bookmarks,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let existing: Vec<String> = bookmarks.lines()
+        .filter_map(|line| line.split(' ').next())
+        .map(str::to_string)
+        .collect();
+    if !bookmarks.is_empty() && !bookmarks.ends_with('\n') {
+        bookmarks.push('\n');
+    }
+
+    let mut added = 0;
+    for (directory, _) in directories {
+        let url = file_url(directory);
+        if existing.contains(&url) {
+            continue;
+        }
+        let _ = writeln!(bookmarks, "{} {}: {}", url, label, folder_name(directory));
+        added += 1;
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, bookmarks)?;
+    info!("Added {} folders to the file manager bookmarks in {}", added, path.display());
+    Ok(added)
+}
+
+/// Writes a shortcut to each of `directories` into a folder named after
+/// `label` inside `parent`, each named after its rank and folder name so the
+/// biggest come first. Returns how many were written.
+pub fn write_shortcuts(directories: &[(PathBuf, u64)], parent: &Path, label: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let into = parent.join(format!("Disk Cleaner - {}", sanitize(label)));
+    std::fs::create_dir_all(&into)?;
+    let shortcuts: Vec<(PathBuf, &Path)> = directories.iter()
+        .enumerate()
+        .map(|(index, (directory, _))| {
+            let name = format!("{:02} {}", index + 1, sanitize(&folder_name(directory)));
+            (into.join(name), directory.as_path())
+        })
+        .collect();
+    write_shortcut_files(&shortcuts)?;
+    info!("Wrote {} folder shortcuts to {}", shortcuts.len(), into.display());
+    Ok(shortcuts.len())
+}
+
+/// Freedesktop link entries, which file managers show and open as the folder.
+#[cfg(target_os = "linux")]
+fn write_shortcut_files(shortcuts: &[(PathBuf, &Path)]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    for (shortcut, directory) in shortcuts {
+        let entry = format!(
+            "[Desktop Entry]\nType=Link\nName={}\nIcon=folder\nURL={}\n",
+            folder_name(directory),
+            file_url(directory)
+        );
+        std::fs::write(with_suffix(shortcut, ".desktop"), entry)?;
+    }
+    Ok(())
+}
+
+/// `.lnk` files are a binary format, Windows Script Host writes them.
+#[cfg(windows)]
+fn write_shortcut_files(shortcuts: &[(PathBuf, &Path)]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let quote = |path: &Path| format!("'{}'", path.display().to_string().replace('\'', "''"));
+    let mut script = String::from("$shell = New-Object -ComObject WScript.Shell;");
+    for (shortcut, directory) in shortcuts {
+        let _ = write!(
+            script,
+            "$link = $shell.CreateShortcut({}); $link.TargetPath = {}; $link.Save();",
+            quote(&with_suffix(shortcut, ".lnk")),
+            quote(directory)
+        );
+    }
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-Command", &script])
+        .output()?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string().into());
+    }
+    Ok(())
+}
+
+/// Finder follows symbolic links like aliases.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn write_shortcut_files(shortcuts: &[(PathBuf, &Path)]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    for (shortcut, directory) in shortcuts {
+        std::os::unix::fs::symlink(directory, shortcut)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn write_shortcut_files(_shortcuts: &[(PathBuf, &Path)]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    Err("shortcuts are not supported on this platform".into())
+}
+
+/// `path` with `suffix` appended, where `with_extension` would replace
+/// whatever follows a dot in a folder name.
+#[cfg(any(target_os = "linux", windows))]
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn folder_name(directory: &Path) -> String {
+    directory.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| directory.display().to_string())
+}
+
+/// `name` with the characters file names cannot hold on any platform replaced.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control() { '_' } else { c })
+        .collect()
+}
+
+/// `file://` URL of `path`, with everything but unreserved characters and
+/// separators percent-encoded.
+fn file_url(path: &Path) -> String {
+    let mut url = String::from("file://");
+    let text = path.to_string_lossy().replace('\\', "/");
+    if !text.starts_with('/') {
+        url.push('/');
+    }
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            let _ = write!(url, "%{:02X}", byte);
+        }
+    }
+    url
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use log::{info, debug};
+use rusqlite::{Connection, OpenFlags};
+use serde::{Serialize, Deserialize};
+
+use crate::config;
+
+/// Microseconds between 1601-01-01 (Chromium's epoch) and 1970-01-01.
+const CHROMIUM_EPOCH_OFFSET_MICROS: i64 = 11_644_473_600_000_000;
+
+const CHROMIUM_DOWNLOADS_QUERY: &str = "
+    SELECT d.target_path,
+           COALESCE(NULLIF(d.tab_url, ''),
+                    (SELECT c.url FROM downloads_url_chains c
+                     WHERE c.id = d.id ORDER BY c.chain_index DESC LIMIT 1)),
+           d.start_time
+    FROM downloads d";
+
+const FIREFOX_DOWNLOADS_QUERY: &str = "
+    SELECT a.content, p.url, a.dateAdded
+    FROM moz_annos a
+    JOIN moz_anno_attributes n ON a.anno_attribute_id = n.id
+    JOIN moz_places p ON p.id = a.place_id
+    WHERE n.name = 'downloads/destinationFileURI'";
+
+/// Where and when a file was downloaded, according to a browser's history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadRecord {
+    pub source_url: String,
+    pub downloaded_at: Option<DateTime<Utc>>,
+    pub browser: String,
+}
+
+/// Reads the download history of the installed browsers, keyed by the path the
+/// file was saved to. Databases that are missing or unreadable are skipped.
+pub fn load_download_history() -> HashMap<PathBuf, DownloadRecord> {
+    let mut history = HashMap::new();
+
+    for (browser, database) in chromium_databases() {
+        read_database(&database, browser, CHROMIUM_DOWNLOADS_QUERY, &mut history, |target, time| {
+            let time = (time != 0).then(|| DateTime::from_timestamp_micros(time - CHROMIUM_EPOCH_OFFSET_MICROS)).flatten();
+            (PathBuf::from(target), time)
+        });
+    }
+
+    for database in firefox_databases() {
+        read_database(&database, "Firefox", FIREFOX_DOWNLOADS_QUERY, &mut history, |target, time| {
+            (file_uri_to_path(&target), DateTime::from_timestamp_micros(time))
+        });
+    }
+
+    info!("Loaded {} entries from browser download history", history.len());
+    history
+}
+
+fn read_database(
+    database: &Path,
+    browser: &str,
+    query: &str,
+    history: &mut HashMap<PathBuf, DownloadRecord>,
+    convert: impl Fn(String, i64) -> (PathBuf, Option<DateTime<Utc>>),
+) {
+    // Browsers keep their databases locked while running, so read a copy
+    let Some(copy) = copy_database(database) else {
+        return;
+    };
+
+    let result = (|| -> rusqlite::Result<()> {
+        let connection = Connection::open_with_flags(&copy, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut statement = connection.prepare(query)?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                row.get::<_, Option<i64>>(2)?.unwrap_or_default(),
+            ))
+        })?;
+
+        for (target, url, time) in rows.flatten() {
+            let (path, downloaded_at) = convert(target, time);
+            history.insert(path, DownloadRecord {
+                source_url: url,
+                downloaded_at,
+                browser: browser.to_string(),
+            });
+        }
+
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        debug!("Could not read download history {}: {}", database.display(), e);
+    }
+
+    let _ = std::fs::remove_file(&copy);
+}
+
+fn copy_database(database: &Path) -> Option<PathBuf> {
+    let dir = config::app_cache_dir()?.join("browser-history");
+    std::fs::create_dir_all(&dir).ok()?;
+
+    let key = blake3::hash(database.to_string_lossy().as_bytes()).to_hex();
+    let copy = dir.join(&key[..16]);
+    std::fs::copy(database, &copy).ok()?;
+    Some(copy)
+}
+
+/// `History` databases of the Chromium-based browsers, one per profile.
+fn chromium_databases() -> Vec<(&'static str, PathBuf)> {
+    let browsers: &[(&str, &[&str])] = if cfg!(windows) {
+        &[
+            ("Chrome", &["Google", "Chrome", "User Data"]),
+            ("Edge", &["Microsoft", "Edge", "User Data"]),
+            ("Brave", &["BraveSoftware", "Brave-Browser", "User Data"]),
+        ]
+    } else if cfg!(target_os = "macos") {
+        &[
+            ("Chrome", &["Google", "Chrome"]),
+            ("Edge", &["Microsoft Edge"]),
+            ("Brave", &["BraveSoftware", "Brave-Browser"]),
+        ]
+    } else {
+        &[
+            ("Chrome", &["google-chrome"]),
+            ("Chromium", &["chromium"]),
+            ("Edge", &["microsoft-edge"]),
+            ("Brave", &["BraveSoftware", "Brave-Browser"]),
+        ]
+    };
+
+    let base = if cfg!(windows) { dirs::data_local_dir() } else { dirs::config_dir() };
+    let Some(base) = base else {
+        return Vec::new();
+    };
+
+    browsers
+        .iter()
+        .flat_map(|(browser, parts)| {
+            let root = parts.iter().fold(base.clone(), |path, part| path.join(part));
+            profile_files(&root, "History").into_iter().map(move |database| (*browser, database))
+        })
+        .collect()
+}
+
+/// `places.sqlite` databases of the Firefox profiles.
+fn firefox_databases() -> Vec<PathBuf> {
+    let root = if cfg!(target_os = "linux") {
+        dirs::home_dir().map(|home| home.join(".mozilla").join("firefox"))
+    } else if cfg!(windows) {
+        dirs::config_dir().map(|dir| dir.join("Mozilla").join("Firefox").join("Profiles"))
+    } else {
+        dirs::config_dir().map(|dir| dir.join("Firefox").join("Profiles"))
+    };
+
+    root.map(|root| profile_files(&root, "places.sqlite")).unwrap_or_default()
+}
+
+fn profile_files(root: &Path, name: &str) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().join(name))
+        .filter(|file| file.is_file())
+        .collect()
+}
+
+/// Converts a `file://` URI to a path, decoding percent escapes.
+pub fn file_uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    // Windows URIs look like file:///C:/Users/...
+    let path = if cfg!(windows) { path.trim_start_matches('/') } else { path };
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    PathBuf::from(String::from_utf8_lossy(&decoded).to_string())
+}
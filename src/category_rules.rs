@@ -0,0 +1,147 @@
+use std::path::Path;
+use std::sync::OnceLock;
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Serialize, Deserialize};
+
+use crate::config::Config;
+use crate::file_category::FileCategory;
+
+/// How the pattern of a rule is matched against a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PatternKind {
+    /// A glob over the full path, e.g. `**/renders/*.exr`
+    Glob,
+    /// An extension, with or without the dot
+    Extension,
+    /// Text anywhere in the path
+    PathContains,
+}
+
+impl PatternKind {
+    pub fn all() -> [Self; 3] {
+        [Self::Glob, Self::Extension, Self::PathContains]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Glob => "Glob",
+            Self::Extension => "Extension",
+            Self::PathContains => "Path contains",
+        }
+    }
+}
+
+/// A rule from the settings putting the files it matches in a category,
+/// ahead of the built-in heuristics. Matching ignores case.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategoryRule {
+    pub kind: PatternKind,
+    pub pattern: String,
+    pub category: FileCategory,
+}
+
+impl CategoryRule {
+    /// Why the rule cannot match anything, if so.
+    pub fn error(&self) -> Option<String> {
+        if self.pattern.trim().is_empty() {
+            return Some("empty pattern".to_string());
+        }
+        match self.kind {
+            PatternKind::Glob => build_glob(&self.pattern).err().map(|e| e.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn description(&self, config: &Config) -> String {
+        let pattern = match self.kind {
+            PatternKind::Glob => self.pattern.clone(),
+            PatternKind::Extension => format!("*.{}", self.pattern.trim_start_matches('.')),
+            PatternKind::PathContains => format!("paths containing \"{}\"", self.pattern),
+        };
+        format!("{} as {}", pattern, config.category_name(self.category))
+    }
+}
+
+fn build_glob(pattern: &str) -> Result<GlobMatcher, globset::Error> {
+    Ok(GlobBuilder::new(pattern.trim())
+        .case_insensitive(true)
+        .build()?
+        .compile_matcher())
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Glob(GlobMatcher),
+    /// Lowercase, without the dot
+    Extension(String),
+    /// Lowercase
+    PathContains(String),
+    /// A rule whose pattern does not compile, never matching
+    Invalid,
+}
+
+/// The categorization rules of the settings, in the order they are tried.
+/// Their patterns are compiled once, on the first match after a change.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<CategoryRule>", into = "Vec<CategoryRule>")]
+pub struct CategoryRules {
+    rules: Vec<CategoryRule>,
+    compiled: OnceLock<Vec<Matcher>>,
+}
+
+impl From<Vec<CategoryRule>> for CategoryRules {
+    fn from(rules: Vec<CategoryRule>) -> Self {
+        Self { rules, compiled: OnceLock::new() }
+    }
+}
+
+impl From<CategoryRules> for Vec<CategoryRule> {
+    fn from(rules: CategoryRules) -> Self {
+        rules.rules
+    }
+}
+
+impl CategoryRules {
+    pub fn rules(&self) -> &[CategoryRule] {
+        &self.rules
+    }
+
+    pub fn set(&mut self, rules: Vec<CategoryRule>) {
+        *self = rules.into();
+    }
+
+    fn compiled(&self) -> &[Matcher] {
+        self.compiled.get_or_init(|| {
+            self.rules.iter()
+                .map(|rule| match rule.kind {
+                    PatternKind::Glob => build_glob(&rule.pattern).map_or(Matcher::Invalid, Matcher::Glob),
+                    PatternKind::Extension => Matcher::Extension(rule.pattern.trim().trim_start_matches('.').to_lowercase()),
+                    PatternKind::PathContains => Matcher::PathContains(rule.pattern.trim().to_lowercase()),
+                })
+                .collect()
+        })
+    }
+
+    /// The first rule matching `path`.
+    pub fn matching(&self, path: &Path) -> Option<&CategoryRule> {
+        if self.rules.is_empty() {
+            return None;
+        }
+
+        let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+        let mut lowercase_path = None;
+        let index = self.compiled().iter().position(|matcher| match matcher {
+            Matcher::Glob(glob) => glob.is_match(path),
+            Matcher::Extension(wanted) => extension.as_ref() == Some(wanted),
+            Matcher::PathContains(text) => !text.is_empty() && lowercase_path
+                .get_or_insert_with(|| path.to_string_lossy().to_lowercase())
+                .contains(text.as_str()),
+            Matcher::Invalid => false,
+        })?;
+        self.rules.get(index)
+    }
+
+    pub fn category(&self, path: &Path) -> Option<FileCategory> {
+        self.matching(path).map(|rule| rule.category)
+    }
+}
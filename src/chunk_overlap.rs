@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use fastcdc::v2020::StreamCDC;
+use log::info;
+
+/// Chunk sizes for content-defined chunking. Cut points follow the content,
+/// so data inserted or removed early in a file only changes the chunks around
+/// it, and two VM images sharing most of their blocks share most chunks.
+const MIN_CHUNK_SIZE: u32 = 64 * 1024;
+const AVG_CHUNK_SIZE: u32 = 256 * 1024;
+const MAX_CHUNK_SIZE: u32 = 1024 * 1024;
+
+/// Content hash and length of one chunk.
+type Chunk = ([u8; 32], u64);
+
+/// How much content two files have in common, chunk by chunk.
+#[derive(Debug, Clone)]
+pub struct ChunkOverlap {
+    pub first: PathBuf,
+    pub second: PathBuf,
+    pub first_size: u64,
+    pub second_size: u64,
+    /// Bytes of the first file found somewhere in the second
+    pub first_shared: u64,
+    /// Bytes of the second file found somewhere in the first
+    pub second_shared: u64,
+}
+
+impl ChunkOverlap {
+    /// Content only the second file holds, lost if it is deleted and the first kept.
+    pub fn second_unique(&self) -> u64 {
+        self.second_size - self.second_shared
+    }
+
+    pub fn first_unique(&self) -> u64 {
+        self.first_size - self.first_shared
+    }
+}
+
+/// Splits both files into content-defined chunks and measures how much of
+/// each is also found in the other. Both files are read in full, so
+/// `bytes_read` is advanced as they are to show progress.
+pub fn compare(first: &Path, second: &Path, bytes_read: &AtomicU64) -> Result<ChunkOverlap, Box<dyn std::error::Error + Send + Sync>> {
+    info!("Comparing chunks of {} and {}", first.display(), second.display());
+
+    let first_chunks = chunk_hashes(first, bytes_read)?;
+    let second_chunks = chunk_hashes(second, bytes_read)?;
+
+    let shared = |chunks: &[Chunk], other: &[Chunk]| {
+        let other: HashSet<&[u8; 32]> = other.iter().map(|(hash, _)| hash).collect();
+        chunks.iter()
+            .filter(|(hash, _)| other.contains(hash))
+            .map(|(_, length)| length)
+            .sum::<u64>()
+    };
+
+    let overlap = ChunkOverlap {
+        first: first.to_path_buf(),
+        second: second.to_path_buf(),
+        first_size: first_chunks.iter().map(|(_, length)| length).sum(),
+        second_size: second_chunks.iter().map(|(_, length)| length).sum(),
+        first_shared: shared(&first_chunks, &second_chunks),
+        second_shared: shared(&second_chunks, &first_chunks),
+    };
+    info!(
+        "{} of {} bytes of {} are also in {}",
+        overlap.second_shared,
+        overlap.second_size,
+        second.display(),
+        first.display()
+    );
+    Ok(overlap)
+}
+
+fn chunk_hashes(path: &Path, bytes_read: &AtomicU64) -> Result<Vec<Chunk>, Box<dyn std::error::Error + Send + Sync>> {
+    let file = BufReader::new(File::open(path)?);
+    let mut chunks = Vec::new();
+    for chunk in StreamCDC::new(file, MIN_CHUNK_SIZE, AVG_CHUNK_SIZE, MAX_CHUNK_SIZE) {
+        let chunk = chunk?;
+        bytes_read.fetch_add(chunk.length as u64, Ordering::Relaxed);
+        chunks.push((*blake3::hash(&chunk.data).as_bytes(), chunk.length as u64));
+    }
+    Ok(chunks)
+}
@@ -1,82 +1,843 @@
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Instant;
 use log::{info, warn, error};
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
 
+use crate::audit::{CleanupReport, RemovedFile};
+use crate::config;
+use crate::error::Error;
+use crate::history;
+use crate::low_memory;
+use crate::package_caches;
+use crate::quarantine::{self, QuarantinedFile, QuarantineRun};
+use crate::secure_wipe;
+use crate::file_identity::{FileId, FileIdentity};
 use crate::progress::{ProgressTracker, ProgressState};
+use crate::volumes::{self, FatVolumes};
+
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub command: String,
+    pub success: bool,
+    pub output: String,
+}
+
+/// What a cleanup would do, without having touched any file. See [`Cleaner::dry_run`].
+#[derive(Debug, Clone, Default)]
+pub struct DryRunReport {
+    pub moved_to_trash: bool,
+    pub removable: Vec<RemovedFile>,
+    /// Files the cleanup would skip, with the reason
+    pub refused: Vec<(PathBuf, String)>,
+    /// The refused files the current user lacks the permissions to remove,
+    /// which an administrator can take ownership of
+    pub permission_denied: Vec<PathBuf>,
+}
+
+impl DryRunReport {
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.removable.iter().map(|file| file.size).sum()
+    }
+}
 
 pub struct Cleaner {
     use_trash: bool,
+    /// Move files to the application's quarantine, see [`quarantine`]
+    use_quarantine: bool,
+    /// Write a checksum manifest next to the quarantined files, see [`quarantine::write_checksum_manifest`]
+    checksum_manifest: bool,
+    /// Canonical directories deletions must stay inside. `None` means unrestricted.
+    allowed_roots: Option<Vec<PathBuf>>,
+    /// Identities recorded at scan time, keyed by file
+    expected_identities: HashMap<FileId, FileIdentity>,
+    note: Option<String>,
+    /// Stop once this many bytes have been freed
+    target_bytes: Option<u64>,
+    /// Overwrite files this many times and delete them permanently, bypassing
+    /// the trash and the quarantine. 0 deletes files normally.
+    wipe_passes: u32,
+    /// Files removed at once, 0 for one per core
+    threads: usize,
+    /// Remove files one at a time in the order given
+    in_order: bool,
+    trash_fallback: TrashFallback,
+    on_file: Option<FileCallback>,
+}
+
+/// What to do with a file the trash cannot take, because it is full or the
+/// drive has none, or because it is on a Windows drive seen from WSL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TrashFallback {
+    /// Leave the file in place and report it as failed
+    #[default]
+    Skip,
+    /// Move it to the application's quarantine
+    Quarantine,
+    /// Delete it permanently, marking it as such in the cleanup record
+    DeletePermanently,
+}
+
+impl TrashFallback {
+    pub fn all() -> Vec<Self> {
+        vec![Self::Skip, Self::Quarantine, Self::DeletePermanently]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Skip => "Skip the file",
+            Self::Quarantine => "Move it to the quarantine",
+            Self::DeletePermanently => "Delete it permanently",
+        }
+    }
+}
+
+/// What became of one file of a cleanup, as told to [`Cleaner::on_file_cleaned`].
+#[derive(Debug, Clone, Copy)]
+pub enum FileOutcome<'a> {
+    MovedToTrash,
+    /// Moved to the quarantine, or the quarantine folder of a FAT drive
+    Quarantined(&'a Path),
+    Deleted,
+    /// Overwritten, then deleted
+    Wiped,
+    Failed(&'a Error),
+}
+
+impl FileOutcome<'_> {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::MovedToTrash => "trashed",
+            Self::Quarantined(_) => "quarantined",
+            Self::Deleted => "deleted",
+            Self::Wiped => "wiped",
+            Self::Failed(_) => "failed",
+        }
+    }
+}
+
+/// Called with the path, size and outcome of each file a cleanup removed or
+/// failed to remove. Files are removed in parallel, so it is called from
+/// several threads at once and in no particular order.
+pub type FileCallback = Arc<dyn Fn(&Path, u64, FileOutcome) + Send + Sync>;
+
+/// Files of a cleanup with their sizes.
+type SizedFiles = Vec<(PathBuf, u64)>;
+
+/// How a removed file left its place.
+enum Removal {
+    Trashed,
+    Quarantined(PathBuf),
+    Deleted,
+    Wiped,
+}
+
+/// What became of one file of a cleanup.
+enum Outcome {
+    Removed(RemovedFile),
+    Failed(PathBuf, Error),
+    /// Not reached, the cleanup was cancelled or reached its target first
+    Skipped,
 }
 
 impl Cleaner {
     pub fn new(use_trash: bool) -> Self {
-        Self { use_trash }
+        Self {
+            use_trash,
+            use_quarantine: false,
+            checksum_manifest: false,
+            allowed_roots: None,
+            expected_identities: HashMap::new(),
+            note: None,
+            target_bytes: None,
+            wipe_passes: 0,
+            threads: 0,
+            in_order: false,
+            trash_fallback: TrashFallback::Skip,
+            on_file: None,
+        }
+    }
+
+    /// Removes up to `threads` files at once, one per core when 0. Folders are
+    /// removed after all the files, one at a time and deepest first.
+    /// The low-memory profile caps them at its own thread count. Files are
+    /// removed one at a time instead when a target or an order is set.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = if low_memory::is_enabled() && (threads == 0 || threads > low_memory::THREADS) {
+            low_memory::THREADS
+        } else {
+            threads
+        };
+        self
+    }
+
+    /// What to do with files the trash cannot take, see [`TrashFallback`].
+    pub fn with_trash_fallback(mut self, fallback: TrashFallback) -> Self {
+        self.trash_fallback = fallback;
+        self
+    }
+
+    /// Reports each removed or failed file to `callback` as the cleanup goes,
+    /// for integrations that mirror deletions into their own records.
+    pub fn on_file_cleaned(mut self, callback: FileCallback) -> Self {
+        self.on_file = Some(callback);
+        self
+    }
+
+    /// Overwrites each file `passes` times before deleting it permanently, so
+    /// its content cannot be recovered. 0 turns the wipe off.
+    pub fn with_secure_wipe(mut self, passes: u32) -> Self {
+        self.wipe_passes = passes.min(secure_wipe::MAX_PASSES);
+        self
+    }
+
+    /// Whether cleaned files can be brought back from the trash or the quarantine.
+    fn moves_to_trash(&self) -> bool {
+        (self.use_trash || self.use_quarantine) && self.wipe_passes == 0
+    }
+
+    /// Stops the cleanup as soon as `target_bytes` have been freed, leaving the
+    /// rest of the files in place.
+    pub fn with_target(mut self, target_bytes: Option<u64>) -> Self {
+        self.target_bytes = target_bytes;
+        self
+    }
+
+    /// Removes files one at a time in the order given, for a cleanup the user
+    /// ordered so the files they care least about go first.
+    pub fn with_order(mut self, in_order: bool) -> Self {
+        self.in_order = in_order;
+        self
+    }
+
+    fn target_reached(&self, freed_bytes: u64) -> bool {
+        self.target_bytes.is_some_and(|target| freed_bytes >= target)
+    }
+
+    /// Moves files to the quarantine instead of the trash, from where they can
+    /// be restored until the quarantine is emptied.
+    pub fn with_quarantine(mut self, enabled: bool) -> Self {
+        self.use_quarantine = enabled;
+        self
+    }
+
+    /// Writes the BLAKE3 of the quarantined files into a manifest next to them.
+    pub fn with_checksum_manifest(mut self, enabled: bool) -> Self {
+        self.checksum_manifest = enabled;
+        self
+    }
+
+    /// Labels the run, the note is kept in its cleanup report.
+    pub fn with_note(mut self, note: &str) -> Self {
+        let note = note.trim();
+        self.note = (!note.is_empty()).then(|| note.to_string());
+        self
+    }
+
+    /// Makes deletions fail for files whose device and inode no longer match
+    /// the ones recorded when they were scanned. Every file is checked just
+    /// before it is removed, and permanent deletions check it again on the
+    /// directory handle they unlink through. Moves to the trash or the
+    /// quarantine go by path, so they only have the first check.
+    pub fn with_expected_identities(mut self, identities: HashMap<FileId, FileIdentity>) -> Self {
+        self.expected_identities = identities;
+        self
+    }
+
+    /// Restricts deletions to files that really live under `roots` once symbolic
+    /// links in their parent directories are resolved.
+    pub fn with_allowed_roots(mut self, roots: &[PathBuf]) -> Self {
+        self.allowed_roots = Some(roots
+            .iter()
+            .filter_map(|root| root.canonicalize().ok())
+            .collect());
+        self
     }
 
     pub fn clean_files(
         &self,
         files: &[PathBuf],
-        progress: Arc<Mutex<ProgressTracker>>,
-    ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        progress: Arc<ProgressTracker>,
+    ) -> Result<CleanupReport, Error> {
         info!("Starting cleanup of {} files", files.len());
+        let (files, folders) = split_folders(files);
 
-        let mut total_cleaned_bytes = 0u64;
-        let total_files = files.len();
+        let started_at = chrono::Utc::now();
+        let started = Instant::now();
+        let quarantined = Mutex::new(Vec::new());
+        let freed_bytes = AtomicU64::new(0);
+        let files_processed = AtomicUsize::new(0);
+        let bytes_processed = AtomicU64::new(0);
+        let total_files = files.len() + folders.len();
+        let total_bytes = files.iter().map(|(_, size)| size).sum();
+        let app_owned = config::app_owned_paths();
+        let fat_volumes = FatVolumes::detect();
+        let wsl_windows_drives = volumes::windows_drives_from_wsl();
+        let run_dir = if self.use_quarantine && self.wipe_passes == 0 { Some(quarantine::run_dir(&started_at)?) } else { None };
+        // Named on the first file the trash turns down, if any
+        let fallback_run_dir = OnceLock::new();
+        let fat_run_dirs = Mutex::new(HashSet::new());
 
-        for (index, file_path) in files.iter().enumerate() {
-            // Update progress
-            {
-                let mut progress_guard = progress.lock().unwrap();
-                progress_guard.state = ProgressState::Cleaning {
-                    files_processed: index + 1,
-                    total_files,
-                };
+        let record = |stored: QuarantinedFile| {
+            let stored_at = stored.stored_at.clone();
+            quarantined.lock().unwrap().push(stored);
+            Removal::Quarantined(stored_at)
+        };
+        let store = |file_path: &PathBuf, run_dir: &Path| {
+            quarantine::store(file_path, run_dir).map_err(Error::from).map(record)
+        };
+
+        let clean = |(file_path, size): &(PathBuf, u64)| {
+            if progress.is_cancelled() || self.target_reached(freed_bytes.load(Ordering::Relaxed)) {
+                return Outcome::Skipped;
             }
 
-            // Get file size before deletion
-            let file_size = match std::fs::metadata(file_path) {
-                Ok(metadata) => metadata.len(),
-                Err(e) => {
-                    warn!("Could not get metadata for {}: {}", file_path.display(), e);
-                    continue;
+            // Update progress
+            let index = files_processed.fetch_add(1, Ordering::Relaxed);
+            progress.update(ProgressState::Cleaning {
+                files_processed: index + 1,
+                total_files,
+                bytes_processed: bytes_processed.fetch_add(*size, Ordering::Relaxed) + size,
+                total_bytes,
+                freed_bytes: freed_bytes.load(Ordering::Relaxed),
+                started,
+            }, index + 1 == total_files);
+
+            let expected = self.expected_identities.get(&FileId::of(file_path)).copied();
+            let (file_path, file_size) = match self.check_file(file_path, &app_owned, expected, &HashSet::new()) {
+                Ok(checked) => checked,
+                Err(reason) => {
+                    warn!("Refusing to clean {}: {}", file_path.display(), reason);
+                    let error = Error::Other(reason);
+                    self.report_file(file_path, *size, FileOutcome::Failed(&error));
+                    return Outcome::Failed(file_path.clone(), error);
                 }
             };
+            let file_path = &file_path;
 
             // Attempt to delete the file
-            let result = if self.use_trash {
-                self.move_to_trash(file_path)
+            let result = if self.wipe_passes > 0 {
+                self.wipe(file_path, expected).map(|()| Removal::Wiped)
+            } else if let Some(run_dir) = &run_dir {
+                store(file_path, run_dir)
+            } else if !self.use_trash {
+                self.delete_permanently(file_path, expected).map(|()| Removal::Deleted)
+            } else if let Some(mount_point) = fat_volumes.mount_point_for(file_path) {
+                let fat_run_dir = Self::fat_run_dir(mount_point, &started_at);
+                fat_run_dirs.lock().unwrap().insert(fat_run_dir.clone());
+                quarantine::store_relative(file_path, mount_point, &fat_run_dir).map_err(Error::from).map(record)
             } else {
-                self.delete_permanently(file_path)
+                let trashed = match wsl_windows_drives.iter().find(|drive| file_path.starts_with(drive)) {
+                    Some(drive) => Err(Error::TrashUnavailable(format!(
+                        "{} is a Windows drive seen from WSL, the Windows Recycle Bin cannot be reached",
+                        drive.display()
+                    ))),
+                    None => self.move_to_trash(file_path),
+                };
+                match trashed {
+                    Err(Error::TrashUnavailable(reason)) if self.trash_fallback != TrashFallback::Skip => {
+                        warn!("The trash cannot take {} ({}), falling back to: {}", file_path.display(), reason, self.trash_fallback.label());
+                        if self.trash_fallback == TrashFallback::Quarantine {
+                            match fallback_run_dir.get_or_init(|| quarantine::run_dir(&started_at).map_err(|e| e.to_string())) {
+                                Ok(run_dir) => store(file_path, run_dir),
+                                Err(e) => Err(Error::Other(e.clone())),
+                            }
+                        } else {
+                            self.delete_permanently(file_path, expected).map(|()| Removal::Deleted)
+                        }
+                    }
+                    result => result.map(|()| Removal::Trashed),
+                }
             };
 
             match result {
-                Ok(()) => {
-                    total_cleaned_bytes += file_size;
+                Ok(removal) => {
+                    freed_bytes.fetch_add(file_size, Ordering::Relaxed);
                     info!("Successfully cleaned: {} ({} bytes)", file_path.display(), file_size);
+                    let outcome = match &removal {
+                        Removal::Trashed => FileOutcome::MovedToTrash,
+                        Removal::Quarantined(quarantined_to) => FileOutcome::Quarantined(quarantined_to),
+                        Removal::Deleted => FileOutcome::Deleted,
+                        Removal::Wiped => FileOutcome::Wiped,
+                    };
+                    self.report_file(file_path, file_size, outcome);
+                    let deleted_permanently = matches!(removal, Removal::Deleted | Removal::Wiped);
+                    Outcome::Removed(RemovedFile {
+                        path: file_path.clone(),
+                        size: file_size,
+                        quarantined_to: match removal {
+                            Removal::Quarantined(quarantined_to) => Some(quarantined_to),
+                            _ => None,
+                        },
+                        deleted_permanently,
+                    })
                 }
                 Err(e) => {
                     error!("Failed to clean {}: {}", file_path.display(), e);
+                    self.report_file(file_path, file_size, FileOutcome::Failed(&e));
+                    Outcome::Failed(file_path.clone(), e)
+                }
+            }
+        };
+
+        // Files go in parallel, each one taking mostly a system call, unless
+        // they must go in order or stop exactly at the target, as the dry run does
+        let mut outcomes: Vec<Outcome> = if self.in_order || self.target_bytes.is_some() {
+            files.iter().map(clean).collect()
+        } else {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(self.threads).build().map_err(|e| e.to_string())?;
+            pool.install(|| files.par_iter().map(clean).collect())
+        };
+        // A folder can only go once everything inside it is gone
+        outcomes.extend(folders.iter().map(clean));
+
+        let mut removed = Vec::new();
+        let mut failed = Vec::new();
+        let mut skipped = 0;
+        for outcome in outcomes {
+            match outcome {
+                Outcome::Removed(file) => removed.push(file),
+                Outcome::Failed(file, reason) => failed.push((file, reason)),
+                Outcome::Skipped => skipped += 1,
+            }
+        }
+        let cancelled = progress.is_cancelled();
+        if skipped > 0 {
+            if cancelled {
+                warn!("Cleanup cancelled with {} of {} files left", skipped, total_files);
+            } else {
+                info!("Freed {} bytes, reaching the target with {} of {} files left", freed_bytes.into_inner(), skipped, total_files);
+            }
+        }
+        let quarantined = quarantined.into_inner().unwrap();
+
+        if !quarantined.is_empty() {
+            let run_dirs: Vec<PathBuf> = run_dir
+                .or_else(|| fallback_run_dir.into_inner().and_then(Result::ok))
+                .into_iter()
+                .chain(fat_run_dirs.into_inner().unwrap())
+                .collect();
+            if self.checksum_manifest {
+                for run_dir in &run_dirs {
+                    let files: Vec<QuarantinedFile> = quarantined.iter()
+                        .filter(|file| file.stored_at.starts_with(run_dir))
+                        .cloned()
+                        .collect();
+                    if let Err(e) = quarantine::write_checksum_manifest(run_dir, &files) {
+                        error!("Failed to write the checksum manifest in {}: {}", run_dir.display(), e);
+                    }
                 }
             }
+            let run = QuarantineRun {
+                quarantined_at: started_at,
+                note: self.note.clone(),
+                files: quarantined,
+            };
+            if let Err(e) = quarantine::record_run(run) {
+                let run_dirs: Vec<String> = run_dirs.iter().map(|dir| dir.display().to_string()).collect();
+                error!("Failed to record quarantined files, they stay in {}: {}", run_dirs.join(", "), e);
+            }
         }
 
-        info!("Cleanup completed. Total cleaned: {} bytes", total_cleaned_bytes);
-        Ok(total_cleaned_bytes)
+        let report = CleanupReport {
+            started_at,
+            finished_at: chrono::Utc::now(),
+            moved_to_trash: self.moves_to_trash(),
+            files: removed,
+            failed,
+            note: self.note.clone(),
+            cancelled,
+            files_left: skipped,
+        };
+        info!("Cleanup completed. Total cleaned: {} bytes", report.cleaned_bytes());
+        if !report.files.is_empty() || !report.failed.is_empty() {
+            if let Err(e) = history::record(&report) {
+                error!("Failed to record the cleanup in the history: {}", e);
+            }
+        }
+        Ok(report)
     }
 
-    fn move_to_trash(&self, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        trash::delete(file_path).map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
+    fn report_file(&self, path: &Path, size: u64, outcome: FileOutcome) {
+        if let Some(on_file) = &self.on_file {
+            on_file(path, size, outcome);
+        }
     }
 
-    fn delete_permanently(&self, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        std::fs::remove_file(file_path).map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
+    /// Goes through the same checks as [`Self::clean_files`] and also checks
+    /// that each file could be removed, without removing anything.
+    pub fn dry_run(&self, files: &[PathBuf], progress: Arc<ProgressTracker>) -> DryRunReport {
+        info!("Dry run of a cleanup of {} files", files.len());
+        let started = Instant::now();
+        let (files, folders) = split_folders(files);
+        let files: SizedFiles = files.into_iter().chain(folders).collect();
+
+        let mut report = DryRunReport {
+            moved_to_trash: self.moves_to_trash(),
+            ..DryRunReport::default()
+        };
+        let mut freed_bytes = 0;
+        let mut removable = HashSet::new();
+        let total_files = files.len();
+        let total_bytes = files.iter().map(|(_, size)| size).sum();
+        let mut bytes_processed = 0;
+        let app_owned = config::app_owned_paths();
+        let fat_volumes = FatVolumes::detect();
+
+        for (index, (file_path, size)) in files.iter().enumerate() {
+            if progress.is_cancelled() || self.target_reached(freed_bytes) {
+                break;
+            }
+            bytes_processed += size;
+            progress.update(ProgressState::Cleaning {
+                files_processed: index + 1,
+                total_files,
+                bytes_processed,
+                total_bytes,
+                freed_bytes,
+                started,
+            }, index + 1 == total_files);
+
+            let expected = self.expected_identities.get(&FileId::of(file_path)).copied();
+            let checked = self.check_file(file_path, &app_owned, expected, &removable)
+                .and_then(|(resolved, size)| match is_removable(&resolved) {
+                    Ok(()) => Ok((resolved, size)),
+                    Err(reason) => {
+                        report.permission_denied.push(file_path.clone());
+                        Err(reason)
+                    }
+                });
+            match checked {
+                Ok((resolved, size)) => {
+                    let quarantined = self.use_trash && self.wipe_passes == 0 && fat_volumes.holds(&resolved);
+                    freed_bytes += size;
+                    removable.insert(resolved.clone());
+                    report.removable.push(RemovedFile {
+                        path: resolved,
+                        size,
+                        // The run's quarantine directory is only named when it starts
+                        quarantined_to: quarantined.then(PathBuf::new),
+                        deleted_permanently: !self.moves_to_trash(),
+                    });
+                }
+                Err(reason) => report.refused.push((file_path.clone(), reason)),
+            }
+        }
+
+        info!(
+            "Dry run: {} files, {} bytes would be cleaned, {} refused",
+            report.removable.len(),
+            report.reclaimed_bytes(),
+            report.refused.len()
+        );
+        report
+    }
+
+    /// The checks made before cleaning a file. Returns the file with links in
+    /// its parent directories resolved and its size, or why it must be left.
+    /// A folder must hold nothing but folders, apart from the `pending` files
+    /// a dry run would have removed before it.
+    fn check_file(
+        &self,
+        file_path: &Path,
+        app_owned: &[PathBuf],
+        expected: Option<FileIdentity>,
+        pending: &HashSet<PathBuf>,
+    ) -> Result<(PathBuf, u64), String> {
+        if app_owned.iter().any(|owned| file_path.starts_with(owned)) || config::is_quarantined(file_path) {
+            return Err("application data".to_string());
+        }
+        if let Some(reason) = package_caches::kept_reason(file_path) {
+            return Err(format!("{} is kept", reason));
+        }
+
+        // Resolve links in the parent directories now, right before deleting,
+        // so a link planted in a junk directory cannot redirect the deletion
+        let file_path = self.resolve_target(file_path).map_err(|e| e.to_string())?;
+
+        // Get file size before deletion. A symbolic link is removed itself, never its target.
+        match std::fs::symlink_metadata(&file_path) {
+            Ok(metadata) if expected.is_some() && FileIdentity::of(&metadata) != expected => {
+                Err("file was replaced since the scan".to_string())
+            }
+            Ok(metadata) if metadata.is_dir() => {
+                if holds_only_folders(&file_path, pending) {
+                    Ok((file_path, 0))
+                } else {
+                    Err("folder is not empty".to_string())
+                }
+            }
+            Ok(metadata) => Ok((file_path, metadata.len())),
+            Err(e) => Err(format!("could not get metadata: {}", e)),
+        }
+    }
+
+    pub fn run_commands(&self, commands: &[String]) -> Vec<CommandOutput> {
+        commands
+            .iter()
+            .map(|command| {
+                info!("Running custom command: {}", command);
+
+                match Self::shell_command(command).output() {
+                    Ok(output) => {
+                        let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+                        text.push_str(&String::from_utf8_lossy(&output.stderr));
+
+                        if !output.status.success() {
+                            warn!("Command '{}' exited with {}", command, output.status);
+                        }
+
+                        CommandOutput {
+                            command: command.clone(),
+                            success: output.status.success(),
+                            output: text,
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to run command '{}': {}", command, e);
+                        CommandOutput {
+                            command: command.clone(),
+                            success: false,
+                            output: e.to_string(),
+                        }
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn shell_command(command: &str) -> Command {
+        if cfg!(windows) {
+            let mut cmd = Command::new("cmd");
+            cmd.args(["/C", command]);
+            cmd
+        } else {
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", command]);
+            cmd
+        }
+    }
+
+    /// Returns `path` with its parent directory canonicalized, keeping the last
+    /// component as is so a symbolic link is not followed. Fails when the
+    /// result is not inside one of the allowed roots.
+    fn resolve_target(&self, path: &Path) -> Result<PathBuf, String> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err("not a file path".to_string());
+        };
+        let parent = parent.canonicalize().map_err(|e| e.to_string())?;
+        let resolved = parent.join(name);
+
+        if let Some(roots) = &self.allowed_roots {
+            if !roots.iter().any(|root| resolved.starts_with(root)) {
+                return Err(format!("resolves to {}, outside the scanned directories", resolved.display()));
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    fn move_to_trash(&self, file_path: &Path) -> Result<(), Error> {
+        trash::delete(file_path).map_err(|e| trash_error(file_path, e))
+    }
+
+    /// Quarantine directory of the cleanup run started at `started_at` on the
+    /// FAT volume mounted at `mount_point`. Files there keep their path relative
+    /// to the volume, so they can be restored by hand, and staying on the volume
+    /// keeps their move a rename. They are recorded with the main quarantine.
+    fn fat_run_dir(mount_point: &Path, started_at: &chrono::DateTime<chrono::Utc>) -> PathBuf {
+        mount_point
+            .join(config::QUARANTINE_DIR_NAME)
+            .join(started_at.format("%Y%m%dT%H%M%SZ").to_string())
+    }
+
+    /// Trashing goes through the platform trash APIs, which only take paths, so
+    /// it relies on the identity check made just before. Permanent deletion on
+    /// Unix re-checks the identity and unlinks through directory handles.
+    #[cfg(unix)]
+    fn delete_permanently(&self, file_path: &Path, expected: Option<FileIdentity>) -> Result<(), Error> {
+        if file_path.is_dir() {
+            remove_empty_subfolders(file_path).map_err(|e| Error::io(file_path, e))?;
+        }
+        crate::file_identity::unlink_verified(file_path, expected).map_err(|e| Error::io(file_path, e))
+    }
+
+    #[cfg(not(unix))]
+    fn delete_permanently(&self, file_path: &Path, _expected: Option<FileIdentity>) -> Result<(), Error> {
+        let removed = if file_path.is_dir() {
+            remove_empty_subfolders(file_path).and_then(|()| std::fs::remove_dir(file_path))
+        } else {
+            std::fs::remove_file(file_path)
+        };
+        removed.map_err(|e| Error::io(file_path, e))
+    }
+
+    /// Overwrites a file and deletes it permanently. Folders hold nothing to
+    /// overwrite and are only removed.
+    fn wipe(&self, file_path: &Path, expected: Option<FileIdentity>) -> Result<(), Error> {
+        let metadata = std::fs::symlink_metadata(file_path).map_err(|e| Error::io(file_path, e))?;
+        if metadata.is_file() {
+            secure_wipe::overwrite(file_path, self.wipe_passes, expected).map_err(|e| Error::io(file_path, e))?;
+        }
+        self.delete_permanently(file_path, expected)
     }
 
     pub fn estimate_cleanup_size(&self, files: &[PathBuf]) -> u64 {
         files
             .iter()
             .filter_map(|file| std::fs::metadata(file).ok())
+            .filter(|metadata| metadata.is_file())
             .map(|metadata| metadata.len())
             .sum()
     }
 }
+
+/// Failures of the trash on the file itself keep their cause, the others
+/// mean the trash cannot take files here.
+fn trash_error(file_path: &Path, error: trash::Error) -> Error {
+    match error {
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        trash::Error::FileSystem { path, source } if path == file_path => Error::io(file_path, source),
+        trash::Error::Os { code, .. } if cfg!(windows) => Error::io(file_path, std::io::Error::from_raw_os_error(code)),
+        trash::Error::CouldNotAccess { .. } | trash::Error::CanonicalizePath { .. } | trash::Error::ConvertOsString { .. } => {
+            Error::Other(error.to_string())
+        }
+        error => Error::TrashUnavailable(error.to_string()),
+    }
+}
+
+/// `files` with their sizes, and apart the folders, deepest first, so they
+/// are removed once the files in them are gone and before their parents.
+/// Folders and files that cannot be read count as empty.
+fn split_folders(files: &[PathBuf]) -> (SizedFiles, SizedFiles) {
+    let mut folders = Vec::new();
+    let mut others = Vec::new();
+    for file in files {
+        match std::fs::symlink_metadata(file) {
+            Ok(metadata) if metadata.is_dir() => folders.push((file.clone(), 0)),
+            Ok(metadata) => others.push((file.clone(), metadata.len())),
+            Err(_) => others.push((file.clone(), 0)),
+        }
+    }
+    folders.sort_by_key(|(folder, _)| std::cmp::Reverse(folder.components().count()));
+    (others, folders)
+}
+
+/// Whether `folder` holds nothing but folders, leaving aside the entries in `pending`.
+fn holds_only_folders(folder: &Path, pending: &HashSet<PathBuf>) -> bool {
+    std::fs::read_dir(folder).is_ok_and(|mut entries| {
+        entries.all(|entry| entry.is_ok_and(|entry| {
+            let path = entry.path();
+            pending.contains(&path)
+                || entry.file_type().is_ok_and(|file_type| file_type.is_dir()) && holds_only_folders(&path, pending)
+        }))
+    })
+}
+
+/// Removes the folders inside `folder`, deepest first. Fails on the first
+/// entry that is not a folder, leaving it and its parents in place.
+pub fn remove_empty_subfolders(folder: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(folder)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            return Err(std::io::Error::other(format!("{} is not empty", folder.display())));
+        }
+        remove_empty_subfolders(&entry.path())?;
+        std::fs::remove_dir(entry.path())?;
+    }
+    Ok(())
+}
+
+/// Whether the current user may remove `path`: removing a file takes write
+/// access to its directory, and in a sticky directory such as `/tmp` also
+/// owning the file or the directory.
+#[cfg(unix)]
+fn is_removable(path: &Path) -> Result<(), String> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+
+    let parent = path.parent().ok_or("no parent directory")?;
+    let c_parent = CString::new(parent.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+    if unsafe { libc::access(c_parent.as_ptr(), libc::W_OK | libc::X_OK) } != 0 {
+        return Err(format!("no write permission on {}", parent.display()));
+    }
+
+    let directory = std::fs::metadata(parent).map_err(|e| e.to_string())?;
+    #[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS
+    if directory.mode() & libc::S_ISVTX as u32 != 0 {
+        let user = unsafe { libc::geteuid() };
+        let file = std::fs::symlink_metadata(path).map_err(|e| e.to_string())?;
+        if user != 0 && file.uid() != user && directory.uid() != user {
+            return Err(format!("owned by another user in the sticky directory {}", parent.display()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn is_removable(path: &Path) -> Result<(), String> {
+    let metadata = std::fs::symlink_metadata(path).map_err(|e| e.to_string())?;
+    if metadata.permissions().readonly() {
+        return Err("read-only file".to_string());
+    }
+    #[cfg(windows)]
+    crate::acl::check_delete_access(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn resolve_target_keeps_files_inside_the_roots() {
+        let dir = TempDir::new("resolve-inside");
+        let root = dir.dir("root");
+        let file = dir.file("root/sub/file.tmp", b"junk");
+        let cleaner = Cleaner::new(false).with_allowed_roots(std::slice::from_ref(&root));
+
+        assert_eq!(cleaner.resolve_target(&file).unwrap(), file);
+        // Without allowed roots, any file resolves
+        assert_eq!(Cleaner::new(false).resolve_target(&file).unwrap(), file);
+    }
+
+    #[test]
+    fn resolve_target_refuses_paths_leaving_the_roots() {
+        let dir = TempDir::new("resolve-outside");
+        let root = dir.dir("root");
+        dir.file("root-other/file.tmp", b"junk");
+        dir.file("outside/file.tmp", b"keep");
+        let cleaner = Cleaner::new(false).with_allowed_roots(std::slice::from_ref(&root));
+
+        // A sibling sharing the root's name as a prefix is not inside it
+        assert!(cleaner.resolve_target(&dir.path().join("root-other/file.tmp")).is_err());
+        assert!(cleaner.resolve_target(&root.join("../outside/file.tmp")).is_err());
+        assert!(cleaner.resolve_target(Path::new("/")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_target_follows_linked_folders_but_not_the_file() {
+        let dir = TempDir::new("resolve-symlink");
+        let root = dir.dir("root");
+        let outside = dir.file("outside/document.txt", b"keep");
+        std::os::unix::fs::symlink(dir.path().join("outside"), root.join("linked")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link.txt")).unwrap();
+        let cleaner = Cleaner::new(false).with_allowed_roots(std::slice::from_ref(&root));
+
+        // A folder link swapped in for a junk folder leads out of the root
+        assert!(cleaner.resolve_target(&root.join("linked/document.txt")).is_err());
+        // A link to a file is itself removed, leaving its target alone
+        assert_eq!(cleaner.resolve_target(&root.join("link.txt")).unwrap(), root.join("link.txt"));
+
+        let report = cleaner.dry_run(&[root.join("linked/document.txt")], Arc::new(ProgressTracker::default()));
+        assert!(report.removable.is_empty());
+        assert_eq!(report.refused.len(), 1);
+    }
+}
@@ -1,22 +1,195 @@
 use serde::{Serialize, Deserialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use log::{info, error};
 
+use crate::category_rules::CategoryRules;
+use crate::cleaner::TrashFallback;
+use crate::duplicate_finder::DuplicateKeepPolicy;
+use crate::file_category::FileCategory;
+use crate::formatting::DateStyle;
+use crate::learned_rules::{LearnedRule, RuleAction};
+use crate::logging::LogSettings;
+use crate::notifier::{SmtpSettings, Webhook};
+use crate::photo_finder::PhotoKeepPolicy;
+use crate::storage::StorageSettings;
+use crate::retention::RetentionRule;
+
+/// Name of the directory the application uses inside the platform config, cache and data dirs.
+const APP_DIR_NAME: &str = "intelligent-disk-cleaner";
+
+pub fn app_config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR_NAME))
+}
+
+pub fn app_cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(APP_DIR_NAME))
+}
+
+/// Home of the quarantine and history database.
+pub fn app_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_DIR_NAME))
+}
+
+/// Directories owned by the application itself. They are never scanned,
+/// deduplicated or cleaned, whatever the user settings say.
+pub fn app_owned_paths() -> Vec<PathBuf> {
+    [app_config_dir(), app_cache_dir(), app_data_dir()]
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Directory at the root of FAT and exFAT drives that files are moved to
+/// instead of the trash, which those drives lack.
+pub const QUARANTINE_DIR_NAME: &str = ".intelligent-disk-cleaner-quarantine";
+
+pub fn is_quarantined(path: &Path) -> bool {
+    path.components().any(|component| component.as_os_str() == QUARANTINE_DIR_NAME)
+}
+
+pub fn is_app_owned(path: &Path) -> bool {
+    app_owned_paths().iter().any(|owned| path.starts_with(owned)) || is_quarantined(path)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub use_trash: bool,
+    /// Move cleaned files to the application's quarantine, from where they can be restored
+    #[serde(default)]
+    pub use_quarantine: bool,
+    /// Write a BLAKE3 checksum manifest next to the quarantined files of each cleanup
+    #[serde(default)]
+    pub checksum_manifest: bool,
     pub include_hidden_files: bool,
     pub follow_symlinks: bool,
     pub min_file_size: u64,
     pub max_file_age_days: u32,
     pub excluded_paths: Vec<PathBuf>,
     pub excluded_extensions: Vec<String>,
+    /// Enumerate NTFS volumes through the master file table (Windows, requires admin rights)
+    #[serde(default)]
+    pub use_mft_enumeration: bool,
+    /// Annotate downloads with their source URL from the browsers' history
+    #[serde(default)]
+    pub read_browser_history: bool,
+    /// Which copy to keep when the same photo exists in several formats
+    #[serde(default)]
+    pub photo_keep_policy: PhotoKeepPolicy,
+    /// Names that differ only in their Unicode form, as copies between macOS
+    /// and Linux do, count as the same name when grouping versions and copies
+    #[serde(default = "default_unify_unicode_names")]
+    pub unify_unicode_names: bool,
+    /// Times a securely wiped file is overwritten before it is deleted
+    #[serde(default = "default_secure_wipe_passes")]
+    pub secure_wipe_passes: u32,
+    /// Files removed at once during a cleanup, 0 for one per core
+    #[serde(default)]
+    pub cleanup_threads: usize,
+    /// Work on fewer files at once and cache less, for computers with little memory
+    #[serde(default)]
+    pub low_memory: bool,
+    /// What happens to files the trash cannot take, because it is full or the drive has none
+    #[serde(default)]
+    pub trash_fallback: TrashFallback,
+    /// Shell commands run after cleaning a category, e.g. `docker system prune -f`
+    #[serde(default)]
+    pub category_commands: HashMap<FileCategory, String>,
+    /// Files and directories that still show up in scan results but are never cleaned
+    #[serde(default)]
+    pub protected_paths: Vec<PathBuf>,
+    /// Rules generalized from files the user reassigned or protected by hand
+    #[serde(default)]
+    pub learned_rules: Vec<LearnedRule>,
+    /// Policies like "keep logs 14 days", enforced by `--retention` runs
+    #[serde(default)]
+    pub retention_rules: Vec<RetentionRule>,
+    /// How dates are written in saved reports
+    #[serde(default)]
+    pub report_date_style: DateStyle,
+    /// Keep an Ed25519-signed record of every cleanup in the data directory
+    #[serde(default)]
+    pub sign_cleanup_records: bool,
+    /// Files changed more recently than this are left out of duplicate detection
+    #[serde(default = "default_duplicate_min_age_hours")]
+    pub duplicate_min_age_hours: u32,
+    /// Read files on network shares in full when looking for duplicates,
+    /// instead of matching them by name and size
+    #[serde(default = "default_hash_network_files")]
+    pub hash_network_files: bool,
+    /// Which file of each duplicate group is kept
+    #[serde(default)]
+    pub duplicate_keep_policy: DuplicateKeepPolicy,
+    /// Folders whose copies are kept first under [`DuplicateKeepPolicy::PreferredFolders`], in order
+    #[serde(default)]
+    pub preferred_folders: Vec<PathBuf>,
+    /// Look for duplicates as soon as a scan finishes
+    #[serde(default)]
+    pub find_duplicates_after_scan: bool,
+    /// Email the summary of headless runs through this server
+    #[serde(default)]
+    pub email_reports: Option<SmtpSettings>,
+    /// Endpoints notified when scans and cleanups finish or fail
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    /// Categories hidden from the results and never cleaned
+    #[serde(default)]
+    pub disabled_categories: Vec<FileCategory>,
+    /// Categories ticked for cleanup when the application starts
+    #[serde(default = "default_preselected_categories")]
+    pub preselected_categories: Vec<FileCategory>,
+    /// Names the user gave categories, shown instead of the default ones
+    #[serde(default)]
+    pub category_names: HashMap<FileCategory, String>,
+    /// Keep files the system lists as recently opened out of Old Files and Downloads
+    #[serde(default = "default_protect_recent_files")]
+    pub protect_recent_files: bool,
+    /// Patterns putting files in a category ahead of the built-in heuristics
+    #[serde(default, alias = "rules")]
+    pub category_rules: CategoryRules,
+    /// Log levels and the optional log file, applied as soon as they are saved
+    #[serde(default)]
+    pub logging: LogSettings,
+    /// How much cleanup history and cache the application keeps
+    #[serde(default)]
+    pub storage: StorageSettings,
+}
+
+fn default_duplicate_min_age_hours() -> u32 {
+    1
+}
+
+fn default_hash_network_files() -> bool {
+    true
+}
+
+fn default_protect_recent_files() -> bool {
+    true
+}
+
+fn default_unify_unicode_names() -> bool {
+    true
+}
+
+fn default_secure_wipe_passes() -> u32 {
+    1
+}
+
+/// Only the categories that are safe to delete start out selected, so nobody
+/// empties their Downloads folder by clicking through with the defaults.
+fn default_preselected_categories() -> Vec<FileCategory> {
+    FileCategory::all()
+        .into_iter()
+        .filter(FileCategory::is_safe_to_delete)
+        .collect()
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             use_trash: true,
+            use_quarantine: false,
+            checksum_manifest: false,
             include_hidden_files: false,
             follow_symlinks: false,
             min_file_size: 0,
@@ -40,14 +213,42 @@ impl Default for Config {
                 ".ini".to_string(),
                 ".cfg".to_string(),
             ],
+            use_mft_enumeration: false,
+            read_browser_history: false,
+            photo_keep_policy: PhotoKeepPolicy::default(),
+            unify_unicode_names: default_unify_unicode_names(),
+            secure_wipe_passes: default_secure_wipe_passes(),
+            cleanup_threads: 0,
+            low_memory: false,
+            trash_fallback: TrashFallback::Skip,
+            category_commands: HashMap::new(),
+            protected_paths: Vec::new(),
+            learned_rules: Vec::new(),
+            retention_rules: Vec::new(),
+            report_date_style: DateStyle::default(),
+            sign_cleanup_records: false,
+            duplicate_min_age_hours: default_duplicate_min_age_hours(),
+            hash_network_files: default_hash_network_files(),
+            duplicate_keep_policy: DuplicateKeepPolicy::default(),
+            preferred_folders: Vec::new(),
+            find_duplicates_after_scan: false,
+            email_reports: None,
+            webhooks: Vec::new(),
+            disabled_categories: Vec::new(),
+            preselected_categories: default_preselected_categories(),
+            category_names: HashMap::new(),
+            protect_recent_files: default_protect_recent_files(),
+            category_rules: CategoryRules::default(),
+            logging: LogSettings::default(),
+            storage: StorageSettings::default(),
         }
     }
 }
 
 impl Config {
     pub fn load() -> Self {
-        if let Some(config_dir) = dirs::config_dir() {
-            let config_path = config_dir.join("intelligent-disk-cleaner").join("config.json");
+        if let Some(config_dir) = app_config_dir() {
+            let config_path = config_dir.join("config.json");
             
             if config_path.exists() {
                 match std::fs::read_to_string(&config_path) {
@@ -74,8 +275,7 @@ impl Config {
     }
 
     pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(config_dir) = dirs::config_dir() {
-            let app_config_dir = config_dir.join("intelligent-disk-cleaner");
+        if let Some(app_config_dir) = app_config_dir() {
             std::fs::create_dir_all(&app_config_dir)?;
             
             let config_path = app_config_dir.join("config.json");
@@ -88,15 +288,17 @@ impl Config {
         Ok(())
     }
 
-    pub fn is_path_excluded(&self, path: &PathBuf) -> bool {
-        // Check if path is in excluded paths
-        for excluded in &self.excluded_paths {
-            if path.starts_with(excluded) {
-                return true;
-            }
-        }
-        
-        // Check if file extension is excluded
+    pub fn is_path_excluded(&self, path: &Path) -> bool {
+        is_app_owned(path) || self.is_under_excluded_path(path) || self.is_extension_excluded(path)
+    }
+    
+    /// Whether `path` is one of the excluded paths or lies below one. Unlike
+    /// [`Self::is_path_excluded`], directories are not judged by extension.
+    pub fn is_under_excluded_path(&self, path: &Path) -> bool {
+        self.excluded_paths.iter().any(|excluded| path.starts_with(excluded))
+    }
+    
+    pub fn is_extension_excluded(&self, path: &Path) -> bool {
         if let Some(extension) = path.extension() {
             let ext_str = extension.to_string_lossy().to_lowercase();
             for excluded_ext in &self.excluded_extensions {
@@ -108,4 +310,84 @@ impl Config {
         
         false
     }
+
+    pub fn is_path_protected(&self, path: &Path) -> bool {
+        is_app_owned(path)
+            || self.protected_paths.iter().any(|protected| path.starts_with(protected))
+            || self.learned_rules.iter().any(|rule| rule.action == RuleAction::Protect && rule.matches(path))
+    }
+
+    pub fn category_name(&self, category: FileCategory) -> String {
+        self.category_names.get(&category)
+            .cloned()
+            .unwrap_or_else(|| category.default_name().to_string())
+    }
+
+    pub fn is_category_enabled(&self, category: FileCategory) -> bool {
+        !self.disabled_categories.contains(&category)
+    }
+
+    pub fn is_category_preselected(&self, category: FileCategory) -> bool {
+        self.is_category_enabled(category) && self.preselected_categories.contains(&category)
+    }
+
+    /// Category the user assigned to `path`, by a categorization rule of the
+    /// settings or else a learned rule, if any.
+    pub fn user_category(&self, path: &Path) -> Option<FileCategory> {
+        self.category_rules.category(path).or_else(|| self.learned_category(path))
+    }
+
+    /// Category assigned to `path` by a learned rule, if any. The most recent rule wins.
+    pub fn learned_category(&self, path: &Path) -> Option<FileCategory> {
+        self.learned_rules.iter().rev().find_map(|rule| match rule.action {
+            RuleAction::Category(category) if rule.matches(path) => Some(category),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_excluding(paths: &[&str], extensions: &[&str]) -> Config {
+        Config {
+            excluded_paths: paths.iter().map(PathBuf::from).collect(),
+            excluded_extensions: extensions.iter().map(|extension| extension.to_string()).collect(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn excludes_everything_below_an_excluded_path() {
+        let config = config_excluding(&["/home/user/projects/keep"], &[]);
+
+        assert!(config.is_path_excluded(Path::new("/home/user/projects/keep")));
+        assert!(config.is_path_excluded(Path::new("/home/user/projects/keep/build/cache/file.tmp")));
+        assert!(!config.is_path_excluded(Path::new("/home/user/projects")));
+        assert!(!config.is_path_excluded(Path::new("/home/user/projects/other/file.tmp")));
+        // Paths are compared by component, not as text
+        assert!(!config.is_path_excluded(Path::new("/home/user/projects/keeper/file.tmp")));
+    }
+
+    #[test]
+    fn nested_excluded_paths_exclude_their_own_subtrees() {
+        let config = config_excluding(&["/data/archive/2020", "/data"], &[]);
+
+        assert!(config.is_under_excluded_path(Path::new("/data/archive/2020/file.tmp")));
+        assert!(config.is_under_excluded_path(Path::new("/data/archive/2021/file.tmp")));
+        assert!(!config.is_under_excluded_path(Path::new("/database/file.tmp")));
+    }
+
+    #[test]
+    fn excludes_extensions_whatever_their_case() {
+        let config = config_excluding(&[], &[".Log", ".dll"]);
+
+        assert!(config.is_path_excluded(Path::new("/var/log/app/today.LOG")));
+        assert!(config.is_path_excluded(Path::new("/tmp/nested/deeper/library.dll")));
+        assert!(!config.is_path_excluded(Path::new("/tmp/catalog")));
+        assert!(!config.is_path_excluded(Path::new("/tmp/library.dll.tmp")));
+        // Folders are not judged by extension
+        assert!(!config.is_under_excluded_path(Path::new("/tmp/cache.log/file.tmp")));
+    }
 }
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+use walkdir::WalkDir;
+
+use crate::config;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DirSize {
+    /// Bytes in all files below the directory
+    pub size: u64,
+    pub files: usize,
+    /// Bytes and number of the files directly inside the directory
+    own_size: u64,
+    own_files: usize,
+    modified: SystemTime,
+}
+
+impl DirSize {
+    /// Bytes of the files directly inside the directory, not in its subdirectories
+    pub fn own_size(&self) -> u64 {
+        self.own_size
+    }
+
+    pub fn own_files(&self) -> usize {
+        self.own_files
+    }
+}
+
+impl Default for DirSize {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            files: 0,
+            own_size: 0,
+            own_files: 0,
+            modified: SystemTime::UNIX_EPOCH,
+        }
+    }
+}
+
+/// Persistent sizes of the directories seen by past scans, so summaries can be
+/// shown on launch before anything is rescanned.
+///
+/// A refresh only re-reads the directories whose modification time changed.
+/// Files rewritten in place do not touch their directory, so their new size is
+/// only picked up by the next full scan.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DirSizeCache {
+    dirs: HashMap<PathBuf, DirSize>,
+}
+
+impl DirSizeCache {
+    fn cache_path() -> Option<PathBuf> {
+        config::app_cache_dir().map(|dir| dir.join("dir_sizes.bin"))
+    }
+
+    pub fn load() -> Self {
+        let Some(path) = Self::cache_path() else {
+            return Self::default();
+        };
+        let Ok(file) = File::open(&path) else {
+            return Self::default();
+        };
+
+        let result = zstd::Decoder::new(BufReader::new(file))
+            .map_err(|e| e.into())
+            .and_then(bincode::deserialize_from::<_, Self>);
+
+        match result {
+            Ok(cache) => cache,
+            Err(e) => {
+                warn!("Discarding unreadable directory size cache {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Some(path) = Self::cache_path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let partial = path.with_extension("bin.partial");
+        let mut encoder = zstd::Encoder::new(BufWriter::new(File::create(&partial)?), COMPRESSION_LEVEL)?;
+        bincode::serialize_into(&mut encoder, self)?;
+        encoder.finish()?.flush()?;
+        std::fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, dir: &Path) -> Option<&DirSize> {
+        self.dirs.get(dir)
+    }
+
+    /// The known directories directly inside `dir`.
+    pub fn subdirectories(&self, dir: &Path) -> Vec<(PathBuf, DirSize)> {
+        self.dirs.iter()
+            .filter(|(path, _)| path.parent() == Some(dir))
+            .map(|(path, size)| (path.clone(), *size))
+            .collect()
+    }
+
+    /// Replaces everything known under `root` with the sizes of a fresh scan,
+    /// given as bytes and file count per directory.
+    pub fn record_scan(&mut self, root: &Path, sizes: HashMap<PathBuf, (u64, usize)>) {
+        self.remove_tree(root);
+
+        for (dir, (size, files)) in sizes {
+            self.add(root, &dir, size as i64, files as isize);
+        }
+
+        for (dir, entry) in self.dirs.iter_mut().filter(|(dir, _)| dir.starts_with(root)) {
+            entry.modified = Self::modified(dir).unwrap_or(SystemTime::UNIX_EPOCH);
+        }
+
+        info!("Recorded sizes of {} directories under {}", self.dirs.len(), root.display());
+    }
+
+    /// Re-reads the directories under `root` that changed since they were
+    /// recorded. Returns how many there were.
+    pub fn refresh(&mut self, root: &Path) -> usize {
+        let mut known: Vec<PathBuf> = self.dirs.keys()
+            .filter(|dir| dir.starts_with(root))
+            .cloned()
+            .collect();
+        // Parents first, so a removed tree is dropped before visiting its children
+        known.sort();
+
+        let mut changed = 0;
+        for dir in known {
+            let Some(cached) = self.dirs.get(&dir).copied() else {
+                continue;
+            };
+
+            match Self::modified(&dir) {
+                Some(modified) if modified == cached.modified => {}
+                Some(modified) => {
+                    changed += 1;
+                    self.reread(root, &dir, cached, modified);
+                }
+                None => {
+                    changed += 1;
+                    self.remove_tree(&dir);
+                }
+            }
+        }
+
+        if changed > 0 {
+            info!("Refreshed {} changed directories under {}", changed, root.display());
+        }
+        changed
+    }
+
+    /// Recounts the files directly in `dir` and walks the subdirectories that
+    /// appeared since it was recorded.
+    fn reread(&mut self, root: &Path, dir: &Path, cached: DirSize, modified: SystemTime) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+
+        let (mut own_size, mut own_files) = (0u64, 0usize);
+        let mut new_dirs = Vec::new();
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_file() {
+                own_size += metadata.len();
+                own_files += 1;
+            } else if metadata.is_dir() && !self.dirs.contains_key(&entry.path()) {
+                new_dirs.push(entry.path());
+            }
+        }
+
+        self.add(
+            root,
+            dir,
+            own_size as i64 - cached.own_size as i64,
+            own_files as isize - cached.own_files as isize,
+        );
+        if let Some(entry) = self.dirs.get_mut(dir) {
+            entry.modified = modified;
+        }
+
+        for new_dir in new_dirs {
+            for entry in WalkDir::new(&new_dir).into_iter().flatten() {
+                let path = entry.path();
+                if entry.file_type().is_dir() {
+                    self.add(root, path, 0, 0);
+                    if let Some(modified) = Self::modified(path) {
+                        self.dirs.entry(path.to_path_buf()).or_default().modified = modified;
+                    }
+                } else if entry.file_type().is_file() {
+                    let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                    if let Some(parent) = path.parent() {
+                        self.add(root, parent, size as i64, 1);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds bytes and files directly inside `dir`, and to the totals of its
+    /// ancestors. Ancestors within `root` are created as needed, those above
+    /// it are only updated when already known.
+    fn add(&mut self, root: &Path, dir: &Path, size: i64, files: isize) {
+        let entry = self.dirs.entry(dir.to_path_buf()).or_default();
+        entry.own_size = entry.own_size.saturating_add_signed(size);
+        entry.own_files = entry.own_files.saturating_add_signed(files);
+
+        for ancestor in dir.ancestors() {
+            let entry = if ancestor.starts_with(root) {
+                self.dirs.entry(ancestor.to_path_buf()).or_default()
+            } else {
+                match self.dirs.get_mut(ancestor) {
+                    Some(entry) => entry,
+                    None => continue,
+                }
+            };
+            entry.size = entry.size.saturating_add_signed(size);
+            entry.files = entry.files.saturating_add_signed(files);
+        }
+    }
+
+    /// Forgets `dir` and everything below it, and takes its size out of the
+    /// totals of its known ancestors.
+    fn remove_tree(&mut self, dir: &Path) {
+        if let Some(removed) = self.dirs.get(dir).copied() {
+            for ancestor in dir.ancestors().skip(1) {
+                if let Some(entry) = self.dirs.get_mut(ancestor) {
+                    entry.size = entry.size.saturating_sub(removed.size);
+                    entry.files = entry.files.saturating_sub(removed.files);
+                }
+            }
+        }
+        self.dirs.retain(|path, _| !path.starts_with(dir));
+    }
+
+    fn modified(dir: &Path) -> Option<SystemTime> {
+        std::fs::symlink_metadata(dir).and_then(|metadata| metadata.modified()).ok()
+    }
+}
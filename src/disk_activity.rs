@@ -0,0 +1,120 @@
+//! Live view of what is writing to disk right now: the write rate of each
+//! process from its IO counters, and how fast the watched volume fills. Where
+//! a scan shows what takes the space, this shows what is still adding to it.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::volumes;
+
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Processes that wrote less than this per second are left out of a sample.
+const MIN_WRITE_RATE: u64 = 1024;
+
+#[derive(Debug, Clone)]
+pub struct ProcessWrites {
+    pub pid: u32,
+    pub name: String,
+    pub bytes_per_second: u64,
+    /// Written since the process started
+    pub total_written: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DiskActivitySample {
+    /// The processes writing, fastest first
+    pub processes: Vec<ProcessWrites>,
+    /// Change of the watched volume's used space per second, negative when space is freed
+    pub volume_growth: Option<i64>,
+}
+
+/// Keeps the counters of the previous sample, which the next one is measured against.
+pub struct DiskActivityMonitor {
+    system: System,
+    last_sample: Instant,
+    volume: Option<PathBuf>,
+    last_used: Option<u64>,
+}
+
+impl DiskActivityMonitor {
+    /// Starts watching, with `path` naming the volume whose filling is measured.
+    pub fn new(path: Option<&Path>) -> Self {
+        let mut system = System::new();
+        system.refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::nothing().with_disk_usage());
+        let volume = path.and_then(volumes::volume_usage);
+        Self {
+            system,
+            last_sample: Instant::now(),
+            last_used: volume.as_ref().map(|(_, used)| *used),
+            volume: volume.map(|(mount_point, _)| mount_point),
+        }
+    }
+
+    /// The mount point of the watched volume.
+    pub fn volume(&self) -> Option<&Path> {
+        self.volume.as_deref()
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.last_sample.elapsed() >= SAMPLE_INTERVAL
+    }
+
+    /// What was written since the previous sample.
+    pub fn sample(&mut self) -> DiskActivitySample {
+        self.system.refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::nothing().with_disk_usage());
+        let seconds = self.last_sample.elapsed().as_secs_f64().max(0.001);
+        self.last_sample = Instant::now();
+
+        let mut processes: Vec<ProcessWrites> = self.system.processes()
+            .iter()
+            .map(|(pid, process)| {
+                let usage = process.disk_usage();
+                ProcessWrites {
+                    pid: pid.as_u32(),
+                    name: process.name().to_string_lossy().to_string(),
+                    bytes_per_second: (usage.written_bytes as f64 / seconds) as u64,
+                    total_written: usage.total_written_bytes,
+                }
+            })
+            .filter(|process| process.bytes_per_second >= MIN_WRITE_RATE)
+            .collect();
+        processes.sort_by_key(|process| std::cmp::Reverse(process.bytes_per_second));
+
+        let used = self.volume.as_deref().and_then(volumes::volume_usage).map(|(_, used)| used);
+        let volume_growth = match (self.last_used, used) {
+            (Some(last), Some(now)) => Some(((now as f64 - last as f64) / seconds) as i64),
+            _ => None,
+        };
+        self.last_used = used;
+
+        DiskActivitySample { processes, volume_growth }
+    }
+}
+
+/// The regular files process `pid` has open, biggest first, to spot the one
+/// that keeps growing. Only Linux tells this without extra tools.
+#[cfg(target_os = "linux")]
+pub fn open_files(pid: u32) -> Vec<(PathBuf, u64)> {
+    let Ok(entries) = std::fs::read_dir(format!("/proc/{}/fd", pid)) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(PathBuf, u64)> = entries
+        .flatten()
+        .filter_map(|entry| std::fs::read_link(entry.path()).ok())
+        .filter_map(|target| {
+            let metadata = std::fs::metadata(&target).ok()?;
+            metadata.is_file().then_some((target, metadata.len()))
+        })
+        .collect();
+    files.sort();
+    files.dedup();
+    files.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    files
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn open_files(_pid: u32) -> Vec<(PathBuf, u64)> {
+    Vec::new()
+}
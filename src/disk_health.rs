@@ -0,0 +1,131 @@
+use std::path::Path;
+use std::process::Command;
+use log::{info, debug};
+use serde_json::Value;
+use sysinfo::DiskKind;
+
+use crate::volumes;
+
+/// ATA attributes whose raw value counts damaged sectors.
+const REALLOCATED_SECTORS: u64 = 5;
+const PENDING_SECTORS: u64 = 197;
+const UNCORRECTABLE_SECTORS: u64 = 198;
+
+/// Usage and SMART health of the drive holding a scanned directory.
+#[derive(Debug, Clone)]
+pub struct DiskHealth {
+    pub device: String,
+    pub kind: DiskKind,
+    pub total_space: u64,
+    pub available_space: u64,
+    /// Everything below is read with `smartctl` and stays empty when it is not
+    /// installed or lacks the rights to query the drive.
+    pub model: Option<String>,
+    pub smart_passed: Option<bool>,
+    pub temperature_celsius: Option<i64>,
+    pub power_on_hours: Option<u64>,
+    /// Reallocated, pending and uncorrectable sectors, or NVMe media errors
+    pub bad_sectors: Option<u64>,
+}
+
+impl DiskHealth {
+    /// A drive that failed its self-assessment or is already remapping sectors.
+    pub fn is_failing(&self) -> bool {
+        self.smart_passed == Some(false) || self.bad_sectors.unwrap_or(0) > 0
+    }
+
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("{:?}", self.kind)];
+        if let Some(model) = &self.model {
+            parts.push(model.clone());
+        }
+        match self.smart_passed {
+            Some(true) => parts.push("SMART: passed".to_string()),
+            Some(false) => parts.push("SMART: FAILED".to_string()),
+            None => parts.push("SMART: unavailable (needs smartctl and admin rights)".to_string()),
+        }
+        if let Some(temperature) = self.temperature_celsius {
+            parts.push(format!("{} °C", temperature));
+        }
+        if let Some(hours) = self.power_on_hours {
+            parts.push(format!("{} hours powered on", hours));
+        }
+        if let Some(sectors) = self.bad_sectors.filter(|sectors| *sectors > 0) {
+            parts.push(format!("{} bad sectors", sectors));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Reads usage and SMART data for the drive holding `path`.
+pub fn read_health(path: &Path) -> Option<DiskHealth> {
+    let mut health = volumes::with_disk_for(path, |disk| DiskHealth {
+        device: disk.name().to_string_lossy().to_string(),
+        kind: disk.kind(),
+        total_space: disk.total_space(),
+        available_space: disk.available_space(),
+        model: None,
+        smart_passed: None,
+        temperature_celsius: None,
+        power_on_hours: None,
+        bad_sectors: None,
+    })?;
+
+    if let Some(smart) = run_smartctl(&whole_device(&health.device)) {
+        health.model = smart["model_name"].as_str().map(str::to_string);
+        health.smart_passed = smart["smart_status"]["passed"].as_bool();
+        health.temperature_celsius = smart["temperature"]["current"].as_i64();
+        health.power_on_hours = smart["power_on_time"]["hours"].as_u64();
+        health.bad_sectors = bad_sectors(&smart);
+    }
+
+    info!("Drive health of {}: {}", health.device, health.summary());
+    Some(health)
+}
+
+fn run_smartctl(device: &str) -> Option<Value> {
+    let output = match Command::new("smartctl").args(["--json", "-H", "-A", "-i", device]).output() {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("smartctl not available: {}", e);
+            return None;
+        }
+    };
+
+    // smartctl uses its exit code as a bit mask of findings, so parse the output whatever it is
+    let smart: Value = serde_json::from_slice(&output.stdout).ok()?;
+    smart.get("smart_status").is_some().then_some(smart)
+}
+
+fn bad_sectors(smart: &Value) -> Option<u64> {
+    if let Some(table) = smart["ata_smart_attributes"]["table"].as_array() {
+        return Some(
+            table.iter()
+                .filter(|attribute| {
+                    matches!(attribute["id"].as_u64(), Some(REALLOCATED_SECTORS | PENDING_SECTORS | UNCORRECTABLE_SECTORS))
+                })
+                .filter_map(|attribute| attribute["raw"]["value"].as_u64())
+                .sum(),
+        );
+    }
+
+    smart["nvme_smart_health_information_log"]["media_errors"].as_u64()
+}
+
+/// SMART data belongs to the whole drive: `/dev/sda2` becomes `/dev/sda`,
+/// `/dev/nvme0n1p2` becomes `/dev/nvme0n1` and `/dev/disk1s2` becomes `/dev/disk1`.
+fn whole_device(partition: &str) -> String {
+    let trimmed = partition.trim_end_matches(|c: char| c.is_ascii_digit());
+
+    // These drive names end with a number, their partitions add a letter and another number
+    if ["nvme", "mmcblk", "disk"].iter().any(|prefix| partition.contains(prefix)) {
+        return [trimmed.strip_suffix('p'), trimmed.strip_suffix('s')]
+            .into_iter()
+            .flatten()
+            .find(|device| device.ends_with(|c: char| c.is_ascii_digit()))
+            .unwrap_or(partition)
+            .to_string();
+    }
+
+    trimmed.to_string()
+}
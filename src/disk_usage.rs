@@ -0,0 +1,48 @@
+use std::fs::Metadata;
+use std::path::Path;
+
+/// Space a file actually occupies on disk, which is less than its length when
+/// the file system compresses it (NTFS, ZFS, APFS) or when it is sparse.
+/// Falls back to the logical length when the allocation cannot be read.
+///
+/// Btrfs reports the uncompressed allocation here, so its transparent
+/// compression is not reflected.
+pub fn size_on_disk(path: &Path, metadata: &Metadata) -> u64 {
+    platform_size_on_disk(path, metadata).unwrap_or(metadata.len())
+}
+
+#[cfg(unix)]
+fn platform_size_on_disk(_path: &Path, metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    // st_blocks is always counted in 512-byte units
+    Some(metadata.blocks() * 512)
+}
+
+#[cfg(windows)]
+fn platform_size_on_disk(path: &Path, _metadata: &Metadata) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCompressedFileSizeW(file_name: *const u16, file_size_high: *mut u32) -> u32;
+    }
+
+    const INVALID_FILE_SIZE: u32 = u32::MAX;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut high = 0u32;
+    let low = unsafe { GetCompressedFileSizeW(wide.as_ptr(), &mut high) };
+
+    // INVALID_FILE_SIZE is also a valid low word, the last error tells them apart
+    if low == INVALID_FILE_SIZE && std::io::Error::last_os_error().raw_os_error() != Some(0) {
+        return None;
+    }
+
+    Some((u64::from(high) << 32) | u64::from(low))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn platform_size_on_disk(_path: &Path, _metadata: &Metadata) -> Option<u64> {
+    None
+}
@@ -0,0 +1,324 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use flate2::read::ZlibDecoder;
+use log::{info, debug};
+use rayon::prelude::*;
+
+/// Minimum similarity for two documents to be grouped.
+pub const DEFAULT_SIMILARITY_THRESHOLD: f64 = 0.8;
+
+/// Number of consecutive words hashed together into one shingle.
+const SHINGLE_SIZE: usize = 5;
+
+/// Documents with fewer shingles than this carry too little text to compare.
+const MIN_SHINGLES: usize = 20;
+
+/// Office formats are zip archives; these entries hold their text.
+const OFFICE_TEXT_ENTRIES: &[(&str, &str)] = &[
+    ("docx", "word/document.xml"),
+    ("odt", "content.xml"),
+    ("ods", "content.xml"),
+    ("odp", "content.xml"),
+];
+
+#[derive(Debug, Clone)]
+pub struct SimilarDocuments {
+    /// The newest document first, then the others with their similarity to it (0.0 to 1.0)
+    pub files: Vec<(PathBuf, f64)>,
+}
+
+/// Groups near-identical documents such as drafts and re-exports by comparing
+/// the shingled text of each file. Unlike the duplicate finder, the files do not
+/// need to be byte-for-byte equal.
+pub struct DocumentFinder;
+
+impl DocumentFinder {
+    pub fn find_similar_documents(files: &[PathBuf], threshold: f64) -> Vec<SimilarDocuments> {
+        let documents: Vec<(PathBuf, HashSet<u64>)> = files
+            .par_iter()
+            .filter(|file| Self::is_document(file))
+            .filter_map(|file| {
+                let text = Self::extract_text(file)?;
+                let shingles = Self::shingles(&text);
+                (shingles.len() >= MIN_SHINGLES).then(|| (file.clone(), shingles))
+            })
+            .collect();
+
+        info!("Comparing text of {} documents", documents.len());
+
+        // Union-find over the pairs above the threshold
+        let mut parent: Vec<usize> = (0..documents.len()).collect();
+        fn find(parent: &mut [usize], mut index: usize) -> usize {
+            while parent[index] != index {
+                parent[index] = parent[parent[index]];
+                index = parent[index];
+            }
+            index
+        }
+
+        let similar: Vec<(usize, usize)> = Self::candidate_pairs(&documents, threshold)
+            .into_par_iter()
+            .filter(|&(i, j)| Self::similarity(&documents[i].1, &documents[j].1) >= threshold)
+            .collect();
+        for (i, j) in similar {
+            let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+            parent[a] = b;
+        }
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of_root = std::collections::HashMap::new();
+        for index in 0..documents.len() {
+            let root = find(&mut parent, index);
+            let group = *group_of_root.entry(root).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(index);
+        }
+
+        let result: Vec<SimilarDocuments> = groups
+            .into_iter()
+            .filter(|group| group.len() > 1)
+            .map(|mut group| {
+                group.sort_by_cached_key(|&index| std::cmp::Reverse(Self::modified(&documents[index].0)));
+                let reference = &documents[group[0]].1;
+                SimilarDocuments {
+                    files: group
+                        .iter()
+                        .map(|&index| (documents[index].0.clone(), Self::similarity(reference, &documents[index].1)))
+                        .collect(),
+                }
+            })
+            .collect();
+
+        info!("Found {} groups of similar documents", result.len());
+        result
+    }
+
+    /// The pairs of documents that may reach `threshold`, instead of all of
+    /// them. Documents that similar always share one of the first shingles of
+    /// each in a fixed order, as many as they may have that the other lacks
+    /// plus one, so only documents sharing one of those are paired.
+    fn candidate_pairs(documents: &[(PathBuf, HashSet<u64>)], threshold: f64) -> HashSet<(usize, usize)> {
+        let mut by_shingle: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (index, (_, shingles)) in documents.iter().enumerate() {
+            let mut sorted: Vec<u64> = shingles.iter().copied().collect();
+            sorted.sort_unstable();
+            // The margin keeps rounding from shortening the prefix
+            let shared = ((threshold * sorted.len() as f64) - 1e-9).ceil().max(0.0) as usize;
+            let prefix = (sorted.len() - shared.min(sorted.len()) + 1).min(sorted.len());
+            for shingle in &sorted[..prefix] {
+                by_shingle.entry(*shingle).or_default().push(index);
+            }
+        }
+
+        let mut pairs = HashSet::new();
+        for indices in by_shingle.values() {
+            for (position, &i) in indices.iter().enumerate() {
+                pairs.extend(indices[position + 1..].iter().map(|&j| (i, j)));
+            }
+        }
+        debug!("Comparing {} of {} document pairs", pairs.len(), documents.len() * documents.len().saturating_sub(1) / 2);
+        pairs
+    }
+
+    fn is_document(path: &Path) -> bool {
+        matches!(
+            Self::extension(path).as_str(),
+            "txt" | "md" | "rtf" | "pdf" | "docx" | "odt" | "ods" | "odp"
+        )
+    }
+
+    fn extract_text(path: &Path) -> Option<String> {
+        let extension = Self::extension(path);
+
+        let text = if let Some((_, entry)) = OFFICE_TEXT_ENTRIES.iter().find(|(ext, _)| *ext == extension) {
+            Self::office_text(path, entry)
+        } else if extension == "pdf" {
+            Self::pdf_text(path)
+        } else {
+            std::fs::read(path).ok().map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+        };
+
+        if text.is_none() {
+            debug!("Could not extract text from {}", path.display());
+        }
+        text
+    }
+
+    fn office_text(path: &Path, entry: &str) -> Option<String> {
+        let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+        let mut xml = String::new();
+        archive.by_name(entry).ok()?.read_to_string(&mut xml).ok()?;
+        Some(Self::strip_markup(&xml))
+    }
+
+    /// Best-effort PDF text: the literal strings of every (possibly deflated)
+    /// content stream. Enough to compare documents, not to display them.
+    fn pdf_text(path: &Path) -> Option<String> {
+        let data = std::fs::read(path).ok()?;
+        let mut text = String::new();
+        let mut rest = data.as_slice();
+
+        while let Some(start) = Self::find(rest, b"stream") {
+            let body = &rest[start + b"stream".len()..];
+            let body = body.strip_prefix(b"\r").unwrap_or(body);
+            let body = body.strip_prefix(b"\n").unwrap_or(body);
+            let Some(end) = Self::find(body, b"endstream") else {
+                break;
+            };
+
+            let raw = &body[..end];
+            let mut inflated = Vec::new();
+            let content = if ZlibDecoder::new(raw).read_to_end(&mut inflated).is_ok() {
+                inflated.as_slice()
+            } else {
+                raw
+            };
+            Self::pdf_literal_strings(content, &mut text);
+
+            rest = &body[end + b"endstream".len()..];
+        }
+
+        Some(text)
+    }
+
+    fn pdf_literal_strings(content: &[u8], text: &mut String) {
+        let mut depth = 0usize;
+        let mut escaped = false;
+
+        for &byte in content {
+            match (depth, byte, escaped) {
+                (_, _, true) => {
+                    escaped = false;
+                    if depth > 0 {
+                        text.push(byte as char);
+                    }
+                }
+                (d, b'\\', false) if d > 0 => escaped = true,
+                (_, b'(', false) => depth += 1,
+                (d, b')', false) if d > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        text.push(' ');
+                    }
+                }
+                (d, _, false) if d > 0 => text.push(byte as char),
+                _ => {}
+            }
+        }
+    }
+
+    fn strip_markup(xml: &str) -> String {
+        let mut text = String::with_capacity(xml.len() / 2);
+        let mut in_tag = false;
+
+        for c in xml.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' => {
+                    in_tag = false;
+                    text.push(' ');
+                }
+                _ if !in_tag => text.push(c),
+                _ => {}
+            }
+        }
+
+        text
+    }
+
+    fn shingles(text: &str) -> HashSet<u64> {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect();
+
+        words
+            .windows(SHINGLE_SIZE)
+            .map(|window| {
+                let mut hasher = DefaultHasher::new();
+                window.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect()
+    }
+
+    /// Jaccard similarity of two shingle sets.
+    fn similarity(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+        let intersection = a.intersection(b).count();
+        let union = a.len() + b.len() - intersection;
+        if union == 0 {
+            0.0
+        } else {
+            intersection as f64 / union as f64
+        }
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+
+    fn modified(path: &Path) -> SystemTime {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+
+    fn extension(path: &Path) -> String {
+        path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn text(words: std::ops::Range<usize>) -> String {
+        words.map(|word| format!("word{} ", word)).collect()
+    }
+
+    #[test]
+    fn groups_near_identical_documents_only() {
+        let dir = TempDir::new("similar-documents");
+        let files = vec![
+            dir.file("draft.txt", text(0..200).as_bytes()),
+            dir.file("final.txt", format!("{} closing words", text(0..200)).as_bytes()),
+            dir.file("other.txt", text(1000..1200).as_bytes()),
+            dir.file("half.txt", text(100..300).as_bytes()),
+        ];
+
+        let groups = DocumentFinder::find_similar_documents(&files, DEFAULT_SIMILARITY_THRESHOLD);
+        assert_eq!(groups.len(), 1);
+        let grouped: HashSet<&PathBuf> = groups[0].files.iter().map(|(file, _)| file).collect();
+        assert_eq!(grouped, HashSet::from([&files[0], &files[1]]));
+    }
+
+    #[test]
+    fn candidate_pairs_keep_every_pair_above_the_threshold() {
+        let documents: Vec<(PathBuf, HashSet<u64>)> = (0..40)
+            .map(|index| (PathBuf::from(format!("{}.txt", index)), DocumentFinder::shingles(&text(index * 3..index * 3 + 100))))
+            .collect();
+
+        for threshold in [0.5, 0.8, 0.95] {
+            let candidates = DocumentFinder::candidate_pairs(&documents, threshold);
+            for i in 0..documents.len() {
+                for j in (i + 1)..documents.len() {
+                    if DocumentFinder::similarity(&documents[i].1, &documents[j].1) >= threshold {
+                        assert!(candidates.contains(&(i, j)), "{} and {} missed at {}", i, j, threshold);
+                    }
+                }
+            }
+            assert!(candidates.len() < documents.len() * (documents.len() - 1) / 2);
+        }
+    }
+}
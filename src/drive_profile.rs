@@ -0,0 +1,112 @@
+use std::path::Path;
+use sysinfo::DiskKind;
+
+use crate::config::Config;
+use crate::volumes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveType {
+    Ssd,
+    Hdd,
+    Removable,
+    Network,
+    Unknown,
+}
+
+/// What is known about the drive holding a scan path.
+#[derive(Debug, Clone)]
+pub struct DriveProfile {
+    pub path: String,
+    pub drive_type: DriveType,
+    /// FAT and exFAT have no trash and imprecise times, see [`volumes::FAT_TIME_MARGIN`]
+    pub is_fat: bool,
+}
+
+impl DriveProfile {
+    pub fn detect(path: &str) -> Self {
+        let detected = volumes::with_disk_for(Path::new(path), |disk| {
+            let file_system = disk.file_system().to_string_lossy().to_lowercase();
+            let drive_type = if volumes::is_network_file_system(&file_system) {
+                DriveType::Network
+            } else if disk.is_removable() {
+                DriveType::Removable
+            } else {
+                match disk.kind() {
+                    DiskKind::SSD => DriveType::Ssd,
+                    DiskKind::HDD => DriveType::Hdd,
+                    DiskKind::Unknown(_) => DriveType::Unknown,
+                }
+            };
+            (drive_type, volumes::is_fat_file_system(&file_system))
+        });
+        let (drive_type, is_fat) = detected.unwrap_or((DriveType::Unknown, false));
+
+        Self {
+            path: path.to_string(),
+            drive_type,
+            is_fat,
+        }
+    }
+}
+
+impl DriveType {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Ssd => "an SSD",
+            Self::Hdd => "a spinning hard drive",
+            Self::Removable => "a removable drive",
+            Self::Network => "a network share",
+            Self::Unknown => "a drive of unknown type",
+        }
+    }
+}
+
+/// A setting that suits the drive type better than the current one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suggestion {
+    /// Hashing is cheap on flash storage, so look for duplicates right away
+    FindDuplicatesAfterScan(bool),
+    UseTrash(bool),
+}
+
+impl Suggestion {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::FindDuplicatesAfterScan(true) => "Reading files is fast here: find duplicates automatically after each scan",
+            Self::FindDuplicatesAfterScan(false) => "Hashing every file is slow here: only find duplicates when asked",
+            Self::UseTrash(true) => "Move files to the trash instead of deleting them, so files removed by mistake can be restored from the drive",
+            Self::UseTrash(false) => "Network shares have no trash: delete files directly instead of failing to move them",
+        }
+    }
+
+    pub fn apply(&self, config: &mut Config) {
+        match *self {
+            Self::FindDuplicatesAfterScan(enabled) => config.find_duplicates_after_scan = enabled,
+            Self::UseTrash(enabled) => config.use_trash = enabled,
+        }
+    }
+
+    fn is_applied(&self, config: &Config) -> bool {
+        match *self {
+            Self::FindDuplicatesAfterScan(enabled) => config.find_duplicates_after_scan == enabled,
+            Self::UseTrash(enabled) => config.use_trash == enabled,
+        }
+    }
+}
+
+/// Settings worth changing before scanning a drive of `drive_type`, leaving
+/// out those already in effect.
+pub fn suggestions(drive_type: DriveType, config: &Config) -> Vec<Suggestion> {
+    let suggestions: &[Suggestion] = match drive_type {
+        DriveType::Ssd => &[Suggestion::FindDuplicatesAfterScan(true)],
+        DriveType::Hdd => &[Suggestion::FindDuplicatesAfterScan(false)],
+        DriveType::Removable => &[Suggestion::FindDuplicatesAfterScan(false), Suggestion::UseTrash(true)],
+        DriveType::Network => &[Suggestion::FindDuplicatesAfterScan(false), Suggestion::UseTrash(false)],
+        DriveType::Unknown => &[],
+    };
+
+    suggestions.iter()
+        .filter(|suggestion| !suggestion.is_applied(config))
+        .copied()
+        .collect()
+}
@@ -1,111 +1,590 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::fs::File;
-use std::io::Read;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use std::io::{Read, Seek, SeekFrom};
 use blake3::Hasher;
 use rayon::prelude::*;
 use log::{info, warn};
+use serde::{Serialize, Deserialize};
 
-use crate::progress::{ProgressTracker, ProgressState};
+use crate::config;
+use crate::hash_cache::{self, HashKind};
+use crate::progress::{ProgressTracker, ProgressState, ShareThroughput};
+use crate::volumes::{self, FatVolumes};
 
+/// Bytes hashed at each end of a file by the partial hash prefilter.
+const PARTIAL_HASH_BYTES: u64 = 64 * 1024;
+
+/// Files by the hash of their content.
+type HashGroups = HashMap<String, Vec<PathBuf>>;
+
+/// Groups of files with the same content.
+#[derive(Debug, Clone, Default)]
+pub struct Duplicates {
+    pub groups: Vec<Vec<PathBuf>>,
+    /// The search was cancelled: only the files hashed before it stopped are
+    /// grouped, so some copies may be missing from a group
+    pub cancelled: bool,
+}
+
+/// Which file of a duplicate group is kept when the others are cleaned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DuplicateKeepPolicy {
+    /// The earliest modified copy, usually the original
+    #[default]
+    Oldest,
+    Newest,
+    /// The copy with the shortest path, usually the least nested
+    ShortestPath,
+    /// The copy in the earliest of the preferred folders, e.g. `~/Pictures`
+    /// before `~/Downloads`, the oldest when none or several match
+    PreferredFolders,
+}
+
+impl DuplicateKeepPolicy {
+    pub fn all() -> Vec<Self> {
+        vec![Self::Oldest, Self::Newest, Self::ShortestPath, Self::PreferredFolders]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Oldest => "Oldest",
+            Self::Newest => "Newest",
+            Self::ShortestPath => "Shortest path",
+            Self::PreferredFolders => "In preferred folders",
+        }
+    }
+
+    /// Moves the file to keep to the front of each group, after dropping the
+    /// files gone since they were hashed and the groups left with one file.
+    /// Files whose time is unknown come last under every policy. Ties go to
+    /// the shorter path, then the path order, so the choice never depends on
+    /// the order the files were hashed in.
+    pub fn apply(self, groups: &mut Vec<Vec<PathBuf>>, preferred_folders: &[PathBuf]) {
+        for group in groups.iter_mut() {
+            group.retain(|file| file.exists());
+            group.sort_by_cached_key(|file| {
+                let rank = match self {
+                    Self::PreferredFolders => preferred_folders.iter()
+                        .position(|folder| file.starts_with(folder))
+                        .unwrap_or(preferred_folders.len()),
+                    _ => 0,
+                };
+                let modified = std::fs::metadata(file)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map(|since| since.as_nanos() as i128);
+                let age = match self {
+                    Self::Newest => modified.map(|modified| -modified),
+                    Self::ShortestPath => Some(0),
+                    Self::Oldest | Self::PreferredFolders => modified,
+                };
+                (rank, age.is_none(), age, file.as_os_str().len(), file.clone())
+            });
+        }
+        groups.retain(|group| group.len() > 1);
+    }
+}
+
+/// How files are read for hashing, tuned to the storage they live on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadProfile {
+    /// SSDs serve many small parallel reads well
+    Parallel,
+    /// Spinning disks lose most of their time seeking between files read in
+    /// parallel, so they are read one file at a time in large chunks
+    Sequential,
+    /// Network shares are read one file at a time in small chunks, leaving
+    /// bandwidth for everyone else on a NAS reached over Wi-Fi
+    Network,
+}
+
+impl ReadProfile {
+    fn for_kind(kind: Option<sysinfo::DiskKind>) -> Self {
+        match kind {
+            Some(sysinfo::DiskKind::HDD) => Self::Sequential,
+            _ => Self::Parallel,
+        }
+    }
+
+    fn chunk_size(&self) -> usize {
+        match self {
+            Self::Parallel => 256 * 1024,
+            Self::Sequential => 4 * 1024 * 1024,
+            Self::Network => 64 * 1024,
+        }
+    }
+}
+
+/// Files to hash on one network share, and how fast they are read.
+struct ShareQueue {
+    mount_point: PathBuf,
+    files: Vec<(PathBuf, u64)>,
+    bytes_read: AtomicU64,
+    started: Instant,
+}
+
+impl ShareQueue {
+    fn throughput(&self) -> ShareThroughput {
+        let seconds = self.started.elapsed().as_secs_f64().max(1.0);
+        ShareThroughput {
+            mount_point: self.mount_point.clone(),
+            bytes_per_second: (self.bytes_read.load(Ordering::Relaxed) as f64 / seconds) as u64,
+        }
+    }
+}
+
+/// Finds files with the same content. Hashes go through the shared
+/// [`hash_cache`], so files hashed before, by this finder or anything else,
+/// are not read again.
 pub struct DuplicateFinder {
-    hash_cache: HashMap<PathBuf, String>,
+    min_age: Duration,
+    hash_network_files: bool,
+    keep_policy: DuplicateKeepPolicy,
+    preferred_folders: Vec<PathBuf>,
 }
 
 impl DuplicateFinder {
     pub fn new() -> Self {
         Self {
-            hash_cache: HashMap::new(),
+            min_age: Duration::ZERO,
+            hash_network_files: true,
+            keep_policy: DuplicateKeepPolicy::default(),
+            preferred_folders: Vec::new(),
         }
     }
 
+    /// Puts the file `policy` keeps first in each group found.
+    pub fn with_keep_policy(mut self, policy: DuplicateKeepPolicy, preferred_folders: &[PathBuf]) -> Self {
+        self.keep_policy = policy;
+        self.preferred_folders = preferred_folders.to_vec();
+        self
+    }
+
+    /// Without network hashing, files on network shares count as duplicates
+    /// when their name and size match, instead of being read in full.
+    pub fn with_network_hashing(mut self, enabled: bool) -> Self {
+        self.hash_network_files = enabled;
+        self
+    }
+
+    /// Leaves out files created or modified more recently than `min_age`, which
+    /// may still be written by a download or a sync client.
+    pub fn with_min_age(mut self, min_age: Duration) -> Self {
+        self.min_age = min_age;
+        self
+    }
+
+    /// Times on FAT drives are imprecise, so files there must be older by a
+    /// margin to be sure they are no longer being written.
+    fn is_recent(&self, metadata: &std::fs::Metadata, on_fat: bool) -> bool {
+        let min_age = if on_fat && !self.min_age.is_zero() {
+            self.min_age + volumes::FAT_TIME_MARGIN
+        } else {
+            self.min_age
+        };
+        [metadata.modified(), metadata.created()]
+            .into_iter()
+            .flatten()
+            .any(|time| time.elapsed().is_ok_and(|age| age < min_age))
+    }
+
+    /// Cancelling through `progress` stops the hashing and returns the groups
+    /// of the files hashed so far.
     pub fn find_duplicates(
         &mut self,
         files: &[PathBuf],
-        progress: Arc<Mutex<ProgressTracker>>,
-    ) -> Result<Vec<Vec<PathBuf>>, Box<dyn std::error::Error + Send + Sync>> {
+        progress: Arc<ProgressTracker>,
+    ) -> Duplicates {
         info!("Starting duplicate detection for {} files", files.len());
 
         // First pass: group by file size
         let mut size_groups: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        let app_owned = config::app_owned_paths();
+        let fat_volumes = FatVolumes::detect();
+        let mut recent = 0;
         
         for file in files {
+            if app_owned.iter().any(|owned| file.starts_with(owned)) {
+                continue;
+            }
+
             if let Ok(metadata) = std::fs::metadata(file) {
-                size_groups.entry(metadata.len()).or_insert_with(Vec::new).push(file.clone());
+                if self.is_recent(&metadata, fat_volumes.holds(file)) {
+                    recent += 1;
+                    continue;
+                }
+                size_groups.entry(metadata.len()).or_default().push(file.clone());
             }
         }
 
+        if recent > 0 {
+            info!("Skipped {} files changed in the last {:?}", recent, self.min_age);
+        }
+
         // Filter groups with only one file (no duplicates possible)
-        let potential_duplicates: Vec<_> = size_groups
+        let potential_duplicates: Vec<(PathBuf, u64)> = size_groups
             .into_iter()
             .filter(|(_, files)| files.len() > 1)
-            .flat_map(|(_, files)| files)
+            .flat_map(|(size, files)| files.into_iter().map(move |file| (file, size)))
             .collect();
 
         info!("Found {} files with matching sizes", potential_duplicates.len());
 
         if potential_duplicates.is_empty() {
-            return Ok(Vec::new());
+            return Duplicates::default();
         }
 
-        // Second pass: compute hashes for files with matching sizes
-        let hash_map: Arc<Mutex<HashMap<String, Vec<PathBuf>>>> = Arc::new(Mutex::new(HashMap::new()));
-        let processed_count = Arc::new(Mutex::new(0usize));
+        // Second pass: hash both ends of the files, which tells most files of
+        // the same size apart without reading them in full
+        let network_shares = volumes::network_shares();
+        let (potential_duplicates, confirmed) = self.prefilter_by_partial_hash(potential_duplicates, &network_shares, &progress);
+        if progress.is_cancelled() {
+            // Files whose ends match still need a full hash, only small files read whole are certain
+            return self.extract_groups(&confirmed, true);
+        }
+
+        // Third pass: compute full hashes for files whose partial hashes collide
+        let hash_map: Arc<Mutex<HashGroups>> = Arc::new(Mutex::new(confirmed));
+        let processed_count = AtomicUsize::new(0);
         let total_files = potential_duplicates.len();
+        let started = Instant::now();
 
-        potential_duplicates.par_iter().for_each(|file_path| {
-            // Update progress
-            {
-                let mut count = processed_count.lock().unwrap();
-                *count += 1;
-                let current_count = *count;
-                
-                if current_count % 10 == 0 || current_count == total_files {
-                    let mut progress_guard = progress.lock().unwrap();
-                    progress_guard.state = ProgressState::FindingDuplicates {
-                        files_processed: current_count,
-                        total_files,
-                    };
-                }
+        let disk_kinds = volumes::disk_kinds();
+        let mut shares: Vec<ShareQueue> = network_shares
+            .into_iter()
+            .map(|mount_point| ShareQueue {
+                mount_point,
+                files: Vec::new(),
+                bytes_read: AtomicU64::new(0),
+                started: Instant::now(),
+            })
+            .collect();
+        let mut sequential = Vec::new();
+        let mut parallel = Vec::new();
+        for (file, size) in potential_duplicates {
+            if let Some(share) = shares.iter_mut().find(|share| file.starts_with(&share.mount_point)) {
+                share.files.push((file, size));
+                continue;
+            }
+            let kind = disk_kinds.iter()
+                .find(|(mount_point, _)| file.starts_with(mount_point))
+                .map(|(_, kind)| *kind);
+            match ReadProfile::for_kind(kind) {
+                ReadProfile::Sequential => sequential.push((file, size)),
+                _ => parallel.push((file, size)),
             }
+        }
+        shares.retain(|share| !share.files.is_empty());
+        // Neighbouring paths tend to be stored close together on disk
+        sequential.sort();
+
+        if !self.hash_network_files {
+            let mut hash_groups = hash_map.lock().unwrap();
+            let mut matched = 0;
+            for (file, size) in shares.drain(..).flat_map(|share| share.files) {
+                let name = file.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+                // Never collides with a content hash, which has no colon
+                hash_groups.entry(format!("{}:{}", size, name)).or_default().push(file);
+                matched += 1;
+            }
+            processed_count.fetch_add(matched, Ordering::Relaxed);
+            if matched > 0 {
+                info!("Matched {} files on network shares by name and size instead of hashing them", matched);
+            }
+        }
+        
+        // A single huge file takes as long as many small ones, progress goes by bytes read
+        let bytes_processed = AtomicU64::new(0);
+        let total_bytes: u64 = sequential.iter()
+            .chain(&parallel)
+            .chain(shares.iter().flat_map(|share| &share.files))
+            .map(|(_, size)| size)
+            .sum();
+
+        info!(
+            "Hashing {} files on spinning disks one at a time, {} in parallel, {} on {} network shares",
+            sequential.len(),
+            parallel.len(),
+            shares.iter().map(|share| share.files.len()).sum::<usize>(),
+            shares.len()
+        );
 
-            match self.calculate_file_hash(file_path) {
+        let publish = |force: bool| {
+            progress.update(ProgressState::FindingDuplicates {
+                files_processed: processed_count.load(Ordering::Relaxed),
+                total_files,
+                bytes_processed: bytes_processed.load(Ordering::Relaxed),
+                total_bytes,
+                shares: shares.iter().map(ShareQueue::throughput).collect(),
+                started,
+            }, force);
+        };
+        let hash_file = |file_path: &PathBuf, size: u64, profile: ReadProfile| {
+            if progress.is_cancelled() {
+                return;
+            }
+
+            // Update progress
+            let current_count = processed_count.fetch_add(1, Ordering::Relaxed) + 1;
+            publish(current_count == total_files);
+
+            // The bytes are counted as they are read, so the bar keeps moving through a huge file
+            let mut read = 0;
+            let result = hash_cache::shared().get_or_compute(file_path, HashKind::Full, || {
+                self.calculate_file_hash(file_path, profile, &mut |chunk| {
+                    read += chunk;
+                    bytes_processed.fetch_add(chunk, Ordering::Relaxed);
+                    publish(false);
+                })
+            });
+            // A file that failed or shrank since the scan still counts for its size
+            bytes_processed.fetch_add(size.saturating_sub(read), Ordering::Relaxed);
+
+            match result {
                 Ok(hash) => {
                     let mut hash_groups = hash_map.lock().unwrap();
-                    hash_groups.entry(hash).or_insert_with(Vec::new).push(file_path.clone());
+                    hash_groups.entry(hash).or_default().push(file_path.clone());
                 }
                 Err(e) => {
                     warn!("Failed to hash file {}: {}", file_path.display(), e);
                 }
             }
-        });
+        };
+
+        // Each share is read by a single thread, whatever the number of cores
+        rayon::join(
+            || sequential.iter().for_each(|(file_path, size)| hash_file(file_path, *size, ReadProfile::Sequential)),
+            || rayon::join(
+                || parallel.par_iter().for_each(|(file_path, size)| hash_file(file_path, *size, ReadProfile::Parallel)),
+                || shares.par_iter().for_each(|share| {
+                    for (file_path, size) in &share.files {
+                        hash_file(file_path, *size, ReadProfile::Network);
+                        share.bytes_read.fetch_add(*size, Ordering::Relaxed);
+                    }
+                }),
+            ),
+        );
+
+        // The hashes computed before a cancellation are as good as any
+        if let Err(e) = hash_cache::shared().save() {
+            warn!("Failed to save the hash cache: {}", e);
+        }
 
-        // Extract duplicate groups (groups with more than one file)
         let hash_groups = hash_map.lock().unwrap();
-        let duplicates: Vec<Vec<PathBuf>> = hash_groups
+        self.extract_groups(&hash_groups, progress.is_cancelled())
+    }
+
+    /// The groups with more than one file.
+    fn extract_groups(&self, hash_groups: &HashGroups, cancelled: bool) -> Duplicates {
+        let mut groups: Vec<Vec<PathBuf>> = hash_groups
             .values()
             .filter(|group| group.len() > 1)
             .cloned()
             .collect();
+        self.keep_policy.apply(&mut groups, &self.preferred_folders);
 
-        info!("Found {} groups of duplicate files", duplicates.len());
+        if cancelled {
+            info!("Duplicate search cancelled, found {} groups before stopping", groups.len());
+        } else {
+            info!("Found {} groups of duplicate files", groups.len());
+        }
+        Duplicates { groups, cancelled }
+    }
+
+    /// Groups `candidates` by size and the hash of their first and last
+    /// [`PARTIAL_HASH_BYTES`], and returns the files sharing both with another
+    /// file, which still need a full hash. Files small enough to be read whole
+    /// by the partial hash are grouped by it right away, and returned apart.
+    /// Files on network shares are read one at a time per share, and not at all
+    /// when network hashing is off.
+    fn prefilter_by_partial_hash(
+        &self,
+        candidates: Vec<(PathBuf, u64)>,
+        network_shares: &[PathBuf],
+        progress: &ProgressTracker,
+    ) -> (Vec<(PathBuf, u64)>, HashGroups) {
+        let mut local = Vec::new();
+        let mut by_share: Vec<Vec<(PathBuf, u64)>> = vec![Vec::new(); network_shares.len()];
+        for (file, size) in candidates {
+            match network_shares.iter().position(|mount_point| file.starts_with(mount_point)) {
+                Some(share) => by_share[share].push((file, size)),
+                None => local.push((file, size)),
+            }
+        }
+        let mut unread = Vec::new();
+        if !self.hash_network_files {
+            unread.extend(by_share.drain(..).flatten());
+        }
+
+        let processed_count = AtomicUsize::new(0);
+        let total_files = local.len() + by_share.iter().map(Vec::len).sum::<usize>();
+        let started = Instant::now();
+        // Only both ends of the larger files are read
+        let read_bytes = |size: u64| size.min(2 * PARTIAL_HASH_BYTES);
+        let bytes_processed = AtomicU64::new(0);
+        let total_bytes = local.iter().chain(by_share.iter().flatten()).map(|(_, size)| read_bytes(*size)).sum();
+        let partial_hash = |(file, size): &(PathBuf, u64)| {
+            if progress.is_cancelled() {
+                return None;
+            }
+            let current_count = processed_count.fetch_add(1, Ordering::Relaxed) + 1;
+            let current_bytes = bytes_processed.fetch_add(read_bytes(*size), Ordering::Relaxed) + read_bytes(*size);
+            progress.update(ProgressState::FindingDuplicates {
+                files_processed: current_count,
+                total_files,
+                bytes_processed: current_bytes,
+                total_bytes,
+                shares: Vec::new(),
+                started,
+            }, current_count == total_files);
+
+            match hash_cache::shared().get_or_compute(file, HashKind::Partial, || Self::calculate_partial_hash(file, *size)) {
+                Ok(hash) => Some(((*size, hash), file.clone())),
+                Err(e) => {
+                    warn!("Failed to hash file {}: {}", file.display(), e);
+                    None
+                }
+            }
+        };
+        let (local_hashes, share_hashes): (Vec<_>, Vec<_>) = rayon::join(
+            || local.par_iter().filter_map(partial_hash).collect(),
+            || by_share.par_iter().flat_map_iter(|files| files.iter().filter_map(partial_hash)).collect(),
+        );
+
+        let mut groups: HashMap<(u64, String), Vec<PathBuf>> = HashMap::new();
+        for (key, file) in local_hashes.into_iter().chain(share_hashes) {
+            groups.entry(key).or_default().push(file);
+        }
+
+        let mut remaining = Vec::new();
+        let mut confirmed = HashMap::new();
+        for ((size, hash), files) in groups {
+            if files.len() < 2 {
+                continue;
+            }
+            if size <= 2 * PARTIAL_HASH_BYTES {
+                confirmed.insert(hash, files);
+            } else {
+                remaining.extend(files.into_iter().map(|file| (file, size)));
+            }
+        }
 
-        Ok(duplicates)
+        info!(
+            "Partial hashes left {} of {} files to hash in full, {} small files already matched",
+            remaining.len(),
+            total_files,
+            confirmed.values().map(Vec::len).sum::<usize>()
+        );
+        remaining.extend(unread);
+        (remaining, confirmed)
     }
 
-    fn calculate_file_hash(&self, file_path: &PathBuf) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    /// Hash of the first and last [`PARTIAL_HASH_BYTES`] of a file of `size`
+    /// bytes. Smaller files are read whole, giving the same hash as
+    /// [`Self::calculate_file_hash`].
+    fn calculate_partial_hash(file_path: &Path, size: u64) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
         let mut file = File::open(file_path)?;
         let mut hasher = Hasher::new();
         
+        if size <= 2 * PARTIAL_HASH_BYTES {
+            hasher.update_reader(file)?;
+        } else {
+            let mut buffer = vec![0; PARTIAL_HASH_BYTES as usize];
+            file.read_exact(&mut buffer)?;
+            hasher.update(&buffer);
+            file.seek(SeekFrom::Start(size - PARTIAL_HASH_BYTES))?;
+            file.read_exact(&mut buffer)?;
+            hasher.update(&buffer);
+        }
+
+        Ok(hasher.finalize().to_hex().to_string())
+    }
+
+    /// Returns the files among `candidates` whose content is identical to `target`.
+    pub fn find_duplicates_of(&mut self, target: &Path, candidates: &[PathBuf]) -> Vec<PathBuf> {
+        let target_size = match std::fs::metadata(target) {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                warn!("Could not get metadata for {}: {}", target.display(), e);
+                return Vec::new();
+            }
+        };
+
+        let same_size: Vec<PathBuf> = candidates
+            .iter()
+            .filter(|candidate| candidate.as_path() != target)
+            .filter(|candidate| !config::is_app_owned(candidate))
+            .filter(|candidate| {
+                std::fs::metadata(candidate)
+                    .map(|metadata| metadata.len() == target_size)
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+
+        if same_size.is_empty() {
+            return Vec::new();
+        }
+
+        let target_hash = match self.cached_hash(target) {
+            Some(hash) => hash,
+            None => return Vec::new(),
+        };
+
+        let duplicates = same_size
+            .into_iter()
+            .filter(|candidate| self.cached_hash(candidate).as_deref() == Some(target_hash.as_str()))
+            .collect();
+        if let Err(e) = hash_cache::shared().save() {
+            warn!("Failed to save the hash cache: {}", e);
+        }
+        duplicates
+    }
+
+    fn cached_hash(&self, file_path: &Path) -> Option<String> {
+        let hash = hash_cache::shared().get_or_compute(file_path, HashKind::Full, || {
+            self.calculate_file_hash(file_path, ReadProfile::Parallel, &mut |_| {})
+        });
+        match hash {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                warn!("Failed to hash file {}: {}", file_path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Full BLAKE3 of a file, calling `on_read` with the size of each chunk read.
+    fn calculate_file_hash(
+        &self,
+        file_path: &Path,
+        profile: ReadProfile,
+        on_read: &mut dyn FnMut(u64),
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let mut file = File::open(file_path)?;
+        let mut hasher = Hasher::new();
+        
+        // Let the kernel read ahead aggressively, the whole file is read once in order
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::io::AsRawFd;
+            unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL) };
+        }
+        
         // Read file in chunks to handle large files efficiently
-        let mut buffer = [0; 8192];
+        let mut buffer = vec![0; profile.chunk_size()];
         loop {
             let bytes_read = file.read(&mut buffer)?;
             if bytes_read == 0 {
                 break;
             }
             hasher.update(&buffer[..bytes_read]);
+            on_read(bytes_read as u64);
         }
 
         Ok(hasher.finalize().to_hex().to_string())
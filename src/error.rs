@@ -0,0 +1,115 @@
+//! Errors of scanning, duplicate detection and cleaning, sorted by what the
+//! user can do about them, so the interface can say it and offer a retry
+//! where one may succeed.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error("permission denied on {}", path.display())]
+    PermissionDenied { path: PathBuf },
+    #[error("{} is in use by another program", path.display())]
+    FileInUse { path: PathBuf },
+    #[error("the trash is unavailable: {0}")]
+    TrashUnavailable(String),
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: Arc<io::Error>,
+    },
+    #[error("cancelled")]
+    Cancelled,
+    /// Anything else, such as a file refused by the safety checks
+    #[error("{0}")]
+    Other(String),
+}
+
+impl Error {
+    /// Sorts an IO error on `path` into the variant that tells what to do about it.
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        if source.kind() == io::ErrorKind::PermissionDenied {
+            return Self::PermissionDenied { path: path.to_path_buf() };
+        }
+        if is_in_use(&source) {
+            return Self::FileInUse { path: path.to_path_buf() };
+        }
+        Self::Io { path: path.to_path_buf(), source: Arc::new(source) }
+    }
+
+    /// What the user can do about the error, if anything.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::PermissionDenied { .. } if cfg!(windows) => Some("Run the cleaner as administrator to take ownership of the file."),
+            Self::PermissionDenied { .. } => Some("The file belongs to another user or its folder cannot be written; check its permissions."),
+            Self::FileInUse { .. } => Some("Close the program using the file, then retry."),
+            Self::TrashUnavailable(_) => Some("Choose what happens when the trash cannot take a file in the settings, or turn off \"Use Trash\"."),
+            Self::Io { source, .. } if source.kind() == io::ErrorKind::NotFound => {
+                Some("The file was moved or removed since the scan; scan again.")
+            }
+            Self::Io { .. } => Some("The drive may be busy or disconnected; retry once it is available."),
+            Self::Cancelled | Self::Other(_) => None,
+        }
+    }
+
+    /// Whether trying again, once the user acted on the hint, may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::FileInUse { .. } => true,
+            Self::Io { source, .. } => source.kind() != io::ErrorKind::NotFound,
+            _ => false,
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Self::Other(message.to_string())
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for Error {
+    fn from(error: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        match error.downcast::<Self>() {
+            Ok(error) => *error,
+            Err(error) => Self::Other(error.to_string()),
+        }
+    }
+}
+
+/// Cleanup records store the message, the variant is only known to the run
+/// that hit the error.
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Error {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::Other)
+    }
+}
+
+/// A file held open without sharing on Windows, or a running executable or
+/// busy mount point on Unix.
+fn is_in_use(error: &io::Error) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    match error.raw_os_error() {
+        Some(code) if cfg!(windows) => code == ERROR_SHARING_VIOLATION || code == ERROR_LOCK_VIOLATION,
+        #[cfg(unix)]
+        Some(code) => code == libc::ETXTBSY || code == libc::EBUSY,
+        _ => false,
+    }
+}
@@ -1,6 +1,214 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use aho_corasick::AhoCorasick;
 use serde::{Serialize, Deserialize};
 
+use crate::formatting;
+use crate::orphan_temps::{self, Owner};
+use crate::package_caches;
+
+/// Substrings of the lowercase path that put a file in a category wherever
+/// they appear, so a directory matching one holds nothing but files of that
+/// category or of the ones checked before it.
+const PATH_RULES: &[(FileCategory, &[&str])] = &[
+    (FileCategory::TemporaryFiles, &["/tmp/", "\\temp\\", "/var/tmp/", "\\windows\\temp\\"]),
+    (FileCategory::CacheFiles, &["cache", "\\appdata\\local\\"]),
+    (FileCategory::LogFiles, &["/var/log/", "\\logs\\"]),
+    (FileCategory::BrowserData, &["browser", "firefox", "chrome", "safari", "cookies", "history"]),
+    (FileCategory::Downloads, &["download"]),
+    (FileCategory::RecycleBin, &["recycle", "trash"]),
+];
+
+/// Storage areas of backup tools, with `/` standing for either separator.
+const BACKUP_AREAS: &[(&str, &str)] = &[
+    ("/backups.backupdb/", "Inside a Time Machine backup"),
+    (".previous.inprogress/", "Inside a Time Machine backup in progress"),
+    ("/.mobilebackups", "Time Machine local snapshot storage"),
+    ("com.apple.timemachine", "Time Machine local snapshot storage"),
+    ("/filehistory/", "Inside a Windows File History backup"),
+    ("/backintime/", "Inside a Back In Time snapshot"),
+    ("/timeshift/snapshots/", "Inside a Timeshift snapshot"),
+    ("/rsnapshot/", "Inside an rsnapshot backup tree"),
+];
+
+/// Folders that build tools and package managers fill with files they can
+/// regenerate. Names other tools use as well only count with one of the
+/// files listed beside them in the project folder, relative to it.
+const ARTIFACT_DIRS: &[(&str, &[&str])] = &[
+    ("node_modules", &[]),
+    ("__pycache__", &[]),
+    (".gradle", &[]),
+    (".venv", &[]),
+    (".tox", &[]),
+    (".pytest_cache", &[]),
+    (".mypy_cache", &[]),
+    (".next", &[]),
+    (".dart_tool", &[]),
+    ("target", &["Cargo.toml", "pom.xml"]),
+    ("build", &["build.gradle", "build.gradle.kts", "CMakeLists.txt", "package.json", "setup.py", "pubspec.yaml"]),
+    ("venv", &["venv/pyvenv.cfg"]),
+];
+
+/// What a pattern of the [`PathMatcher`] stands for.
+#[derive(Clone, Copy, PartialEq)]
+enum PathPattern {
+    Backup(&'static str),
+    Category(FileCategory),
+    Mozilla,
+    /// The name of one of the [`ARTIFACT_DIRS`], maybe as part of a longer name
+    DevArtifact,
+}
+
+/// All the substrings the rules look for in a path, compiled once into a
+/// single automaton. A path is then scanned once, whatever the number of
+/// patterns, and without lowercasing it.
+struct PathMatcher {
+    automaton: AhoCorasick,
+    /// What each pattern of the automaton stands for, in rule order
+    patterns: Vec<PathPattern>,
+}
+
+impl PathMatcher {
+    fn get() -> &'static Self {
+        static MATCHER: OnceLock<PathMatcher> = OnceLock::new();
+        MATCHER.get_or_init(|| {
+            let mut needles = Vec::new();
+            let mut patterns = Vec::new();
+            for (needle, reason) in BACKUP_AREAS {
+                needles.push(needle.to_string());
+                patterns.push(PathPattern::Backup(reason));
+                if needle.contains('/') {
+                    needles.push(needle.replace('/', "\\"));
+                    patterns.push(PathPattern::Backup(reason));
+                }
+            }
+            for (category, category_needles) in PATH_RULES {
+                for needle in *category_needles {
+                    needles.push(needle.to_string());
+                    patterns.push(PathPattern::Category(*category));
+                }
+            }
+            needles.push("mozilla".to_string());
+            patterns.push(PathPattern::Mozilla);
+            for (name, _) in ARTIFACT_DIRS {
+                needles.push(name.to_string());
+                patterns.push(PathPattern::DevArtifact);
+            }
+            assert!(patterns.len() <= u64::BITS as usize, "too many path patterns for the match set");
+
+            let automaton = AhoCorasick::builder()
+                .ascii_case_insensitive(true)
+                .build(&needles)
+                .expect("path patterns are valid");
+            Self { automaton, patterns }
+        })
+    }
+
+    fn find<'a>(&'static self, path: &'a [u8]) -> PathMatches<'a> {
+        let found = self.automaton.find_overlapping_iter(path)
+            .fold(0u64, |found, m| found | 1 << m.pattern().as_usize());
+        PathMatches { matcher: self, path, found }
+    }
+}
+
+/// The patterns of the [`PathMatcher`] found in one path.
+struct PathMatches<'a> {
+    matcher: &'static PathMatcher,
+    path: &'a [u8],
+    /// One bit per pattern
+    found: u64,
+}
+
+impl PathMatches<'_> {
+    /// The patterns found, in rule order.
+    fn patterns(&self) -> impl Iterator<Item = PathPattern> + '_ {
+        self.matcher.patterns.iter()
+            .enumerate()
+            .filter(|(index, _)| self.found & 1 << index != 0)
+            .map(|(_, pattern)| *pattern)
+    }
+
+    fn has(&self, pattern: PathPattern) -> bool {
+        self.patterns().any(|found| found == pattern)
+    }
+
+    /// The first category whose path rule matches, in [`PATH_RULES`] order.
+    fn first_category(&self) -> Option<FileCategory> {
+        self.patterns().find_map(|pattern| match pattern {
+            PathPattern::Category(category) => Some(category),
+            _ => None,
+        })
+    }
+
+    /// Recognizes the storage areas of backup tools. Their files are old copies
+    /// on purpose, and deleting them by hand can corrupt the backup set.
+    fn backup_reason(&self) -> Option<&'static str> {
+        let area = self.patterns().find_map(|pattern| match pattern {
+            PathPattern::Backup(reason) => Some(reason),
+            _ => None,
+        });
+        if area.is_some() {
+            return area;
+        }
+
+        // rsnapshot rotates its snapshots as hourly.0, daily.3, weekly.1 ...
+        const INTERVALS: &[&str] = &["hourly", "daily", "weekly", "monthly", "alpha", "beta", "gamma"];
+        let rotated = self.path.split(|&b| b == b'/' || b == b'\\').any(|component| {
+            component.iter().position(|&b| b == b'.').is_some_and(|dot| {
+                let (interval, number) = (&component[..dot], &component[dot + 1..]);
+                INTERVALS.iter().any(|name| interval.eq_ignore_ascii_case(name.as_bytes()))
+                    && !number.is_empty()
+                    && number.iter().all(u8::is_ascii_digit)
+            })
+        });
+        rotated.then_some("Inside a rotated rsnapshot-style backup snapshot")
+    }
+}
+
+/// The build or dependency folder of a development project that `dir` is or
+/// lies in, the outermost one when they nest.
+pub fn dev_artifact_root(dir: &Path) -> Option<PathBuf> {
+    let mut current = PathBuf::new();
+    for component in dir.components() {
+        current.push(component);
+        let Component::Normal(name) = component else {
+            continue;
+        };
+        let artifact = ARTIFACT_DIRS.iter()
+            .find(|(artifact, _)| name.as_encoded_bytes().eq_ignore_ascii_case(artifact.as_bytes()));
+        if let Some((_, markers)) = artifact {
+            if markers.is_empty() || has_project_marker(&current, markers) {
+                return Some(current);
+            }
+        }
+    }
+    None
+}
+
+/// Whether one of `markers` exists in the project folder holding `artifact`.
+/// The answer is remembered for the session, as every file below the folder
+/// asks again.
+fn has_project_marker(artifact: &Path, markers: &[&str]) -> bool {
+    static FOUND: OnceLock<Mutex<HashMap<PathBuf, bool>>> = OnceLock::new();
+    let found = FOUND.get_or_init(Default::default);
+    if let Some(known) = found.lock().unwrap().get(artifact) {
+        return *known;
+    }
+    let exists = artifact.parent()
+        .is_some_and(|project| markers.iter().any(|marker| project.join(marker).exists()));
+    found.lock().unwrap().insert(artifact.to_path_buf(), exists);
+    exists
+}
+
+fn starts_with_ignore_case(name: &[u8], prefix: &str) -> bool {
+    name.get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(prefix.as_bytes()))
+}
+
+fn ends_with_ignore_case(name: &[u8], suffix: &str) -> bool {
+    name.len() >= suffix.len() && name[name.len() - suffix.len()..].eq_ignore_ascii_case(suffix.as_bytes())
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FileCategory {
     TemporaryFiles,
@@ -13,6 +221,9 @@ pub enum FileCategory {
     OldFiles,
     Downloads,
     RecycleBin,
+    BackupSnapshots,
+    DevArtifacts,
+    PackageCaches,
 }
 
 impl FileCategory {
@@ -28,89 +239,167 @@ impl FileCategory {
             Self::OldFiles,
             Self::Downloads,
             Self::RecycleBin,
+            Self::BackupSnapshots,
+            Self::DevArtifacts,
+            Self::PackageCaches,
         ]
     }
 
-    pub fn categorize(path: &Path) -> Self {
-        let path_str = path.to_string_lossy().to_lowercase();
-        let filename = path.file_name()
-            .map(|n| n.to_string_lossy().to_lowercase())
-            .unwrap_or_default();
+    /// Name shown to the user unless renamed in the settings, in the language
+    /// of the session when it is translated and in English otherwise.
+    pub fn default_name(self) -> &'static str {
+        match formatting::language() {
+            "pt" => match self {
+                Self::TemporaryFiles => "Arquivos temporários",
+                Self::CacheFiles => "Cache",
+                Self::LogFiles => "Logs",
+                Self::BrowserData => "Dados do navegador",
+                Self::SystemJunk => "Lixo do sistema",
+                Self::EmptyFolders => "Pastas vazias",
+                Self::LargeFiles => "Arquivos grandes",
+                Self::OldFiles => "Arquivos antigos",
+                Self::Downloads => "Downloads",
+                Self::RecycleBin => "Lixeira",
+                Self::BackupSnapshots => "Backups e snapshots",
+                Self::DevArtifacts => "Artefatos de desenvolvimento",
+                Self::PackageCaches => "Caches de pacotes",
+            },
+            "es" => match self {
+                Self::TemporaryFiles => "Archivos temporales",
+                Self::CacheFiles => "Caché",
+                Self::LogFiles => "Registros",
+                Self::BrowserData => "Datos del navegador",
+                Self::SystemJunk => "Basura del sistema",
+                Self::EmptyFolders => "Carpetas vacías",
+                Self::LargeFiles => "Archivos grandes",
+                Self::OldFiles => "Archivos antiguos",
+                Self::Downloads => "Descargas",
+                Self::RecycleBin => "Papelera",
+                Self::BackupSnapshots => "Copias de seguridad",
+                Self::DevArtifacts => "Artefactos de desarrollo",
+                Self::PackageCaches => "Cachés de paquetes",
+            },
+            _ => match self {
+                Self::TemporaryFiles => "Temporary files",
+                Self::CacheFiles => "Cache files",
+                Self::LogFiles => "Log files",
+                Self::BrowserData => "Browser data",
+                Self::SystemJunk => "System junk",
+                Self::EmptyFolders => "Empty folders",
+                Self::LargeFiles => "Large files",
+                Self::OldFiles => "Old files",
+                Self::Downloads => "Downloads",
+                Self::RecycleBin => "Recycle bin",
+                Self::BackupSnapshots => "Backups and snapshots",
+                Self::DevArtifacts => "Build artifacts",
+                Self::PackageCaches => "Package caches",
+            },
+        }
+    }
 
-        // Check file extension
-        let extension = path.extension()
-            .map(|ext| ext.to_string_lossy().to_lowercase())
-            .unwrap_or_default();
+    /// Categorizes a file. `metadata` saves reading it again when the caller
+    /// already has it.
+    pub fn categorize(path: &Path, metadata: Option<&std::fs::Metadata>) -> Self {
+        Self::categorize_with_reason(path, metadata).0
+    }
+
+    /// Categorizes a file and returns a short explanation of which rule matched.
+    pub fn categorize_with_reason(path: &Path, metadata: Option<&std::fs::Metadata>) -> (Self, &'static str) {
+        let matches = PathMatcher::get().find(path.as_os_str().as_encoded_bytes());
+        let filename = path.file_name().map(|name| name.as_encoded_bytes()).unwrap_or_default();
+        let extension = path.extension().map(|ext| ext.as_encoded_bytes()).unwrap_or_default();
+        let is_extension = |candidates: &[&str]| candidates.iter().any(|candidate| extension.eq_ignore_ascii_case(candidate.as_bytes()));
+
+        if let Some(reason) = matches.backup_reason() {
+            return (Self::BackupSnapshots, reason);
+        }
+
+        // Downloads of package managers, ahead of the generic cache rule
+        if package_caches::manager_of(path).is_some() {
+            return (Self::PackageCaches, "Downloaded by a package manager, which fetches it again when needed");
+        }
+
+        // Build outputs and dependencies of development projects
+        if matches.has(PathPattern::DevArtifact) && path.parent().and_then(dev_artifact_root).is_some() {
+            return (Self::DevArtifacts, "Inside a build or dependency folder of a development project");
+        }
+
+        // Temporary files naming their owner, deletable once it is gone
+        match orphan_temps::owner(path, metadata) {
+            Some(Owner::Gone(reason)) => return (Self::TemporaryFiles, reason),
+            Some(Owner::Active) => return (Self::TemporaryFiles, "Temporary file still in use by a running program, left out of scans"),
+            None => {}
+        }
 
         // Temporary files
-        if extension == "tmp" || extension == "temp" || 
-           filename.starts_with("~") || filename.starts_with(".#") ||
-           path_str.contains("/tmp/") || path_str.contains("\\temp\\") ||
-           path_str.contains("/var/tmp/") || path_str.contains("\\windows\\temp\\") {
-            return Self::TemporaryFiles;
+        if is_extension(&["tmp", "temp"]) ||
+           filename.starts_with(b"~") || filename.starts_with(b".#") ||
+           matches.has(PathPattern::Category(Self::TemporaryFiles)) {
+            return (Self::TemporaryFiles, "Temporary file extension, name prefix or temp directory");
         }
 
         // Cache files
-        if path_str.contains("cache") || path_str.contains(".cache") ||
-           extension == "cache" || 
-           path_str.contains("/var/cache/") || path_str.contains("\\appdata\\local\\") {
-            return Self::CacheFiles;
+        if is_extension(&["cache"]) || matches.has(PathPattern::Category(Self::CacheFiles)) {
+            return (Self::CacheFiles, "Located in a cache directory or has a cache extension");
         }
 
         // Log files
-        if extension == "log" || extension == "out" || extension == "err" ||
-           filename.ends_with(".log") || filename.ends_with(".out") ||
-           path_str.contains("/var/log/") || path_str.contains("\\logs\\") {
-            return Self::LogFiles;
+        if is_extension(&["log", "out", "err"]) ||
+           ends_with_ignore_case(filename, ".log") || ends_with_ignore_case(filename, ".out") ||
+           matches.has(PathPattern::Category(Self::LogFiles)) {
+            return (Self::LogFiles, "Log file extension or located in a log directory");
         }
 
         // Browser data
-        if path_str.contains("browser") || path_str.contains("firefox") ||
-           path_str.contains("chrome") || path_str.contains("safari") ||
-           path_str.contains("cookies") || path_str.contains("history") ||
-           extension == "sqlite" && path_str.contains("mozilla") {
-            return Self::BrowserData;
+        if matches.has(PathPattern::Category(Self::BrowserData)) ||
+           is_extension(&["sqlite"]) && matches.has(PathPattern::Mozilla) {
+            return (Self::BrowserData, "Path belongs to a web browser profile");
         }
 
         // Downloads
-        if path_str.contains("download") || path_str.contains("downloads") {
-            return Self::Downloads;
+        if matches.has(PathPattern::Category(Self::Downloads)) {
+            return (Self::Downloads, "Located in a downloads directory");
         }
 
         // Recycle bin / Trash
-        if path_str.contains("recycle") || path_str.contains("trash") ||
-           path_str.contains(".trash") || path_str.contains("$recycle.bin") {
-            return Self::RecycleBin;
+        if matches.has(PathPattern::Category(Self::RecycleBin)) {
+            return (Self::RecycleBin, "Located in the trash or recycle bin");
         }
 
         // System junk
-        if extension == "bak" || extension == "old" || extension == "backup" ||
-           filename.starts_with("core.") || filename == "thumbs.db" ||
-           filename == ".ds_store" || filename == "desktop.ini" {
-            return Self::SystemJunk;
+        if is_extension(&["bak", "old", "backup"]) ||
+           starts_with_ignore_case(filename, "core.") || filename.eq_ignore_ascii_case(b"thumbs.db") ||
+           filename.eq_ignore_ascii_case(b".ds_store") || filename.eq_ignore_ascii_case(b"desktop.ini") {
+            return (Self::SystemJunk, "Backup, core dump or OS metadata file");
         }
 
-        // Large files (over 100MB)
-        if let Ok(metadata) = std::fs::metadata(path) {
+        let read_metadata;
+        let metadata = match metadata {
+            Some(metadata) => Some(metadata),
+            None => {
+                read_metadata = std::fs::metadata(path).ok();
+                read_metadata.as_ref()
+            }
+        };
+        if let Some(metadata) = metadata {
+            // Large files (over 100MB)
             if metadata.len() > 100 * 1024 * 1024 {
-                return Self::LargeFiles;
+                return (Self::LargeFiles, "File is larger than 100MB");
             }
-        }
 
-        // Old files (over 30 days)
-        if let Ok(metadata) = std::fs::metadata(path) {
+            // Old files (over 30 days)
             if let Ok(modified) = metadata.modified() {
                 if let Ok(duration) = modified.elapsed() {
                     let age_days = duration.as_secs() / (24 * 60 * 60);
                     if age_days > 30 {
-                        return Self::OldFiles;
+                        return (Self::OldFiles, "File was not modified in the last 30 days");
                     }
                 }
             }
         }
 
         // Default category
-        Self::SystemJunk
+        (Self::SystemJunk, "No specific rule matched")
     }
 
     pub fn description(&self) -> &'static str {
@@ -125,14 +414,146 @@ impl FileCategory {
             Self::OldFiles => "Files older than 30 days",
             Self::Downloads => "Files in download directories",
             Self::RecycleBin => "Files in trash/recycle bin",
+            Self::BackupSnapshots => "Backups made by Time Machine, File History or other backup tools; prune them with that tool",
+            Self::DevArtifacts => "Build outputs and dependencies of development projects, like node_modules or Rust target folders, rebuilt on the next build",
+            Self::PackageCaches => "Packages downloaded by cargo, pip, npm, apt and other package managers, fetched again when needed; their indexes and databases are kept",
         }
     }
 
     pub fn is_safe_to_delete(&self) -> bool {
         match self {
             Self::TemporaryFiles | Self::CacheFiles | Self::LogFiles | 
-            Self::SystemJunk | Self::EmptyFolders | Self::RecycleBin => true,
-            Self::BrowserData | Self::LargeFiles | Self::OldFiles | Self::Downloads => false,
+            Self::SystemJunk | Self::EmptyFolders | Self::RecycleBin | Self::PackageCaches => true,
+            Self::BrowserData | Self::LargeFiles | Self::OldFiles | Self::Downloads |
+            Self::BackupSnapshots | Self::DevArtifacts => false,
+        }
+    }
+
+    /// Position of the category's rule in [`Self::categorize_with_reason`].
+    fn rule_rank(self) -> usize {
+        match self {
+            Self::BackupSnapshots => 0,
+            Self::PackageCaches => 1,
+            Self::DevArtifacts => 2,
+            Self::TemporaryFiles => 3,
+            Self::CacheFiles => 4,
+            Self::LogFiles => 5,
+            Self::BrowserData => 6,
+            Self::Downloads => 7,
+            Self::RecycleBin => 8,
+            Self::SystemJunk | Self::LargeFiles | Self::OldFiles | Self::EmptyFolders => 9,
+        }
+    }
+
+    /// Whether a file anywhere below `dir` can be put in this category. Once a
+    /// rule that matches on the path alone matches `dir`, the files below it
+    /// never reach the rules checked after that one, so the scanner can skip
+    /// directories that cannot hold any of the categories it looks for.
+    pub fn may_occur_under(self, dir: &Path) -> bool {
+        let mut dir_bytes = dir.as_os_str().as_encoded_bytes().to_vec();
+        dir_bytes.push(std::path::MAIN_SEPARATOR as u8);
+        let matches = PathMatcher::get().find(&dir_bytes);
+
+        let bound = if matches.backup_reason().is_some() {
+            Some(Self::BackupSnapshots)
+        } else if matches.has(PathPattern::DevArtifact) && dev_artifact_root(dir).is_some() {
+            Some(Self::DevArtifacts)
+        } else {
+            matches.first_category()
+        };
+
+        bound.is_none_or(|bound| self.rule_rank() <= bound.rule_rank())
+    }
+}
+
+/// How costly it is to get a cache back once it has been deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum RebuildCost {
+    /// Regenerated locally in moments (thumbnails, bytecode, font caches)
+    Instant,
+    /// Downloaded or rebuilt again on next use at little cost (browser and package caches)
+    Cheap,
+    /// Large downloads or long recomputation (ML models, shader caches, toolchains)
+    Expensive,
+}
+
+impl RebuildCost {
+    pub fn all() -> Vec<Self> {
+        vec![Self::Instant, Self::Cheap, Self::Expensive]
+    }
+
+    pub fn for_path(path: &Path) -> Self {
+        let path_str = path.to_string_lossy().to_lowercase().replace('\\', "/");
+
+        // Model weights, shader caches and toolchains take long to get back
+        const EXPENSIVE: &[&str] = &[
+            "huggingface", "/torch/hub", "/.ollama/", "/lm-studio/", "/whisper/",
+            "shadercache", "dxcache", "glcache", "/nv/computecache", "/mesa_shader_cache",
+            "/.rustup/", "/.gradle/wrapper", "/android/sdk", "/.nuget/packages",
+        ];
+        if EXPENSIVE.iter().any(|pattern| path_str.contains(pattern)) {
+            return Self::Expensive;
+        }
+
+        // Generated locally from files already on disk
+        const INSTANT: &[&str] = &[
+            "thumbnail", "/__pycache__/", "fontconfig", "iconcache", "/.pytest_cache/", "/.mypy_cache/",
+        ];
+        let extension = path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if INSTANT.iter().any(|pattern| path_str.contains(pattern)) || extension == "pyc" {
+            return Self::Instant;
+        }
+
+        Self::Cheap
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Instant => "rebuilt instantly",
+            Self::Cheap => "cheap to rebuild",
+            Self::Expensive => "expensive to rebuild",
+        }
+    }
+}
+
+/// How long ago a download was last written, the way people sort through
+/// their downloads: recent ones are likely still wanted, year-old ones rarely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DownloadAge {
+    ThisWeek,
+    ThisMonth,
+    OneToThreeMonths,
+    ThreeToTwelveMonths,
+    OverAYear,
+}
+
+impl DownloadAge {
+    pub fn all() -> Vec<Self> {
+        vec![Self::ThisWeek, Self::ThisMonth, Self::OneToThreeMonths, Self::ThreeToTwelveMonths, Self::OverAYear]
+    }
+
+    pub fn of(metadata: &std::fs::Metadata) -> Self {
+        let days = metadata.modified().ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map_or(0, |age| age.as_secs() / (24 * 60 * 60));
+        match days {
+            0..7 => Self::ThisWeek,
+            7..30 => Self::ThisMonth,
+            30..90 => Self::OneToThreeMonths,
+            90..365 => Self::ThreeToTwelveMonths,
+            _ => Self::OverAYear,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::ThisWeek => "This week",
+            Self::ThisMonth => "This month",
+            Self::OneToThreeMonths => "1 to 3 months old",
+            Self::ThreeToTwelveMonths => "3 to 12 months old",
+            Self::OverAYear => "Over a year old",
         }
     }
 }
@@ -0,0 +1,200 @@
+use std::fs::Metadata;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+
+/// Device and inode of a file, recorded at scan time so deletion can check that
+/// a path still names the file that was scanned and not one swapped in since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileIdentity {
+    pub device: u64,
+    pub inode: u64,
+}
+
+impl FileIdentity {
+    #[cfg(unix)]
+    pub fn of(metadata: &Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        Some(Self {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+        })
+    }
+
+    /// Windows only exposes file IDs through handles, so no identity is recorded there.
+    #[cfg(not(unix))]
+    pub fn of(_metadata: &Metadata) -> Option<Self> {
+        None
+    }
+}
+
+/// Stable handle of a scanned file, used instead of its path in identity maps
+/// and UI state. It is a hash of the path, so it stays the same across re-sorts,
+/// moves between categories and saved snapshots, and sets of them are much
+/// smaller than sets of paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FileId(u64);
+
+impl FileId {
+    pub fn of(path: &Path) -> Self {
+        let hash = blake3::hash(path.as_os_str().as_encoded_bytes());
+        Self(u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap()))
+    }
+}
+
+/// Removes the file or empty folder at the canonical `path` without following any symbolic link
+/// on the way: each directory is opened relative to the previous one with
+/// `O_NOFOLLOW`, which pins it, and the file is checked and unlinked relative
+/// to the last one. The check and the unlink remain two calls, so someone able
+/// to write to that directory could still swap the file in between.
+#[cfg(unix)]
+pub fn unlink_verified(path: &Path, expected: Option<FileIdentity>) -> std::io::Result<()> {
+    use std::ffi::{CString, OsStr};
+    use std::io::{Error, ErrorKind};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Component;
+
+    struct Fd(libc::c_int);
+    impl Drop for Fd {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.0) };
+        }
+    }
+
+    let c_string = |part: &OsStr| {
+        CString::new(part.as_bytes()).map_err(|_| Error::new(ErrorKind::InvalidInput, "path contains a NUL byte"))
+    };
+
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(Error::new(ErrorKind::InvalidInput, "not a file path"));
+    };
+
+    let root = c_string(OsStr::new("/"))?;
+    let mut dir = Fd(unsafe { libc::open(root.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC) });
+    if dir.0 < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    for component in parent.components() {
+        let part = match component {
+            Component::RootDir => continue,
+            Component::Normal(part) => c_string(part)?,
+            _ => return Err(Error::new(ErrorKind::InvalidInput, "path is not canonical")),
+        };
+        let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+        let next = unsafe { libc::openat(dir.0, part.as_ptr(), flags) };
+        if next < 0 {
+            return Err(Error::last_os_error());
+        }
+        dir = Fd(next);
+    }
+
+    let name = c_string(name)?;
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstatat(dir.0, name.as_ptr(), &mut stat, libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+        return Err(Error::last_os_error());
+    }
+
+    // rmdir only ever removes an empty folder
+    let flags = if stat.st_mode & libc::S_IFMT == libc::S_IFDIR { libc::AT_REMOVEDIR } else { 0 };
+
+    if let Some(expected) = expected {
+        // The field types differ between platforms (i32 device numbers on macOS)
+        #[allow(clippy::unnecessary_cast)]
+        let found = FileIdentity {
+            device: stat.st_dev as u64,
+            inode: stat.st_ino as u64,
+        };
+        if found != expected {
+            return Err(Error::other("file was replaced since the scan"));
+        }
+    }
+
+    if unsafe { libc::unlinkat(dir.0, name.as_ptr(), flags) } != 0 {
+        return Err(Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn identity(path: &Path) -> Option<FileIdentity> {
+        FileIdentity::of(&std::fs::symlink_metadata(path).unwrap())
+    }
+
+    #[test]
+    fn unlinks_the_file_scanned() {
+        let dir = TempDir::new("unlink-file");
+        let file = dir.file("junk.tmp", b"junk");
+
+        unlink_verified(&file, identity(&file)).unwrap();
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn refuses_a_file_replaced_since_the_scan() {
+        let dir = TempDir::new("unlink-replaced");
+        let file = dir.file("junk.tmp", b"junk");
+        let scanned = identity(&file);
+        // Keeping the scanned file linked elsewhere stops its inode from being reused
+        std::fs::hard_link(&file, dir.path().join("kept.tmp")).unwrap();
+        let replacement = dir.file("replacement.tmp", b"important");
+        std::fs::rename(&replacement, &file).unwrap();
+
+        let error = unlink_verified(&file, scanned).unwrap_err();
+        assert_eq!(error.to_string(), "file was replaced since the scan");
+        assert_eq!(std::fs::read(&file).unwrap(), b"important");
+    }
+
+    #[test]
+    fn does_not_follow_a_folder_swapped_for_a_link() {
+        let dir = TempDir::new("unlink-swapped");
+        let outside = dir.file("documents/report.txt", b"important");
+        let junk = dir.file("junk/report.txt", b"junk");
+        let scanned = identity(&junk);
+        std::fs::remove_dir_all(dir.path().join("junk")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("documents"), dir.path().join("junk")).unwrap();
+
+        assert!(unlink_verified(&junk, scanned).is_err());
+        assert!(unlink_verified(&junk, None).is_err());
+        assert!(outside.exists());
+    }
+
+    #[test]
+    fn unlinks_a_link_and_not_its_target() {
+        let dir = TempDir::new("unlink-link");
+        let target = dir.file("report.txt", b"important");
+        let link = dir.path().join("link.txt");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        unlink_verified(&link, identity(&link)).unwrap();
+        assert!(std::fs::symlink_metadata(&link).is_err());
+        assert!(target.exists());
+    }
+
+    #[test]
+    fn removes_only_empty_folders() {
+        let dir = TempDir::new("unlink-folders");
+        let empty = dir.dir("empty");
+        dir.file("full/file.txt", b"data");
+
+        unlink_verified(&empty, identity(&empty)).unwrap();
+        assert!(!empty.exists());
+        assert!(unlink_verified(&dir.path().join("full"), None).is_err());
+    }
+
+    #[test]
+    fn refuses_paths_that_are_not_canonical() {
+        let dir = TempDir::new("unlink-relative");
+        let file = dir.file("junk.tmp", b"junk");
+        dir.dir("sub");
+
+        let error = unlink_verified(&dir.path().join("sub/../junk.tmp"), None).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(file.exists());
+    }
+}
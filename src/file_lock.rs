@@ -0,0 +1,112 @@
+//! Files that resist deletion whatever is ticked: read-only, immutable or
+//! append-only (`chattr +i`, `chattr +a`) and Windows system files. They are
+//! found during the scan, so the results can say so and leave them unticked
+//! instead of failing on them at cleanup.
+
+use std::fs::Metadata;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FileLock {
+    /// A read-only file, or on Unix a file in a folder that cannot be written,
+    /// which is what removing it takes
+    ReadOnly,
+    /// Marked immutable, which not even root can remove without clearing it first
+    Immutable,
+    /// Marked append-only, which blocks removal like immutable does
+    AppendOnly,
+    /// A Windows system file
+    System,
+}
+
+impl FileLock {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::ReadOnly => "read-only",
+            Self::Immutable => "immutable",
+            Self::AppendOnly => "append-only",
+            Self::System => "system file",
+        }
+    }
+}
+
+/// What keeps the scanned file at `path` from being removed, if anything.
+/// `metadata` is the one the scan already read.
+pub fn lock_of(path: &Path, metadata: &Metadata) -> Option<FileLock> {
+    flag_lock(path, metadata).or_else(|| read_only(path, metadata).then_some(FileLock::ReadOnly))
+}
+
+/// The immutable and append-only attributes, which `statx` reports without
+/// opening the file.
+#[cfg(all(target_os = "linux", any(target_env = "gnu", target_env = "musl")))]
+fn flag_lock(path: &Path, _metadata: &Metadata) -> Option<FileLock> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statx = unsafe { std::mem::zeroed() };
+    let flags = libc::AT_SYMLINK_NOFOLLOW | libc::AT_STATX_DONT_SYNC;
+    if unsafe { libc::statx(libc::AT_FDCWD, c_path.as_ptr(), flags, 0, &mut stat) } != 0 {
+        return None;
+    }
+    let attributes = stat.stx_attributes & stat.stx_attributes_mask;
+    if attributes & libc::STATX_ATTR_IMMUTABLE as u64 != 0 {
+        Some(FileLock::Immutable)
+    } else if attributes & libc::STATX_ATTR_APPEND as u64 != 0 {
+        Some(FileLock::AppendOnly)
+    } else {
+        None
+    }
+}
+
+/// The user and system immutable and append-only flags of `chflags`.
+#[cfg(target_os = "macos")]
+fn flag_lock(_path: &Path, metadata: &Metadata) -> Option<FileLock> {
+    use std::os::macos::fs::MetadataExt;
+
+    let flags = metadata.st_flags();
+    if flags & (libc::UF_IMMUTABLE | libc::SF_IMMUTABLE) != 0 {
+        Some(FileLock::Immutable)
+    } else if flags & (libc::UF_APPEND | libc::SF_APPEND) != 0 {
+        Some(FileLock::AppendOnly)
+    } else {
+        None
+    }
+}
+
+#[cfg(windows)]
+fn flag_lock(_path: &Path, metadata: &Metadata) -> Option<FileLock> {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+    (metadata.file_attributes() & FILE_ATTRIBUTE_SYSTEM != 0).then_some(FileLock::System)
+}
+
+#[cfg(not(any(
+    all(target_os = "linux", any(target_env = "gnu", target_env = "musl")),
+    target_os = "macos",
+    windows,
+)))]
+fn flag_lock(_path: &Path, _metadata: &Metadata) -> Option<FileLock> {
+    None
+}
+
+/// The permission bits of a file say nothing about removing it on Unix, so
+/// its folder is checked for write access instead.
+#[cfg(unix)]
+fn read_only(path: &Path, _metadata: &Metadata) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let Some(parent) = path.parent() else {
+        return false;
+    };
+    CString::new(parent.as_os_str().as_bytes())
+        .is_ok_and(|c_parent| unsafe { libc::access(c_parent.as_ptr(), libc::W_OK | libc::X_OK) } != 0)
+}
+
+#[cfg(not(unix))]
+fn read_only(_path: &Path, metadata: &Metadata) -> bool {
+    metadata.permissions().readonly()
+}
@@ -0,0 +1,77 @@
+use std::sync::OnceLock;
+use chrono::{DateTime, Local, Locale, SecondsFormat, Utc};
+use serde::{Serialize, Deserialize};
+
+/// How dates are written in reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DateStyle {
+    /// The user's locale and time zone, for people reading the report
+    #[default]
+    Localized,
+    /// ISO 8601 in UTC, for scripts and spreadsheets
+    Iso8601,
+}
+
+impl DateStyle {
+    pub fn all() -> Vec<Self> {
+        vec![Self::Localized, Self::Iso8601]
+    }
+
+    pub fn format(&self, time: DateTime<Utc>) -> String {
+        match self {
+            Self::Localized => format_date(time),
+            Self::Iso8601 => time.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+/// The locale of the user session, e.g. `pt_BR`, or POSIX when it is unknown.
+pub fn locale() -> Locale {
+    static LOCALE: OnceLock<Locale> = OnceLock::new();
+    *LOCALE.get_or_init(|| {
+        sys_locale::get_locale()
+            .and_then(|name| {
+                // "pt-BR" or "pt_BR.UTF-8" both become "pt_BR"
+                let name = name.split('.').next().unwrap_or_default().replace('-', "_");
+                Locale::try_from(name.as_str()).ok()
+            })
+            .unwrap_or(Locale::POSIX)
+    })
+}
+
+/// Lowercase language code of the user session, e.g. `pt`, or `en` when it is unknown.
+pub fn language() -> &'static str {
+    static LANGUAGE: OnceLock<String> = OnceLock::new();
+    LANGUAGE.get_or_init(|| {
+        sys_locale::get_locale()
+            .and_then(|name| name.split(['-', '_', '.']).next().map(str::to_lowercase))
+            .filter(|language| !language.is_empty())
+            .unwrap_or_else(|| "en".to_string())
+    })
+}
+
+/// A date in the local time zone, written the way the user's locale writes it.
+pub fn format_date(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format_localized("%x %X", locale()).to_string()
+}
+
+/// A rough duration for estimates, e.g. `40 s`, `3 min` or `1 h 5 min`.
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..60 => format!("{} s", seconds.max(1)),
+        60..3600 => format!("{} min", seconds.div_ceil(60)),
+        _ => format!("{} h {} min", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+/// A size with decimal units and the locale's decimal separator, e.g. `1,50 GB`.
+pub fn format_size(bytes: u64) -> String {
+    let size = humansize::format_size(bytes, humansize::DECIMAL);
+    let separator = pure_rust_locales::locale_match!(locale() => LC_NUMERIC::DECIMAL_POINT);
+    if separator == "." {
+        size
+    } else {
+        size.replacen('.', separator, 1)
+    }
+}
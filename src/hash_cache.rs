@@ -0,0 +1,183 @@
+//! Content hashes shared by everything that reads files to compare them: the
+//! duplicate finder's partial and full hashes and the quarantine's checks.
+//! Each hash is kept with the size and modification time of the file it was
+//! computed from and is only reused while both still match, so a file is read
+//! once however many features look at it, in this session and the next ones.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+use dashmap::DashMap;
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+
+use crate::config;
+use crate::low_memory;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Bumped whenever the saved layout or the hashes themselves change.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashKind {
+    /// Hash of both ends of a file, see the duplicate finder's prefilter
+    Partial,
+    /// BLAKE3 of the whole content
+    Full,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedHashes {
+    size: u64,
+    modified: SystemTime,
+    partial: Option<String>,
+    full: Option<String>,
+}
+
+impl CachedHashes {
+    fn get(&self, kind: HashKind) -> Option<&String> {
+        match kind {
+            HashKind::Partial => self.partial.as_ref(),
+            HashKind::Full => self.full.as_ref(),
+        }
+    }
+
+    fn set(&mut self, kind: HashKind, hash: String) {
+        match kind {
+            HashKind::Partial => self.partial = Some(hash),
+            HashKind::Full => self.full = Some(hash),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedHashes {
+    version: u32,
+    entries: Vec<(PathBuf, CachedHashes)>,
+}
+
+/// Hashes by file, safe to read and fill from many threads at once.
+#[derive(Default)]
+pub struct HashCache {
+    entries: DashMap<PathBuf, CachedHashes>,
+    changed: AtomicBool,
+}
+
+static SHARED: OnceLock<HashCache> = OnceLock::new();
+
+/// The cache every subsystem goes through, loaded from disk on first use.
+/// In low-memory mode it stays empty and every hash is computed.
+pub fn shared() -> &'static HashCache {
+    SHARED.get_or_init(|| if low_memory::is_enabled() { HashCache::default() } else { HashCache::load() })
+}
+
+impl HashCache {
+    fn cache_path() -> Option<PathBuf> {
+        config::app_cache_dir().map(|dir| dir.join("hashes.bin"))
+    }
+
+    fn load() -> Self {
+        let Some(path) = Self::cache_path() else {
+            return Self::default();
+        };
+        let Ok(file) = File::open(&path) else {
+            return Self::default();
+        };
+
+        let result = zstd::Decoder::new(BufReader::new(file))
+            .map_err(|e| e.into())
+            .and_then(bincode::deserialize_from::<_, SavedHashes>);
+
+        match result {
+            Ok(saved) if saved.version == FORMAT_VERSION => {
+                info!("Loaded {} cached file hashes", saved.entries.len());
+                Self {
+                    entries: saved.entries.into_iter().collect(),
+                    changed: AtomicBool::new(false),
+                }
+            }
+            Ok(_) => {
+                info!("Discarding file hashes saved in an older format");
+                Self::default()
+            }
+            Err(e) => {
+                warn!("Discarding unreadable hash cache {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the cache to disk if anything was hashed since it was loaded,
+    /// leaving out the files that no longer exist.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if !self.changed.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let Some(path) = Self::cache_path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        self.entries.retain(|file, _| file.symlink_metadata().is_ok());
+        let saved = SavedHashes {
+            version: FORMAT_VERSION,
+            entries: self.entries.iter().map(|entry| (entry.key().clone(), entry.value().clone())).collect(),
+        };
+        let partial = path.with_extension("bin.partial");
+        let mut encoder = zstd::Encoder::new(BufWriter::new(File::create(&partial)?), COMPRESSION_LEVEL)?;
+        bincode::serialize_into(&mut encoder, &saved)?;
+        encoder.finish()?.flush()?;
+        std::fs::rename(&partial, &path)?;
+        info!("Saved {} file hashes", saved.entries.len());
+        Ok(())
+    }
+
+    /// The `kind` hash of `path`, from the cache while the file's size and
+    /// modification time are unchanged, otherwise from `compute`, whose
+    /// result is then cached.
+    pub fn get_or_compute<E>(
+        &self,
+        path: &Path,
+        kind: HashKind,
+        compute: impl FnOnce() -> Result<String, E>,
+    ) -> Result<String, E> {
+        if low_memory::is_enabled() {
+            return compute();
+        }
+        // Read before hashing, so a file changed while it is read is hashed again next time
+        let stamp = std::fs::metadata(path)
+            .ok()
+            .and_then(|metadata| Some((metadata.len(), metadata.modified().ok()?)));
+        let Some((size, modified)) = stamp else {
+            return compute();
+        };
+
+        if let Some(entry) = self.entries.get(path) {
+            if entry.size == size && entry.modified == modified {
+                if let Some(hash) = entry.get(kind) {
+                    return Ok(hash.clone());
+                }
+            }
+        }
+
+        let hash = compute()?;
+        let mut entry = self.entries.entry(path.to_path_buf()).or_insert_with(|| CachedHashes {
+            size,
+            modified,
+            partial: None,
+            full: None,
+        });
+        if entry.size != size || entry.modified != modified {
+            *entry = CachedHashes { size, modified, partial: None, full: None };
+        }
+        entry.set(kind, hash.clone());
+        self.changed.store(true, Ordering::Relaxed);
+        Ok(hash)
+    }
+}
@@ -0,0 +1,335 @@
+use std::fmt::Write;
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use log::{info, error};
+
+use crate::audit::{self, CleanupReport};
+use crate::cleaner::{Cleaner, FileCallback, FileOutcome};
+use crate::config::Config;
+use crate::file_identity::FileId;
+use crate::file_category::FileCategory;
+use crate::formatting;
+use crate::mounted_snapshot::SnapshotMapping;
+use crate::notifier::{self, NotificationEvent};
+use crate::progress::ProgressTracker;
+use crate::scanner::{Scanner, ScanResult};
+use crate::storage;
+
+/// Scans the folders in `paths` without opening a window, for scheduled runs from cron or the
+/// task scheduler. With `clean` set, the categories that are safe to delete are
+/// cleaned as well. The summary is logged, emailed when SMTP is configured and
+/// posted to the configured webhooks. With `snapshot_of`, the single path is a
+/// read-only snapshot of that folder, which is cleaned instead of the snapshot.
+pub fn run(
+    paths: &[PathBuf],
+    clean: bool,
+    note: &str,
+    deletion_log: Option<&Path>,
+    snapshot_of: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Config::load();
+    let result = scan_and_clean(&config, paths, clean, note, deletion_log, snapshot_of);
+    storage::prune(&config.storage);
+
+    if let Err(e) = &result {
+        let title = format!("Disk cleaner run failed on {}", describe(paths));
+        notifier::notify(&config.webhooks, NotificationEvent::Failure, &title, &e.to_string());
+    }
+    result
+}
+
+/// Enforces the retention rules of the configuration, for a daily scheduled
+/// run. A failing rule is reported and the others still run.
+pub fn run_retention() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Config::load();
+    if config.retention_rules.is_empty() {
+        info!("No retention rules configured");
+        return Ok(());
+    }
+
+    let mut summary = String::new();
+    let mut failed = 0;
+    for rule in &config.retention_rules {
+        match rule.enforce(&config, Arc::new(ProgressTracker::default())) {
+            Ok(report) => {
+                let _ = writeln!(
+                    summary,
+                    "{}: {} files, {} {}",
+                    rule.description(&config),
+                    report.files.len(),
+                    formatting::format_size(report.cleaned_bytes()),
+                    if report.moved_to_trash { "moved to the trash" } else { "freed" }
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                error!("Retention rule failed: {}", e);
+                let _ = writeln!(summary, "{}: failed, {}", rule.description(&config), e);
+            }
+        }
+    }
+    info!("{}", summary);
+
+    let event = if failed > 0 { NotificationEvent::Failure } else { NotificationEvent::CleanupComplete };
+    notifier::notify(&config.webhooks, event, "Retention rules enforced", &summary);
+    if let Some(smtp) = &config.email_reports {
+        if let Err(e) = notifier::send_email(smtp, "Disk cleaner retention report", &summary) {
+            error!("Failed to email report: {}", e);
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} retention rules failed", failed, config.retention_rules.len()).into());
+    }
+    Ok(())
+}
+
+/// Scans the folders in `paths` and prints what `--clean` would remove from
+/// them, without cleaning or notifying anyone.
+pub fn explain(paths: &[PathBuf]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Config::load();
+    info!("Explaining scheduled cleanup of {}", describe(paths));
+    let results = Scanner::new(config.clone()).scan(paths, Arc::new(ProgressTracker::default()))?;
+    print!("{}", explain_plan(&config, &results));
+    Ok(())
+}
+
+fn scan_and_clean(
+    config: &Config,
+    paths: &[PathBuf],
+    clean: bool,
+    note: &str,
+    deletion_log: Option<&Path>,
+    snapshot_of: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let progress = Arc::new(ProgressTracker::default());
+    let snapshot = match (snapshot_of, paths) {
+        (Some(live_root), [snapshot_root]) => Some(SnapshotMapping::new(snapshot_root, live_root)?),
+        (Some(_), _) => return Err("--snapshot-of takes a single snapshot to scan".into()),
+        (None, _) => None,
+    };
+
+    info!("Headless scan of {}", describe(paths));
+    let mut results = Scanner::new(config.clone()).scan(paths, progress.clone())?;
+    if snapshot.is_some() {
+        // Nothing in a read-only snapshot can be removed, the live files are checked when mapped
+        results.locked_files.clear();
+    }
+    notifier::notify(
+        &config.webhooks,
+        NotificationEvent::ScanComplete,
+        &format!("Scan of {} complete", describe(paths)),
+        &summary(config, paths, &results, None),
+    );
+
+    let cleanup = if clean {
+        Some(clean_safe_categories(config, paths, &results, note, deletion_log, snapshot.as_ref(), progress)?)
+    } else {
+        None
+    };
+
+    let summary = summary(config, paths, &results, cleanup.as_ref());
+    if cleanup.is_some() {
+        notifier::notify(
+            &config.webhooks,
+            NotificationEvent::CleanupComplete,
+            &format!("Cleanup of {} complete", describe(paths)),
+            &summary,
+        );
+    }
+    info!("{}", summary);
+
+    if let Some(smtp) = &config.email_reports {
+        let subject = format!("Disk cleaner report for {}", describe(paths));
+        if let Err(e) = notifier::send_email(smtp, &subject, &summary) {
+            error!("Failed to email report: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Files of the categories a scheduled `--clean` run deletes: those safe to
+/// delete and enabled, minus protected and excluded files and those cleaning
+/// would fail on.
+fn scheduled_cleanup_files(config: &Config, results: &ScanResult) -> Vec<(FileCategory, PathBuf)> {
+    FileCategory::all()
+        .into_iter()
+        .filter(|category| category.is_safe_to_delete() && config.is_category_enabled(*category))
+        .filter_map(|category| results.files_by_category.get(&category).map(|files| (category, files)))
+        .flat_map(|(category, files)| files.iter().map(move |file| (category, file.clone())))
+        .filter(|(_, file)| !config.is_path_protected(file) && !config.is_path_excluded(file))
+        .filter(|(_, file)| !results.locked_files.contains_key(&FileId::of(file)))
+        .collect()
+}
+
+/// Simulates a scheduled cleanup against the files as they are on disk now,
+/// so rules can be checked before they run unattended. Files gone since the
+/// scan are left out.
+pub fn explain_plan(config: &Config, results: &ScanResult) -> String {
+    const BIGGEST_SHOWN: usize = 10;
+
+    let mut by_category: Vec<(FileCategory, usize, u64)> = Vec::new();
+    let mut files: Vec<(PathBuf, u64)> = Vec::new();
+    for (category, file) in scheduled_cleanup_files(config, results) {
+        let Ok(metadata) = std::fs::symlink_metadata(&file) else {
+            continue;
+        };
+        // Removing an empty folder frees nothing worth counting
+        let file_size = if metadata.is_dir() { 0 } else { metadata.len() };
+        match by_category.last_mut() {
+            Some((last, count, size)) if *last == category => {
+                *count += 1;
+                *size += file_size;
+            }
+            _ => by_category.push((category, 1, file_size)),
+        }
+        files.push((file, file_size));
+    }
+
+    let total: u64 = files.iter().map(|(_, size)| size).sum();
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "A scheduled cleanup would currently {} {} files, {}",
+        if config.use_trash { "move to the trash" } else { "delete" },
+        files.len(),
+        formatting::format_size(total)
+    );
+    for (category, count, size) in &by_category {
+        let _ = writeln!(text, "  {}: {} files, {}", config.category_name(*category), count, formatting::format_size(*size));
+    }
+
+    files.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    if !files.is_empty() {
+        let _ = writeln!(text, "\nBiggest:");
+        for (file, size) in files.iter().take(BIGGEST_SHOWN) {
+            let _ = writeln!(text, "  {}  {}", formatting::format_size(*size), file.display());
+        }
+    }
+    text
+}
+
+fn clean_safe_categories(
+    config: &Config,
+    paths: &[PathBuf],
+    results: &ScanResult,
+    note: &str,
+    deletion_log: Option<&Path>,
+    snapshot: Option<&SnapshotMapping>,
+    progress: Arc<ProgressTracker>,
+) -> Result<CleanupReport, Box<dyn std::error::Error + Send + Sync>> {
+    let files: Vec<PathBuf> = scheduled_cleanup_files(config, results)
+        .into_iter()
+        .map(|(_, file)| file)
+        .collect();
+
+    // The snapshot itself is never cleaned, only the live files it maps to
+    let (files, identities, roots) = match snapshot {
+        Some(snapshot) => {
+            let (live_files, identities) = snapshot.live_files(&files);
+            (live_files, identities, vec![snapshot.live_root().to_path_buf()])
+        }
+        None => {
+            let identities = results.identities_of(&files);
+            (files, identities, paths.to_vec())
+        }
+    };
+
+    let mut cleaner = Cleaner::new(config.use_trash)
+        .with_quarantine(config.use_quarantine)
+        .with_checksum_manifest(config.checksum_manifest)
+        .with_threads(config.cleanup_threads)
+        .with_trash_fallback(config.trash_fallback)
+        .with_allowed_roots(&roots)
+        .with_expected_identities(identities)
+        .with_note(note);
+    if let Some(deletion_log) = deletion_log {
+        cleaner = cleaner.on_file_cleaned(deletion_logger(deletion_log)?);
+    }
+    let report = cleaner.clean_files(&files, progress)?;
+
+    if config.sign_cleanup_records && !report.files.is_empty() {
+        if let Err(e) = audit::write_signed_report(&report) {
+            error!("Failed to write signed cleanup record: {}", e);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Appends a JSON line per cleaned file to `path` as the cleanup goes, for
+/// asset databases and audit systems to mirror the deletions from.
+fn deletion_logger(path: &Path) -> std::io::Result<FileCallback> {
+    let log = Mutex::new(OpenOptions::new().create(true).append(true).open(path)?);
+    Ok(Arc::new(move |file: &Path, size: u64, outcome: FileOutcome| {
+        let mut line = serde_json::json!({
+            "time": chrono::Utc::now().to_rfc3339(),
+            "path": file,
+            "size": size,
+            "outcome": outcome.label(),
+        });
+        match outcome {
+            FileOutcome::Quarantined(to) => line["quarantined_to"] = serde_json::json!(to),
+            FileOutcome::Failed(e) => line["error"] = serde_json::json!(e.to_string()),
+            _ => {}
+        }
+        if let Err(e) = writeln!(log.lock().unwrap(), "{}", line) {
+            error!("Failed to log the cleanup of {}: {}", file.display(), e);
+        }
+    }))
+}
+
+fn summary(config: &Config, paths: &[PathBuf], results: &ScanResult, cleanup: Option<&CleanupReport>) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "Scanned {} in {:.1}s", describe(paths), results.scan_duration.as_secs_f64());
+    let _ = writeln!(text, "{} files, {}", results.total_files, formatting::format_size(results.total_size));
+    if results.roots.len() > 1 {
+        for root in &results.roots {
+            let _ = writeln!(text, "  {}: {} files, {}", root.path.display(), root.files, formatting::format_size(root.size));
+        }
+    }
+
+    if let Some(coverage) = &results.coverage {
+        let _ = writeln!(text, "{:.0}% of the used space on {} accounted for", coverage.percent(), coverage.mount_point.display());
+    }
+
+    let _ = writeln!(text);
+    for category in FileCategory::all() {
+        let count = results.files_by_category.get(&category).map_or(0, Vec::len);
+        if count == 0 {
+            continue;
+        }
+        let size = results.category_stats.get(&category).map_or(0, |stats| stats.size);
+        let _ = writeln!(text, "{}: {} files, {}", config.category_name(category), count, formatting::format_size(size));
+    }
+
+    if let Some(report) = cleanup {
+        let _ = writeln!(text);
+        let _ = writeln!(
+            text,
+            "Cleaned {} files, {} {}",
+            report.files.len(),
+            formatting::format_size(report.cleaned_bytes()),
+            if report.moved_to_trash { "moved to the trash" } else { "freed" }
+        );
+        let deleted = report.files.iter().filter(|file| file.deleted_permanently).count();
+        if report.moved_to_trash && deleted > 0 {
+            let _ = writeln!(text, "{} files the trash could not take were deleted permanently", deleted);
+        }
+        if let Some(note) = &report.note {
+            let _ = writeln!(text, "Note: {}", note);
+        }
+    }
+
+    text
+}
+
+fn describe(paths: &[PathBuf]) -> String {
+    paths.iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,172 @@
+//! Every cleanup, recorded in a SQLite database in the data directory: when it
+//! ran, each file it removed with its size, whether the files went to the
+//! trash, and what failed. Sessions are only ever added; the storage settings
+//! may drop the oldest ones.
+
+use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use log::info;
+use rusqlite::{params, Connection};
+
+use crate::audit::CleanupReport;
+use crate::config;
+
+const HISTORY_FILE_NAME: &str = "history.sqlite";
+
+/// One recorded cleanup.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: i64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub moved_to_trash: bool,
+    pub note: Option<String>,
+    pub files: usize,
+    pub bytes: u64,
+    pub failures: usize,
+}
+
+/// A file of a session: removed with its size, or not removed with the reason.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub error: Option<String>,
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    config::app_data_dir().map(|dir| dir.join(HISTORY_FILE_NAME))
+}
+
+fn open() -> Result<Connection, Box<dyn std::error::Error + Send + Sync>> {
+    let path = history_path().ok_or("no data directory")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let connection = Connection::open(&path)?;
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sessions (
+            id INTEGER PRIMARY KEY,
+            started_at TEXT NOT NULL,
+            finished_at TEXT NOT NULL,
+            moved_to_trash INTEGER NOT NULL,
+            note TEXT,
+            files INTEGER NOT NULL,
+            bytes INTEGER NOT NULL,
+            failures INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS entries (
+            session_id INTEGER NOT NULL REFERENCES sessions(id),
+            path TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            error TEXT
+        );
+        CREATE INDEX IF NOT EXISTS entries_by_session ON entries(session_id);",
+    )?;
+    Ok(connection)
+}
+
+/// Adds the cleanup of `report` to the history.
+pub fn record(report: &CleanupReport) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut connection = open()?;
+    let transaction = connection.transaction()?;
+    transaction.execute(
+        "INSERT INTO sessions (started_at, finished_at, moved_to_trash, note, files, bytes, failures)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            report.started_at.to_rfc3339(),
+            report.finished_at.to_rfc3339(),
+            report.moved_to_trash,
+            report.note,
+            report.files.len() as i64,
+            report.cleaned_bytes() as i64,
+            report.failed.len() as i64,
+        ],
+    )?;
+    let session = transaction.last_insert_rowid();
+    {
+        let mut insert = transaction.prepare("INSERT INTO entries (session_id, path, size, error) VALUES (?1, ?2, ?3, ?4)")?;
+        for file in &report.files {
+            insert.execute(params![session, file.path.to_string_lossy(), file.size as i64, None::<String>])?;
+        }
+        for (path, error) in &report.failed {
+            insert.execute(params![session, path.to_string_lossy(), 0, error.to_string()])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+/// The recorded cleanups, newest first.
+pub fn sessions() -> Result<Vec<Session>, Box<dyn std::error::Error + Send + Sync>> {
+    let connection = open()?;
+    let mut statement = connection.prepare(
+        "SELECT id, started_at, finished_at, moved_to_trash, note, files, bytes, failures
+         FROM sessions ORDER BY id DESC",
+    )?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, bool>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, i64>(5)?,
+            row.get::<_, i64>(6)?,
+            row.get::<_, i64>(7)?,
+        ))
+    })?;
+
+    let mut sessions = Vec::new();
+    for row in rows {
+        let (id, started_at, finished_at, moved_to_trash, note, files, bytes, failures) = row?;
+        let date = |text: &str| DateTime::parse_from_rfc3339(text).map(|date| date.with_timezone(&Utc));
+        sessions.push(Session {
+            id,
+            started_at: date(&started_at)?,
+            finished_at: date(&finished_at)?,
+            moved_to_trash,
+            note,
+            files: files as usize,
+            bytes: bytes as u64,
+            failures: failures as usize,
+        });
+    }
+    Ok(sessions)
+}
+
+/// The files of session `id`, removed ones first.
+pub fn entries(id: i64) -> Result<Vec<Entry>, Box<dyn std::error::Error + Send + Sync>> {
+    let connection = open()?;
+    let mut statement = connection.prepare(
+        "SELECT path, size, error FROM entries WHERE session_id = ?1 ORDER BY error IS NOT NULL, size DESC",
+    )?;
+    let entries = statement
+        .query_map([id], |row| {
+            Ok(Entry {
+                path: PathBuf::from(row.get::<_, String>(0)?),
+                size: row.get::<_, i64>(1)? as u64,
+                error: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(entries)
+}
+
+/// Drops all but the `keep` most recent sessions. Returns how many were dropped.
+pub fn prune(keep: usize) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    if !history_path().is_some_and(|path| path.exists()) {
+        return Ok(0);
+    }
+    let mut connection = open()?;
+    let transaction = connection.transaction()?;
+    let cutoff = "SELECT id FROM sessions ORDER BY id DESC LIMIT -1 OFFSET ?1";
+    transaction.execute(&format!("DELETE FROM entries WHERE session_id IN ({})", cutoff), [keep as i64])?;
+    let dropped = transaction.execute(&format!("DELETE FROM sessions WHERE id IN ({})", cutoff), [keep as i64])?;
+    transaction.commit()?;
+    if dropped > 0 {
+        connection.execute_batch("VACUUM")?;
+        info!("Dropped the {} oldest sessions from the cleanup history", dropped);
+    }
+    Ok(dropped)
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+
+use crate::config::Config;
+use crate::file_category::FileCategory;
+
+/// What a learned rule does to the files it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleAction {
+    Category(FileCategory),
+    Protect,
+}
+
+/// A rule generalized from a manual reassignment in the results, e.g. "treat
+/// `*.bak` under `~/projects` as protected". Applied to every later scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LearnedRule {
+    /// Lowercase extension without the dot
+    pub extension: String,
+    pub under: PathBuf,
+    pub action: RuleAction,
+}
+
+impl LearnedRule {
+    /// Generalizes a manual decision about `files` into a rule covering their
+    /// extension inside their closest common directory. Returns `None` when the
+    /// files do not share an extension, as the rule would then be too broad.
+    pub fn generalize(files: &[PathBuf], action: RuleAction) -> Option<Self> {
+        let extension = Self::extension(files.first()?)?;
+        if files.iter().any(|file| Self::extension(file).as_ref() != Some(&extension)) {
+            return None;
+        }
+
+        let mut under = files[0].parent()?.to_path_buf();
+        for file in &files[1..] {
+            while !file.starts_with(&under) {
+                under = under.parent()?.to_path_buf();
+            }
+        }
+
+        // A rule for the whole file system is never what was meant
+        under.parent()?;
+
+        Some(Self { extension, under, action })
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        path.starts_with(&self.under) && Self::extension(path).as_ref() == Some(&self.extension)
+    }
+
+    pub fn description(&self, config: &Config) -> String {
+        let action = match self.action {
+            RuleAction::Category(category) => format!("as {}", config.category_name(category)),
+            RuleAction::Protect => "as protected".to_string(),
+        };
+        format!("Treat *.{} under {} {}", self.extension, self.under.display(), action)
+    }
+
+    fn extension(path: &Path) -> Option<String> {
+        path.extension().map(|ext| ext.to_string_lossy().to_lowercase())
+    }
+}
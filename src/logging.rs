@@ -0,0 +1,208 @@
+//! Logging configured from the settings and changeable while the application
+//! runs: a global level, per-module overrides and an optional log file in the
+//! config directory, rotated once it grows past a size. `RUST_LOG`, when set,
+//! is applied on top of the settings, as before.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, RwLock};
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{Serialize, Deserialize};
+
+use crate::config;
+
+const LOG_FILE_NAME: &str = "intelligent-disk-cleaner.log";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogSettings {
+    pub level: LevelFilter,
+    /// Levels for single modules, e.g. `intelligent_disk_cleaner::scanner`, overriding `level`
+    #[serde(default)]
+    pub module_levels: Vec<(String, LevelFilter)>,
+    /// Also write the log to a file in the config directory
+    #[serde(default)]
+    pub log_to_file: bool,
+    /// The log file is rotated once it grows past this size
+    #[serde(default = "default_max_file_size_mb")]
+    pub max_file_size_mb: u64,
+    /// Rotated log files kept besides the current one
+    #[serde(default = "default_kept_files")]
+    pub kept_files: u32,
+}
+
+fn default_max_file_size_mb() -> u64 {
+    5
+}
+
+fn default_kept_files() -> u32 {
+    3
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Error,
+            module_levels: Vec::new(),
+            log_to_file: false,
+            max_file_size_mb: default_max_file_size_mb(),
+            kept_files: default_kept_files(),
+        }
+    }
+}
+
+pub fn log_dir() -> Option<PathBuf> {
+    config::app_config_dir().map(|dir| dir.join("logs"))
+}
+
+/// The log file being written, with its size so rotation needs no metadata calls.
+struct LogFile {
+    file: File,
+    size: u64,
+    max_size: u64,
+    kept_files: u32,
+}
+
+impl LogFile {
+    fn open(settings: &LogSettings) -> std::io::Result<Self> {
+        let dir = log_dir().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no config directory"))?;
+        std::fs::create_dir_all(&dir)?;
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE_NAME))?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            file,
+            size,
+            max_size: settings.max_file_size_mb.max(1) * 1024 * 1024,
+            kept_files: settings.kept_files,
+        })
+    }
+
+    fn write(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts `name.log.1` to `name.log.2` and so on, dropping the oldest,
+    /// then starts the current file over.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let Some(dir) = log_dir() else {
+            return Ok(());
+        };
+        let numbered = |n: u32| dir.join(format!("{}.{}", LOG_FILE_NAME, n));
+        let current = dir.join(LOG_FILE_NAME);
+        if self.kept_files == 0 {
+            self.file = File::create(&current)?;
+        } else {
+            let _ = std::fs::remove_file(numbered(self.kept_files));
+            for n in (1..self.kept_files).rev() {
+                let _ = std::fs::rename(numbered(n), numbered(n + 1));
+            }
+            std::fs::rename(&current, numbered(1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&current)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+struct Logger {
+    /// Filters records and writes them to stderr
+    stderr: RwLock<env_logger::Logger>,
+    file: Mutex<Option<LogFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.read().is_ok_and(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        let Ok(logger) = self.stderr.read() else {
+            return;
+        };
+        if !logger.matches(record) {
+            return;
+        }
+        logger.log(record);
+
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(log_file) = file.as_mut() {
+                let line = format!(
+                    "[{} {:<5} {}] {}\n",
+                    Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                    record.level(),
+                    record.target(),
+                    record.args()
+                );
+                if log_file.write(&line).is_err() {
+                    // Stop writing to a file that fails rather than failing on every line
+                    *file = None;
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(log_file) = file.as_mut() {
+                let _ = log_file.file.flush();
+            }
+        }
+    }
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+fn build_filter(settings: &LogSettings) -> env_logger::Logger {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(settings.level);
+    for (module, level) in &settings.module_levels {
+        builder.filter_module(module, *level);
+    }
+    builder.parse_env("RUST_LOG");
+    builder.build()
+}
+
+/// Installs the logger with the default settings, until [`apply`] is called
+/// with the saved ones. Call once at startup, before anything is logged.
+pub fn init() {
+    let logger = LOGGER.get_or_init(|| Logger {
+        stderr: RwLock::new(build_filter(&LogSettings::default())),
+        file: Mutex::new(None),
+    });
+    if log::set_logger(logger).is_ok() {
+        apply(&LogSettings::default());
+    }
+}
+
+/// Switches the running logger to `settings`.
+pub fn apply(settings: &LogSettings) {
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+
+    let filter = build_filter(settings);
+    log::set_max_level(filter.filter());
+    if let Ok(mut stderr) = logger.stderr.write() {
+        *stderr = filter;
+    }
+
+    if let Ok(mut file) = logger.file.lock() {
+        if !settings.log_to_file {
+            *file = None;
+        } else if let Some(log_file) = file.as_mut() {
+            log_file.max_size = settings.max_file_size_mb.max(1) * 1024 * 1024;
+            log_file.kept_files = settings.kept_files;
+        } else {
+            match LogFile::open(settings) {
+                Ok(log_file) => *file = Some(log_file),
+                Err(e) => eprintln!("Could not open the log file: {}", e),
+            }
+        }
+    }
+}
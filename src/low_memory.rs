@@ -0,0 +1,49 @@
+//! A profile for old computers with 2 to 4 GB of memory and 32-bit builds,
+//! which need cleaning the most: fewer files are worked on at once, hashes
+//! are not cached in memory and result lists show fewer files per page.
+//! It is chosen at startup, as the worker threads are started only once.
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use log::info;
+use sysinfo::{MemoryRefreshKind, RefreshKind, System};
+
+use crate::config::Config;
+
+/// Worker threads for scanning, hashing and cleaning, each holding buffers
+/// and paths of its own.
+pub const THREADS: usize = 2;
+
+/// Files shown at once in the result lists, each row kept by the interface.
+pub const FILE_LIST_PAGE: usize = 200;
+
+/// Memory under which the profile is suggested in the settings.
+const LOW_MEMORY_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns the profile on for this run when the configuration asks for it or
+/// the build is 32-bit, whose address space is small whatever is installed.
+pub fn apply(config: &Config) {
+    if !config.low_memory && !cfg!(target_pointer_width = "32") {
+        return;
+    }
+    ENABLED.store(true, Ordering::Relaxed);
+    if let Err(e) = rayon::ThreadPoolBuilder::new().num_threads(THREADS).build_global() {
+        info!("Keeping the existing worker threads: {}", e);
+    }
+    info!("Low-memory mode on, {} worker threads", THREADS);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether the computer has little enough memory to be better off with the profile.
+pub fn is_recommended() -> bool {
+    static RECOMMENDED: OnceLock<bool> = OnceLock::new();
+    *RECOMMENDED.get_or_init(|| {
+        let system = System::new_with_specifics(RefreshKind::nothing().with_memory(MemoryRefreshKind::nothing().with_ram()));
+        system.total_memory() > 0 && system.total_memory() <= LOW_MEMORY_BYTES
+    })
+}
@@ -1,30 +1,151 @@
-use env_logger;
-use log::info;
+use log::{info, error};
 
+#[cfg(windows)]
+mod acl;
+mod analyzer;
 mod app;
+mod audit;
+mod bookmarks;
+mod browser_history;
+mod category_rules;
+mod chunk_overlap;
 mod scanner;
+mod secure_wipe;
+mod snapshot;
+mod storage;
+mod sync_folders;
+mod system_storage;
+mod dir_sizes;
+mod document_finder;
 mod duplicate_finder;
+mod error;
 mod cleaner;
 mod config;
+mod disk_activity;
+mod disk_health;
+mod disk_usage;
+mod drive_profile;
 mod file_category;
+mod file_identity;
+mod file_lock;
+mod formatting;
+mod hash_cache;
+mod headless;
+mod history;
+mod learned_rules;
+mod logging;
+mod low_memory;
+#[cfg(windows)]
+mod mft;
+mod mounted_snapshot;
+mod music_finder;
+mod notifier;
+mod orphan_temps;
+mod package_caches;
+mod photo_finder;
+mod power;
 mod progress;
+mod quarantine;
+mod recent_files;
+mod report;
+mod retention;
+#[cfg(test)]
+mod test_support;
+mod tui;
+mod unicode_names;
+mod version_finder;
+mod volumes;
+mod wine_prefixes;
+
+use std::path::PathBuf;
 
 use app::DiskCleanerApp;
 
 fn main() -> Result<(), eframe::Error> {
-    env_logger::init();
+    logging::init();
+    let config = config::Config::load();
+    logging::apply(&config.logging);
     info!("Starting Intelligent Disk Cleaner");
+    low_memory::apply(&config);
+
+    // `--headless <path>... [--clean | --explain] [--note <text>] [--log-deletions <file>] [--snapshot-of <folder>]` scans without a window, for scheduled runs
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--headless") {
+        let paths: Vec<PathBuf> = args.iter()
+            .skip(2)
+            .take_while(|arg| !arg.starts_with("--"))
+            .map(PathBuf::from)
+            .collect();
+        if paths.is_empty() {
+            eprintln!("Usage: intelligent-disk-cleaner --headless <path>... [--clean | --explain] [--note <text>] [--log-deletions <file>] [--snapshot-of <folder>]");
+            std::process::exit(2);
+        }
+        if args.iter().skip(2).any(|arg| arg == "--explain") {
+            if let Err(e) = headless::explain(&paths) {
+                error!("Explaining the scheduled cleanup failed: {}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        let clean = args.iter().skip(2).any(|arg| arg == "--clean");
+        let note = args.iter()
+            .skip(2)
+            .skip_while(|arg| *arg != "--note")
+            .nth(1)
+            .map(String::as_str)
+            .unwrap_or_default();
+        let deletion_log = args.iter()
+            .skip(2)
+            .skip_while(|arg| *arg != "--log-deletions")
+            .nth(1)
+            .map(PathBuf::from);
+        let snapshot_of = args.iter()
+            .skip(2)
+            .skip_while(|arg| *arg != "--snapshot-of")
+            .nth(1)
+            .map(PathBuf::from);
+        if let Err(e) = headless::run(&paths, clean, note, deletion_log.as_deref(), snapshot_of.as_deref()) {
+            error!("Headless run failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // `--retention` enforces the retention rules, for a daily scheduled run
+    if args.get(1).map(String::as_str) == Some("--retention") {
+        if let Err(e) = headless::run_retention() {
+            error!("Retention run failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // `--tui [path]...` runs the terminal interface, for servers and SSH sessions
+    if args.get(1).map(String::as_str) == Some("--tui") {
+        let mut paths: Vec<PathBuf> = args.iter().skip(2).map(PathBuf::from).collect();
+        if paths.is_empty() {
+            paths.push(dirs::home_dir().unwrap_or_else(|| PathBuf::from("/")));
+        }
+        if let Err(e) = tui::run(paths) {
+            error!("Terminal interface failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // A single file argument opens the app with its analysis card
+    let initial_file = std::env::args_os().nth(1).map(PathBuf::from);
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1200.0, 800.0])
-            .with_min_inner_size([800.0, 600.0]),
+            .with_inner_size(app::WINDOW_SIZE)
+            .with_min_inner_size(app::MIN_WINDOW_SIZE),
         ..Default::default()
     };
 
     eframe::run_native(
         "Intelligent Disk Cleaner",
         options,
-        Box::new(|_cc| Ok(Box::new(DiskCleanerApp::new()))),
+        Box::new(|_cc| Ok(Box::new(DiskCleanerApp::new(initial_file)))),
     )
 }
@@ -0,0 +1,252 @@
+//! Fast file enumeration for NTFS volumes.
+//!
+//! Instead of walking directories, the master file table is read in bulk through
+//! `FSCTL_ENUM_USN_DATA` and paths are rebuilt from the parent references. This
+//! needs administrator rights and an NTFS volume; callers fall back to a regular
+//! directory walk on any error.
+
+use std::collections::HashMap;
+use std::ffi::{c_void, OsStr, OsString};
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Component, Path, PathBuf, Prefix};
+
+type Handle = *mut c_void;
+
+const INVALID_HANDLE_VALUE: Handle = -1isize as Handle;
+const GENERIC_READ: u32 = 0x8000_0000;
+const FILE_SHARE_READ: u32 = 0x0000_0001;
+const FILE_SHARE_WRITE: u32 = 0x0000_0002;
+const OPEN_EXISTING: u32 = 3;
+const FSCTL_ENUM_USN_DATA: u32 = 0x0009_00b3;
+const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x0000_0010;
+const ERROR_HANDLE_EOF: i32 = 38;
+
+/// Size of the fixed part of a `USN_RECORD_V2`, before the file name.
+const USN_RECORD_V2_HEADER: usize = 60;
+
+#[repr(C)]
+struct MftEnumDataV0 {
+    start_file_reference_number: u64,
+    low_usn: i64,
+    high_usn: i64,
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn CreateFileW(
+        file_name: *const u16,
+        desired_access: u32,
+        share_mode: u32,
+        security_attributes: *mut c_void,
+        creation_disposition: u32,
+        flags_and_attributes: u32,
+        template_file: Handle,
+    ) -> Handle;
+
+    fn DeviceIoControl(
+        device: Handle,
+        io_control_code: u32,
+        in_buffer: *const c_void,
+        in_buffer_size: u32,
+        out_buffer: *mut c_void,
+        out_buffer_size: u32,
+        bytes_returned: *mut u32,
+        overlapped: *mut c_void,
+    ) -> i32;
+
+    fn CloseHandle(handle: Handle) -> i32;
+}
+
+struct Volume(Handle);
+
+impl Drop for Volume {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+struct Record {
+    parent: u64,
+    name: OsString,
+    is_dir: bool,
+}
+
+/// Lists every regular file under `root` from the master file table of its volume.
+pub fn enumerate_files(root: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+    let drive = drive_letter(root).ok_or("scan path is not on a lettered drive")?;
+    let volume = open_volume(drive)?;
+    let records = read_records(&volume)?;
+
+    let volume_root = PathBuf::from(format!("{}:\\", drive));
+    let mut dir_cache: HashMap<u64, PathBuf> = HashMap::new();
+
+    let files = records
+        .values()
+        .filter(|record| !record.is_dir)
+        .map(|record| resolve_dir(record.parent, &records, &volume_root, &mut dir_cache).join(&record.name))
+        .filter(|path| path.starts_with(root))
+        .collect();
+
+    Ok(files)
+}
+
+fn drive_letter(path: &Path) -> Option<char> {
+    match path.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(letter) => Some(letter as char),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn open_volume(drive: char) -> Result<Volume, Box<dyn std::error::Error + Send + Sync>> {
+    let device: Vec<u16> = OsStr::new(&format!("\\\\.\\{}:", drive))
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    let handle = unsafe {
+        CreateFileW(
+            device.as_ptr(),
+            GENERIC_READ,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            std::ptr::null_mut(),
+            OPEN_EXISTING,
+            0,
+            std::ptr::null_mut(),
+        )
+    };
+
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(Volume(handle))
+}
+
+fn read_records(volume: &Volume) -> Result<HashMap<u64, Record>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut records = HashMap::new();
+    let mut input = MftEnumDataV0 {
+        start_file_reference_number: 0,
+        low_usn: 0,
+        high_usn: i64::MAX,
+    };
+
+    // u64 elements keep the output buffer 8-byte aligned as the API expects
+    let mut buffer = vec![0u64; 64 * 1024 / 8];
+
+    loop {
+        let mut returned = 0u32;
+        let ok = unsafe {
+            DeviceIoControl(
+                volume.0,
+                FSCTL_ENUM_USN_DATA,
+                &input as *const MftEnumDataV0 as *const c_void,
+                std::mem::size_of::<MftEnumDataV0>() as u32,
+                buffer.as_mut_ptr() as *mut c_void,
+                (buffer.len() * 8) as u32,
+                &mut returned,
+                std::ptr::null_mut(),
+            )
+        };
+
+        if ok == 0 {
+            let error = std::io::Error::last_os_error();
+            if error.raw_os_error() == Some(ERROR_HANDLE_EOF) {
+                break;
+            }
+            return Err(error.into());
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, returned as usize) };
+        if bytes.len() < 8 {
+            break;
+        }
+
+        // The output starts with the reference number to resume from
+        input.start_file_reference_number = read_u64(bytes, 0);
+
+        let mut offset = 8;
+        while offset + USN_RECORD_V2_HEADER <= bytes.len() {
+            let record = &bytes[offset..];
+            let length = read_u32(record, 0) as usize;
+            if length < USN_RECORD_V2_HEADER || length > record.len() {
+                break;
+            }
+
+            if read_u16(record, 4) == 2 {
+                let name_length = read_u16(record, 56) as usize;
+                let name_offset = read_u16(record, 58) as usize;
+
+                if name_offset + name_length <= length {
+                    let name: Vec<u16> = record[name_offset..name_offset + name_length]
+                        .chunks_exact(2)
+                        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                        .collect();
+
+                    records.insert(read_u64(record, 8), Record {
+                        parent: read_u64(record, 16),
+                        name: OsString::from_wide(&name),
+                        is_dir: read_u32(record, 52) & FILE_ATTRIBUTE_DIRECTORY != 0,
+                    });
+                }
+            }
+
+            offset += length;
+        }
+    }
+
+    Ok(records)
+}
+
+/// Rebuilds the path of a directory from its parent chain. Chains that end at the
+/// root directory or at an unknown record are anchored at the volume root.
+fn resolve_dir(
+    reference: u64,
+    records: &HashMap<u64, Record>,
+    volume_root: &Path,
+    cache: &mut HashMap<u64, PathBuf>,
+) -> PathBuf {
+    let mut chain = Vec::new();
+    let mut current = reference;
+
+    let mut path = loop {
+        if let Some(path) = cache.get(&current) {
+            break path.clone();
+        }
+
+        match records.get(&current) {
+            Some(record) if record.parent != current && chain.len() < 1024 => {
+                chain.push(current);
+                current = record.parent;
+            }
+            _ => break volume_root.to_path_buf(),
+        }
+    };
+
+    for reference in chain.into_iter().rev() {
+        path.push(&records[&reference].name);
+        cache.insert(reference, path.clone());
+    }
+
+    path
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut value = [0u8; 4];
+    value.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(value)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(value)
+}
@@ -0,0 +1,172 @@
+//! Scanning a read-only snapshot of a folder instead of the folder itself,
+//! e.g. a btrfs or ZFS snapshot or an attached backup image: the analysis
+//! cannot touch live data, and its results are mapped back to the live folder
+//! for the cleanup. A live file is only cleaned while it still has the size
+//! and modification time of its copy in the snapshot.
+
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use log::{debug, info, warn};
+
+use crate::file_identity::{FileId, FileIdentity};
+use crate::file_lock;
+
+pub struct SnapshotMapping {
+    snapshot_root: PathBuf,
+    live_root: PathBuf,
+}
+
+impl SnapshotMapping {
+    /// Fails when the snapshot is writable, as scanning it would then offer
+    /// no more safety than scanning the live folder.
+    pub fn new(snapshot_root: &Path, live_root: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let snapshot_root = snapshot_root.canonicalize()
+            .map_err(|e| format!("cannot open the snapshot {}: {}", snapshot_root.display(), e))?;
+        let live_root = live_root.canonicalize()
+            .map_err(|e| format!("cannot open the live folder {}: {}", live_root.display(), e))?;
+        if snapshot_root.starts_with(&live_root) || live_root.starts_with(&snapshot_root) {
+            return Err("the snapshot and the live folder must not contain each other".into());
+        }
+        match is_read_only(&snapshot_root) {
+            Some(true) => {}
+            Some(false) => return Err(format!("{} is not read-only", snapshot_root.display()).into()),
+            None => warn!("Cannot tell whether {} is read-only, make sure it is a snapshot", snapshot_root.display()),
+        }
+        info!("Scanning snapshot {} of {}", snapshot_root.display(), live_root.display());
+        Ok(Self { snapshot_root, live_root })
+    }
+
+    pub fn live_root(&self) -> &Path {
+        &self.live_root
+    }
+
+    /// The live counterpart of `snapshot_file` with its metadata, unless it is
+    /// gone or changed since the snapshot, or cannot be removed.
+    pub fn live_path(&self, snapshot_file: &Path) -> Option<(PathBuf, Metadata)> {
+        let live_file = self.live_root.join(snapshot_file.strip_prefix(&self.snapshot_root).ok()?);
+        let snapshot = std::fs::symlink_metadata(snapshot_file).ok()?;
+        let live = std::fs::symlink_metadata(&live_file).ok()?;
+        if snapshot.is_dir() != live.is_dir() {
+            return None;
+        }
+        // A folder's time changes with its content, being a folder is enough
+        let unchanged = snapshot.is_dir() || (snapshot.len() == live.len() && snapshot.modified().ok()? == live.modified().ok()?);
+        (unchanged && file_lock::lock_of(&live_file, &live).is_none()).then_some((live_file, live))
+    }
+
+    /// The live counterparts of `snapshot_files`, leaving out those gone,
+    /// changed since the snapshot or locked, with their identities as checked, for the
+    /// cleaner to refuse a file swapped in after the check.
+    pub fn live_files(&self, snapshot_files: &[PathBuf]) -> (Vec<PathBuf>, HashMap<FileId, FileIdentity>) {
+        let mut live_files = Vec::new();
+        let mut identities = HashMap::new();
+        for (live_file, metadata) in snapshot_files.iter().filter_map(|file| self.live_path(file)) {
+            if let Some(identity) = FileIdentity::of(&metadata) {
+                identities.insert(FileId::of(&live_file), identity);
+            }
+            live_files.push(live_file);
+        }
+        let changed = snapshot_files.len() - live_files.len();
+        if changed > 0 {
+            info!("Left out {} files gone, changed or locked in {} since the snapshot", changed, self.live_root.display());
+        }
+        (live_files, identities)
+    }
+}
+
+/// Read-only mounts, read-only btrfs subvolumes (snapshots taken with `-r`,
+/// mounted read-write along with the rest of the filesystem), and otherwise
+/// whatever refuses a new file with `EROFS`.
+#[cfg(unix)]
+fn is_read_only(path: &Path) -> Option<bool> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } == 0 && stat.f_flag & libc::ST_RDONLY != 0 {
+        return Some(true);
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(read_only) = btrfs_subvolume_read_only(path) {
+        return Some(read_only);
+    }
+
+    let probe = path.join(format!("{}-probe-{}", crate::config::QUARANTINE_DIR_NAME, std::process::id()));
+    match std::fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Some(false)
+        }
+        Err(e) if e.raw_os_error() == Some(libc::EROFS) => Some(true),
+        Err(e) => {
+            debug!("Cannot create a file in {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// The read-only flag of the btrfs subvolume rooted at `path`, or None when
+/// `path` is not the root of a btrfs subvolume.
+#[cfg(target_os = "linux")]
+fn btrfs_subvolume_read_only(path: &Path) -> Option<bool> {
+    use std::os::fd::AsRawFd;
+
+    const BTRFS_IOCTL_MAGIC: u32 = 0x94;
+    const BTRFS_IOC_SUBVOL_GETFLAGS: libc::Ioctl = libc::_IOR::<u64>(BTRFS_IOCTL_MAGIC, 25);
+    const BTRFS_SUBVOL_RDONLY: u64 = 1 << 1;
+
+    let dir = std::fs::File::open(path).ok()?;
+    let mut flags: u64 = 0;
+    if unsafe { libc::ioctl(dir.as_raw_fd(), BTRFS_IOC_SUBVOL_GETFLAGS, &mut flags) } != 0 {
+        return None;
+    }
+    Some(flags & BTRFS_SUBVOL_RDONLY != 0)
+}
+
+/// Shadow copies are read-only by design, but Windows has no mount flag to
+/// check it on.
+#[cfg(not(unix))]
+fn is_read_only(_path: &Path) -> Option<bool> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::time::{Duration, SystemTime};
+
+    fn set_modified(file: &Path, modified: SystemTime) {
+        std::fs::File::options().write(true).open(file).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn live_path_leaves_out_changed_and_missing_files() {
+        let dir = TempDir::new("mounted-snapshot");
+        let mapping = SnapshotMapping { snapshot_root: dir.dir("snapshot"), live_root: dir.dir("live") };
+        let taken = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        for name in ["same.log", "resized.log", "touched.log", "gone.log"] {
+            set_modified(&dir.file(&format!("snapshot/{}", name), b"content"), taken);
+        }
+        set_modified(&dir.file("live/same.log", b"content"), taken);
+        set_modified(&dir.file("live/resized.log", b"longer content"), taken);
+        set_modified(&dir.file("live/touched.log", b"content"), taken + Duration::from_secs(60));
+
+        let live = |name: &str| mapping.live_path(&mapping.snapshot_root.join(name)).map(|(file, _)| file);
+        assert_eq!(live("same.log"), Some(mapping.live_root.join("same.log")));
+        assert_eq!(live("resized.log"), None);
+        assert_eq!(live("touched.log"), None);
+        assert_eq!(live("gone.log"), None);
+    }
+
+    #[test]
+    fn writable_snapshot_is_refused() {
+        let dir = TempDir::new("writable-snapshot");
+        let snapshot = dir.dir("snapshot");
+        assert!(SnapshotMapping::new(&snapshot, &dir.dir("live")).is_err());
+        assert_eq!(std::fs::read_dir(&snapshot).unwrap().count(), 0);
+    }
+}
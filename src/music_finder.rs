@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use log::{info, debug};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "m4a", "aac", "ogg", "oga", "wav"];
+
+/// Two rips of the same track rarely differ by more than this in length.
+const DURATION_TOLERANCE_SECS: f64 = 2.0;
+
+#[derive(Debug, Clone)]
+struct TrackInfo {
+    path: PathBuf,
+    artist: String,
+    title: String,
+    duration_secs: f64,
+    bitrate: u64,
+}
+
+/// Finds the same song ripped or downloaded several times, possibly at different
+/// bitrates or in different formats, by matching artist and title tags and the
+/// track duration.
+pub struct MusicFinder;
+
+impl MusicFinder {
+    /// Returns groups of copies of the same track, highest bitrate first.
+    pub fn find_duplicate_tracks(files: &[PathBuf]) -> Vec<Vec<PathBuf>> {
+        let mut by_song: HashMap<(String, String), Vec<TrackInfo>> = HashMap::new();
+
+        for file in files.iter().filter(|file| Self::is_audio(file)) {
+            if let Some(track) = Self::read_track(file) {
+                by_song.entry((track.artist.clone(), track.title.clone())).or_default().push(track);
+            }
+        }
+
+        let mut result = Vec::new();
+
+        for (_, mut tracks) in by_song {
+            if tracks.len() < 2 {
+                continue;
+            }
+
+            // Same tags but clearly different lengths are different recordings
+            // (live version, radio edit), so split them apart by duration.
+            tracks.sort_by(|a, b| a.duration_secs.total_cmp(&b.duration_secs));
+            let mut cluster: Vec<TrackInfo> = Vec::new();
+
+            for track in tracks {
+                if let Some(last) = cluster.last() {
+                    if track.duration_secs - last.duration_secs > DURATION_TOLERANCE_SECS {
+                        Self::push_group(&mut result, std::mem::take(&mut cluster));
+                    }
+                }
+                cluster.push(track);
+            }
+            Self::push_group(&mut result, cluster);
+        }
+
+        info!("Found {} songs with several copies", result.len());
+        result
+    }
+
+    fn push_group(result: &mut Vec<Vec<PathBuf>>, mut cluster: Vec<TrackInfo>) {
+        if cluster.len() > 1 {
+            cluster.sort_by_key(|track| std::cmp::Reverse(track.bitrate));
+            result.push(cluster.into_iter().map(|track| track.path).collect());
+        }
+    }
+
+    fn is_audio(path: &Path) -> bool {
+        path.extension()
+            .map(|ext| AUDIO_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+            .unwrap_or(false)
+    }
+
+    fn read_track(path: &Path) -> Option<TrackInfo> {
+        let file = File::open(path).ok()?;
+        let size = file.metadata().ok()?.len();
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension() {
+            hint.with_extension(&extension.to_string_lossy());
+        }
+
+        let mut probed = match symphonia::default::get_probe().format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        ) {
+            Ok(probed) => probed,
+            Err(e) => {
+                debug!("Could not read audio file {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        // Tags can live before the container (ID3) or inside it (Vorbis comments, MP4 atoms)
+        let mut artist = None;
+        let mut title = None;
+        let mut read_tags = |revision: &MetadataRevision| {
+            for tag in revision.tags() {
+                match tag.std_key {
+                    Some(StandardTagKey::Artist) => artist = Some(Self::normalize(&tag.value.to_string())),
+                    Some(StandardTagKey::TrackTitle) => title = Some(Self::normalize(&tag.value.to_string())),
+                    _ => {}
+                }
+            }
+        };
+
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|metadata| metadata.current()) {
+            read_tags(revision);
+        }
+        if let Some(revision) = probed.format.metadata().current() {
+            read_tags(revision);
+        }
+
+        let params = &probed.format.default_track()?.codec_params;
+        let duration_secs = match (params.time_base, params.n_frames) {
+            (Some(time_base), Some(frames)) => {
+                let time = time_base.calc_time(frames);
+                time.seconds as f64 + time.frac
+            }
+            _ => match (params.sample_rate, params.n_frames) {
+                (Some(rate), Some(frames)) if rate > 0 => frames as f64 / rate as f64,
+                _ => return None,
+            },
+        };
+
+        if duration_secs <= 0.0 {
+            return None;
+        }
+
+        let (artist, title) = (artist?, title?);
+        if artist.is_empty() || title.is_empty() {
+            return None;
+        }
+
+        Some(TrackInfo {
+            path: path.to_path_buf(),
+            artist,
+            title,
+            duration_secs,
+            bitrate: (size as f64 * 8.0 / duration_secs) as u64,
+        })
+    }
+
+    fn normalize(value: &str) -> String {
+        value
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
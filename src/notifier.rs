@@ -0,0 +1,153 @@
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use log::{info, error};
+use serde::{Serialize, Deserialize};
+
+/// Read instead of `password` when set, so the password can stay out of the config file.
+const SMTP_PASSWORD_VAR: &str = "DISK_CLEANER_SMTP_PASSWORD";
+
+/// A webhook that does not answer in this time is given up on.
+const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Where the report of a headless run is emailed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpSettings {
+    pub server: String,
+    pub port: u16,
+    /// Connect with TLS right away (usually port 465) instead of upgrading with STARTTLS
+    #[serde(default)]
+    pub implicit_tls: bool,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        Self {
+            server: String::new(),
+            port: 587,
+            implicit_tls: false,
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+            to: Vec::new(),
+        }
+    }
+}
+
+/// What happened, so a webhook can subscribe to only some events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotificationEvent {
+    ScanComplete,
+    CleanupComplete,
+    Failure,
+}
+
+/// Payload shape expected by the receiving end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WebhookFormat {
+    /// `{"event": ..., "title": ..., "text": ..., "host": ...}`
+    #[default]
+    Json,
+    Slack,
+    Discord,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// Events to post, all of them when empty
+    #[serde(default)]
+    pub events: Vec<NotificationEvent>,
+}
+
+impl Webhook {
+    fn wants(&self, event: NotificationEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+
+    fn payload(&self, event: NotificationEvent, title: &str, text: &str) -> serde_json::Value {
+        match self.format {
+            WebhookFormat::Json => serde_json::json!({
+                "event": event,
+                "title": title,
+                "text": text,
+                "host": host_name(),
+            }),
+            WebhookFormat::Slack => serde_json::json!({
+                "text": format!("*{}*\n```{}```", title, text),
+            }),
+            // Discord rejects messages longer than 2000 characters
+            WebhookFormat::Discord => serde_json::json!({
+                "content": truncate(&format!("**{}**\n```{}```", title, text), 2000),
+            }),
+        }
+    }
+}
+
+/// Posts the event to every webhook subscribed to it. Failures are logged, a
+/// notification never fails the operation it reports on.
+pub fn notify(webhooks: &[Webhook], event: NotificationEvent, title: &str, text: &str) {
+    for webhook in webhooks.iter().filter(|webhook| webhook.wants(event)) {
+        let payload = webhook.payload(event, title, text);
+        let result = ureq::post(&webhook.url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .send_json(payload);
+
+        match result {
+            Ok(_) => info!("Posted {:?} notification to webhook", event),
+            Err(e) => error!("Failed to post {:?} notification: {}", event, e),
+        }
+    }
+}
+
+fn host_name() -> String {
+    sysinfo::System::host_name().unwrap_or_default()
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 4).collect();
+    truncated.push_str("…```");
+    truncated
+}
+
+/// Sends a plain text email to every recipient in `settings`.
+pub fn send_email(settings: &SmtpSettings, subject: &str, body: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if settings.to.is_empty() {
+        return Err("no email recipients configured".into());
+    }
+
+    let mut message = Message::builder()
+        .from(settings.from.parse::<Mailbox>()?)
+        .subject(subject);
+    for recipient in &settings.to {
+        message = message.to(recipient.parse::<Mailbox>()?);
+    }
+    let message = message.body(body.to_string())?;
+
+    let builder = if settings.implicit_tls {
+        SmtpTransport::relay(&settings.server)?
+    } else {
+        SmtpTransport::starttls_relay(&settings.server)?
+    };
+    let mut builder = builder.port(settings.port);
+
+    if !settings.username.is_empty() {
+        let password = std::env::var(SMTP_PASSWORD_VAR).unwrap_or_else(|_| settings.password.clone());
+        builder = builder.credentials(Credentials::new(settings.username.clone(), password));
+    }
+
+    builder.build().send(&message)?;
+    info!("Emailed report to {}", settings.to.join(", "));
+    Ok(())
+}
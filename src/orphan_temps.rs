@@ -0,0 +1,167 @@
+//! Temporary files that name the process owning them: `tmpXXXX.<pid>` and
+//! `name.<pid>.tmp` from atomic writes, Vim swap files and Emacs lock files,
+//! plus unfinished browser downloads. A name prefix alone cannot tell a
+//! leftover from a file a running program still writes to, so these are only
+//! offered for deletion once their owner is gone.
+
+use std::fs::{File, Metadata};
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+/// How long an unfinished download goes unwritten before it counts as abandoned.
+const STALLED_DOWNLOAD_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Process start times are whole seconds, file times are not.
+const START_TIME_MARGIN: u64 = 2;
+
+/// Vim's swap header: `b0VIM <version>`, then the page size, modification
+/// time, inode and PID as little-endian 4-byte fields, the user and the host.
+const VIM_MAGIC: &[u8] = b"b0VIM ";
+const VIM_PID_OFFSET: usize = 24;
+const VIM_HOST_OFFSET: usize = 68;
+const VIM_HOST_SIZE: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Owner {
+    /// The owning process exited or the download stalled; the reason says which
+    Gone(&'static str),
+    /// Still written to by a running process
+    Active,
+}
+
+/// Who owns the temporary file at `path`, or `None` for files that do not
+/// name their owner. `metadata` is the one the scan already read, if any.
+pub fn owner(path: &Path, metadata: Option<&Metadata>) -> Option<Owner> {
+    let name = path.file_name()?.to_str()?;
+    let named_pid = pid_in_name(name);
+    let is_emacs_lock = name.len() > 2 && name.starts_with(".#");
+    if named_pid.is_none() && !is_emacs_lock && !is_vim_swap_name(name) && !is_partial_download_name(name) {
+        return None;
+    }
+    let modified = match metadata {
+        Some(metadata) => metadata.modified(),
+        None => std::fs::symlink_metadata(path).and_then(|metadata| metadata.modified()),
+    }.ok()?;
+
+    if let Some(pid) = named_pid {
+        return Some(owner_of(pid, modified, "Temporary file named after a process that has exited"));
+    }
+    if is_emacs_lock {
+        let (host, pid) = emacs_lock_owner(path)?;
+        return Some(owner_on(&host, pid, modified, "Emacs lock file left by an editor that is no longer running"));
+    }
+    if is_vim_swap_name(name) {
+        let (host, pid) = vim_swap_owner(path)?;
+        return Some(owner_on(&host, pid, modified, "Vim swap file left by an editor that is no longer running"));
+    }
+    if is_partial_download_name(name) {
+        let stalled = modified.elapsed().is_ok_and(|age| age > STALLED_DOWNLOAD_AGE);
+        return Some(if stalled {
+            Owner::Gone("Unfinished download not written to for over a week")
+        } else {
+            Owner::Active
+        });
+    }
+    None
+}
+
+/// The PID in `tmpXXXX.<pid>` or `name.<pid>.tmp`.
+fn pid_in_name(name: &str) -> Option<u32> {
+    let mut segments = name.rsplit('.');
+    let last = segments.next()?;
+    if let Some(pid) = parse_pid(last) {
+        let stem = &name[..name.len() - last.len() - 1];
+        let stem = stem.to_ascii_lowercase();
+        return (stem.contains("tmp") || stem.contains("temp")).then_some(pid);
+    }
+    if last.eq_ignore_ascii_case("tmp") || last.eq_ignore_ascii_case("temp") {
+        return segments.next().filter(|_| segments.next().is_some()).and_then(parse_pid);
+    }
+    None
+}
+
+/// PIDs go up to 4194304 on Linux; shorter runs of digits are more likely
+/// counters or dates than process IDs.
+fn parse_pid(segment: &str) -> Option<u32> {
+    if !(2..=7).contains(&segment.len()) || !segment.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    segment.parse().ok().filter(|&pid| pid > 1)
+}
+
+/// `.name.swp`, and `.swo`, `.swn`... when that one was taken.
+fn is_vim_swap_name(name: &str) -> bool {
+    name.starts_with('.') && name.len() > 5 && {
+        let extension = &name.as_bytes()[name.len() - 4..];
+        extension[0] == b'.' && extension[1] == b's' && extension[2] == b'w' && extension[3].is_ascii_lowercase()
+    }
+}
+
+fn is_partial_download_name(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    [".part", ".crdownload", ".partial", ".download"].iter().any(|suffix| lower.len() > suffix.len() && lower.ends_with(suffix))
+}
+
+/// Emacs locks are symbolic links, or files where links are unsupported, to
+/// `user@host.pid:boot`.
+fn emacs_lock_owner(path: &Path) -> Option<(String, u32)> {
+    let target = match std::fs::read_link(path) {
+        Ok(target) => target.to_string_lossy().to_string(),
+        Err(_) => {
+            let mut content = String::new();
+            File::open(path).ok()?.take(256).read_to_string(&mut content).ok()?;
+            content
+        }
+    };
+    let (_, host_and_pid) = target.split_once('@')?;
+    let host_and_pid = host_and_pid.split(':').next()?;
+    let (host, pid) = host_and_pid.rsplit_once('.')?;
+    Some((host.to_string(), pid.parse().ok()?))
+}
+
+fn vim_swap_owner(path: &Path) -> Option<(String, u32)> {
+    let mut header = [0u8; VIM_HOST_OFFSET + VIM_HOST_SIZE];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+    if !header.starts_with(VIM_MAGIC) {
+        return None;
+    }
+    let pid = u32::from_le_bytes(header[VIM_PID_OFFSET..VIM_PID_OFFSET + 4].try_into().ok()?);
+    let host = &header[VIM_HOST_OFFSET..];
+    let host = &host[..host.iter().position(|&byte| byte == 0).unwrap_or(host.len())];
+    Some((String::from_utf8_lossy(host).to_string(), pid))
+}
+
+/// A PID recorded on another computer, such as a file on a shared drive,
+/// says nothing about the processes here.
+fn owner_on(host: &str, pid: u32, modified: SystemTime, reason: &'static str) -> Owner {
+    if host.is_empty() || host.eq_ignore_ascii_case(local_host_name()) {
+        owner_of(pid, modified, reason)
+    } else {
+        Owner::Active
+    }
+}
+
+fn local_host_name() -> &'static str {
+    static HOST_NAME: OnceLock<String> = OnceLock::new();
+    HOST_NAME.get_or_init(|| System::host_name().unwrap_or_default())
+}
+
+/// Whether process `pid` can still be the one that wrote the file last
+/// modified at `modified`: a process started after that got a reused PID.
+fn owner_of(pid: u32, modified: SystemTime, reason: &'static str) -> Owner {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, ProcessRefreshKind::nothing());
+    let Some(process) = system.process(pid) else {
+        return Owner::Gone(reason);
+    };
+    let modified = modified.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    if process.start_time() > modified + START_TIME_MARGIN {
+        Owner::Gone(reason)
+    } else {
+        Owner::Active
+    }
+}
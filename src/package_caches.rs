@@ -0,0 +1,157 @@
+//! Download caches of package managers. Only the parts a manager fetches
+//! again on its own are recognized, such as cargo's downloaded crates;
+//! indexes, databases and locks next to them are kept whatever the category
+//! rules say, as deleting them breaks the manager or forces a slow rebuild.
+
+use std::path::Path;
+use std::sync::OnceLock;
+use aho_corasick::AhoCorasick;
+
+/// Folders of one package manager that only hold what it downloaded.
+struct PackageCache {
+    manager: &'static str,
+    /// Lowercase, with `/` standing for either separator
+    areas: &'static [&'static str],
+    /// Only files with one of these extensions count, any file when empty
+    extensions: &'static [&'static str],
+}
+
+const PACKAGE_CACHES: &[PackageCache] = &[
+    PackageCache {
+        manager: "cargo",
+        areas: &["/.cargo/registry/cache/", "/.cargo/registry/src/", "/.cargo/git/checkouts/"],
+        extensions: &[],
+    },
+    PackageCache {
+        manager: "pip",
+        areas: &["/.cache/pip/", "/appdata/local/pip/cache/", "/library/caches/pip/"],
+        extensions: &[],
+    },
+    PackageCache {
+        manager: "npm",
+        areas: &["/.npm/_cacache/", "/npm-cache/_cacache/"],
+        extensions: &[],
+    },
+    PackageCache {
+        manager: "yarn",
+        areas: &["/.cache/yarn/", "/appdata/local/yarn/cache/", "/library/caches/yarn/"],
+        extensions: &[],
+    },
+    PackageCache {
+        manager: "pnpm",
+        areas: &["/.cache/pnpm/", "/appdata/local/pnpm-cache/", "/library/caches/pnpm/"],
+        extensions: &[],
+    },
+    PackageCache {
+        manager: "apt",
+        areas: &["/var/cache/apt/archives/"],
+        extensions: &["deb"],
+    },
+    PackageCache {
+        manager: "dnf",
+        areas: &["/var/cache/dnf/", "/var/cache/yum/"],
+        extensions: &["rpm"],
+    },
+    PackageCache {
+        manager: "Homebrew",
+        areas: &["/library/caches/homebrew/", "/.cache/homebrew/"],
+        extensions: &[],
+    },
+    PackageCache {
+        manager: "Gradle",
+        areas: &["/.gradle/caches/modules-2/files-2.1/"],
+        extensions: &[],
+    },
+    PackageCache {
+        manager: "Go",
+        areas: &["/go/pkg/mod/cache/download/"],
+        extensions: &[],
+    },
+];
+
+/// Parts of package manager folders that are never cleaned, with `/`
+/// standing for either separator.
+const KEPT_AREAS: &[(&str, &str)] = &[
+    ("/.cargo/registry/index/", "cargo's registry index"),
+    ("/.cargo/git/db/", "cargo's git database"),
+    ("/var/cache/apt/archives/lock", "apt's lock"),
+    ("/var/cache/apt/archives/partial/", "apt's unfinished downloads"),
+    ("/var/lib/dpkg/", "dpkg's package database"),
+    ("/var/lib/rpm/", "the RPM database"),
+    ("/.npm/_cacache/index-v5/", "npm's cache index"),
+];
+
+/// What a pattern of the automaton stands for.
+#[derive(Clone, Copy)]
+enum Area {
+    Cache(&'static PackageCache),
+    Kept(&'static str),
+}
+
+/// Every area, with both separators, compiled once into a single automaton.
+fn matcher() -> &'static (AhoCorasick, Vec<Area>) {
+    static MATCHER: OnceLock<(AhoCorasick, Vec<Area>)> = OnceLock::new();
+    MATCHER.get_or_init(|| {
+        let mut needles = Vec::new();
+        let mut areas = Vec::new();
+        let mut add = |needle: &str, area: Area| {
+            needles.push(needle.to_string());
+            areas.push(area);
+            needles.push(needle.replace('/', "\\"));
+            areas.push(area);
+        };
+        for (needle, reason) in KEPT_AREAS {
+            add(needle, Area::Kept(reason));
+        }
+        for cache in PACKAGE_CACHES {
+            for needle in cache.areas {
+                add(needle, Area::Cache(cache));
+            }
+        }
+        let automaton = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(&needles)
+            .expect("package cache areas are valid");
+        (automaton, areas)
+    })
+}
+
+/// The areas found in `path`, in table order with the kept ones first. A
+/// separator is added at the end so a folder matches the areas it is the
+/// root of.
+fn areas_of(path: &Path) -> impl Iterator<Item = Area> {
+    let (automaton, areas) = matcher();
+    let mut bytes = path.as_os_str().as_encoded_bytes().to_vec();
+    bytes.push(std::path::MAIN_SEPARATOR as u8);
+    let mut found: Vec<usize> = automaton.find_overlapping_iter(&bytes)
+        .map(|m| m.pattern().as_usize())
+        .collect();
+    found.sort_unstable();
+    found.dedup();
+    found.into_iter().map(|index| areas[index])
+}
+
+/// The package manager whose download cache holds `path`, if any.
+pub fn manager_of(path: &Path) -> Option<&'static str> {
+    let extension = path.extension().map(|ext| ext.as_encoded_bytes()).unwrap_or_default();
+    let mut cache = None;
+    for area in areas_of(path) {
+        match area {
+            Area::Kept(_) => return None,
+            Area::Cache(found) => cache = cache.or(Some(found)),
+        }
+    }
+    cache
+        .filter(|cache| cache.extensions.is_empty()
+            || cache.extensions.iter().any(|wanted| extension.eq_ignore_ascii_case(wanted.as_bytes())))
+        .map(|cache| cache.manager)
+}
+
+/// Whether `path` is a part of a package manager's folders that must stay,
+/// and which.
+pub fn kept_reason(path: &Path) -> Option<&'static str> {
+    areas_of(path).find_map(|area| match area {
+        Area::Kept(reason) => Some(reason),
+        Area::Cache(_) => None,
+    })
+}
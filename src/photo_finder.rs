@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use log::{info, debug};
+use serde::{Serialize, Deserialize};
+
+use crate::unicode_names;
+
+const RAW_EXTENSIONS: &[&str] = &["dng", "cr2", "cr3", "nef", "arw", "orf", "rw2", "raf", "pef", "srw"];
+const JPEG_EXTENSIONS: &[&str] = &["jpg", "jpeg"];
+const HEIF_EXTENSIONS: &[&str] = &["heic", "heif"];
+const OTHER_PHOTO_EXTENSIONS: &[&str] = &["png", "tif", "tiff", "webp"];
+
+/// Which copy of a photo saved in several formats is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PhotoKeepPolicy {
+    #[default]
+    PreferRaw,
+    PreferJpeg,
+    PreferHeif,
+    Largest,
+}
+
+impl PhotoKeepPolicy {
+    pub fn all() -> Vec<Self> {
+        vec![Self::PreferRaw, Self::PreferJpeg, Self::PreferHeif, Self::Largest]
+    }
+}
+
+/// Groups the same photo saved in different formats: RAW+JPEG pairs, HEIC
+/// originals and their JPEG exports, and so on. Photos are matched on their EXIF
+/// capture time and camera, or on a shared file name in the same folder when
+/// they carry no EXIF data.
+pub struct PhotoFinder;
+
+impl PhotoFinder {
+    /// Returns groups of format copies, the copy to keep under `policy` first.
+    /// With `unify_names` set, names in either Unicode form match each other.
+    pub fn find_format_copies(files: &[PathBuf], policy: PhotoKeepPolicy, unify_names: bool) -> Vec<Vec<PathBuf>> {
+        let mut groups: HashMap<String, Vec<PathBuf>> = HashMap::new();
+
+        for file in files.iter().filter(|file| Self::is_photo(file)) {
+            let key = match Self::capture_key(file) {
+                Some(capture) => format!("exif:{}", capture),
+                None => match (file.parent(), file.file_stem()) {
+                    (Some(parent), Some(stem)) => {
+                        let key = format!("name:{}/{}", parent.display(), stem.to_string_lossy().to_lowercase());
+                        if unify_names {
+                            unicode_names::composed(&key).into_owned()
+                        } else {
+                            key
+                        }
+                    }
+                    _ => continue,
+                },
+            };
+            groups.entry(key).or_default().push(file.clone());
+        }
+
+        let mut result: Vec<Vec<PathBuf>> = groups
+            .into_values()
+            .filter(|group| group.len() > 1 && Self::format_count(group) == group.len())
+            .collect();
+        Self::apply_keep_policy(&mut result, policy);
+
+        info!("Found {} photos saved in several formats", result.len());
+        result
+    }
+
+    /// Puts the copy to keep under `policy` first in each group, without
+    /// reading the photos again.
+    pub fn apply_keep_policy(groups: &mut [Vec<PathBuf>], policy: PhotoKeepPolicy) {
+        for group in groups {
+            group.sort_by_cached_key(|file| std::cmp::Reverse(Self::keep_score(file, policy)));
+        }
+    }
+
+    fn is_photo(path: &Path) -> bool {
+        let extension = Self::extension(path);
+        [RAW_EXTENSIONS, JPEG_EXTENSIONS, HEIF_EXTENSIONS, OTHER_PHOTO_EXTENSIONS]
+            .iter()
+            .any(|list| list.contains(&extension.as_str()))
+    }
+
+    /// Capture time with sub-seconds plus camera model, when the file has EXIF data.
+    fn capture_key(path: &Path) -> Option<String> {
+        let file = File::open(path).ok()?;
+        let exif = match exif::Reader::new().read_from_container(&mut BufReader::new(file)) {
+            Ok(exif) => exif,
+            Err(e) => {
+                debug!("No EXIF data in {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        let field = |tag| {
+            exif.get_field(tag, exif::In::PRIMARY)
+                .map(|field| field.display_value().to_string())
+        };
+
+        let taken = field(exif::Tag::DateTimeOriginal)?;
+        let subsec = field(exif::Tag::SubSecTimeOriginal).unwrap_or_default();
+        let model = field(exif::Tag::Model).unwrap_or_default();
+
+        Some(format!("{}.{}|{}", taken, subsec, model))
+    }
+
+    /// Number of distinct formats in a group. Groups holding the same format twice
+    /// are burst shots taken within the same second and are left alone.
+    fn format_count(group: &[PathBuf]) -> usize {
+        let mut formats: Vec<&str> = group.iter().map(|file| Self::format_of(file)).collect();
+        formats.sort_unstable();
+        formats.dedup();
+        formats.len()
+    }
+
+    fn format_of(path: &Path) -> &'static str {
+        let extension = Self::extension(path);
+        if RAW_EXTENSIONS.contains(&extension.as_str()) {
+            "raw"
+        } else if JPEG_EXTENSIONS.contains(&extension.as_str()) {
+            "jpeg"
+        } else if HEIF_EXTENSIONS.contains(&extension.as_str()) {
+            "heif"
+        } else {
+            "other"
+        }
+    }
+
+    /// Higher is better. Ties between formats are broken by file size.
+    fn keep_score(path: &Path, policy: PhotoKeepPolicy) -> (u8, u64) {
+        let size = std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0);
+        let preferred = match policy {
+            PhotoKeepPolicy::PreferRaw => "raw",
+            PhotoKeepPolicy::PreferJpeg => "jpeg",
+            PhotoKeepPolicy::PreferHeif => "heif",
+            PhotoKeepPolicy::Largest => return (0, size),
+        };
+
+        (u8::from(Self::format_of(path) == preferred), size)
+    }
+
+    fn extension(path: &Path) -> String {
+        path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn keep_policy_reorders_found_groups() {
+        let dir = TempDir::new("photo-copies");
+        let raw = dir.file("IMG_0001.CR2", &[0; 300]);
+        let jpeg = dir.file("IMG_0001.jpg", &[0; 100]);
+        let heif = dir.file("IMG_0001.heic", &[0; 200]);
+        dir.file("IMG_0002.jpg", &[0; 100]);
+
+        let files = vec![jpeg.clone(), heif.clone(), raw.clone(), dir.path().join("IMG_0002.jpg")];
+        let mut groups = PhotoFinder::find_format_copies(&files, PhotoKeepPolicy::PreferRaw, false);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0][0], raw);
+
+        PhotoFinder::apply_keep_policy(&mut groups, PhotoKeepPolicy::PreferJpeg);
+        assert_eq!(groups[0][0], jpeg);
+        PhotoFinder::apply_keep_policy(&mut groups, PhotoKeepPolicy::PreferHeif);
+        assert_eq!(groups[0], vec![heif, raw, jpeg]);
+    }
+}
@@ -0,0 +1,46 @@
+//! Whether the computer runs on battery, so long disk work can be announced
+//! before it drains it.
+
+/// A Linux computer is on battery when it has a mains adapter and the adapter
+/// is offline. Desktops have no adapter entry and never count as on battery.
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> bool {
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let mut has_mains = false;
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        let read = |name: &str| std::fs::read_to_string(path.join(name)).map(|value| value.trim().to_string());
+        if read("type").is_ok_and(|kind| kind == "Mains") {
+            has_mains = true;
+            if read("online").is_ok_and(|online| online == "1") {
+                return false;
+            }
+        }
+    }
+    has_mains
+}
+
+/// `pmset` names the power source in its first line, e.g. `Now drawing from 'Battery Power'`.
+#[cfg(target_os = "macos")]
+pub fn on_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+}
+
+/// `BatteryStatus` 1 means discharging, with no charger plugged in.
+#[cfg(windows)]
+pub fn on_battery() -> bool {
+    std::process::Command::new("powershell")
+        .args(["-NoProfile", "-Command", "(Get-CimInstance Win32_Battery).BatteryStatus"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).lines().any(|line| line.trim() == "1"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn on_battery() -> bool {
+    false
+}
@@ -1,4 +1,14 @@
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use crate::audit::CleanupReport;
+use crate::cleaner::CommandOutput;
+use crate::duplicate_finder::Duplicates;
+use crate::error::Error;
+use crate::formatting;
 use crate::scanner::ScanResult;
 
 #[derive(Debug, Clone)]
@@ -6,80 +16,345 @@ pub enum ProgressState {
     Idle,
     Scanning {
         current_path: String,
+        /// Files found so far while they are listed, then files categorized
         files_processed: usize,
+        /// Files to categorize, `None` while they are still being listed
+        total_files: Option<usize>,
+        /// Size of the files categorized so far
+        bytes_processed: u64,
+        /// When the current phase, listing or categorizing, started
+        started: Instant,
     },
     FindingDuplicates {
         files_processed: usize,
         total_files: usize,
+        /// Bytes read out of those the current pass has to read
+        bytes_processed: u64,
+        total_bytes: u64,
+        /// Read speed on each network share being hashed
+        shares: Vec<ShareThroughput>,
+        /// When the current pass started
+        started: Instant,
     },
     Cleaning {
         files_processed: usize,
         total_files: usize,
+        /// Size of the files handled so far, removed or not
+        bytes_processed: u64,
+        total_bytes: u64,
+        /// Space freed by the files removed so far
+        freed_bytes: u64,
+        started: Instant,
     },
     Complete {
-        scan_result: Option<ScanResult>,
-        duplicates: Option<Vec<Vec<PathBuf>>>,
+        scan_result: Option<Box<ScanResult>>,
+        duplicates: Option<Duplicates>,
         cleaned_bytes: Option<u64>,
+        /// Files the cleanup could not remove, with the reason
+        failed_files: Vec<(PathBuf, Error)>,
+        command_outputs: Vec<CommandOutput>,
+        /// Files the cleanup removed
+        cleaned_files: usize,
+        /// Files a cancelled cleanup left in place, `None` when it ran to the end
+        files_left: Option<usize>,
+    },
+    Error {
+        /// What failed, e.g. "Scan"
+        action: &'static str,
+        error: Error,
     },
-    Error(String),
 }
 
+/// Time a task must have run before its rate is shown, earlier ones swing too much.
+const MIN_RATE_ELAPSED: Duration = Duration::from_millis(500);
+
+impl ProgressState {
+    fn started_and_counts(&self) -> Option<(Instant, usize, u64)> {
+        match self {
+            Self::Scanning { started, files_processed, bytes_processed, .. }
+            | Self::FindingDuplicates { started, files_processed, bytes_processed, .. }
+            | Self::Cleaning { started, files_processed, bytes_processed, .. } => {
+                Some((*started, *files_processed, *bytes_processed))
+            }
+            _ => None,
+        }
+    }
+
+    /// Bytes and files handled per second since the current phase started.
+    pub fn throughput(&self) -> Option<(u64, u64)> {
+        let (started, files, bytes) = self.started_and_counts()?;
+        let elapsed = started.elapsed();
+        if elapsed < MIN_RATE_ELAPSED {
+            return None;
+        }
+        let seconds = elapsed.as_secs_f64();
+        Some(((bytes as f64 / seconds) as u64, (files as f64 / seconds) as u64))
+    }
+
+    /// Time left at the pace kept so far, `None` until there is a pace to go by.
+    pub fn remaining(&self) -> Option<Duration> {
+        let (started, _, _) = self.started_and_counts()?;
+        let fraction = f64::from(self.fraction()?);
+        let elapsed = started.elapsed();
+        if elapsed < MIN_RATE_ELAPSED || fraction <= 0.0 {
+            return None;
+        }
+        Some(elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+
+    /// Rate and time left as a line for the progress display, e.g. `2.3 GB/s, ~40 s remaining`.
+    pub fn rate_summary(&self) -> Option<String> {
+        let (bytes_per_second, files_per_second) = self.throughput()?;
+        let mut summary = if bytes_per_second > 0 {
+            format!("{}/s", formatting::format_size(bytes_per_second))
+        } else {
+            format!("{} files/s", files_per_second)
+        };
+        if let Some(remaining) = self.remaining() {
+            summary.push_str(&format!(", ~{} remaining", formatting::format_duration(remaining)));
+        }
+        Some(summary)
+    }
+
+    /// Share of the work done, weighted by bytes so a single huge file counts
+    /// for what it takes, and by files when there are no bytes to weigh.
+    /// `None` when the total is unknown.
+    pub fn fraction(&self) -> Option<f32> {
+        if let Self::Scanning { files_processed, total_files, .. } = self {
+            return total_files
+                .filter(|total| *total > 0)
+                .map(|total| (*files_processed as f64 / total as f64).min(1.0) as f32);
+        }
+        let (files_processed, total_files, bytes_processed, total_bytes) = match self {
+            Self::FindingDuplicates { files_processed, total_files, bytes_processed, total_bytes, .. }
+            | Self::Cleaning { files_processed, total_files, bytes_processed, total_bytes, .. } => {
+                (*files_processed, *total_files, *bytes_processed, *total_bytes)
+            }
+            _ => return None,
+        };
+        if total_bytes > 0 {
+            Some((bytes_processed as f64 / total_bytes as f64).min(1.0) as f32)
+        } else if total_files > 0 {
+            Some((files_processed as f64 / total_files as f64).min(1.0) as f32)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShareThroughput {
+    pub mount_point: PathBuf,
+    pub bytes_per_second: u64,
+}
+
+/// Minimum time between two intermediate progress updates published by workers.
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Identifies a background task among those of a [`ProgressHub`].
+pub type TaskId = u64;
+
+/// A state a task reported. Complete and error states end the task.
 #[derive(Debug)]
-pub struct ProgressTracker {
+pub struct ProgressEvent {
+    pub task: TaskId,
     pub state: ProgressState,
 }
 
+/// Handed to the worker of one background task, which reports its progress
+/// through it and checks it for cancellation.
+///
+/// Reports are sent as events to the [`ProgressHub`] that started the task, so
+/// workers never share state with the UI. Intermediate updates are rate
+/// limited while final states are always sent. A tracker made with
+/// `default()` belongs to no hub and drops its reports, for runs without an
+/// interface.
+#[derive(Debug)]
+pub struct ProgressTracker {
+    task: TaskId,
+    events: Option<Sender<ProgressEvent>>,
+    created: Instant,
+    last_publish_ms: AtomicU64,
+    cancel_requested: AtomicBool,
+}
+
 impl Default for ProgressTracker {
     fn default() -> Self {
-        Self {
-            state: ProgressState::Idle,
-        }
+        Self::new(0, None)
     }
 }
 
 impl ProgressTracker {
-    pub fn new() -> Self {
-        Self::default()
+    fn new(task: TaskId, events: Option<Sender<ProgressEvent>>) -> Self {
+        Self {
+            task,
+            events,
+            created: Instant::now(),
+            last_publish_ms: AtomicU64::new(0),
+            cancel_requested: AtomicBool::new(false),
+        }
     }
 
-    pub fn reset(&mut self) {
-        self.state = ProgressState::Idle;
+    pub fn set_state(&self, state: ProgressState) {
+        if let Some(events) = &self.events {
+            // The hub is gone once the interface closed, nobody is left to tell
+            let _ = events.send(ProgressEvent { task: self.task, state });
+        }
     }
 
-    pub fn set_scan_complete(&mut self, result: ScanResult) {
-        self.state = ProgressState::Complete {
-            scan_result: Some(result),
+    /// Publishes an intermediate update unless one was published less than
+    /// [`PUBLISH_INTERVAL`] ago. Pass `force` for the final update of a phase.
+    pub fn update(&self, state: ProgressState, force: bool) {
+        let now_ms = self.created.elapsed().as_millis() as u64;
+        let last_ms = self.last_publish_ms.load(Ordering::Relaxed);
+
+        if force {
+            self.last_publish_ms.store(now_ms, Ordering::Relaxed);
+        } else if now_ms.saturating_sub(last_ms) < PUBLISH_INTERVAL.as_millis() as u64
+            || self.last_publish_ms
+                .compare_exchange(last_ms, now_ms, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            // Too soon, or another worker published in the meantime
+            return;
+        }
+
+        self.set_state(state);
+    }
+
+    /// Asks the task to stop at the next safe point.
+    pub fn request_cancel(&self) {
+        self.cancel_requested.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_requested.load(Ordering::Relaxed)
+    }
+
+    pub fn set_scan_complete(&self, result: ScanResult) {
+        self.set_state(ProgressState::Complete {
+            scan_result: Some(Box::new(result)),
             duplicates: None,
             cleaned_bytes: None,
-        };
+            failed_files: Vec::new(),
+            command_outputs: Vec::new(),
+            cleaned_files: 0,
+            files_left: None,
+        });
     }
 
-    pub fn set_duplicates_complete(&mut self, duplicates: Vec<Vec<PathBuf>>) {
-        self.state = ProgressState::Complete {
+    pub fn set_duplicates_complete(&self, duplicates: Duplicates) {
+        self.set_state(ProgressState::Complete {
             scan_result: None,
             duplicates: Some(duplicates),
             cleaned_bytes: None,
-        };
+            failed_files: Vec::new(),
+            command_outputs: Vec::new(),
+            cleaned_files: 0,
+            files_left: None,
+        });
     }
 
-    pub fn set_cleanup_complete(&mut self, cleaned_bytes: u64) {
-        self.state = ProgressState::Complete {
+    /// A cancelled cleanup completes too, with the files removed before it stopped.
+    pub fn set_cleanup_complete(&self, report: CleanupReport, command_outputs: Vec<CommandOutput>) {
+        self.set_state(ProgressState::Complete {
             scan_result: None,
             duplicates: None,
-            cleaned_bytes: Some(cleaned_bytes),
-        };
+            cleaned_bytes: Some(report.cleaned_bytes()),
+            failed_files: report.failed,
+            command_outputs,
+            cleaned_files: report.files.len(),
+            files_left: report.cancelled.then_some(report.files_left),
+        });
     }
 
-    pub fn set_error(&mut self, error: String) {
-        self.state = ProgressState::Error(error);
+    pub fn set_error(&self, action: &'static str, error: Error) {
+        self.set_state(ProgressState::Error { action, error });
+    }
+}
+
+/// A task still running, with the last state it reported.
+#[derive(Debug)]
+pub struct RunningTask {
+    pub tracker: Arc<ProgressTracker>,
+    pub state: ProgressState,
+}
+
+/// The UI's end of the progress events: starts a tracker per background task
+/// and keeps the latest state of each running one, so several tasks can run
+/// and be shown at once.
+#[derive(Debug)]
+pub struct ProgressHub {
+    sender: Sender<ProgressEvent>,
+    receiver: Receiver<ProgressEvent>,
+    next_task: TaskId,
+    /// In the order they were started
+    tasks: Vec<RunningTask>,
+}
+
+impl Default for ProgressHub {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            next_task: 1,
+            tasks: Vec::new(),
+        }
+    }
+}
+
+impl ProgressHub {
+    /// A tracker for a new task, which counts as running until it sends a
+    /// complete or error state, or its worker drops the tracker.
+    pub fn start_task(&mut self) -> Arc<ProgressTracker> {
+        let tracker = Arc::new(ProgressTracker::new(self.next_task, Some(self.sender.clone())));
+        self.next_task += 1;
+        self.tasks.push(RunningTask {
+            tracker: Arc::clone(&tracker),
+            state: ProgressState::Idle,
+        });
+        tracker
+    }
+
+    /// Applies the events sent since the last call and returns the final
+    /// states of the tasks that ended, in the order they ended. Their payload
+    /// is moved out of the event, never copied.
+    pub fn poll(&mut self) -> Vec<ProgressState> {
+        let mut finished = Vec::new();
+        while let Ok(event) = self.receiver.try_recv() {
+            let position = self.tasks.iter().position(|task| task.tracker.task == event.task);
+            match event.state {
+                ProgressState::Complete { .. } | ProgressState::Error { .. } => {
+                    if let Some(position) = position {
+                        self.tasks.remove(position);
+                    }
+                    finished.push(event.state);
+                }
+                state => {
+                    if let Some(position) = position {
+                        self.tasks[position].state = state;
+                    }
+                }
+            }
+        }
+        // Workers that ended without a final state, like dry runs, or that panicked
+        self.tasks.retain(|task| Arc::strong_count(&task.tracker) > 1);
+        finished
+    }
+
+    pub fn tasks(&self) -> &[RunningTask] {
+        &self.tasks
     }
 
     pub fn is_busy(&self) -> bool {
-        matches!(
-            self.state,
-            ProgressState::Scanning { .. } | 
-            ProgressState::FindingDuplicates { .. } | 
-            ProgressState::Cleaning { .. }
-        )
+        !self.tasks.is_empty()
+    }
+
+    /// Asks every running task to stop at its next safe point.
+    pub fn request_cancel(&self) {
+        for task in &self.tasks {
+            task.tracker.request_cancel();
+        }
     }
 }
@@ -0,0 +1,280 @@
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use log::{info, warn};
+
+use crate::cleaner;
+use crate::config;
+use crate::hash_cache::{self, HashKind};
+
+/// Checksum manifest written into a run's folder when enabled, in the format
+/// of `b3sum`, so the files can also be checked with `b3sum --check` from there.
+const CHECKSUM_MANIFEST: &str = "checksums.b3";
+
+/// A file moved to the quarantine, with what is needed to put it back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedFile {
+    pub original: PathBuf,
+    pub stored_at: PathBuf,
+    pub size: u64,
+    /// BLAKE3 of the content, checked again before restoring
+    pub hash: String,
+}
+
+/// The files one cleanup moved to the quarantine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineRun {
+    pub quarantined_at: DateTime<Utc>,
+    #[serde(default)]
+    pub note: Option<String>,
+    pub files: Vec<QuarantinedFile>,
+}
+
+impl QuarantineRun {
+    pub fn size(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+}
+
+fn quarantine_dir() -> Option<PathBuf> {
+    config::app_data_dir().map(|dir| dir.join(config::QUARANTINE_DIR_NAME))
+}
+
+fn manifest_path() -> Option<PathBuf> {
+    quarantine_dir().map(|dir| dir.join("manifest.json"))
+}
+
+/// Directory the files of the run started at `started_at` are moved into.
+pub fn run_dir(started_at: &DateTime<Utc>) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let dir = quarantine_dir().ok_or("no data directory")?;
+    Ok(dir.join(started_at.format("%Y%m%dT%H%M%SZ").to_string()))
+}
+
+/// Moves `file` into `run_dir` at its full original path, so files with the
+/// same name from different folders never collide. Files on another volume
+/// are copied and then removed, as they cannot be renamed across volumes.
+pub fn store(file: &Path, run_dir: &Path) -> Result<QuarantinedFile, Box<dyn std::error::Error + Send + Sync>> {
+    let mut stored_at = run_dir.to_path_buf();
+    for component in file.components() {
+        match component {
+            Component::Prefix(prefix) => stored_at.push(prefix.as_os_str().to_string_lossy().replace([':', '\\', '?'], "")),
+            Component::Normal(name) => stored_at.push(name),
+            _ => {}
+        }
+    }
+    store_at(file, stored_at)
+}
+
+/// Moves `file` into `run_dir` at its path relative to `base`, such as the
+/// root of its volume, so it can also be restored by hand.
+pub fn store_relative(file: &Path, base: &Path, run_dir: &Path) -> Result<QuarantinedFile, Box<dyn std::error::Error + Send + Sync>> {
+    let relative = file.strip_prefix(base)?;
+    store_at(file, run_dir.join(relative))
+}
+
+fn store_at(file: &Path, stored_at: PathBuf) -> Result<QuarantinedFile, Box<dyn std::error::Error + Send + Sync>> {
+    let hash = hash_file(file)?;
+    let metadata = std::fs::symlink_metadata(file)?;
+    let size = if metadata.is_dir() { 0 } else { metadata.len() };
+
+    if let Some(parent) = stored_at.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    move_file(file, &stored_at, &hash)?;
+
+    Ok(QuarantinedFile {
+        original: file.to_path_buf(),
+        stored_at,
+        size,
+        hash,
+    })
+}
+
+/// The cleanups whose files are still in the quarantine, oldest first.
+pub fn load_runs() -> Vec<QuarantineRun> {
+    let Some(path) = manifest_path() else {
+        return Vec::new();
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Failed to parse quarantine manifest: {}", e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn save_runs(runs: &[QuarantineRun]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = manifest_path().ok_or("no data directory")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(runs)?)?;
+    Ok(())
+}
+
+pub fn record_run(run: QuarantineRun) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut runs = load_runs();
+    info!("Recording {} quarantined files", run.files.len());
+    runs.push(run);
+    save_runs(&runs)
+}
+
+/// Moves a quarantined file back to where it was. Refuses to overwrite a file
+/// created there since, and to restore content that changed in the quarantine.
+pub fn restore(file: &QuarantinedFile) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if file.original.symlink_metadata().is_ok() {
+        return Err(format!("{} already exists", file.original.display()).into());
+    }
+    if hash_file(&file.stored_at)? != file.hash {
+        return Err(format!("{} changed in the quarantine", file.stored_at.display()).into());
+    }
+
+    if let Some(parent) = file.original.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    move_file(&file.stored_at, &file.original, &file.hash)?;
+    forget(std::slice::from_ref(&file.stored_at))?;
+    info!("Restored {}", file.original.display());
+    Ok(())
+}
+
+/// Restores every file of `run`. Returns the files that could not be restored, with the reason.
+pub fn restore_run(run: &QuarantineRun) -> Vec<(PathBuf, String)> {
+    run.files.iter()
+        .filter_map(|file| restore(file).err().map(|e| (file.original.clone(), e.to_string())))
+        .collect()
+}
+
+/// Deletes the files of `run` from the quarantine for good, freeing their space.
+pub fn purge_run(run: &QuarantineRun) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    for file in &run.files {
+        if let Err(e) = std::fs::remove_file(&file.stored_at) {
+            warn!("Could not delete {}: {}", file.stored_at.display(), e);
+        }
+    }
+    let dir = run_dir(&run.quarantined_at)?;
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    // Files of FAT drives are stored on the drive, in folders of their own
+    for file in run.files.iter().filter(|file| !file.stored_at.starts_with(&dir)) {
+        remove_empty_parents(&file.stored_at);
+    }
+    let stored: Vec<PathBuf> = run.files.iter().map(|file| file.stored_at.clone()).collect();
+    forget(&stored)
+}
+
+/// Removes the folders above `stored_at` left empty, up to the quarantine folder.
+fn remove_empty_parents(stored_at: &Path) {
+    for dir in stored_at.ancestors().skip(1) {
+        if dir.file_name().is_none_or(|name| name == config::QUARANTINE_DIR_NAME) || std::fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+/// Drops the files stored at `stored` from the manifest, and the runs left empty.
+fn forget(stored: &[PathBuf]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut runs = load_runs();
+    for run in &mut runs {
+        run.files.retain(|file| !stored.contains(&file.stored_at));
+    }
+    runs.retain(|run| !run.files.is_empty());
+    save_runs(&runs)
+}
+
+/// Writes the BLAKE3 of each file of the run stored in `run_dir` into a
+/// manifest next to them, for checking the files without the application,
+/// e.g. after copying the quarantine to another drive.
+pub fn write_checksum_manifest(run_dir: &Path, files: &[QuarantinedFile]) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let mut manifest = String::new();
+    for file in files.iter().filter(|file| !file.hash.is_empty()) {
+        let relative = file.stored_at.strip_prefix(run_dir).unwrap_or(&file.stored_at);
+        manifest.push_str(&format!("{}  {}\n", file.hash, relative.display()));
+    }
+    let path = run_dir.join(CHECKSUM_MANIFEST);
+    std::fs::write(&path, manifest)?;
+    info!("Wrote the checksums of {} quarantined files to {}", files.len(), path.display());
+    Ok(path)
+}
+
+/// Moves a file, or a folder holding nothing but folders. A file copied to
+/// another volume must hash to `hash` before the original is removed.
+fn move_file(from: &Path, to: &Path, hash: &str) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices && from.is_dir() => {
+            for entry in walkdir::WalkDir::new(from) {
+                let entry = entry?;
+                if let Ok(relative) = entry.path().strip_prefix(from) {
+                    std::fs::create_dir_all(to.join(relative))?;
+                }
+            }
+            cleaner::remove_empty_subfolders(from)?;
+            std::fs::remove_dir(from)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            std::fs::copy(from, to)?;
+            if content_hash(to)? != hash {
+                std::fs::remove_file(to)?;
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("the copy of {} differs from the original, which was kept", from.display()),
+                ));
+            }
+            std::fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+/// BLAKE3 of the content of a file, empty for an empty folder. A file the
+/// duplicate finder already hashed is not read again.
+fn hash_file(path: &Path) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if path.is_dir() {
+        return Ok(String::new());
+    }
+    hash_cache::shared().get_or_compute(path, HashKind::Full, || Ok(content_hash(path)?))
+}
+
+/// BLAKE3 of the content of a file, always read from disk.
+fn content_hash(path: &Path) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn stores_a_file_relative_to_its_volume() {
+        let dir = TempDir::new("quarantine-relative");
+        let file = dir.file("volume/photos/old.jpg", b"picture");
+        let run_dir = dir.path().join("volume").join(config::QUARANTINE_DIR_NAME).join("20260101T000000Z");
+
+        let stored = store_relative(&file, &dir.path().join("volume"), &run_dir).unwrap();
+        assert_eq!(stored.stored_at, run_dir.join("photos/old.jpg"));
+        assert_eq!(stored.original, file);
+        assert_eq!(stored.size, 7);
+        assert_eq!(stored.hash, blake3::hash(b"picture").to_hex().to_string());
+        assert!(!file.exists());
+        assert_eq!(std::fs::read(&stored.stored_at).unwrap(), b"picture");
+    }
+
+    #[test]
+    fn removes_the_folders_emptied_up_to_the_quarantine() {
+        let dir = TempDir::new("quarantine-parents");
+        let quarantine = dir.dir(config::QUARANTINE_DIR_NAME);
+        let stored = dir.file(&format!("{}/run/photos/old.jpg", config::QUARANTINE_DIR_NAME), b"picture");
+        dir.file(&format!("{}/other/kept.jpg", config::QUARANTINE_DIR_NAME), b"picture");
+        std::fs::remove_file(&stored).unwrap();
+
+        remove_empty_parents(&stored);
+        assert!(!quarantine.join("run").exists());
+        assert!(quarantine.join("other/kept.jpg").exists());
+    }
+}
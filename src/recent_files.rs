@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use log::{info, debug};
+
+/// How far back the recently used items are looked up where the system lets us choose.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+const RECENT_DAYS: u32 = 90;
+
+/// Reads the files the user opened recently according to the operating
+/// system: the GTK and KDE recent documents on Linux, the Recent folder and
+/// jump lists on Windows, and the Spotlight last used dates on macOS. Lists
+/// that are missing or unreadable are skipped.
+pub fn load_recent_files() -> HashSet<PathBuf> {
+    let files = platform_recent_files();
+    info!("Loaded {} recently used files", files.len());
+    files
+}
+
+#[cfg(target_os = "linux")]
+fn platform_recent_files() -> HashSet<PathBuf> {
+    let mut files = HashSet::new();
+    let Some(data_dir) = dirs::data_dir() else {
+        return files;
+    };
+
+    match std::fs::read_to_string(data_dir.join("recently-used.xbel")) {
+        Ok(xbel) => files.extend(xbel_files(&xbel)),
+        Err(e) => debug!("No GTK recent files list: {}", e),
+    }
+
+    // KDE keeps one .desktop file per recent document
+    if let Ok(entries) = std::fs::read_dir(data_dir.join("RecentDocuments")) {
+        for entry in entries.flatten() {
+            let Ok(content) = std::fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            files.extend(
+                content.lines()
+                    .filter_map(|line| line.strip_prefix("URL[$e]=").or_else(|| line.strip_prefix("URL=")))
+                    .filter(|url| url.starts_with("file://"))
+                    .map(crate::browser_history::file_uri_to_path),
+            );
+        }
+    }
+
+    files
+}
+
+/// Local files bookmarked in a GTK `recently-used.xbel` list.
+#[cfg(target_os = "linux")]
+fn xbel_files(xbel: &str) -> Vec<PathBuf> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(xbel);
+    let mut files = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element) | Event::Empty(element)) if element.name().as_ref() == b"bookmark" => {
+                let href = element.try_get_attribute("href").ok().flatten();
+                if let Some(href) = href.and_then(|href| href.unescape_value().ok()) {
+                    if href.starts_with("file://") {
+                        files.push(crate::browser_history::file_uri_to_path(&href));
+                    }
+                }
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => {
+                debug!("Stopped reading recent files list: {}", e);
+                break;
+            }
+        }
+    }
+    files
+}
+
+#[cfg(windows)]
+fn platform_recent_files() -> HashSet<PathBuf> {
+    let mut files = HashSet::new();
+    let Some(recent) = dirs::data_dir().map(|dir| dir.join("Microsoft").join("Windows").join("Recent")) else {
+        return files;
+    };
+
+    // Shortcuts to the recently opened documents
+    if let Ok(entries) = std::fs::read_dir(&recent) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("lnk")) {
+                if let Some(target) = std::fs::read(&path).ok().and_then(|data| lnk_target(&data)) {
+                    files.insert(target);
+                }
+            }
+        }
+    }
+
+    // Jump lists are compound files holding one shortcut per stream
+    if let Ok(entries) = std::fs::read_dir(recent.join("AutomaticDestinations")) {
+        for entry in entries.flatten() {
+            files.extend(jump_list_targets(&entry.path()));
+        }
+    }
+
+    files
+}
+
+#[cfg(windows)]
+fn jump_list_targets(path: &std::path::Path) -> Vec<PathBuf> {
+    use std::io::Read;
+
+    let Ok(mut compound) = cfb::open(path) else {
+        return Vec::new();
+    };
+    let streams: Vec<PathBuf> = compound.walk()
+        .filter(|entry| entry.is_stream() && entry.name() != "DestList")
+        .map(|entry| entry.path().to_path_buf())
+        .collect();
+
+    streams.iter()
+        .filter_map(|stream| {
+            let mut data = Vec::new();
+            compound.open_stream(stream).ok()?.read_to_end(&mut data).ok()?;
+            lnk_target(&data)
+        })
+        .collect()
+}
+
+/// Target of a Windows shortcut, read from the local base path of its link
+/// info (MS-SHLLINK 2.3). Shortcuts to network or virtual locations have none.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn lnk_target(data: &[u8]) -> Option<PathBuf> {
+    const HEADER_SIZE: usize = 0x4C;
+    const HAS_LINK_TARGET_ID_LIST: u32 = 0x1;
+    const HAS_LINK_INFO: u32 = 0x2;
+    const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+
+    let u16_at = |offset: usize| data.get(offset..offset + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]));
+    let u32_at = |offset: usize| data.get(offset..offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+
+    if u32_at(0)? as usize != HEADER_SIZE {
+        return None;
+    }
+    let flags = u32_at(0x14)?;
+    if flags & HAS_LINK_INFO == 0 {
+        return None;
+    }
+
+    let mut info = HEADER_SIZE;
+    if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+        info += 2 + u16_at(info)? as usize;
+    }
+
+    let info_header_size = u32_at(info + 4)? as usize;
+    if u32_at(info + 8)? & VOLUME_ID_AND_LOCAL_BASE_PATH == 0 {
+        return None;
+    }
+
+    // Newer shortcuts also store the path as UTF-16
+    if info_header_size >= 0x24 {
+        let base = info + u32_at(info + 28)? as usize;
+        let suffix = info + u32_at(info + 32)? as usize;
+        let mut path = utf16_string(data, base)?;
+        path.push_str(&utf16_string(data, suffix).unwrap_or_default());
+        return Some(PathBuf::from(path));
+    }
+
+    let base = info + u32_at(info + 16)? as usize;
+    let suffix = info + u32_at(info + 24)? as usize;
+    let mut path = ansi_string(data, base)?;
+    path.push_str(&ansi_string(data, suffix).unwrap_or_default());
+    Some(PathBuf::from(path))
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn ansi_string(data: &[u8], offset: usize) -> Option<String> {
+    let bytes = data.get(offset..)?;
+    let end = bytes.iter().position(|&byte| byte == 0)?;
+    Some(String::from_utf8_lossy(&bytes[..end]).to_string())
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn utf16_string(data: &[u8], offset: usize) -> Option<String> {
+    let units: Vec<u16> = data.get(offset..)?
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+/// Spotlight records when each file was last opened, which covers the recent
+/// items of the Apple menu and of every application.
+#[cfg(target_os = "macos")]
+fn platform_recent_files() -> HashSet<PathBuf> {
+    let Some(home) = dirs::home_dir() else {
+        return HashSet::new();
+    };
+    let query = format!("kMDItemLastUsedDate >= $time.today(-{})", RECENT_DAYS);
+
+    match std::process::Command::new("mdfind").arg("-onlyin").arg(&home).arg(query).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        Err(e) => {
+            debug!("Could not query Spotlight for recent files: {}", e);
+            HashSet::new()
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+fn platform_recent_files() -> HashSet<PathBuf> {
+    HashSet::new()
+}
@@ -0,0 +1,130 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, Utc};
+use log::info;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::file_category::FileCategory;
+use crate::scanner::ScanResult;
+
+/// Where reports are saved when the user does not pick a location.
+pub fn default_report_path() -> Option<PathBuf> {
+    let dir = dirs::document_dir().or_else(dirs::home_dir)?;
+    let name = format!("disk-cleaner-report-{}.csv", Local::now().format("%Y%m%d-%H%M%S"));
+    Some(dir.join(name))
+}
+
+/// Writes one CSV row per scanned file: path, category name, size in bytes, and
+/// the modification and creation times written in the configured date style.
+pub fn write_csv_report(results: &ScanResult, path: &Path, config: &Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let dates = config.report_date_style;
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "path,category,size_bytes,modified,created")?;
+
+    let mut rows = 0;
+    for category in FileCategory::all() {
+        let Some(files) = results.files_by_category.get(&category) else {
+            continue;
+        };
+        let name = csv_field(&config.category_name(category));
+
+        for file in files {
+            let metadata = std::fs::metadata(file).ok();
+            let size = metadata.as_ref().map(|metadata| metadata.len()).unwrap_or(0);
+            let time = |time: std::io::Result<std::time::SystemTime>| {
+                time.ok()
+                    .map(|time| dates.format(DateTime::<Utc>::from(time)))
+                    .unwrap_or_default()
+            };
+            let modified = metadata.as_ref().map(|metadata| time(metadata.modified())).unwrap_or_default();
+            let created = metadata.as_ref().map(|metadata| time(metadata.created())).unwrap_or_default();
+
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                csv_field(&file.to_string_lossy()),
+                name,
+                size,
+                csv_field(&modified),
+                csv_field(&created)
+            )?;
+            rows += 1;
+        }
+    }
+
+    writer.flush()?;
+    info!("Wrote report of {} files to {}", rows, path.display());
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ExportedScan<'a> {
+    roots: Vec<&'a Path>,
+    total_files: usize,
+    total_size: u64,
+    scan_duration_secs: f64,
+    files: Vec<ExportedFile<'a>>,
+}
+
+#[derive(Serialize)]
+struct ExportedFile<'a> {
+    path: &'a Path,
+    category: String,
+    size_bytes: u64,
+    /// In UTC, whatever the date style of the settings, so scripts can parse it
+    modified: Option<DateTime<Utc>>,
+}
+
+/// Writes the results as one JSON document: the scan totals and a record per
+/// file with its path, category name, size in bytes and modification time.
+pub fn write_json_report(results: &ScanResult, path: &Path, config: &Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut files = Vec::new();
+    for category in FileCategory::all() {
+        let Some(category_files) = results.files_by_category.get(&category) else {
+            continue;
+        };
+        let name = config.category_name(category);
+        for file in category_files {
+            let metadata = std::fs::metadata(file).ok();
+            files.push(ExportedFile {
+                path: file,
+                category: name.clone(),
+                size_bytes: metadata.as_ref().map_or(0, |metadata| metadata.len()),
+                modified: metadata.and_then(|metadata| metadata.modified().ok()).map(DateTime::<Utc>::from),
+            });
+        }
+    }
+
+    let rows = files.len();
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &ExportedScan {
+        roots: results.roots.iter().map(|root| root.path.as_path()).collect(),
+        total_files: results.total_files,
+        total_size: results.total_size,
+        scan_duration_secs: results.scan_duration.as_secs_f64(),
+        files,
+    })?;
+    writer.flush()?;
+    info!("Exported {} files to {}", rows, path.display());
+    Ok(())
+}
+
+/// Writes the results as JSON or CSV, following the extension of `path`.
+pub fn export(results: &ScanResult, path: &Path, config: &Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+        write_json_report(results, path, config)
+    } else {
+        write_csv_report(results, path, config)
+    }
+}
+
+/// Quotes a field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+use log::{info, error};
+
+use crate::audit::{self, CleanupReport};
+use crate::cleaner::{Cleaner, TrashFallback};
+use crate::config::Config;
+use crate::file_category::FileCategory;
+use crate::progress::ProgressTracker;
+use crate::scanner::Scanner;
+use crate::volumes::{FatVolumes, FAT_TIME_MARGIN};
+
+/// A retention policy such as "keep logs 14 days under ~/projects", enforced
+/// by scheduled `--retention` runs like logrotate or tmpwatch would.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionRule {
+    pub category: FileCategory,
+    /// Files of the category modified longer ago than this are removed
+    pub keep_days: u32,
+    pub roots: Vec<PathBuf>,
+}
+
+impl Default for RetentionRule {
+    fn default() -> Self {
+        Self {
+            category: FileCategory::LogFiles,
+            keep_days: 14,
+            roots: Vec::new(),
+        }
+    }
+}
+
+impl RetentionRule {
+    pub fn description(&self, config: &Config) -> String {
+        format!(
+            "Keep {} for {} days in {}",
+            config.category_name(self.category),
+            self.keep_days,
+            self.roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>().join(", ")
+        )
+    }
+
+    /// Scans the roots of the rule and moves the files of its category older
+    /// than `keep_days` to the trash or the quarantine.
+    /// Files are never deleted outright, so a rule set too tight can be undone.
+    pub fn enforce(&self, config: &Config, progress: Arc<ProgressTracker>) -> Result<CleanupReport, Box<dyn std::error::Error + Send + Sync>> {
+        if self.roots.is_empty() {
+            return Err(format!("{} has no folders", self.description(config)).into());
+        }
+        info!("Enforcing retention rule: {}", self.description(config));
+
+        // The files past retention are exactly those a normal scan leaves out as too old
+        let mut scan_config = config.clone();
+        scan_config.max_file_age_days = u32::MAX;
+        scan_config.disabled_categories.retain(|category| *category != self.category);
+        let results = Scanner::new(scan_config).scan(&self.roots, progress.clone())?;
+
+        let keep = Duration::from_secs(u64::from(self.keep_days) * 24 * 60 * 60);
+        let fat_volumes = FatVolumes::detect();
+        let expired: Vec<PathBuf> = results.files_by_category
+            .get(&self.category)
+            .into_iter()
+            .flatten()
+            .filter(|file| !config.is_path_protected(file) && !config.is_path_excluded(file))
+            .filter(|file| {
+                // FAT times are imprecise, keep files there a day longer rather than too short
+                let keep = if fat_volumes.holds(file) { keep + FAT_TIME_MARGIN } else { keep };
+                std::fs::symlink_metadata(file)
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > keep))
+            })
+            .cloned()
+            .collect();
+        info!("{} files past retention", expired.len());
+
+        let report = Cleaner::new(true)
+            .with_quarantine(config.use_quarantine)
+            .with_checksum_manifest(config.checksum_manifest)
+            // Expired files are never deleted for good, whatever the trash does
+            .with_trash_fallback(match config.trash_fallback {
+                TrashFallback::DeletePermanently => TrashFallback::Skip,
+                fallback => fallback,
+            })
+            .with_allowed_roots(&self.roots)
+            .with_expected_identities(results.identities_of(&expired))
+            .with_note(&self.description(config))
+            .clean_files(&expired, progress)?;
+
+        if config.sign_cleanup_records && !report.files.is_empty() {
+            if let Err(e) = audit::write_signed_report(&report) {
+                error!("Failed to write signed cleanup record: {}", e);
+            }
+        }
+
+        Ok(report)
+    }
+}
@@ -1,15 +1,28 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
 use walkdir::WalkDir;
 use rayon::prelude::*;
 use log::{info, warn};
 use serde::{Serialize, Deserialize};
 
-use crate::config::Config;
-use crate::file_category::FileCategory;
+use crate::browser_history::{self, DownloadRecord};
+use crate::config::{self, Config};
+use crate::disk_usage;
+use crate::error::Error;
+use crate::file_identity::{FileId, FileIdentity};
+use crate::file_lock::{self, FileLock};
+use crate::dir_sizes::DirSizeCache;
+use crate::package_caches;
+use crate::learned_rules::RuleAction;
+use crate::orphan_temps::{self, Owner};
+use crate::file_category::{self, DownloadAge, FileCategory, RebuildCost};
 use crate::progress::{ProgressTracker, ProgressState};
+use crate::recent_files;
+use crate::sync_folders;
+use crate::volumes::{FatVolumes, FAT_TIME_MARGIN};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanResult {
@@ -17,6 +30,216 @@ pub struct ScanResult {
     pub total_size: u64,
     pub files_by_category: HashMap<FileCategory, Vec<PathBuf>>,
     pub scan_duration: std::time::Duration,
+    /// Source of the files in downloads directories, from the browsers' history
+    #[serde(default)]
+    pub download_sources: HashMap<PathBuf, DownloadRecord>,
+    /// Size of the cache files by how costly they are to rebuild
+    #[serde(default)]
+    pub cache_rebuild_sizes: HashMap<RebuildCost, u64>,
+    /// How much of the volume's used space the scan accounted for
+    #[serde(default)]
+    pub coverage: Option<ScanCoverage>,
+    /// Total sizes of each category, kept up to date as files move between categories
+    #[serde(default)]
+    pub category_stats: HashMap<FileCategory, CategoryStats>,
+    /// Device and inode of each file at scan time, keyed by file
+    #[serde(default)]
+    pub identities: HashMap<FileId, FileIdentity>,
+    /// Online-only files of cloud sync folders, left out of the results as
+    /// deleting them frees no local space
+    #[serde(default)]
+    pub placeholder_files: usize,
+    #[serde(default)]
+    pub placeholder_size: u64,
+    /// The scan was stopped early and only holds the files reached until then
+    #[serde(default)]
+    pub cancelled: bool,
+    /// Old files and downloads left out because the system lists them as recently opened
+    #[serde(default)]
+    pub recently_used_files: usize,
+    /// Temporary files left out because the program that owns them is still running
+    #[serde(default)]
+    pub temps_in_use: usize,
+    /// Files found under each scanned folder, in the order they were given
+    #[serde(default)]
+    pub roots: Vec<RootSummary>,
+    /// Build and dependency folders of development projects, biggest first
+    #[serde(default)]
+    pub dev_artifacts: Vec<ArtifactDir>,
+    /// Size of the package caches by package manager
+    #[serde(default)]
+    pub package_cache_sizes: HashMap<String, u64>,
+    /// Files that cleaning would fail on, such as read-only or immutable ones, keyed by file
+    #[serde(default)]
+    pub locked_files: HashMap<FileId, FileLock>,
+    /// Downloads split by how long ago they were last written
+    #[serde(default)]
+    pub download_buckets: HashMap<DownloadAge, DownloadBucket>,
+}
+
+/// A build or dependency folder of a development project, e.g. the
+/// `target` folder of a Rust repository, with the files found in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactDir {
+    pub path: PathBuf,
+    pub files: usize,
+    pub size: u64,
+}
+
+/// The downloads of one age, reviewed and cleaned together.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DownloadBucket {
+    pub files: Vec<PathBuf>,
+    pub size: u64,
+}
+
+/// What the scan found under one of the folders it was given.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootSummary {
+    pub path: PathBuf,
+    pub files: usize,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CategoryStats {
+    pub size: u64,
+    /// Space actually allocated, smaller than `size` for compressed or sparse files
+    pub disk_size: u64,
+}
+
+impl CategoryStats {
+    fn of_file(path: &Path) -> Self {
+        std::fs::metadata(path)
+            .map(|metadata| Self {
+                size: metadata.len(),
+                disk_size: disk_usage::size_on_disk(path, &metadata),
+            })
+            .unwrap_or_default()
+    }
+
+    fn add(&mut self, other: Self) {
+        self.size += other.size;
+        self.disk_size += other.disk_size;
+    }
+
+    fn subtract(&mut self, other: Self) {
+        self.size = self.size.saturating_sub(other.size);
+        self.disk_size = self.disk_size.saturating_sub(other.disk_size);
+    }
+}
+
+impl ScanResult {
+    /// All scanned files except those inside backups, which hold copies of
+    /// other files by design and would otherwise show up as duplicates, and
+    /// the empty folders.
+    pub fn live_files(&self) -> Vec<PathBuf> {
+        self.files_by_category
+            .iter()
+            .filter(|(category, _)| !matches!(category, FileCategory::BackupSnapshots | FileCategory::EmptyFolders))
+            .flat_map(|(_, files)| files.iter().cloned())
+            .collect()
+    }
+
+    /// Identities recorded for `files`, to hand to the cleaner.
+    pub fn identities_of(&self, files: &[PathBuf]) -> HashMap<FileId, FileIdentity> {
+        files.iter()
+            .map(|file| FileId::of(file))
+            .filter_map(|id| self.identities.get(&id).map(|identity| (id, *identity)))
+            .collect()
+    }
+
+    /// Paths of the files with the given ids, in category order.
+    pub fn paths_of(&self, ids: &HashSet<FileId>) -> Vec<PathBuf> {
+        FileCategory::all()
+            .iter()
+            .filter_map(|category| self.files_by_category.get(category))
+            .flatten()
+            .filter(|file| ids.contains(&FileId::of(file)))
+            .cloned()
+            .collect()
+    }
+
+    /// Removes the files with `ids` from whichever categories hold them, updating the category totals.
+    pub fn remove_files(&mut self, ids: &HashSet<FileId>) {
+        for (category, category_files) in self.files_by_category.iter_mut() {
+            let stats = self.category_stats.entry(*category).or_default();
+            category_files.retain(|file| {
+                let keep = !ids.contains(&FileId::of(file));
+                if !keep {
+                    let file_stats = CategoryStats::of_file(file);
+                    stats.subtract(file_stats);
+                    if *category == FileCategory::DevArtifacts {
+                        if let Some(artifact) = self.dev_artifacts.iter_mut().find(|artifact| file.starts_with(&artifact.path)) {
+                            artifact.files = artifact.files.saturating_sub(1);
+                            artifact.size = artifact.size.saturating_sub(file_stats.size);
+                        }
+                    }
+                    if *category == FileCategory::Downloads {
+                        for bucket in self.download_buckets.values_mut() {
+                            if let Some(index) = bucket.files.iter().position(|download| download == file) {
+                                bucket.files.swap_remove(index);
+                                bucket.size = bucket.size.saturating_sub(file_stats.size);
+                            }
+                        }
+                    }
+                }
+                keep
+            });
+        }
+        self.dev_artifacts.retain(|artifact| artifact.files > 0);
+        self.download_buckets.retain(|_, bucket| !bucket.files.is_empty());
+    }
+
+    pub fn add_files(&mut self, category: FileCategory, files: &[PathBuf]) {
+        let stats = self.category_stats.entry(category).or_default();
+        for file in files {
+            let file_stats = CategoryStats::of_file(file);
+            stats.add(file_stats);
+            if category == FileCategory::DevArtifacts {
+                if let Some(root) = file.parent().and_then(file_category::dev_artifact_root) {
+                    let index = match self.dev_artifacts.iter().position(|artifact| artifact.path == root) {
+                        Some(index) => index,
+                        None => {
+                            self.dev_artifacts.push(ArtifactDir { path: root, files: 0, size: 0 });
+                            self.dev_artifacts.len() - 1
+                        }
+                    };
+                    self.dev_artifacts[index].files += 1;
+                    self.dev_artifacts[index].size += file_stats.size;
+                }
+            }
+            if category == FileCategory::Downloads {
+                if let Ok(metadata) = std::fs::metadata(file) {
+                    let bucket = self.download_buckets.entry(DownloadAge::of(&metadata)).or_default();
+                    bucket.files.push(file.clone());
+                    bucket.size += file_stats.size;
+                }
+            }
+        }
+        self.files_by_category.entry(category).or_default().extend(files.iter().cloned());
+    }
+}
+
+/// Share of a volume's used space that was seen by a scan. The rest lives in
+/// directories that were skipped, excluded or could not be read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanCoverage {
+    pub mount_point: PathBuf,
+    pub volume_used: u64,
+    /// Size of every file seen, including those filtered out by the settings
+    pub bytes_seen: u64,
+    /// Entries that could not be read, mostly for lack of permission
+    pub inaccessible_entries: usize,
+}
+
+impl ScanCoverage {
+    pub fn percent(&self) -> f64 {
+        if self.volume_used == 0 {
+            return 100.0;
+        }
+        (self.bytes_seen as f64 / self.volume_used as f64 * 100.0).min(100.0)
+    }
 }
 
 pub struct Scanner {
@@ -28,67 +251,115 @@ impl Scanner {
         Self { config }
     }
 
-    pub fn scan(&mut self, path: &Path, progress: Arc<Mutex<ProgressTracker>>) -> Result<ScanResult, Box<dyn std::error::Error + Send + Sync>> {
+    /// Scans the folders in `roots` into a single result. When cancelled
+    /// through `progress`, returns the files categorized so far with
+    /// [`ScanResult::cancelled`] set.
+    pub fn scan(&mut self, roots: &[PathBuf], progress: Arc<ProgressTracker>) -> Result<ScanResult, Error> {
         let start_time = std::time::Instant::now();
-        info!("Starting scan of path: {}", path.display());
-
-        let mut walker = WalkDir::new(path);
-        
-        if !self.config.follow_symlinks {
-            walker = walker.follow_links(false);
+        let roots = Self::distinct_roots(roots);
+        if roots.is_empty() {
+            return Err("no folder to scan".into());
         }
+        // A folder that cannot be opened would otherwise scan as empty
+        for root in &roots {
+            std::fs::read_dir(root).map_err(|e| Error::io(root, e))?;
+        }
+        info!("Starting scan of {}", roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>().join(", "));
+        progress.update(ProgressState::Scanning {
+            current_path: roots[0].to_string_lossy().to_string(),
+            files_processed: 0,
+            total_files: None,
+            bytes_processed: 0,
+            started: start_time,
+        }, true);
 
-        let entries: Vec<_> = walker
-            .into_iter()
-            .filter_map(|entry| {
-                match entry {
-                    Ok(entry) => Some(entry),
-                    Err(e) => {
-                        warn!("Error accessing file: {}", e);
-                        None
-                    }
-                }
-            })
-            .filter(|entry| entry.file_type().is_file())
-            .collect();
+        // Listing every file first gives the categorizing below a known total
+        let mut entries = Vec::new();
+        let mut walk_errors = 0;
+        for root in &roots {
+            let (files, errors) = self.enumerate_files(root, entries.len(), start_time, &progress);
+            entries.extend(files);
+            walk_errors += errors;
+        }
 
         info!("Found {} files to process", entries.len());
 
         let files_by_category: Arc<Mutex<HashMap<FileCategory, Vec<PathBuf>>>> = 
             Arc::new(Mutex::new(HashMap::new()));
-        let processed_count = Arc::new(Mutex::new(0usize));
+        let processed_count = AtomicUsize::new(0);
         let total_files = entries.len();
         let total_size = Arc::new(Mutex::new(0u64));
+        let cache_rebuild_sizes: Mutex<HashMap<RebuildCost, u64>> = Mutex::new(HashMap::new());
+        let category_stats: Mutex<HashMap<FileCategory, CategoryStats>> = Mutex::new(HashMap::new());
+        let identities: Mutex<HashMap<FileId, FileIdentity>> = Mutex::new(HashMap::new());
+        let sync_folders = sync_folders::detect_sync_folders();
+        let fat_volumes = FatVolumes::detect();
+        let recent_files = if self.config.protect_recent_files {
+            recent_files::load_recent_files()
+        } else {
+            HashSet::new()
+        };
+        let recently_used_files = AtomicUsize::new(0);
+        let temps_in_use = AtomicUsize::new(0);
+        let placeholder_files = AtomicUsize::new(0);
+        let placeholder_size = AtomicU64::new(0);
+        let bytes_seen = AtomicU64::new(0);
+        let inaccessible_entries = AtomicUsize::new(walk_errors);
+        let dir_sizes: Mutex<HashMap<PathBuf, (u64, usize)>> = Mutex::new(HashMap::new());
+        let root_totals: Vec<(AtomicUsize, AtomicU64)> = roots.iter().map(|_| Default::default()).collect();
+        let artifact_dirs: Mutex<HashMap<PathBuf, (usize, u64)>> = Mutex::new(HashMap::new());
+        let package_cache_sizes: Mutex<HashMap<String, u64>> = Mutex::new(HashMap::new());
+        let locked_files: Mutex<HashMap<FileId, FileLock>> = Mutex::new(HashMap::new());
+        let download_buckets: Mutex<HashMap<DownloadAge, DownloadBucket>> = Mutex::new(HashMap::new());
 
         // Process files in parallel
-        entries.par_iter().for_each(|entry| {
-            let path = entry.path();
+        let processing_started = std::time::Instant::now();
+        entries.par_iter().for_each(|path| {
+            if progress.is_cancelled() {
+                return;
+            }
             
             // Update progress
-            {
-                let mut count = processed_count.lock().unwrap();
-                *count += 1;
-                let current_count = *count;
-                
-                if current_count % 100 == 0 || current_count == total_files {
-                    let mut progress_guard = progress.lock().unwrap();
-                    progress_guard.state = ProgressState::Scanning {
-                        current_path: path.to_string_lossy().to_string(),
-                        files_processed: current_count,
-                    };
-                }
+            let current_count = processed_count.fetch_add(1, Ordering::Relaxed) + 1;
+            if current_count.is_multiple_of(100) || current_count == total_files {
+                progress.update(ProgressState::Scanning {
+                    current_path: path.to_string_lossy().to_string(),
+                    files_processed: current_count,
+                    total_files: Some(total_files),
+                    bytes_processed: bytes_seen.load(Ordering::Relaxed),
+                    started: processing_started,
+                }, current_count == total_files);
             }
 
             // Check file filters
-            if let Ok(metadata) = entry.metadata() {
+            if let Ok(metadata) = std::fs::metadata(path) {
                 let file_size = metadata.len();
+                bytes_seen.fetch_add(file_size, Ordering::Relaxed);
+                if let Some(parent) = path.parent() {
+                    let mut dir_sizes = dir_sizes.lock().unwrap();
+                    let entry = dir_sizes.entry(parent.to_path_buf()).or_default();
+                    entry.0 += file_size;
+                    entry.1 += 1;
+                }
                 
                 if file_size < self.config.min_file_size {
                     return;
                 }
 
+                if sync_folders::sync_folder_for(&sync_folders, path).is_some()
+                    && sync_folders::is_placeholder(path, &metadata)
+                {
+                    placeholder_files.fetch_add(1, Ordering::Relaxed);
+                    placeholder_size.fetch_add(file_size, Ordering::Relaxed);
+                    return;
+                }
+
                 if let Ok(modified) = metadata.modified() {
-                    if let Ok(duration) = modified.elapsed() {
+                    if let Ok(mut duration) = modified.elapsed() {
+                        // Give FAT timestamps the benefit of the doubt rather than drop the file
+                        if fat_volumes.holds(path) {
+                            duration = duration.saturating_sub(FAT_TIME_MARGIN);
+                        }
                         let age_days = duration.as_secs() / (24 * 60 * 60);
                         if age_days > self.config.max_file_age_days as u64 {
                             return;
@@ -96,22 +367,81 @@ impl Scanner {
                     }
                 }
 
-                // Check if hidden file
+                // Check if hidden file. Build folders keep theirs, as they are cleaned as a unit
                 if !self.config.include_hidden_files {
                     if let Some(filename) = path.file_name() {
-                        if filename.to_string_lossy().starts_with('.') {
+                        if filename.to_string_lossy().starts_with('.')
+                            && path.parent().and_then(file_category::dev_artifact_root).is_none()
+                        {
                             return;
                         }
                     }
                 }
 
                 // Categorize file
-                let category = FileCategory::categorize(path);
+                let category = self.config.user_category(path)
+                    .unwrap_or_else(|| FileCategory::categorize(path, Some(&metadata)));
+                if !self.config.is_category_enabled(category) {
+                    return;
+                }
                 
+                // Being old or downloaded says nothing against a file still in use
+                if matches!(category, FileCategory::OldFiles | FileCategory::Downloads) && recent_files.contains(path) {
+                    recently_used_files.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                
+                // A swap file or download still being written to is no leftover
+                if category == FileCategory::TemporaryFiles && orphan_temps::owner(path, Some(&metadata)) == Some(Owner::Active) {
+                    temps_in_use.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                
+                if category == FileCategory::CacheFiles {
+                    let cost = RebuildCost::for_path(path);
+                    *cache_rebuild_sizes.lock().unwrap().entry(cost).or_default() += file_size;
+                }
+                
+                if category == FileCategory::PackageCaches {
+                    if let Some(manager) = package_caches::manager_of(path) {
+                        *package_cache_sizes.lock().unwrap().entry(manager.to_string()).or_default() += file_size;
+                    }
+                }
+                
+                if category == FileCategory::Downloads {
+                    let mut download_buckets = download_buckets.lock().unwrap();
+                    let bucket = download_buckets.entry(DownloadAge::of(&metadata)).or_default();
+                    bucket.files.push(path.clone());
+                    bucket.size += file_size;
+                }
+                
+                // Totals per project folder, so a whole `target` or `node_modules` can be judged at once
+                if category == FileCategory::DevArtifacts {
+                    if let Some(root) = path.parent().and_then(file_category::dev_artifact_root) {
+                        let mut artifact_dirs = artifact_dirs.lock().unwrap();
+                        let entry = artifact_dirs.entry(root).or_default();
+                        entry.0 += 1;
+                        entry.1 += file_size;
+                    }
+                }
+
+                category_stats.lock().unwrap().entry(category).or_default().add(CategoryStats {
+                    size: file_size,
+                    disk_size: disk_usage::size_on_disk(path, &metadata),
+                });
+
+                if let Some(identity) = FileIdentity::of(&metadata) {
+                    identities.lock().unwrap().insert(FileId::of(path), identity);
+                }
+
+                if let Some(lock) = file_lock::lock_of(path, &metadata) {
+                    locked_files.lock().unwrap().insert(FileId::of(path), lock);
+                }
+
                 // Add to results
                 {
                     let mut categories = files_by_category.lock().unwrap();
-                    categories.entry(category).or_insert_with(Vec::new).push(path.to_owned());
+                    categories.entry(category).or_default().push(path.to_owned());
                 }
 
                 // Update total size
@@ -119,13 +449,91 @@ impl Scanner {
                     let mut size = total_size.lock().unwrap();
                     *size += file_size;
                 }
+                if let Some(index) = roots.iter().position(|root| path.starts_with(root)) {
+                    root_totals[index].0.fetch_add(1, Ordering::Relaxed);
+                    root_totals[index].1.fetch_add(file_size, Ordering::Relaxed);
+                }
+            } else {
+                inaccessible_entries.fetch_add(1, Ordering::Relaxed);
             }
         });
 
+        if self.config.is_category_enabled(FileCategory::EmptyFolders) && !progress.is_cancelled() {
+            let empty_folders: Vec<PathBuf> = roots.iter()
+                .flat_map(|root| self.find_empty_folders(root, &progress))
+                .collect();
+            if !empty_folders.is_empty() {
+                info!("Found {} empty folders", empty_folders.len());
+                let mut identities = identities.lock().unwrap();
+                for folder in &empty_folders {
+                    if let Some(identity) = std::fs::symlink_metadata(folder).ok().and_then(|metadata| FileIdentity::of(&metadata)) {
+                        identities.insert(FileId::of(folder), identity);
+                    }
+                }
+                category_stats.lock().unwrap().entry(FileCategory::EmptyFolders).or_default();
+                files_by_category.lock().unwrap().insert(FileCategory::EmptyFolders, empty_folders);
+            }
+        }
+
+        let cancelled = progress.is_cancelled();
+        if cancelled {
+            warn!("Scan cancelled, keeping the {} files processed so far", processed_count.load(Ordering::Relaxed));
+        }
+
         let scan_duration = start_time.elapsed();
         let final_categories = files_by_category.lock().unwrap().clone();
         let final_size = *total_size.lock().unwrap();
-        let final_count = *processed_count.lock().unwrap();
+        let final_count = processed_count.load(Ordering::Relaxed);
+
+        let download_sources = match final_categories.get(&FileCategory::Downloads) {
+            Some(downloads) if self.config.read_browser_history => {
+                let mut history = browser_history::load_download_history();
+                downloads.iter()
+                    .filter_map(|file| history.remove_entry(file))
+                    .collect()
+            }
+            _ => HashMap::new(),
+        };
+
+        // Partial sizes would replace the complete ones of an earlier scan
+        if !cancelled {
+            let mut dir_size_cache = DirSizeCache::load();
+            let mut sizes = dir_sizes.into_inner().unwrap();
+            for root in &roots {
+                let (under_root, rest) = sizes.into_iter().partition(|(dir, _)| dir.starts_with(root));
+                dir_size_cache.record_scan(root, under_root);
+                sizes = rest;
+            }
+            if let Err(e) = dir_size_cache.save() {
+                warn!("Failed to save directory size cache: {}", e);
+            }
+        }
+
+        // The share of a volume seen means nothing once several folders, maybe on several volumes, add up
+        let coverage = match roots.as_slice() {
+            [root] => crate::volumes::volume_usage(root).map(|(mount_point, volume_used)| ScanCoverage {
+                mount_point,
+                volume_used,
+                bytes_seen: bytes_seen.into_inner(),
+                inaccessible_entries: inaccessible_entries.into_inner(),
+            }),
+            _ => None,
+        };
+
+        let root_summaries = roots.iter()
+            .zip(root_totals)
+            .map(|(root, (files, size))| RootSummary {
+                path: root.clone(),
+                files: files.into_inner(),
+                size: size.into_inner(),
+            })
+            .collect();
+
+        let mut dev_artifacts: Vec<ArtifactDir> = artifact_dirs.into_inner().unwrap()
+            .into_iter()
+            .map(|(path, (files, size))| ArtifactDir { path, files, size })
+            .collect();
+        dev_artifacts.sort_by_key(|artifact| std::cmp::Reverse(artifact.size));
 
         info!("Scan completed in {:?}", scan_duration);
         info!("Processed {} files, total size: {} bytes", final_count, final_size);
@@ -135,6 +543,350 @@ impl Scanner {
             total_size: final_size,
             files_by_category: final_categories,
             scan_duration,
+            download_sources,
+            cache_rebuild_sizes: cache_rebuild_sizes.into_inner().unwrap(),
+            coverage,
+            category_stats: category_stats.into_inner().unwrap(),
+            identities: identities.into_inner().unwrap(),
+            placeholder_files: placeholder_files.into_inner(),
+            placeholder_size: placeholder_size.into_inner(),
+            cancelled,
+            recently_used_files: recently_used_files.into_inner(),
+            temps_in_use: temps_in_use.into_inner(),
+            roots: root_summaries,
+            dev_artifacts,
+            package_cache_sizes: package_cache_sizes.into_inner().unwrap(),
+            locked_files: locked_files.into_inner().unwrap(),
+            download_buckets: download_buckets.into_inner().unwrap(),
         })
     }
+
+    /// Drops repeated folders and folders inside another one, which would
+    /// otherwise be scanned twice.
+    fn distinct_roots(roots: &[PathBuf]) -> Vec<PathBuf> {
+        let mut distinct: Vec<PathBuf> = Vec::new();
+        for root in roots {
+            if distinct.iter().any(|kept| root.starts_with(kept)) {
+                continue;
+            }
+            distinct.retain(|kept| !kept.starts_with(root));
+            distinct.push(root.clone());
+        }
+        distinct
+    }
+
+    /// Lists the regular files under `path`, using the NTFS master file table when
+    /// enabled and available, and a recursive directory walk otherwise. Also returns the number of entries that could
+    /// not be read. `already_listed` files were found under the roots before
+    /// this one since `listing_started`, for the progress count.
+    fn enumerate_files(
+        &self,
+        path: &Path,
+        already_listed: usize,
+        listing_started: std::time::Instant,
+        progress: &ProgressTracker,
+    ) -> (Vec<PathBuf>, usize) {
+        // Never descend into the application's own config, cache and data dirs
+        let app_owned = config::app_owned_paths();
+
+        #[cfg(windows)]
+        if self.config.use_mft_enumeration {
+            match crate::mft::enumerate_files(path) {
+                Ok(files) => {
+                    info!("Enumerated {} files from the master file table", files.len());
+                    let files = files
+                        .into_iter()
+                        .filter(|file| !app_owned.iter().any(|owned| file.starts_with(owned)))
+                        .filter(|file| !self.config.is_under_excluded_path(file) && !self.config.is_extension_excluded(file))
+                        .filter(|file| package_caches::kept_reason(file).is_none())
+                        .collect();
+                    return (files, 0);
+                }
+                Err(e) => {
+                    warn!("MFT enumeration unavailable, falling back to directory walk: {}", e);
+                }
+            }
+        }
+
+        let mut walker = WalkDir::new(path);
+        
+        if !self.config.follow_symlinks {
+            walker = walker.follow_links(false);
+        }
+
+        let mut errors = 0;
+        let mut pruned = 0;
+        let mut excluded = 0;
+        let files = walker
+            .into_iter()
+            .filter_entry(|entry| {
+                if app_owned.iter().any(|owned| entry.path().starts_with(owned)) || entry.file_name() == config::QUARANTINE_DIR_NAME {
+                    return false;
+                }
+                // Indexes and databases of package managers are never offered for cleaning
+                if package_caches::kept_reason(entry.path()).is_some() {
+                    excluded += 1;
+                    return false;
+                }
+                // Excluded directories are skipped whole, without reading them
+                if self.config.is_under_excluded_path(entry.path()) {
+                    excluded += 1;
+                    return false;
+                }
+                if entry.file_type().is_file() && self.config.is_extension_excluded(entry.path()) {
+                    excluded += 1;
+                    return false;
+                }
+                if entry.depth() > 0 && entry.file_type().is_dir() && !self.may_hold_enabled_category(entry.path()) {
+                    pruned += 1;
+                    return false;
+                }
+                true
+            })
+            .take_while(|_| !progress.is_cancelled())
+            .filter_map(|entry| {
+                match entry {
+                    Ok(entry) => Some(entry),
+                    Err(e) => {
+                        warn!("Error accessing file: {}", e);
+                        errors += 1;
+                        None
+                    }
+                }
+            })
+            .filter(|entry| entry.file_type().is_file())
+            .enumerate()
+            .map(|(index, entry)| {
+                progress.update(ProgressState::Scanning {
+                    current_path: entry.path().to_string_lossy().to_string(),
+                    files_processed: already_listed + index + 1,
+                    total_files: None,
+                    bytes_processed: 0,
+                    started: listing_started,
+                }, false);
+                entry.into_path()
+            })
+            .collect();
+
+        if pruned > 0 {
+            info!("Skipped {} directories that cannot hold any enabled category", pruned);
+        }
+        if excluded > 0 {
+            info!("Skipped {} excluded files and directories", excluded);
+        }
+        (files, errors)
+    }
+
+    /// Lists the empty folders under `root`, leaving out `root` itself. A folder
+    /// holding nothing but empty folders counts as empty, and only the outermost
+    /// folder of such a tree is listed, as removing it removes the rest.
+    /// Anything the scan leaves out, such as excluded or hidden entries, counts
+    /// as content.
+    fn find_empty_folders(&self, root: &Path, progress: &ProgressTracker) -> Vec<PathBuf> {
+        let app_owned = config::app_owned_paths();
+        let mut empty = Vec::new();
+        self.collect_empty_folders(root, &app_owned, progress, &mut empty);
+        empty
+    }
+
+    /// Whether `dir` is empty in the sense of [`Self::find_empty_folders`].
+    /// When it is not, pushes the outermost empty folders below it to `empty`.
+    fn collect_empty_folders(&self, dir: &Path, app_owned: &[PathBuf], progress: &ProgressTracker, empty: &mut Vec<PathBuf>) -> bool {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return false;
+        };
+
+        let mut is_empty = true;
+        let mut empty_children = Vec::new();
+        for entry in entries {
+            if progress.is_cancelled() {
+                return false;
+            }
+            let Ok(entry) = entry else {
+                is_empty = false;
+                continue;
+            };
+            let path = entry.path();
+            let is_folder = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            let left_out = app_owned.iter().any(|owned| path.starts_with(owned))
+                || entry.file_name() == config::QUARANTINE_DIR_NAME
+                || self.config.is_under_excluded_path(&path)
+                || !self.config.include_hidden_files && entry.file_name().to_string_lossy().starts_with('.');
+            if is_folder && !left_out && self.collect_empty_folders(&path, app_owned, progress, empty) {
+                empty_children.push(path);
+            } else {
+                is_empty = false;
+            }
+        }
+
+        if !is_empty {
+            empty.extend(empty_children);
+        }
+        is_empty
+    }
+
+    /// Whether files below `dir` can end up in a category enabled in the
+    /// settings. When some categories are disabled, whole trees such as cache
+    /// directories can be skipped without reading them.
+    fn may_hold_enabled_category(&self, dir: &Path) -> bool {
+        if self.config.disabled_categories.is_empty() {
+            return true;
+        }
+
+        // Learned rules are applied before the built-in ones
+        let learned = self.config.learned_rules.iter().any(|rule| {
+            matches!(rule.action, RuleAction::Category(category) if self.config.is_category_enabled(category))
+                && (rule.under.starts_with(dir) || dir.starts_with(&rule.under))
+        });
+
+        // Categorization rules are applied first too, and a glob can match anywhere
+        let ruled = self.config.category_rules.rules().iter()
+            .any(|rule| self.config.is_category_enabled(rule.category));
+
+        learned || ruled || FileCategory::all()
+            .into_iter()
+            .filter(|category| self.config.is_category_enabled(*category))
+            .any(|category| category.may_occur_under(dir))
+    }
+}
+
+/// The outermost folders under `roots` that deleting `files` would leave
+/// empty: folders holding nothing but some of `files` and folders emptied the
+/// same way. The roots themselves are never listed.
+pub fn folders_emptied_by(files: &[PathBuf], roots: &[PathBuf]) -> Vec<PathBuf> {
+    let removed: HashSet<&Path> = files.iter().map(PathBuf::as_path).collect();
+    let mut emptied = HashMap::new();
+    let mut outermost: Vec<PathBuf> = Vec::new();
+
+    for file in files {
+        let mut top = None;
+        let mut dir = file.parent();
+        while let Some(current) = dir {
+            let inside_root = roots.iter().any(|root| current.starts_with(root) && current != root);
+            if !inside_root || !becomes_empty(current, &removed, &mut emptied) {
+                break;
+            }
+            top = Some(current);
+            dir = current.parent();
+        }
+        if let Some(top) = top {
+            if !removed.contains(top) && !outermost.iter().any(|folder| folder == top) {
+                outermost.push(top.to_path_buf());
+            }
+        }
+    }
+    outermost
+}
+
+/// Whether every entry of `dir` is in `removed` or a folder emptied the same
+/// way, remembering the answer for each folder in `emptied`.
+fn becomes_empty(dir: &Path, removed: &HashSet<&Path>, emptied: &mut HashMap<PathBuf, bool>) -> bool {
+    if let Some(known) = emptied.get(dir) {
+        return *known;
+    }
+    let empty = std::fs::read_dir(dir).is_ok_and(|mut entries| {
+        entries.all(|entry| entry.is_ok_and(|entry| {
+            let path = entry.path();
+            removed.contains(path.as_path())
+                || entry.file_type().is_ok_and(|file_type| file_type.is_dir()) && becomes_empty(&path, removed, emptied)
+        }))
+    });
+    emptied.insert(dir.to_path_buf(), empty);
+    empty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn listed_files(config: Config, root: &Path) -> HashSet<PathBuf> {
+        let progress = ProgressTracker::default();
+        let (files, _) = Scanner::new(config).enumerate_files(root, 0, std::time::Instant::now(), &progress);
+        files.into_iter().collect()
+    }
+
+    #[test]
+    fn walk_skips_nested_excluded_paths_and_extensions() {
+        let dir = TempDir::new("scan-excluded");
+        let kept = dir.file("cache/kept.tmp", b"junk");
+        let nested_kept = dir.file("cache/deep/er/kept.tmp", b"junk");
+        let excluded = dir.file("cache/deep/excluded/file.tmp", b"keep");
+        let nested_excluded = dir.file("cache/deep/excluded/more/file.tmp", b"keep");
+        let by_extension = dir.file("cache/deep/er/library.DLL", b"keep");
+        let config = Config {
+            excluded_paths: vec![dir.path().join("cache/deep/excluded")],
+            excluded_extensions: vec![".dll".to_string()],
+            ..Config::default()
+        };
+
+        let files = listed_files(config, dir.path());
+        assert!(files.contains(&kept));
+        assert!(files.contains(&nested_kept));
+        assert!(!files.contains(&excluded));
+        assert!(!files.contains(&nested_excluded));
+        assert!(!files.contains(&by_extension));
+    }
+
+    #[test]
+    fn walk_skips_everything_when_the_root_is_excluded() {
+        let dir = TempDir::new("scan-root-excluded");
+        dir.file("a/b/file.tmp", b"keep");
+        let config = Config {
+            excluded_paths: vec![dir.path().to_path_buf()],
+            ..Config::default()
+        };
+
+        assert!(listed_files(config, dir.path()).is_empty());
+    }
+
+    #[test]
+    fn lists_the_outermost_folders_left_empty() {
+        let dir = TempDir::new("emptied-nested");
+        let root = dir.dir("root");
+        let files = vec![
+            dir.file("root/cache/x.tmp", b"junk"),
+            dir.file("root/cache/deep/er/y.tmp", b"junk"),
+        ];
+        dir.dir("root/cache/deep/empty");
+        dir.file("root/kept.txt", b"keep");
+
+        assert_eq!(folders_emptied_by(&files, &[root]), vec![dir.path().join("root/cache")]);
+    }
+
+    #[test]
+    fn leaves_out_folders_still_holding_files() {
+        let dir = TempDir::new("emptied-partial");
+        let root = dir.dir("root");
+        let files = vec![
+            dir.file("root/cache/x.tmp", b"junk"),
+            dir.file("root/cache/sub/y.tmp", b"junk"),
+        ];
+        dir.file("root/cache/kept.txt", b"keep");
+
+        assert_eq!(folders_emptied_by(&files, &[root]), vec![dir.path().join("root/cache/sub")]);
+    }
+
+    #[test]
+    fn never_lists_the_roots() {
+        let dir = TempDir::new("emptied-root");
+        let root = dir.dir("root");
+        let files = vec![dir.file("root/x.tmp", b"junk")];
+
+        assert!(folders_emptied_by(&files, &[root]).is_empty());
+        // A folder outside every root is not listed either
+        assert!(folders_emptied_by(&files, &[dir.path().join("elsewhere")]).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_link_to_a_folder_keeps_its_parent() {
+        let dir = TempDir::new("emptied-link");
+        let root = dir.dir("root");
+        let outside = dir.dir("outside");
+        let files = vec![dir.file("root/cache/x.tmp", b"junk")];
+        std::os::unix::fs::symlink(&outside, dir.path().join("root/cache/link")).unwrap();
+
+        assert!(folders_emptied_by(&files, &[root]).is_empty());
+    }
 }
@@ -0,0 +1,80 @@
+//! Overwriting a file's content before it is unlinked, so what it held cannot
+//! be read back from the free space. Each pass writes fresh random data over
+//! the whole file and flushes it to the drive.
+//!
+//! This only reaches the blocks the file occupies now: SSD wear leveling,
+//! copy-on-write file systems (Btrfs, ZFS, APFS), snapshots and backups may
+//! keep older copies that no overwrite touches.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Seek, SeekFrom, Write};
+use std::path::Path;
+use log::info;
+
+use crate::file_identity::FileIdentity;
+
+pub const MAX_PASSES: u32 = 35;
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Overwrites the content of the regular file at `path` `passes` times.
+/// `expected` is the identity recorded at scan time; a file that does not
+/// match it, a symbolic link or a file with other hard links is left intact,
+/// as overwriting it would destroy content reachable under another name.
+pub fn overwrite(path: &Path, passes: u32, expected: Option<FileIdentity>) -> std::io::Result<()> {
+    let mut file = open_no_follow(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        return Err(Error::new(ErrorKind::InvalidInput, "not a regular file"));
+    }
+    if expected.is_some() && FileIdentity::of(&metadata) != expected {
+        return Err(Error::other("file was replaced since the scan"));
+    }
+    if link_count(&metadata) > 1 {
+        return Err(Error::other("file has other hard links"));
+    }
+
+    let size = metadata.len();
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    for _ in 0..passes {
+        file.seek(SeekFrom::Start(0))?;
+        let mut left = size;
+        while left > 0 {
+            let chunk = left.min(BUFFER_SIZE as u64) as usize;
+            getrandom::getrandom(&mut buffer[..chunk]).map_err(Error::other)?;
+            file.write_all(&buffer[..chunk])?;
+            left -= chunk as u64;
+        }
+        file.sync_all()?;
+    }
+    info!("Overwrote {} ({} bytes) {} times", path.display(), size, passes);
+    Ok(())
+}
+
+#[cfg(unix)]
+fn open_no_follow(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    OpenOptions::new().write(true).custom_flags(libc::O_NOFOLLOW | libc::O_CLOEXEC).open(path)
+}
+
+#[cfg(not(unix))]
+fn open_no_follow(path: &Path) -> std::io::Result<File> {
+    if std::fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Err(Error::new(ErrorKind::InvalidInput, "symbolic link"));
+    }
+    OpenOptions::new().write(true).open(path)
+}
+
+#[cfg(unix)]
+fn link_count(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.nlink()
+}
+
+/// Windows only exposes the link count through handles, so hard links are not detected there.
+#[cfg(not(unix))]
+fn link_count(_metadata: &std::fs::Metadata) -> u64 {
+    1
+}
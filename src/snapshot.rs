@@ -0,0 +1,289 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+
+use crate::config;
+use crate::file_identity::FileId;
+use crate::scanner::ScanResult;
+
+/// Identifies snapshot files, followed by the format version.
+const MAGIC: &[u8; 4] = b"IDCS";
+
+/// Bump whenever `ScanResult` changes shape: bincode is not self-describing,
+/// so snapshots of another version are discarded rather than misread.
+const FORMAT_VERSION: u32 = 11;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// A scan result persisted between sessions.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub scan_paths: Vec<PathBuf>,
+    pub saved_at: DateTime<Utc>,
+    pub result: ScanResult,
+}
+
+fn snapshot_path() -> Option<PathBuf> {
+    config::app_cache_dir().map(|dir| dir.join("last_scan.bin"))
+}
+
+/// Saves the result of the latest scan as zstd-compressed bincode.
+pub fn save(scan_paths: &[PathBuf], result: &ScanResult) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(path) = snapshot_path() else {
+        return Ok(());
+    };
+    let start = std::time::Instant::now();
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Write next to the snapshot and rename, so a crash never leaves half a file
+    let partial = path.with_extension("bin.partial");
+    write_snapshot(BufWriter::new(File::create(&partial)?), scan_paths, result)?;
+    std::fs::rename(&partial, &path)?;
+
+    info!("Saved scan snapshot to {} in {:?}", path.display(), start.elapsed());
+    Ok(())
+}
+
+/// Loads the snapshot of the latest scan, if there is one of the current format.
+pub fn load() -> Option<Snapshot> {
+    let path = snapshot_path()?;
+    let start = std::time::Instant::now();
+    let reader = BufReader::new(File::open(&path).ok()?);
+
+    match read_snapshot(reader) {
+        Ok(snapshot) => {
+            info!("Loaded scan snapshot from {} in {:?}", path.display(), start.elapsed());
+            Some(snapshot)
+        }
+        Err(e) => {
+            warn!("Failed to read scan snapshot {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Writes the header, then the snapshot as zstd-compressed bincode.
+fn write_snapshot(mut writer: impl Write, scan_paths: &[PathBuf], result: &ScanResult) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+
+    let mut encoder = zstd::Encoder::new(writer, COMPRESSION_LEVEL)?;
+    bincode::serialize_into(&mut encoder, &SnapshotRef {
+        scan_paths,
+        saved_at: Utc::now(),
+        result,
+    })?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+/// Reads a snapshot written by [`write_snapshot`], refusing those of another
+/// format version.
+fn read_snapshot(mut reader: impl Read) -> Result<Snapshot, Box<dyn std::error::Error + Send + Sync>> {
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    if &header[..4] != MAGIC || version != FORMAT_VERSION {
+        return Err(format!("unknown format version {}", version).into());
+    }
+
+    let snapshot = bincode::deserialize_from(zstd::Decoder::new(reader)?)?;
+    Ok(snapshot)
+}
+
+/// Borrowed form of [`Snapshot`], so saving does not clone the result.
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    scan_paths: &'a [PathBuf],
+    saved_at: DateTime<Utc>,
+    result: &'a ScanResult,
+}
+
+/// The files of a scan the user marked as the clean state of the machine.
+/// Later scans hide the files it already had, leaving only the junk that
+/// appeared since.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Baseline {
+    pub scan_paths: Vec<PathBuf>,
+    pub saved_at: DateTime<Utc>,
+    files: HashSet<FileId>,
+}
+
+impl Baseline {
+    /// A baseline of the files in `result`, plus those of the `previous`
+    /// baseline, which may have been hidden from it.
+    pub fn of(scan_paths: &[PathBuf], result: &ScanResult, previous: Option<&Baseline>) -> Self {
+        let mut files: HashSet<FileId> = result.files_by_category
+            .values()
+            .flatten()
+            .map(|file| FileId::of(file))
+            .collect();
+        if let Some(previous) = previous {
+            files.extend(&previous.files);
+        }
+
+        Self {
+            scan_paths: scan_paths.to_vec(),
+            saved_at: Utc::now(),
+            files,
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        config::app_data_dir().map(|dir| dir.join("baseline.bin"))
+    }
+
+    pub fn load() -> Option<Self> {
+        let path = Self::path()?;
+        let file = File::open(&path).ok()?;
+
+        let result = zstd::Decoder::new(BufReader::new(file))
+            .map_err(|e| e.into())
+            .and_then(bincode::deserialize_from::<_, Self>);
+
+        match result {
+            Ok(baseline) => Some(baseline),
+            Err(e) => {
+                warn!("Failed to read baseline {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let path = Self::path().ok_or("no data directory")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let partial = path.with_extension("bin.partial");
+        let mut encoder = zstd::Encoder::new(BufWriter::new(File::create(&partial)?), COMPRESSION_LEVEL)?;
+        bincode::serialize_into(&mut encoder, self)?;
+        encoder.finish()?.flush()?;
+        std::fs::rename(&partial, &path)?;
+
+        info!("Saved baseline of {} files to {}", self.files.len(), path.display());
+        Ok(())
+    }
+
+    pub fn clear() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(path) = Self::path() {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the files already present in the baseline from `result`, and
+    /// returns how many there were.
+    pub fn hide_known_files(&self, result: &mut ScanResult) -> usize {
+        let known: HashSet<FileId> = result.files_by_category
+            .values()
+            .flatten()
+            .map(|file| FileId::of(file))
+            .filter(|id| self.files.contains(id))
+            .collect();
+        result.remove_files(&known);
+        known.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+    use crate::file_category::FileCategory;
+
+    fn scan_result(files: usize) -> ScanResult {
+        let categories = [FileCategory::TemporaryFiles, FileCategory::CacheFiles, FileCategory::LogFiles];
+        let mut files_by_category: HashMap<FileCategory, Vec<PathBuf>> = HashMap::new();
+        for index in 0..files {
+            let file = PathBuf::from(format!("/home/user/.cache/app-{}/data/entry-{:08}.tmp", index % 97, index));
+            files_by_category.entry(categories[index % categories.len()]).or_default().push(file);
+        }
+        ScanResult {
+            total_files: files,
+            total_size: files as u64 * 4096,
+            files_by_category,
+            scan_duration: Duration::from_secs(3),
+            download_sources: HashMap::new(),
+            cache_rebuild_sizes: HashMap::new(),
+            coverage: None,
+            category_stats: HashMap::new(),
+            identities: HashMap::new(),
+            placeholder_files: 0,
+            placeholder_size: 0,
+            cancelled: false,
+            recently_used_files: 0,
+            temps_in_use: 0,
+            roots: Vec::new(),
+            dev_artifacts: Vec::new(),
+            package_cache_sizes: HashMap::new(),
+            locked_files: HashMap::new(),
+            download_buckets: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trips() {
+        let result = scan_result(1000);
+        let scan_paths = vec![PathBuf::from("/home/user")];
+        let mut bytes = Vec::new();
+        write_snapshot(&mut bytes, &scan_paths, &result).unwrap();
+
+        let snapshot = read_snapshot(bytes.as_slice()).unwrap();
+        assert_eq!(snapshot.scan_paths, scan_paths);
+        assert_eq!(snapshot.result.total_files, 1000);
+        assert_eq!(snapshot.result.files_by_category, result.files_by_category);
+    }
+
+    #[test]
+    fn snapshot_of_another_version_is_refused() {
+        let mut bytes = Vec::new();
+        write_snapshot(&mut bytes, &[], &scan_result(10)).unwrap();
+        bytes[4..8].copy_from_slice(&(FORMAT_VERSION - 1).to_le_bytes());
+        assert!(read_snapshot(bytes.as_slice()).is_err());
+
+        bytes[..4].copy_from_slice(b"JSON");
+        bytes[4..8].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        assert!(read_snapshot(bytes.as_slice()).is_err());
+    }
+
+    /// Compares the snapshot format with JSON on a large scan, `cargo test
+    /// --release snapshot_benchmark -- --nocapture` prints the figures.
+    #[test]
+    fn snapshot_benchmark() {
+        let result = scan_result(200_000);
+
+        let start = Instant::now();
+        let mut bytes = Vec::new();
+        write_snapshot(&mut bytes, &[], &result).unwrap();
+        let written = start.elapsed();
+        let start = Instant::now();
+        let snapshot = read_snapshot(bytes.as_slice()).unwrap();
+        let read = start.elapsed();
+        assert_eq!(snapshot.result.total_files, result.total_files);
+
+        let start = Instant::now();
+        let json = serde_json::to_vec(&result).unwrap();
+        let json_written = start.elapsed();
+        let start = Instant::now();
+        let _: ScanResult = serde_json::from_slice(&json).unwrap();
+        let json_read = start.elapsed();
+
+        println!(
+            "200000 files: snapshot {} bytes, written in {:?}, read in {:?}; JSON {} bytes, written in {:?}, read in {:?}",
+            bytes.len(), written, read, json.len(), json_written, json_read
+        );
+        assert!(bytes.len() * 10 < json.len());
+    }
+}
@@ -0,0 +1,204 @@
+//! Disk space taken by the application's own files, and the pruning that
+//! keeps it in check: the cleanup history is cut to its most recent records
+//! and the caches to a size cap, evicting the least recently used files.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+use walkdir::WalkDir;
+
+use crate::config;
+use crate::history;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageSettings {
+    /// Cleanup records kept in the history, all of them when `None`
+    #[serde(default)]
+    pub keep_reports: Option<usize>,
+    /// The caches are trimmed to this size, least recently used files first
+    #[serde(default = "default_max_cache_mb")]
+    pub max_cache_mb: u64,
+}
+
+fn default_max_cache_mb() -> u64 {
+    1024
+}
+
+impl Default for StorageSettings {
+    fn default() -> Self {
+        Self {
+            keep_reports: None,
+            max_cache_mb: default_max_cache_mb(),
+        }
+    }
+}
+
+/// A kind of file the application keeps, with the space it takes.
+#[derive(Debug, Clone)]
+pub struct StorageArea {
+    pub name: &'static str,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+fn reports_dir() -> Option<PathBuf> {
+    config::app_data_dir().map(|dir| dir.join("audit"))
+}
+
+/// What the application stores, area by area. Areas that do not exist yet are left out.
+pub fn usage() -> Vec<StorageArea> {
+    let cache = config::app_cache_dir();
+    let data = config::app_data_dir();
+    let areas: [(&'static str, Option<PathBuf>); 9] = [
+        ("Last scan", cache.as_ref().map(|dir| dir.join("last_scan.bin"))),
+        ("Folder sizes", cache.as_ref().map(|dir| dir.join("dir_sizes.bin"))),
+        ("File hashes", cache.as_ref().map(|dir| dir.join("hashes.bin"))),
+        ("Browser history copies", cache.as_ref().map(|dir| dir.join("browser-history"))),
+        ("Cleanup history", history::history_path()),
+        ("Signed cleanup records", reports_dir()),
+        ("Baseline", data.as_ref().map(|dir| dir.join("baseline.bin"))),
+        ("Quarantine", data.as_ref().map(|dir| dir.join(config::QUARANTINE_DIR_NAME))),
+        ("Logs", crate::logging::log_dir()),
+    ];
+    areas.into_iter()
+        .filter_map(|(name, path)| {
+            let path = path?;
+            path.exists().then(|| StorageArea { name, size: size_of(&path), path })
+        })
+        .collect()
+}
+
+fn size_of(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Applies `settings` to the stored files. Returns the bytes freed.
+pub fn prune(settings: &StorageSettings) -> u64 {
+    let mut freed = 0;
+    if let Some(keep) = settings.keep_reports {
+        freed += prune_reports(keep);
+        freed += prune_history(keep);
+    }
+    freed += trim_caches(settings.max_cache_mb * 1024 * 1024);
+    if freed > 0 {
+        info!("Pruned {} bytes of history and caches", freed);
+    }
+    freed
+}
+
+/// The cleanup records, oldest first, each as its report and signature. The
+/// names hold the time of the run, so they sort by age.
+fn report_files() -> Vec<Vec<PathBuf>> {
+    let Some(dir) = reports_dir() else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut reports: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("cleanup-")))
+        .collect();
+    reports.sort();
+    reports.into_iter()
+        .map(|report| {
+            let signature = report.with_extension("sig");
+            vec![report, signature]
+        })
+        .collect()
+}
+
+/// Removes all but the `keep` most recent cleanup records.
+fn prune_reports(keep: usize) -> u64 {
+    let reports = report_files();
+    let excess = reports.len().saturating_sub(keep);
+    reports.iter().take(excess).flatten().map(|file| remove(file)).sum()
+}
+
+/// Drops all but the `keep` most recent sessions of the cleanup history.
+fn prune_history(keep: usize) -> u64 {
+    let Some(path) = history::history_path() else {
+        return 0;
+    };
+    let before = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+    if let Err(e) = history::prune(keep) {
+        warn!("Failed to prune the cleanup history: {}", e);
+    }
+    let after = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+    before.saturating_sub(after)
+}
+
+/// Evicts the least recently used cache files until the caches fit in `max_size`.
+fn trim_caches(max_size: u64) -> u64 {
+    let Some(dir) = config::app_cache_dir() else {
+        return 0;
+    };
+    let mut files: Vec<(PathBuf, u64, SystemTime)> = WalkDir::new(&dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let used = metadata.accessed().or_else(|_| metadata.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+            metadata.is_file().then(|| (entry.into_path(), metadata.len(), used))
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    if total <= max_size {
+        return 0;
+    }
+
+    files.sort_by_key(|(_, _, used)| *used);
+    let mut freed = 0;
+    for (file, size, _) in files {
+        if total <= max_size {
+            break;
+        }
+        if remove(&file) > 0 || size == 0 {
+            total -= size;
+            freed += size;
+        }
+    }
+    freed
+}
+
+/// Removes the caches and the cleanup history. The quarantine, the baseline
+/// and the settings are kept, as they hold the user's files and choices.
+/// Returns the bytes freed.
+pub fn clear_caches_and_history() -> u64 {
+    let mut freed: u64 = report_files().iter().flatten().map(|file| remove(file)).sum();
+    if let Some(path) = history::history_path() {
+        freed += remove(&path);
+    }
+    if let Some(dir) = config::app_cache_dir() {
+        let size = size_of(&dir);
+        match std::fs::remove_dir_all(&dir) {
+            Ok(()) => freed += size,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove the cache directory {}: {}", dir.display(), e),
+        }
+    }
+    info!("Cleared {} bytes of caches and history", freed);
+    freed
+}
+
+/// Removes `file`, returning its size, or 0 when it could not be removed.
+fn remove(file: &Path) -> u64 {
+    let size = std::fs::metadata(file).map_or(0, |metadata| metadata.len());
+    match std::fs::remove_file(file) {
+        Ok(()) => size,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(e) => {
+            warn!("Failed to remove {}: {}", file.display(), e);
+            0
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use log::info;
+
+/// A folder kept in sync with a cloud storage provider.
+#[derive(Debug, Clone)]
+pub struct SyncFolder {
+    pub path: PathBuf,
+    pub provider: &'static str,
+}
+
+impl SyncFolder {
+    pub fn warning(&self) -> String {
+        format!(
+            "{} folder: deleting files here also deletes them from the cloud and every synced device",
+            self.provider
+        )
+    }
+}
+
+/// Finds the Dropbox, OneDrive, Google Drive and iCloud folders of the user.
+pub fn detect_sync_folders() -> Vec<SyncFolder> {
+    let mut folders = Vec::new();
+    let home = dirs::home_dir().unwrap_or_default();
+
+    // Dropbox records its folders in info.json, the default name is a fallback
+    let dropbox_info = if cfg!(windows) {
+        dirs::data_local_dir().map(|dir| dir.join("Dropbox").join("info.json"))
+    } else {
+        Some(home.join(".dropbox").join("info.json"))
+    };
+    if let Some(info) = dropbox_info.and_then(|path| std::fs::read_to_string(path).ok()) {
+        if let Ok(accounts) = serde_json::from_str::<serde_json::Value>(&info) {
+            for account in accounts.as_object().into_iter().flat_map(|accounts| accounts.values()) {
+                if let Some(path) = account["path"].as_str() {
+                    folders.push(SyncFolder { path: PathBuf::from(path), provider: "Dropbox" });
+                }
+            }
+        }
+    }
+
+    let mut candidates: Vec<(PathBuf, &'static str)> = vec![
+        (home.join("Dropbox"), "Dropbox"),
+        (home.join("OneDrive"), "OneDrive"),
+        (home.join("Google Drive"), "Google Drive"),
+        (home.join("Library").join("Mobile Documents"), "iCloud Drive"),
+    ];
+    for variable in ["OneDrive", "OneDriveCommercial", "OneDriveConsumer"] {
+        if let Some(path) = std::env::var_os(variable) {
+            candidates.push((PathBuf::from(path), "OneDrive"));
+        }
+    }
+
+    // macOS File Provider locations, e.g. ~/Library/CloudStorage/GoogleDrive-me@example.com
+    if let Ok(entries) = std::fs::read_dir(home.join("Library").join("CloudStorage")) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            let provider = if name.starts_with("onedrive") {
+                "OneDrive"
+            } else if name.starts_with("googledrive") {
+                "Google Drive"
+            } else if name.starts_with("dropbox") {
+                "Dropbox"
+            } else {
+                "Cloud storage"
+            };
+            candidates.push((entry.path(), provider));
+        }
+    }
+
+    for (path, provider) in candidates {
+        if path.is_dir() && !folders.iter().any(|folder| folder.path == path) {
+            folders.push(SyncFolder { path, provider });
+        }
+    }
+
+    info!("Detected {} cloud sync folders", folders.len());
+    folders
+}
+
+pub fn sync_folder_for<'a>(folders: &'a [SyncFolder], path: &Path) -> Option<&'a SyncFolder> {
+    folders.iter().find(|folder| path.starts_with(&folder.path))
+}
+
+/// Whether a file inside a sync folder is an online-only placeholder whose
+/// content is not stored locally, so deleting it frees no space on this machine.
+#[cfg(windows)]
+pub fn is_placeholder(_path: &Path, metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+    const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+
+    metadata.file_attributes()
+        & (FILE_ATTRIBUTE_OFFLINE | FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS)
+        != 0
+}
+
+/// iCloud keeps evicted files as hidden `.name.icloud` stubs, and the file
+/// provider clients leave dataless files that occupy no blocks. Only meaningful
+/// inside sync folders: elsewhere tiny files can be stored without blocks too.
+#[cfg(unix)]
+pub fn is_placeholder(path: &Path, metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let icloud_stub = path.file_name()
+        .map(|name| {
+            let name = name.to_string_lossy();
+            name.starts_with('.') && name.ends_with(".icloud")
+        })
+        .unwrap_or(false);
+
+    icloud_stub || (metadata.len() > 0 && metadata.blocks() == 0)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn is_placeholder(_path: &Path, _metadata: &Metadata) -> bool {
+    false
+}
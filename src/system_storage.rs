@@ -0,0 +1,163 @@
+//! Space the cleaner deliberately leaves alone, such as system restore points
+//! and phone backups, whose files only the system knows how to remove safely.
+//! Instead of a dead end, each one links to the tool of the system that
+//! manages it: Windows Storage settings, macOS Storage Management or GNOME's
+//! Disk Usage Analyzer.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A program of the system that shows or frees disk space.
+#[derive(Debug, Clone, Copy)]
+pub struct StorageTool {
+    pub name: &'static str,
+    program: &'static str,
+    args: &'static [&'static str],
+}
+
+impl StorageTool {
+    /// Starts the tool without waiting for it to close.
+    pub fn open(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Command::new(self.program).args(self.args).spawn()?;
+        Ok(())
+    }
+}
+
+/// Something taking disk space that the cleaner will not touch.
+#[derive(Debug, Clone)]
+pub struct ManagedArea {
+    pub name: &'static str,
+    /// Why it is left to the system
+    pub reason: &'static str,
+    /// Where its files are, when they sit in a folder of their own
+    pub path: Option<PathBuf>,
+    pub tool: StorageTool,
+}
+
+/// The general storage tool of this system, if it has one installed.
+pub fn storage_tool() -> Option<StorageTool> {
+    platform::storage_tool()
+}
+
+/// The areas found on this computer.
+pub fn managed_areas() -> Vec<ManagedArea> {
+    platform::managed_areas()
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{ManagedArea, StorageTool};
+
+    const STORAGE_SETTINGS: StorageTool = StorageTool {
+        name: "Windows Storage settings",
+        program: "explorer",
+        args: &["ms-settings:storagesense"],
+    };
+
+    const SYSTEM_PROTECTION: StorageTool = StorageTool {
+        name: "System Protection",
+        program: "SystemPropertiesProtection",
+        args: &[],
+    };
+
+    pub fn storage_tool() -> Option<StorageTool> {
+        Some(STORAGE_SETTINGS)
+    }
+
+    pub fn managed_areas() -> Vec<ManagedArea> {
+        let mut areas = vec![ManagedArea {
+            name: "System restore points",
+            reason: "Kept in System Volume Information, which Windows alone can read and prune",
+            path: None,
+            tool: SYSTEM_PROTECTION,
+        }];
+        // iTunes from apple.com and the Microsoft Store app keep backups in different places
+        let backups = [dirs::data_dir().map(|dir| dir.join("Apple Computer")), dirs::home_dir().map(|dir| dir.join("Apple"))];
+        for backup in backups.into_iter().flatten().map(|dir| dir.join("MobileSync").join("Backup")) {
+            if backup.is_dir() {
+                areas.push(ManagedArea {
+                    name: "iPhone and iPad backups",
+                    reason: "A backup missing any of its files cannot be restored; delete whole backups from the Apple Devices app or iTunes",
+                    path: Some(backup),
+                    tool: STORAGE_SETTINGS,
+                });
+            }
+        }
+        areas
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{ManagedArea, StorageTool};
+
+    const STORAGE_MANAGEMENT: StorageTool = StorageTool {
+        name: "Storage Management",
+        program: "open",
+        args: &["x-apple.systempreferences:com.apple.settings.Storage"],
+    };
+
+    pub fn storage_tool() -> Option<StorageTool> {
+        Some(STORAGE_MANAGEMENT)
+    }
+
+    pub fn managed_areas() -> Vec<ManagedArea> {
+        let mut areas = vec![ManagedArea {
+            name: "Time Machine local snapshots",
+            reason: "Held by the file system itself and thinned by macOS when space runs low",
+            path: None,
+            tool: STORAGE_MANAGEMENT,
+        }];
+        let backups = dirs::home_dir().map(|home| home.join("Library/Application Support/MobileSync/Backup"));
+        if let Some(backups) = backups.filter(|backups| backups.is_dir()) {
+            areas.push(ManagedArea {
+                name: "iPhone and iPad backups",
+                reason: "A backup missing any of its files cannot be restored; delete whole backups from Storage Management or Finder",
+                path: Some(backups),
+                tool: STORAGE_MANAGEMENT,
+            });
+        }
+        areas
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+mod platform {
+    use std::path::Path;
+    use super::{ManagedArea, StorageTool};
+
+    const DISK_USAGE_ANALYZER: StorageTool = StorageTool {
+        name: "Disk Usage Analyzer",
+        program: "baobab",
+        args: &[],
+    };
+
+    const TIMESHIFT: StorageTool = StorageTool {
+        name: "Timeshift",
+        program: "timeshift-launcher",
+        args: &[],
+    };
+
+    pub fn storage_tool() -> Option<StorageTool> {
+        is_installed(DISK_USAGE_ANALYZER.program).then_some(DISK_USAGE_ANALYZER)
+    }
+
+    pub fn managed_areas() -> Vec<ManagedArea> {
+        let mut areas = Vec::new();
+        let snapshots = Path::new("/timeshift/snapshots");
+        if snapshots.is_dir() && is_installed(TIMESHIFT.program) {
+            areas.push(ManagedArea {
+                name: "Timeshift restore points",
+                reason: "Snapshots share files through hard links; removing files from one can break the others",
+                path: Some(snapshots.to_path_buf()),
+                tool: TIMESHIFT,
+            });
+        }
+        areas
+    }
+
+    fn is_installed(program: &str) -> bool {
+        std::env::var_os("PATH")
+            .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+    }
+}
@@ -0,0 +1,617 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use log::{info, error};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::audit;
+use crate::cleaner::Cleaner;
+use crate::error::Error;
+use crate::config::Config;
+use crate::duplicate_finder::DuplicateFinder;
+use crate::file_identity::FileId;
+use crate::file_category::FileCategory;
+use crate::formatting;
+use crate::progress::{ProgressHub, ProgressState};
+use crate::scanner::{self, Scanner, ScanResult};
+
+/// How long to wait for a key before redrawing the progress of background work.
+const TICK: Duration = Duration::from_millis(100);
+
+const HIGHLIGHT: Style = Style::new().bg(Color::DarkGray).add_modifier(Modifier::BOLD);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Screen {
+    Categories,
+    Files(FileCategory),
+    Duplicates,
+    Confirm,
+}
+
+/// Terminal frontend for servers and SSH sessions: scans `paths`, lists the
+/// categories and duplicates found and cleans what the user ticks after
+/// asking for confirmation, like the window does.
+pub fn run(paths: Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut terminal = ratatui::try_init()?;
+    let result = Tui::new(paths).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct Tui {
+    config: Config,
+    roots: Vec<PathBuf>,
+    progress: ProgressHub,
+    /// What failed last and why, shown until the next task starts
+    error: Option<(&'static str, Error)>,
+    results: Option<ScanResult>,
+    duplicates: Vec<Vec<PathBuf>>,
+    /// Duplicate groups to clean, keeping the first file of each
+    duplicate_groups_ticked: HashSet<usize>,
+    selected_categories: HashMap<FileCategory, bool>,
+    screen: Screen,
+    categories: TableState,
+    files: ListState,
+    duplicate_groups: ListState,
+    files_to_delete: Vec<PathBuf>,
+    commands_to_run: Vec<String>,
+    status: String,
+    quit: bool,
+}
+
+impl Tui {
+    fn new(roots: Vec<PathBuf>) -> Self {
+        let config = Config::load();
+        let selected_categories = FileCategory::all()
+            .into_iter()
+            .map(|category| (category, config.is_category_preselected(category)))
+            .collect();
+
+        Self {
+            config,
+            roots,
+            progress: ProgressHub::default(),
+            error: None,
+            results: None,
+            duplicates: Vec::new(),
+            duplicate_groups_ticked: HashSet::new(),
+            selected_categories,
+            screen: Screen::Categories,
+            categories: TableState::default().with_selected(0),
+            files: ListState::default().with_selected(Some(0)),
+            duplicate_groups: ListState::default().with_selected(Some(0)),
+            files_to_delete: Vec::new(),
+            commands_to_run: Vec::new(),
+            status: String::new(),
+            quit: false,
+        }
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.start_scan();
+        while !self.quit {
+            self.check_background_tasks();
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if event::poll(TICK)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.handle_key(key.code);
+                    }
+                }
+            }
+        }
+
+        // Let a running cleanup stop at a safe point rather than mid-file
+        if self.progress.is_busy() {
+            self.progress.request_cancel();
+        }
+        Ok(())
+    }
+
+    fn start_scan(&mut self) {
+        if self.progress.is_busy() {
+            return;
+        }
+        self.results = None;
+        self.duplicates.clear();
+        self.duplicate_groups_ticked.clear();
+        self.screen = Screen::Categories;
+
+        let progress = self.progress.start_task();
+        self.error = None;
+        let config = self.config.clone();
+        let roots = self.roots.clone();
+        thread::spawn(move || {
+            match Scanner::new(config).scan(&roots, progress.clone()) {
+                Ok(results) => progress.set_scan_complete(results),
+                Err(e) => {
+                    error!("Scan failed: {}", e);
+                    progress.set_error("Scan", e);
+                }
+            }
+        });
+    }
+
+    fn start_duplicate_scan(&mut self) {
+        let Some(results) = &self.results else {
+            return;
+        };
+        if self.progress.is_busy() {
+            return;
+        }
+
+        let progress = self.progress.start_task();
+        self.error = None;
+        let files = results.live_files();
+        let min_age = Duration::from_secs(u64::from(self.config.duplicate_min_age_hours) * 60 * 60);
+        let hash_network_files = self.config.hash_network_files;
+        let keep_policy = self.config.duplicate_keep_policy;
+        let preferred_folders = self.config.preferred_folders.clone();
+        thread::spawn(move || {
+            let mut finder = DuplicateFinder::new()
+                .with_min_age(min_age)
+                .with_network_hashing(hash_network_files)
+                .with_keep_policy(keep_policy, &preferred_folders);
+            let duplicates = finder.find_duplicates(&files, progress.clone());
+            progress.set_duplicates_complete(duplicates);
+        });
+    }
+
+    /// Collects the files of the ticked categories and duplicate groups and
+    /// asks for confirmation. Protected and excluded files are left out.
+    fn prepare_cleanup(&mut self) {
+        let Some(results) = &self.results else {
+            return;
+        };
+
+        let mut files: Vec<PathBuf> = Vec::new();
+        self.commands_to_run.clear();
+        for category in self.visible_categories() {
+            if !self.selected_categories.get(&category).copied().unwrap_or(false) {
+                continue;
+            }
+            // Read-only and immutable files stay, as cleaning them would fail
+            files.extend(results.files_by_category.get(&category).into_iter().flatten()
+                .filter(|file| !results.locked_files.contains_key(&FileId::of(file)))
+                .cloned());
+            if let Some(command) = self.config.category_commands.get(&category) {
+                self.commands_to_run.push(command.clone());
+            }
+        }
+        for index in &self.duplicate_groups_ticked {
+            if let Some(group) = self.duplicates.get(*index) {
+                files.extend(group.iter().skip(1).cloned());
+            }
+        }
+
+        // Folders the other deletions leave empty go with them
+        if self.selected_categories.get(&FileCategory::EmptyFolders).copied().unwrap_or(false) {
+            let emptied = scanner::folders_emptied_by(&files, &self.roots);
+            files.extend(emptied);
+        }
+
+        files.sort();
+        files.dedup();
+        files.retain(|file| !self.config.is_path_protected(file) && !self.config.is_path_excluded(file));
+        self.files_to_delete = files;
+
+        if self.files_to_delete.is_empty() && self.commands_to_run.is_empty() {
+            self.status = "Nothing is ticked for cleanup".to_string();
+        } else {
+            self.screen = Screen::Confirm;
+        }
+    }
+
+    fn execute_cleanup(&mut self) {
+        self.screen = Screen::Categories;
+        let files = std::mem::take(&mut self.files_to_delete);
+        let commands = std::mem::take(&mut self.commands_to_run);
+        let identities = self.results.as_ref()
+            .map(|results| results.identities_of(&files))
+            .unwrap_or_default();
+
+        let progress = self.progress.start_task();
+        self.error = None;
+        let use_trash = self.config.use_trash;
+        let use_quarantine = self.config.use_quarantine;
+        let checksum_manifest = self.config.checksum_manifest;
+        let threads = self.config.cleanup_threads;
+        let trash_fallback = self.config.trash_fallback;
+        let sign_record = self.config.sign_cleanup_records;
+        let roots = self.roots.clone();
+        thread::spawn(move || {
+            let cleaner = Cleaner::new(use_trash)
+                .with_quarantine(use_quarantine)
+                .with_checksum_manifest(checksum_manifest)
+                .with_threads(threads)
+                .with_trash_fallback(trash_fallback)
+                .with_allowed_roots(&roots)
+                .with_expected_identities(identities);
+            match cleaner.clean_files(&files, progress.clone()) {
+                Ok(report) => {
+                    if sign_record && !report.files.is_empty() {
+                        if let Err(e) = audit::write_signed_report(&report) {
+                            error!("Failed to write signed cleanup record: {}", e);
+                        }
+                    }
+                    let command_outputs = if progress.is_cancelled() {
+                        Vec::new()
+                    } else {
+                        cleaner.run_commands(&commands)
+                    };
+                    progress.set_cleanup_complete(report, command_outputs);
+                }
+                Err(e) => {
+                    error!("Cleanup failed: {}", e);
+                    progress.set_error("Cleanup", e);
+                }
+            }
+        });
+    }
+
+    fn check_background_tasks(&mut self) {
+        for state in self.progress.poll() {
+            self.finish_task(state);
+        }
+    }
+
+    fn finish_task(&mut self, state: ProgressState) {
+        if let ProgressState::Complete { .. } = state {
+            self.error = None;
+        }
+        match state {
+            ProgressState::Complete { scan_result: Some(results), .. } => {
+                self.status = format!(
+                    "Scanned {} files, {} in {:.1}s{}",
+                    results.total_files,
+                    formatting::format_size(results.total_size),
+                    results.scan_duration.as_secs_f64(),
+                    if results.cancelled { " (cancelled, partial results)" } else { "" }
+                );
+                self.results = Some(*results);
+                self.categories.select(Some(0));
+            }
+            ProgressState::Complete { duplicates: Some(duplicates), .. } => {
+                self.status = format!(
+                    "Found {} groups of duplicates{}",
+                    duplicates.groups.len(),
+                    if duplicates.cancelled { " (cancelled, partial results)" } else { "" }
+                );
+                self.duplicates = duplicates.groups;
+                self.duplicate_groups_ticked.clear();
+                self.duplicate_groups.select(Some(0));
+            }
+            ProgressState::Complete { cleaned_bytes: Some(bytes), command_outputs, cleaned_files, files_left, .. } => {
+                let failed = command_outputs.iter().filter(|output| !output.success).count();
+                info!("Cleaned {} from the terminal interface", formatting::format_size(bytes));
+                // The results no longer match the disk, scan again
+                self.start_scan();
+                self.status = match files_left {
+                    Some(files_left) => format!(
+                        "Cancelled after cleaning {} files ({}), {} files left in place",
+                        cleaned_files,
+                        formatting::format_size(bytes),
+                        files_left
+                    ),
+                    None => format!(
+                        "Cleaned {}{}",
+                        formatting::format_size(bytes),
+                        if failed > 0 { format!(", {} commands failed", failed) } else { String::new() }
+                    ),
+                };
+            }
+            ProgressState::Error { action, error } => self.error = Some((action, error)),
+            _ => {}
+        }
+    }
+
+    fn visible_categories(&self) -> Vec<FileCategory> {
+        let Some(results) = &self.results else {
+            return Vec::new();
+        };
+        FileCategory::all()
+            .into_iter()
+            .filter(|category| self.config.is_category_enabled(*category))
+            .filter(|category| results.files_by_category.get(category).is_some_and(|files| !files.is_empty()))
+            .collect()
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        if code == KeyCode::Char('q') {
+            self.quit = true;
+            return;
+        }
+        if self.progress.is_busy() {
+            if code == KeyCode::Char('x') {
+                self.progress.request_cancel();
+            }
+            return;
+        }
+
+        match self.screen {
+            Screen::Categories => {
+                let categories = self.visible_categories();
+                let current = self.categories.selected().and_then(|index| categories.get(index)).copied();
+                match code {
+                    KeyCode::Up | KeyCode::Char('k') => self.categories.select_previous(),
+                    KeyCode::Down | KeyCode::Char('j') => self.categories.select_next(),
+                    KeyCode::Char(' ') => {
+                        if let Some(category) = current {
+                            let selected = self.selected_categories.entry(category).or_default();
+                            *selected = !*selected;
+                        }
+                    }
+                    KeyCode::Enter => {
+                        if let Some(category) = current {
+                            self.files.select(Some(0));
+                            self.screen = Screen::Files(category);
+                        }
+                    }
+                    KeyCode::Char('d') => self.start_duplicate_scan(),
+                    KeyCode::Char('v') => self.screen = Screen::Duplicates,
+                    KeyCode::Char('c') => self.prepare_cleanup(),
+                    KeyCode::Char('r') => self.start_scan(),
+                    _ => {}
+                }
+            }
+            Screen::Files(_) => match code {
+                KeyCode::Up | KeyCode::Char('k') => self.files.select_previous(),
+                KeyCode::Down | KeyCode::Char('j') => self.files.select_next(),
+                KeyCode::PageUp => self.files.scroll_up_by(20),
+                KeyCode::PageDown => self.files.scroll_down_by(20),
+                KeyCode::Esc | KeyCode::Backspace => self.screen = Screen::Categories,
+                _ => {}
+            },
+            Screen::Duplicates => match code {
+                KeyCode::Up | KeyCode::Char('k') => self.duplicate_groups.select_previous(),
+                KeyCode::Down | KeyCode::Char('j') => self.duplicate_groups.select_next(),
+                KeyCode::Char(' ') => {
+                    if let Some(index) = self.duplicate_groups.selected().filter(|index| *index < self.duplicates.len()) {
+                        if !self.duplicate_groups_ticked.remove(&index) {
+                            self.duplicate_groups_ticked.insert(index);
+                        }
+                    }
+                }
+                KeyCode::Char('c') => self.prepare_cleanup(),
+                KeyCode::Esc | KeyCode::Backspace => self.screen = Screen::Categories,
+                _ => {}
+            },
+            Screen::Confirm => match code {
+                KeyCode::Char('y') => self.execute_cleanup(),
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    self.files_to_delete.clear();
+                    self.screen = Screen::Categories;
+                }
+                _ => {}
+            },
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [title, body, progress, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let scanned = self.roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>().join(", ");
+        frame.render_widget(
+            Paragraph::new(format!("Intelligent Disk Cleaner: {}", scanned)).style(Style::new().add_modifier(Modifier::BOLD)),
+            title,
+        );
+
+        match self.screen {
+            Screen::Categories => self.draw_categories(frame, body),
+            Screen::Files(category) => self.draw_files(frame, body, category),
+            Screen::Duplicates => self.draw_duplicates(frame, body),
+            Screen::Confirm => self.draw_confirmation(frame, body),
+        }
+
+        self.draw_progress(frame, progress);
+
+        let keys = if self.progress.is_busy() {
+            "x cancel  q quit"
+        } else {
+            match self.screen {
+                Screen::Categories => "↑↓ move  space tick  enter files  d find duplicates  v view duplicates  c clean  r rescan  q quit",
+                Screen::Files(_) => "↑↓ move  esc back  q quit",
+                Screen::Duplicates => "↑↓ move  space tick group  c clean  esc back  q quit",
+                Screen::Confirm => "y clean  n cancel",
+            }
+        };
+        frame.render_widget(Paragraph::new(keys).style(Style::new().fg(Color::DarkGray)), help);
+    }
+
+    fn draw_categories(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(results) = &self.results else {
+            frame.render_widget(Paragraph::new("Scanning...").block(Block::bordered().title("Categories")), area);
+            return;
+        };
+
+        let mut selected_size = 0;
+        let rows: Vec<Row> = self.visible_categories()
+            .into_iter()
+            .map(|category| {
+                let files = results.files_by_category.get(&category).map_or(0, Vec::len);
+                let size = results.category_stats.get(&category).map_or(0, |stats| stats.size);
+                let selected = self.selected_categories.get(&category).copied().unwrap_or(false);
+                if selected {
+                    selected_size += size;
+                }
+                Row::new(vec![
+                    if selected { "[x]" } else { "[ ]" }.to_string(),
+                    self.config.category_name(category),
+                    format!("{} files", files),
+                    formatting::format_size(size),
+                ])
+            })
+            .collect();
+
+        let table = Table::new(rows, [Constraint::Length(3), Constraint::Fill(1), Constraint::Length(14), Constraint::Length(12)])
+            .block(Block::bordered().title(format!("Categories, {} ticked", formatting::format_size(selected_size))))
+            .row_highlight_style(HIGHLIGHT);
+        frame.render_stateful_widget(table, area, &mut self.categories);
+    }
+
+    fn draw_files(&mut self, frame: &mut Frame, area: Rect, category: FileCategory) {
+        let files = self.results.as_ref()
+            .and_then(|results| results.files_by_category.get(&category))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let items: Vec<ListItem> = files.iter()
+            .map(|file| {
+                let mut text = file.display().to_string();
+                if self.config.is_path_protected(file) {
+                    text.push_str("  (protected)");
+                }
+                ListItem::new(text)
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(Block::bordered().title(format!("{}, {} files", self.config.category_name(category), files.len())))
+            .highlight_style(HIGHLIGHT);
+        frame.render_stateful_widget(list, area, &mut self.files);
+    }
+
+    fn draw_duplicates(&mut self, frame: &mut Frame, area: Rect) {
+        if self.duplicates.is_empty() {
+            let text = if self.results.is_some() { "No duplicates found yet, press d on the categories to look for them." } else { "Scanning..." };
+            frame.render_widget(Paragraph::new(text).block(Block::bordered().title("Duplicates")), area);
+            return;
+        }
+
+        let [groups, files] = Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(area);
+        let items: Vec<ListItem> = self.duplicates.iter()
+            .enumerate()
+            .map(|(index, group)| {
+                let name = group[0].file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+                let ticked = if self.duplicate_groups_ticked.contains(&index) { "[x]" } else { "[ ]" };
+                ListItem::new(format!("{} {} ({} copies)", ticked, name, group.len()))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!("Duplicates, {} groups", self.duplicates.len())))
+            .highlight_style(HIGHLIGHT);
+        frame.render_stateful_widget(list, groups, &mut self.duplicate_groups);
+
+        let group = self.duplicate_groups.selected().and_then(|index| self.duplicates.get(index));
+        let lines: Vec<String> = group.into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(index, file)| format!("{} {}", if index == 0 { "keep  " } else { "delete" }, file.display()))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines.join("\n")).block(Block::bordered().title("Files")).wrap(Wrap { trim: false }),
+            files,
+        );
+    }
+
+    fn draw_confirmation(&self, frame: &mut Frame, area: Rect) {
+        let size: u64 = self.files_to_delete.iter()
+            .filter_map(|file| std::fs::symlink_metadata(file).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        let mut lines = vec![
+            format!(
+                "{} {} files, {}?",
+                if self.config.use_quarantine {
+                    "Move to the quarantine"
+                } else if self.config.use_trash {
+                    "Move to the trash"
+                } else {
+                    "Permanently delete"
+                },
+                self.files_to_delete.len(),
+                formatting::format_size(size)
+            ),
+            String::new(),
+        ];
+        lines.extend(self.files_to_delete.iter().take(usize::from(area.height)).map(|file| file.display().to_string()));
+        if !self.commands_to_run.is_empty() {
+            lines.push(String::new());
+            lines.push("The following commands will be run:".to_string());
+            lines.extend(self.commands_to_run.iter().cloned());
+        }
+
+        frame.render_widget(
+            Paragraph::new(lines.join("\n"))
+                .block(Block::bordered().borders(Borders::ALL).title("Confirm Cleanup").border_style(Style::new().fg(Color::Yellow))),
+            area,
+        );
+    }
+
+    fn draw_progress(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title("Progress");
+        // The latest task, the area has room for one
+        let Some(state) = self.progress.tasks().last().map(|task| &task.state) else {
+            let paragraph = match &self.error {
+                Some((action, error)) => {
+                    let mut message = format!("{} failed: {}", action, error);
+                    if let Some(hint) = error.hint() {
+                        message.push_str(&format!("\n{}", hint));
+                    }
+                    Paragraph::new(message).style(Style::new().fg(Color::Red))
+                }
+                None => Paragraph::new(self.status.as_str()),
+            };
+            frame.render_widget(paragraph.block(block), area);
+            return;
+        };
+        let rate = state.rate_summary().map(|rate| format!(", {}", rate)).unwrap_or_default();
+        match state {
+            ProgressState::Scanning { current_path, files_processed, total_files: None, .. } => {
+                let text = format!("Listing files: {} found{}, {}", files_processed, rate, current_path);
+                frame.render_widget(Paragraph::new(text).block(block), area);
+            }
+            ProgressState::Scanning { files_processed, total_files: Some(total_files), .. } => {
+                let gauge = Gauge::default()
+                    .block(block)
+                    .label(format!("Scanning: {}/{} files{}", files_processed, total_files, rate))
+                    .ratio(state.fraction().unwrap_or(0.0) as f64);
+                frame.render_widget(gauge, area);
+            }
+            ProgressState::FindingDuplicates { files_processed, total_files, bytes_processed, total_bytes, .. } => {
+                let gauge = Gauge::default()
+                    .block(block)
+                    .label(format!(
+                        "Finding duplicates: {}/{} files, {} of {} read{}",
+                        files_processed,
+                        total_files,
+                        formatting::format_size(*bytes_processed),
+                        formatting::format_size(*total_bytes),
+                        rate
+                    ))
+                    .ratio(state.fraction().unwrap_or(0.0) as f64);
+                frame.render_widget(gauge, area);
+            }
+            ProgressState::Cleaning { files_processed, total_files, bytes_processed, total_bytes, freed_bytes, .. } => {
+                let gauge = Gauge::default()
+                    .block(block)
+                    .label(format!(
+                        "Cleaning: {}/{} files, {} of {}, {} freed{}",
+                        files_processed,
+                        total_files,
+                        formatting::format_size(*bytes_processed),
+                        formatting::format_size(*total_bytes),
+                        formatting::format_size(*freed_bytes),
+                        rate
+                    ))
+                    .ratio(state.fraction().unwrap_or(0.0) as f64);
+                frame.render_widget(gauge, area);
+            }
+            ProgressState::Idle | ProgressState::Complete { .. } | ProgressState::Error { .. } => {
+                frame.render_widget(Paragraph::new("Starting...").block(block), area);
+            }
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! File names written in different Unicode normalization forms. macOS keeps
+//! names decomposed (NFD, an `e` followed by a combining accent) while Linux
+//! and Windows keep them as typed, mostly composed (NFC), so the same name
+//! copied between them can differ byte for byte.
+
+use std::borrow::Cow;
+use std::path::Path;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// `text` in composed form (NFC), borrowed when it already is.
+pub fn composed(text: &str) -> Cow<'_, str> {
+    match is_nfc_quick(text.chars()) {
+        IsNormalized::Yes => Cow::Borrowed(text),
+        _ => Cow::Owned(text.nfc().collect()),
+    }
+}
+
+/// `path` as shown to the user, composed so both forms of a name render alike.
+pub fn display(path: &Path) -> String {
+    composed(&path.to_string_lossy()).into_owned()
+}
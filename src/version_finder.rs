@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use log::info;
+
+use crate::unicode_names;
+
+/// File types that are commonly re-downloaded or re-saved as new versions.
+/// Media files are left out, their numbered names (`IMG_1234.jpg`) are not versions.
+const VERSIONED_EXTENSIONS: &[&str] = &[
+    "dmg", "pkg", "exe", "msi", "deb", "rpm", "appimage", "iso",
+    "zip", "gz", "xz", "7z", "rar",
+    "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp",
+];
+
+/// Words that mark a revision of a file rather than a different file.
+const VERSION_WORDS: &[&str] = &["v", "ver", "version", "final", "copy", "new", "latest", "old", "rev", "draft"];
+
+/// Groups files that look like successive versions of the same download or
+/// document, e.g. `app-1.2.3.dmg` and `app-1.2.4.dmg`, or `report_v1.docx` and
+/// `report_final_v7.docx`. Unlike duplicates their contents differ.
+pub struct VersionFinder;
+
+impl VersionFinder {
+    /// Returns the version families found in `files`, each sorted newest first.
+    /// With `unify_names` set, names in either Unicode form can share a family.
+    pub fn find_version_families(files: &[PathBuf], unify_names: bool) -> Vec<Vec<PathBuf>> {
+        let mut families: HashMap<(PathBuf, String, String), Vec<PathBuf>> = HashMap::new();
+
+        for file in files {
+            if let Some(key) = Self::family_key(file, unify_names) {
+                families.entry(key).or_default().push(file.clone());
+            }
+        }
+
+        let mut result: Vec<Vec<PathBuf>> = families
+            .into_values()
+            .filter(|family| family.len() > 1)
+            .map(|mut family| {
+                family.sort_by_cached_key(|file| {
+                    let modified = std::fs::metadata(file)
+                        .and_then(|metadata| metadata.modified())
+                        .unwrap_or(SystemTime::UNIX_EPOCH);
+                    (Self::version_numbers(file), modified)
+                });
+                family.reverse();
+                family
+            })
+            .collect();
+
+        result.sort_by(|a, b| a[0].cmp(&b[0]));
+
+        info!("Found {} version families", result.len());
+        result
+    }
+
+    /// Files of a family share their directory, extension and name once version
+    /// numbers and revision words are removed.
+    fn family_key(path: &Path, unify_names: bool) -> Option<(PathBuf, String, String)> {
+        let mut parent = path.parent()?.to_path_buf();
+        let mut stem = path.file_stem()?.to_string_lossy().to_lowercase();
+        if unify_names {
+            parent = PathBuf::from(unicode_names::display(&parent));
+            stem = unicode_names::composed(&stem).into_owned();
+        }
+        let extension = path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if !VERSIONED_EXTENSIONS.contains(&extension.as_str()) {
+            return None;
+        }
+
+        let (stem, _) = Self::split_copy_number(&stem);
+        let tokens = Self::tokens(stem);
+        let base = tokens.iter()
+            .enumerate()
+            .filter(|&(index, _)| !Self::is_version_token(&tokens, index))
+            .map(|(_, token)| *token)
+            .collect::<Vec<_>>()
+            .join("-");
+
+        if base.is_empty() {
+            return None;
+        }
+
+        Some((parent, extension, base))
+    }
+
+    fn version_numbers(path: &Path) -> Vec<u64> {
+        let stem = path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let (stem, copy) = Self::split_copy_number(&stem);
+        let tokens = Self::tokens(stem);
+
+        (0..tokens.len())
+            .filter(|&index| Self::is_version_token(&tokens, index))
+            .flat_map(|index| tokens[index].trim_start_matches('v').split('.'))
+            .filter_map(|part| part.parse().ok())
+            .chain(copy)
+            .collect()
+    }
+
+    /// Splits off the ` (2)` browsers and file managers append to a second copy.
+    fn split_copy_number(stem: &str) -> (&str, Option<u64>) {
+        stem.strip_suffix(')')
+            .and_then(|rest| rest.rsplit_once(" ("))
+            .and_then(|(base, number)| Some((base, number.parse().ok()?)))
+            .map_or((stem, None), |(base, number)| (base, Some(number)))
+    }
+
+    /// Name words, keeping dotted numbers such as `1.2.3` in one piece.
+    fn tokens(stem: &str) -> Vec<&str> {
+        stem.split(|c: char| !c.is_alphanumeric() && c != '.')
+            .flat_map(|piece| {
+                if Self::is_dotted_number(piece.trim_start_matches('v')) {
+                    vec![piece]
+                } else {
+                    piece.split('.').collect()
+                }
+            })
+            .filter(|token| !token.is_empty())
+            .collect()
+    }
+
+    /// Revision words, dotted numbers (`1.2.3`), `v`-prefixed numbers (`v7`) and
+    /// numbers following a revision word (`rev 3`). Bare numbers such as years or
+    /// page and scan counters tell different files apart and are kept.
+    fn is_version_token(tokens: &[&str], index: usize) -> bool {
+        let token = tokens[index];
+        let number = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_digit());
+
+        VERSION_WORDS.contains(&token)
+            || Self::is_dotted_number(token)
+            || token.strip_prefix('v').is_some_and(|rest| number(rest) || Self::is_dotted_number(rest))
+            || (number(token) && index > 0 && VERSION_WORDS.contains(&tokens[index - 1]))
+    }
+
+    fn is_dotted_number(text: &str) -> bool {
+        let mut parts = text.split('.');
+        parts.clone().count() > 1 && parts.all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn families(names: &[&str]) -> Vec<Vec<PathBuf>> {
+        let files: Vec<PathBuf> = names.iter().map(|name| Path::new("/downloads").join(name)).collect();
+        VersionFinder::find_version_families(&files, false)
+    }
+
+    fn path(name: &str) -> PathBuf {
+        Path::new("/downloads").join(name)
+    }
+
+    #[test]
+    fn groups_successive_versions_newest_first() {
+        assert_eq!(families(&["app-1.2.3.dmg", "app-1.2.10.dmg"]), vec![vec![path("app-1.2.10.dmg"), path("app-1.2.3.dmg")]]);
+        assert_eq!(families(&["report_v1.docx", "report_final_v7.docx"]), vec![vec![path("report_final_v7.docx"), path("report_v1.docx")]]);
+        assert_eq!(families(&["plan rev 2.pdf", "plan rev 3.pdf"]), vec![vec![path("plan rev 3.pdf"), path("plan rev 2.pdf")]]);
+        assert_eq!(families(&["setup.exe", "setup (1).exe"]), vec![vec![path("setup (1).exe"), path("setup.exe")]]);
+    }
+
+    #[test]
+    fn keeps_numbered_files_apart() {
+        assert!(families(&["invoice_2023.pdf", "invoice_2024.pdf"]).is_empty());
+        assert!(families(&["chapter 1.pdf", "chapter 2.pdf", "chapter 9.pdf"]).is_empty());
+        assert!(families(&["scan_001.pdf", "scan_002.pdf"]).is_empty());
+        assert!(families(&["photos-2023.zip", "photos-2024.zip"]).is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use log::info;
+
+/// File systems without a trash or permissions, as found on memory cards and USB sticks.
+const FAT_FILE_SYSTEMS: &[&str] = &["vfat", "fat", "fat12", "fat16", "fat32", "msdos", "exfat"];
+
+/// File systems reached over the network. Their servers have no trash, so
+/// moving files to the trash either fails or silently copies them locally.
+const NETWORK_FILE_SYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb", "smbfs", "smb3", "afpfs", "sshfs", "fuse.sshfs", "9p", "davfs", "webdav",
+];
+
+/// FAT and exFAT round modification times to 2 seconds, and FAT stores them in
+/// local time, so they shift when the drive is used in another time zone. Ages
+/// measured there are only trusted within this margin.
+pub const FAT_TIME_MARGIN: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeKind {
+    /// A Windows drive mounted inside WSL, e.g. `/mnt/c`
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    WindowsDriveFromWsl,
+    /// A WSL distribution disk image seen from Windows
+    #[cfg_attr(not(windows), allow(dead_code))]
+    WslDistribution,
+    /// A partition of another operating system on a dual-boot machine
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    DualBootPartition,
+}
+
+/// A volume that belongs to another environment than the one the app runs in.
+#[derive(Debug, Clone)]
+pub struct ForeignVolume {
+    pub path: PathBuf,
+    pub kind: VolumeKind,
+}
+
+impl ForeignVolume {
+    /// Whether the volume can be scanned like a directory.
+    pub fn is_scannable(&self) -> bool {
+        self.kind != VolumeKind::WslDistribution
+    }
+
+    pub fn warning(&self) -> &'static str {
+        match self.kind {
+            VolumeKind::WindowsDriveFromWsl => {
+                "Windows drive seen from WSL: the Windows Recycle Bin cannot be reached, files cleaned with the trash enabled \
+                 follow the setting for files the trash cannot take"
+            }
+            VolumeKind::WslDistribution => {
+                "WSL disk image: never delete it, shrink it from Windows with 'wsl --shutdown' and Optimize-VHD instead"
+            }
+            VolumeKind::DualBootPartition => {
+                "Partition of another operating system: trashed files are not visible in that system's trash"
+            }
+        }
+    }
+}
+
+/// Detects mounted volumes of other environments (WSL, dual boot).
+pub fn detect_foreign_volumes() -> Vec<ForeignVolume> {
+    let volumes = detect_platform_volumes();
+    info!("Detected {} foreign volumes", volumes.len());
+    volumes
+}
+
+/// Returns the mount point of the volume holding `path` and the bytes in use on it.
+pub fn volume_usage(path: &Path) -> Option<(PathBuf, u64)> {
+    with_disk_for(path, |disk| {
+        let used = disk.total_space().saturating_sub(disk.available_space());
+        (disk.mount_point().to_path_buf(), used)
+    })
+}
+
+/// Whether `path` is `/`, a drive such as `C:\`, or the mount point of a volume.
+pub fn is_volume_root(path: &Path) -> bool {
+    let Ok(path) = path.canonicalize() else {
+        return false;
+    };
+    path.parent().is_none()
+        || sysinfo::Disks::new_with_refreshed_list()
+            .list()
+            .iter()
+            .any(|disk| disk.mount_point() == path)
+}
+
+/// Mount points of the mounted disks with their storage type, longest first so
+/// the first match for a path is the disk holding it.
+pub fn disk_kinds() -> Vec<(PathBuf, sysinfo::DiskKind)> {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let mut kinds: Vec<(PathBuf, sysinfo::DiskKind)> = disks.list()
+        .iter()
+        .map(|disk| (disk.mount_point().to_path_buf(), disk.kind()))
+        .collect();
+    kinds.sort_by_key(|(mount_point, _)| std::cmp::Reverse(mount_point.as_os_str().len()));
+    kinds
+}
+
+pub fn is_fat_file_system(file_system: &str) -> bool {
+    FAT_FILE_SYSTEMS.contains(&file_system.to_lowercase().as_str())
+}
+
+pub fn is_network_file_system(file_system: &str) -> bool {
+    NETWORK_FILE_SYSTEMS.contains(&file_system.to_lowercase().as_str())
+}
+
+/// Mount points of the network shares, longest first so the first match for
+/// a path is the share holding it.
+pub fn network_shares() -> Vec<PathBuf> {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let mut shares: Vec<PathBuf> = disks.list()
+        .iter()
+        .filter(|disk| is_network_file_system(&disk.file_system().to_string_lossy()))
+        .map(|disk| disk.mount_point().to_path_buf())
+        .collect();
+    shares.sort_by_key(|mount_point| std::cmp::Reverse(mount_point.as_os_str().len()));
+    shares
+}
+
+/// Mount points of the mounted disks and whether they are FAT or exFAT, to
+/// tell cheaply for many files whether they live on such a volume.
+pub struct FatVolumes {
+    /// Longest first, so the first match for a path is the disk holding it
+    mounts: Vec<(PathBuf, bool)>,
+}
+
+impl FatVolumes {
+    pub fn detect() -> Self {
+        let disks = sysinfo::Disks::new_with_refreshed_list();
+        let mut mounts: Vec<(PathBuf, bool)> = disks.list()
+            .iter()
+            .map(|disk| (disk.mount_point().to_path_buf(), is_fat_file_system(&disk.file_system().to_string_lossy())))
+            .collect();
+        mounts.sort_by_key(|(mount_point, _)| std::cmp::Reverse(mount_point.as_os_str().len()));
+        Self { mounts }
+    }
+
+    /// Mount point of the FAT or exFAT volume holding `path`, if it is on one.
+    pub fn mount_point_for(&self, path: &Path) -> Option<&Path> {
+        self.mounts.iter()
+            .find(|(mount_point, _)| path.starts_with(mount_point))
+            .filter(|(_, fat)| *fat)
+            .map(|(mount_point, _)| mount_point.as_path())
+    }
+
+    pub fn holds(&self, path: &Path) -> bool {
+        self.mount_point_for(path).is_some()
+    }
+}
+
+/// Calls `f` with the mounted disk holding `path`, the one with the longest
+/// mount point containing it.
+pub fn with_disk_for<T>(path: &Path, f: impl FnOnce(&sysinfo::Disk) -> T) -> Option<T> {
+    let path = path.canonicalize().ok()?;
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+
+    disks.list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(f)
+}
+
+/// Mount points of the Windows drives seen from WSL. Trashing there would only
+/// move files to a hidden `.Trash-<uid>` folder on the drive, which the Windows
+/// Recycle Bin never shows.
+pub fn windows_drives_from_wsl() -> Vec<PathBuf> {
+    detect_platform_volumes()
+        .into_iter()
+        .filter(|volume| volume.kind == VolumeKind::WindowsDriveFromWsl)
+        .map(|volume| volume.path)
+        .collect()
+}
+
+/// Returns the foreign volume containing `path`, if any.
+pub fn foreign_volume_for<'a>(volumes: &'a [ForeignVolume], path: &Path) -> Option<&'a ForeignVolume> {
+    volumes.iter()
+        .filter(|volume| volume.is_scannable())
+        .find(|volume| path.starts_with(&volume.path))
+}
+
+#[cfg(target_os = "linux")]
+fn detect_platform_volumes() -> Vec<ForeignVolume> {
+    let in_wsl = std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|release| release.to_lowercase().contains("microsoft"))
+        .unwrap_or(false);
+
+    let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = unescape_mount_point(fields.next()?);
+            let fs_type = fields.next()?;
+
+            let kind = match fs_type {
+                "drvfs" | "9p" if in_wsl && mount_point.starts_with("/mnt/") => VolumeKind::WindowsDriveFromWsl,
+                "ntfs" | "ntfs3" | "fuseblk" if !in_wsl => VolumeKind::DualBootPartition,
+                _ => return None,
+            };
+
+            Some(ForeignVolume {
+                path: PathBuf::from(mount_point),
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// Mount points in /proc/mounts escape spaces and other characters as octal (`\040`).
+#[cfg(target_os = "linux")]
+fn unescape_mount_point(raw: &str) -> String {
+    let mut result = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let code: String = chars.by_ref().take(3).collect();
+            match u8::from_str_radix(&code, 8) {
+                Ok(byte) => result.push(byte as char),
+                Err(_) => {
+                    result.push(c);
+                    result.push_str(&code);
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+#[cfg(windows)]
+fn detect_platform_volumes() -> Vec<ForeignVolume> {
+    let Some(packages) = dirs::data_local_dir().map(|dir| dir.join("Packages")) else {
+        return Vec::new();
+    };
+
+    let Ok(entries) = std::fs::read_dir(&packages) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().join("LocalState").join("ext4.vhdx"))
+        .filter(|image| image.is_file())
+        .map(|path| ForeignVolume {
+            path,
+            kind: VolumeKind::WslDistribution,
+        })
+        .collect()
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn detect_platform_volumes() -> Vec<ForeignVolume> {
+    Vec::new()
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use log::info;
+use walkdir::WalkDir;
+
+/// Steam gives shortcuts to non-Steam games ids above this, they have no manifest.
+const FIRST_SHORTCUT_APP_ID: u64 = 1 << 31;
+
+#[derive(Debug, Clone)]
+pub struct WinePrefix {
+    pub path: PathBuf,
+    pub name: String,
+    pub size: u64,
+    /// A Proton prefix left behind by a game that is no longer installed
+    pub orphaned: bool,
+}
+
+/// Finds the Proton prefixes of every Steam library and the usual Wine,
+/// Lutris and Bottles prefixes, largest first.
+pub fn find_prefixes() -> Vec<WinePrefix> {
+    let Some(home) = dirs::home_dir() else {
+        return Vec::new();
+    };
+
+    let mut prefixes = Vec::new();
+    let libraries = steam_libraries(&home);
+
+    // App id -> game name, from the manifests of installed games in every library
+    let mut installed: HashMap<u64, String> = HashMap::new();
+    for library in &libraries {
+        let Ok(entries) = std::fs::read_dir(library.join("steamapps")) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let app_id = name.strip_prefix("appmanifest_")
+                .and_then(|rest| rest.strip_suffix(".acf"))
+                .and_then(|id| id.parse().ok());
+            if let Some(app_id) = app_id {
+                let manifest = std::fs::read_to_string(entry.path()).unwrap_or_default();
+                let game = vdf_values(&manifest, "name").into_iter().next().unwrap_or_default();
+                installed.insert(app_id, game);
+            }
+        }
+    }
+
+    for library in &libraries {
+        let Ok(entries) = std::fs::read_dir(library.join("steamapps").join("compatdata")) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(app_id) = entry.file_name().to_string_lossy().parse::<u64>() else {
+                continue;
+            };
+            let name = match installed.get(&app_id) {
+                Some(game) if !game.is_empty() => format!("{} (Proton, app {})", game, app_id),
+                _ if app_id >= FIRST_SHORTCUT_APP_ID => format!("Non-Steam game (Proton, app {})", app_id),
+                _ => format!("Uninstalled game (Proton, app {})", app_id),
+            };
+            prefixes.push(WinePrefix {
+                size: directory_size(&entry.path()),
+                path: entry.path(),
+                name,
+                orphaned: app_id < FIRST_SHORTCUT_APP_ID && !installed.contains_key(&app_id),
+            });
+        }
+    }
+
+    let mut wine_candidates = vec![home.join(".wine")];
+    for parent in [
+        home.join(".local").join("share").join("wineprefixes"),
+        home.join(".local").join("share").join("bottles").join("bottles"),
+        home.join("Games"),
+    ] {
+        if let Ok(entries) = std::fs::read_dir(parent) {
+            wine_candidates.extend(entries.flatten().map(|entry| entry.path()));
+        }
+    }
+
+    for path in wine_candidates {
+        if let Some(prefix) = find_wine_prefix(&path) {
+            let name = format!("{} (Wine)", path.file_name().unwrap_or_default().to_string_lossy());
+            prefixes.push(WinePrefix {
+                size: directory_size(&prefix),
+                path: prefix,
+                name,
+                orphaned: false,
+            });
+        }
+    }
+
+    prefixes.sort_by_key(|prefix| std::cmp::Reverse(prefix.size));
+    info!("Found {} Wine and Proton prefixes", prefixes.len());
+    prefixes
+}
+
+/// A Wine prefix holds `system.reg` and `drive_c`. Lutris puts it one level
+/// deeper, in a `prefix` folder inside the game folder.
+fn find_wine_prefix(path: &Path) -> Option<PathBuf> {
+    [path.to_path_buf(), path.join("prefix")]
+        .into_iter()
+        .find(|candidate| candidate.join("system.reg").is_file() && candidate.join("drive_c").is_dir())
+}
+
+/// The main Steam directory and the extra libraries listed in its libraryfolders.vdf.
+fn steam_libraries(home: &Path) -> Vec<PathBuf> {
+    let roots = [
+        home.join(".steam").join("steam"),
+        home.join(".local").join("share").join("Steam"),
+        home.join(".var").join("app").join("com.valvesoftware.Steam").join(".local").join("share").join("Steam"),
+    ];
+
+    let mut libraries: Vec<PathBuf> = Vec::new();
+    for root in roots {
+        let Ok(root) = root.canonicalize() else {
+            continue;
+        };
+        let folders = std::fs::read_to_string(root.join("steamapps").join("libraryfolders.vdf")).unwrap_or_default();
+        let listed = vdf_values(&folders, "path").into_iter().map(PathBuf::from);
+
+        for library in std::iter::once(root.clone()).chain(listed) {
+            let library = library.canonicalize().unwrap_or(library);
+            if !libraries.contains(&library) {
+                libraries.push(library);
+            }
+        }
+    }
+    libraries
+}
+
+/// Values of every `"key" "value"` line of a Valve KeyValues file.
+fn vdf_values(content: &str, key: &str) -> Vec<String> {
+    let quoted_key = format!("\"{}\"", key);
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(&quoted_key))
+        .map(|value| value.trim().trim_matches('"').replace("\\\\", "\\"))
+        .collect()
+}
+
+fn directory_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}